@@ -7,7 +7,7 @@ fn run(mut sim: Simulation<Basic, LJCut>) {
     let lattice = Cubic::from_density(0.8);
     let rect = Rect::from_lattice(&lattice, [10, 10, 10]);
     let container = Container::from_rect_periodic(rect.clone());
-    let coords = lattice.coords_within_region(&rect, &[0.0, 0.0, 0.0]);
+    let (types, coords) = lattice.coords_within_region(&rect, &[0.0, 0.0, 0.0]);
 
     sim.set_atom_types(vec![Basic::new(1.0)]);
 
@@ -16,7 +16,7 @@ fn run(mut sim: Simulation<Basic, LJCut>) {
 
     sim.set_container(container);
 
-    sim.add_atoms(0, coords);
+    sim.add_atoms(types, coords);
 
     sim.set_temperature(3.0);
     sim.set_timestep(0.005);
@@ -0,0 +1,67 @@
+use super::*;
+
+/// Born-Mayer-Huggins potential `A exp((sigma - r) / rho) - C / r^6 + D / r^8`, which
+/// is the plain Born-Mayer repulsion when `sigma`, `C` and `D` are zero
+pub type BornMayer = Pair<BornMayerCoeff>;
+
+#[derive(Clone, Copy, Debug)]
+pub struct BornMayerCoeff {
+    a: f64,
+    rho: f64,
+    sigma: f64,
+    c: f64,
+    d: f64,
+    rcut: f64,
+}
+impl BornMayerCoeff {
+    pub fn new(a: f64, rho: f64, sigma: f64, c: f64, d: f64, rcut: f64) -> Self {
+        assert!(rho > 0.0, "Rho should be positive, found {}", rho);
+        Self {
+            a,
+            rho,
+            sigma,
+            c,
+            d,
+            rcut,
+        }
+    }
+    pub fn a(&self) -> f64 {
+        self.a
+    }
+    pub fn rho(&self) -> f64 {
+        self.rho
+    }
+    pub fn sigma(&self) -> f64 {
+        self.sigma
+    }
+    pub fn c(&self) -> f64 {
+        self.c
+    }
+    pub fn d(&self) -> f64 {
+        self.d
+    }
+}
+impl PairCoeff for BornMayerCoeff {
+    fn rcut(&self) -> f64 {
+        self.rcut
+    }
+    fn energy(&self, r: f64) -> f64 {
+        let r2 = r * r;
+        let r6 = r2 * r2 * r2;
+        self.a * ((self.sigma - r) / self.rho).exp() - self.c / r6 + self.d / (r6 * r2)
+    }
+    fn force(&self, r: f64) -> f64 {
+        let r2 = r * r;
+        let r7 = r2 * r2 * r2 * r;
+        self.a / self.rho * ((self.sigma - r) / self.rho).exp() - 6.0 * self.c / r7
+            + 8.0 * self.d / (r7 * r2)
+    }
+    fn to_params(&self) -> Vec<f64> {
+        vec![self.a, self.rho, self.sigma, self.c, self.d, self.rcut]
+    }
+    fn from_params(params: &[f64]) -> Self {
+        Self::new(
+            params[0], params[1], params[2], params[3], params[4], params[5],
+        )
+    }
+}
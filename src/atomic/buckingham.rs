@@ -0,0 +1,44 @@
+use super::*;
+
+/// Buckingham potential `A exp(-r / rho) - C / r^6`
+pub type Buckingham = Pair<BuckinghamCoeff>;
+
+#[derive(Clone, Copy, Debug)]
+pub struct BuckinghamCoeff {
+    a: f64,
+    rho: f64,
+    c: f64,
+    rcut: f64,
+}
+impl BuckinghamCoeff {
+    pub fn new(a: f64, rho: f64, c: f64, rcut: f64) -> Self {
+        assert!(rho > 0.0, "Rho should be positive, found {}", rho);
+        Self { a, rho, c, rcut }
+    }
+    pub fn a(&self) -> f64 {
+        self.a
+    }
+    pub fn rho(&self) -> f64 {
+        self.rho
+    }
+    pub fn c(&self) -> f64 {
+        self.c
+    }
+}
+impl PairCoeff for BuckinghamCoeff {
+    fn rcut(&self) -> f64 {
+        self.rcut
+    }
+    fn energy(&self, r: f64) -> f64 {
+        self.a * (-r / self.rho).exp() - self.c / r.powi(6)
+    }
+    fn force(&self, r: f64) -> f64 {
+        self.a / self.rho * (-r / self.rho).exp() - 6.0 * self.c / r.powi(7)
+    }
+    fn to_params(&self) -> Vec<f64> {
+        vec![self.a, self.rho, self.c, self.rcut]
+    }
+    fn from_params(params: &[f64]) -> Self {
+        Self::new(params[0], params[1], params[2], params[3])
+    }
+}
@@ -0,0 +1,217 @@
+use std::f64::consts::PI;
+
+use super::{
+    coulomb::{for_each_charged_pair, screened},
+    *,
+};
+
+/// How the damped Coulomb interactions of `CoulDSF` are brought to zero at the cutoff
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CoulShift {
+    /// Damped shifted force (Fennell and Gezelter), with both the energy and the force
+    /// going to zero at the cutoff
+    Force,
+    /// Wolf summation, with the energy shifted to zero at the cutoff and the forces given
+    /// by its derivative, which jump to zero there
+    Energy,
+}
+
+/// Real-space Coulomb potential between the charges of the atoms, damped by
+/// `erfc(alpha r) / r` and shifted at the cutoff, as with LAMMPS `pair_style coul/dsf`
+/// and `coul/wolf`. Each charge also has a self energy, so that the energy approximates
+/// the Ewald sum of a periodic system at a cost that scales linearly with the number of
+/// atoms.
+///
+/// The potential has no per-type coefficients. The Coulomb constant is 1 unless set.
+///
+/// ```rust
+/// use jmd::atomic::{CoulDSF, CoulShift, COULOMB_METAL};
+///
+/// let mut coul = CoulDSF::new(0.2, 10.0);
+/// coul.set_coulomb_constant(COULOMB_METAL);
+/// coul.set_shift(CoulShift::Energy);
+/// ```
+pub struct CoulDSF {
+    num_types: usize,
+    alpha: f64,
+    cutoff: f64,
+    shift: CoulShift,
+    coulomb_constant: f64,
+}
+impl CoulDSF {
+    /// The potential with the given damping parameter and cutoff, shifted in the force
+    pub fn new(alpha: f64, cutoff: f64) -> Self {
+        assert!(
+            alpha >= 0.0,
+            "Damping parameter should be non-negative, found {}",
+            alpha
+        );
+        assert!(cutoff > 0.0, "Cutoff should be positive, found {}", cutoff);
+        Self {
+            num_types: 0,
+            alpha,
+            cutoff,
+            shift: CoulShift::Force,
+            coulomb_constant: 1.0,
+        }
+    }
+    pub fn set_shift(&mut self, shift: CoulShift) {
+        self.shift = shift;
+    }
+    pub fn shift(&self) -> CoulShift {
+        self.shift
+    }
+    /// Set the constant `1 / (4 pi epsilon_0)` converting products of charges over
+    /// distances to energies, such as `COULOMB_METAL`
+    pub fn set_coulomb_constant(&mut self, coulomb_constant: f64) {
+        self.coulomb_constant = coulomb_constant;
+    }
+    /// The energy and force of a pair of unit charges at the given distance, shifted at
+    /// the cutoff
+    fn pair(&self, r: f64) -> [f64; 2] {
+        let [e, f] = screened(self.alpha, r);
+        let [e_cut, f_cut] = screened(self.alpha, self.cutoff);
+        match self.shift {
+            CoulShift::Force => [e - e_cut + f_cut * (r - self.cutoff), f - f_cut],
+            CoulShift::Energy => [e - e_cut, f],
+        }
+    }
+    /// The self energy of an atom of unit charge
+    fn self_energy(&self) -> f64 {
+        let e_cut = screened(self.alpha, self.cutoff)[0];
+        -(0.5 * e_cut + self.alpha / PI.sqrt())
+    }
+}
+
+impl<T: AtomType> AtomicPotentialTrait<T> for CoulDSF {
+    /// No coefficients are needed
+    type Coeff = ();
+    fn new() -> Self {
+        Self::new(0.0, 1.0)
+    }
+    fn cutoff_distance(&self) -> f64 {
+        self.cutoff
+    }
+    fn compute_forces(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> Vec<[f64; 3]> {
+        let mut forces = vec![[0.0; 3]; atoms.num_total_atoms()];
+        for_each_charged_pair(atoms, neighbor_list, self.cutoff, |i, j, r, dist, qq| {
+            let f_over_r = self.coulomb_constant * qq * self.pair(dist)[1] / dist;
+            for k in 0..3 {
+                forces[i][k] += r[k] * f_over_r;
+                forces[j][k] -= r[k] * f_over_r;
+            }
+        });
+        forces
+    }
+    fn compute_potential_energy(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> f64 {
+        self.compute_per_atom_energy(atoms, neighbor_list)
+            .expect("Per-atom energies are supported")
+            .iter()
+            .sum()
+    }
+    fn compute_virial(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> [f64; 6] {
+        let mut virial = [0.0; 6];
+        for_each_charged_pair(atoms, neighbor_list, self.cutoff, |_, _, r, dist, qq| {
+            let f_over_r = self.coulomb_constant * qq * self.pair(dist)[1] / dist;
+            let pair = pair_virial(r, f_over_r);
+            for k in 0..6 {
+                virial[k] += pair[k];
+            }
+        });
+        virial
+    }
+    /// The self energy of each owned atom is included
+    fn compute_per_atom_energy(
+        &self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+    ) -> Option<Vec<f64>> {
+        let mut energies = vec![0.0; atoms.num_total_atoms()];
+        for (energy, &q) in energies.iter_mut().zip(&atoms.charges[..atoms.nlocal]) {
+            *energy = self.coulomb_constant * q * q * self.self_energy();
+        }
+        for_each_charged_pair(atoms, neighbor_list, self.cutoff, |i, j, _, dist, qq| {
+            let half_energy = 0.5 * self.coulomb_constant * qq * self.pair(dist)[0];
+            energies[i] += half_energy;
+            energies[j] += half_energy;
+        });
+        Some(energies)
+    }
+    fn compute_per_atom_virial(
+        &self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+    ) -> Option<Vec<[f64; 6]>> {
+        let mut virials = vec![[0.0; 6]; atoms.num_total_atoms()];
+        for_each_charged_pair(atoms, neighbor_list, self.cutoff, |i, j, r, dist, qq| {
+            let f_over_r = self.coulomb_constant * qq * self.pair(dist)[1] / dist;
+            let pair = pair_virial(r, 0.5 * f_over_r);
+            for k in 0..6 {
+                virials[i][k] += pair[k];
+                virials[j][k] += pair[k];
+            }
+        });
+        Some(virials)
+    }
+    fn num_types(&self) -> usize {
+        self.num_types
+    }
+    fn set_num_types(&mut self, num_types: usize) {
+        self.num_types = num_types;
+    }
+    fn all_set(&self) -> bool {
+        true
+    }
+    fn set_coeff(&mut self, _typei: usize, _typej: usize, _coeff: &Self::Coeff) {}
+    fn write_restart(&self, writer: &mut RestartWriter) -> io::Result<()> {
+        writer.write_f64(self.alpha)?;
+        writer.write_f64(self.cutoff)?;
+        writer.write_bool(self.shift == CoulShift::Force)?;
+        writer.write_f64(self.coulomb_constant)
+    }
+    fn read_restart(&mut self, reader: &mut RestartReader) -> io::Result<()> {
+        self.alpha = reader.read_f64()?;
+        self.cutoff = reader.read_f64()?;
+        self.shift = if reader.read_bool()? {
+            CoulShift::Force
+        } else {
+            CoulShift::Energy
+        };
+        self.coulomb_constant = reader.read_f64()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atomic::{
+        coulomb::tests::{check_forces, rock_salt, MADELUNG_NACL},
+        tests::energy,
+    };
+
+    #[test]
+    fn test_madelung() {
+        for shift in [CoulShift::Force, CoulShift::Energy] {
+            let mut coul = CoulDSF::new(0.3, 8.0);
+            coul.set_shift(shift);
+            let (atoms, container, nl) = rock_salt(5, 8.0, false, [0.0; 3]);
+            let per_ion = energy(&mut coul, &atoms, &container, &nl) / atoms.nlocal as f64;
+            assert!(
+                (per_ion + 0.5 * MADELUNG_NACL).abs() < 1e-3,
+                "Energy per ion {} should be {}",
+                per_ion,
+                -0.5 * MADELUNG_NACL
+            );
+        }
+    }
+
+    #[test]
+    fn test_forces() {
+        for shift in [CoulShift::Force, CoulShift::Energy] {
+            let mut coul = CoulDSF::new(0.4, 3.5);
+            coul.set_shift(shift);
+            check_forces(&mut coul, 2);
+        }
+    }
+}
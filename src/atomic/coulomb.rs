@@ -0,0 +1,183 @@
+use std::f64::consts::PI;
+
+use super::*;
+use crate::region::Region;
+
+/// The Coulomb constant in metal units, in eV Å per squared elementary charge, for use
+/// with `set_coulomb_constant` of the electrostatic potentials
+pub const COULOMB_METAL: f64 = 14.399645;
+
+/// The screened Coulomb interaction `erfc(alpha r) / r` of unit charges at the given
+/// distance, and its negative derivative
+pub(super) fn screened(alpha: f64, r: f64) -> [f64; 2] {
+    let erfc = libm::erfc(alpha * r) / r;
+    let gaussian = 2.0 * alpha / PI.sqrt() * (-alpha * alpha * r * r).exp();
+    [erfc, (erfc + gaussian) / r]
+}
+
+/// The splitting parameter of Ewald sums for which the root-mean-square error in the
+/// real-space forces is about the given accuracy, from the estimate of Kolafa and Perram,
+/// given the number of atoms and sum of squared charges over all processes
+pub(super) fn splitting_parameter(
+    accuracy: f64,
+    cutoff: f64,
+    num_atoms: usize,
+    q2: f64,
+    volume: f64,
+) -> f64 {
+    let g = accuracy * (num_atoms as f64 * cutoff * volume).sqrt() / (2.0 * q2);
+    if g >= 1.0 {
+        (1.35 - 0.15 * accuracy.ln()) / cutoff
+    } else {
+        (-g.ln()).sqrt() / cutoff
+    }
+}
+
+/// The reciprocal box vectors, with `b_i . a_j = 2 pi delta_ij` for the box vectors `a_j`
+pub(super) fn reciprocal_vectors(container: &Container) -> [[f64; 3]; 3] {
+    let [a0, a1, a2] = container.box_vectors();
+    let cross = |u: [f64; 3], v: [f64; 3]| {
+        [
+            u[1] * v[2] - u[2] * v[1],
+            u[2] * v[0] - u[0] * v[2],
+            u[0] * v[1] - u[1] * v[0],
+        ]
+    };
+    let volume = container.rect().volume();
+    [cross(a1, a2), cross(a2, a0), cross(a0, a1)].map(|b| b.map(|x| 2.0 * PI * x / volume))
+}
+
+/// Call the function with the indices, separation, distance and product of charges of
+/// each pair of charged atoms within the cutoff
+pub(super) fn for_each_charged_pair<T: AtomType>(
+    atoms: &Atoms<T>,
+    neighbor_list: &NeighborList,
+    cutoff: f64,
+    mut f: impl FnMut(usize, usize, [f64; 3], f64, f64),
+) {
+    for_each_pair(atoms, neighbor_list, |i, j, r, r2| {
+        let qiqj = atoms.charges[i] * atoms.charges[j];
+        if qiqj != 0.0 && r2 < cutoff * cutoff {
+            f(i, j, r, r2.sqrt(), qiqj);
+        }
+    });
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+    use crate::{
+        atom_type::Basic,
+        atomic::tests::{check_finite_differences, energy},
+        container::BC,
+    };
+
+    /// Madelung constant of rock salt, for the nearest-neighbor distance
+    pub(crate) const MADELUNG_NACL: f64 = 1.747565;
+
+    /// Atoms of a rock salt crystal of `n` cubic cells with unit nearest-neighbor distance
+    /// and charges of +1 and -1, owned by a single process with periodic images as ghost
+    /// atoms up to the neighbor distance outside the box, and the neighbor list within the
+    /// cutoff. Optionally displaced from the lattice sites, and strained along each axis.
+    pub(crate) fn rock_salt(
+        n: usize,
+        cutoff: f64,
+        displace: bool,
+        strain: [f64; 3],
+    ) -> (Atoms<Basic>, Container, NeighborList) {
+        let lengths = strain.map(|e| 2.0 * n as f64 * (1.0 + e));
+        let container = Container::new(
+            0.0,
+            lengths[0],
+            0.0,
+            lengths[1],
+            0.0,
+            lengths[2],
+            BC::PP,
+            BC::PP,
+            BC::PP,
+        );
+        let mut atoms: Atoms<Basic> = Atoms::new();
+        atoms.atom_types = vec![Basic::new(1.0), Basic::new(1.0)];
+        for x in 0..2 * n {
+            for y in 0..2 * n {
+                for z in 0..2 * n {
+                    let id = atoms.ids.len();
+                    let parity = (x + y + z) % 2;
+                    let mut position = [x as f64, y as f64, z as f64];
+                    for (k, p) in position.iter_mut().enumerate() {
+                        if displace {
+                            // Deterministic pseudo-random displacements
+                            *p += 0.1 * ((id * 7 + k * 13) as f64 * 0.7).sin();
+                        }
+                        *p *= 1.0 + strain[k];
+                    }
+                    atoms.ids.push(id);
+                    atoms.types.push(parity);
+                    atoms.positions.push(position);
+                    atoms.velocities.push([0.0; 3]);
+                    atoms.masks.push(1);
+                    atoms.charges.push(if parity == 0 { 1.0 } else { -1.0 });
+                }
+            }
+        }
+        atoms.nlocal = atoms.ids.len();
+        atoms.num_atoms_global = atoms.nlocal;
+
+        let skin = 0.3;
+        let dist = cutoff + skin;
+        let images = (dist / lengths[0].min(lengths[1]).min(lengths[2])).ceil() as i32;
+        for i in 0..atoms.nlocal {
+            for a in -images..=images {
+                for b in -images..=images {
+                    for c in -images..=images {
+                        if [a, b, c] == [0, 0, 0] {
+                            continue;
+                        }
+                        let mut atom = atoms.atom(i);
+                        for (k, shift) in [a, b, c].into_iter().enumerate() {
+                            atom.position[k] += shift as f64 * lengths[k];
+                        }
+                        if (0..3).all(|k| {
+                            -dist <= atom.position[k] && atom.position[k] < lengths[k] + dist
+                        }) {
+                            atoms.push(atom);
+                        }
+                    }
+                }
+            }
+        }
+        let mut nl = NeighborList::new(&container, cutoff, skin);
+        nl.update(&atoms.positions, atoms.nlocal);
+        (atoms, container, nl)
+    }
+
+    /// Check the forces on a displaced rock salt crystal as in `check_finite_differences`,
+    /// and the diagonal of the virial against the derivatives of the energy with respect
+    /// to strains along each axis
+    pub(crate) fn check_forces<A: AtomicPotentialTrait<Basic>>(potential: &mut A, n: usize) {
+        let cutoff = potential.cutoff_distance();
+        let (mut atoms, container, nl) = rock_salt(n, cutoff, true, [0.0; 3]);
+        check_finite_differences(potential, &mut atoms, &container, &nl, &[0, 5, 11], energy);
+
+        let virial = potential.compute_virial(&atoms, &nl);
+        let h = 1e-6;
+        for k in 0..3 {
+            let mut strain = [0.0; 3];
+            strain[k] = h;
+            let (atoms, container, nl) = rock_salt(n, cutoff, true, strain);
+            let plus = energy(potential, &atoms, &container, &nl);
+            strain[k] = -h;
+            let (atoms, container, nl) = rock_salt(n, cutoff, true, strain);
+            let minus = energy(potential, &atoms, &container, &nl);
+            let expected = -(plus - minus) / (2.0 * h);
+            assert!(
+                (virial[k] - expected).abs() < 1e-5,
+                "Virial {} along {} should be {}",
+                virial[k],
+                k,
+                expected
+            );
+        }
+    }
+}
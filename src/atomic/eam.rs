@@ -0,0 +1,654 @@
+use std::fs;
+
+use super::*;
+use crate::utils::UniformSpline;
+
+/// Conversion of the products of effective charges in `funcfl` files, in Hartree Bohr,
+/// to pair energies times distance in eV Angstrom
+const HARTREE_BOHR: f64 = 27.2 * 0.529;
+
+/// The embedding and density functions of one element, tabulated at equally spaced
+/// densities and distances starting from zero
+#[derive(Clone, Debug)]
+struct EAMElement {
+    name: String,
+    mass: f64,
+    drho: f64,
+    /// Embedding energy `F(rho)`
+    embedding: Vec<f64>,
+    dr: f64,
+    /// Electron density `rho(r)` contributed to neighboring atoms
+    density: Vec<f64>,
+}
+
+/// An element read from a `funcfl` file, with its effective charge `Z(r)` tabulated
+/// at the distances of its density
+struct FuncflElement {
+    element: EAMElement,
+    charge: Vec<f64>,
+    cutoff: f64,
+}
+
+/// The splines interpolating the functions of an element
+#[derive(Clone, Debug)]
+struct ElementSplines {
+    embedding: UniformSpline,
+    rho_max: f64,
+    density: UniformSpline,
+}
+
+/// Embedded-atom method potential, with the energy of each atom `F(rho) + sum phi(r)/2`
+/// given by the electron density `rho` summed over its neighbors and the pair potential
+/// `phi` with each of them, as with LAMMPS `pair_style eam` and `eam/alloy`. The
+/// functions are read from DYNAMO files and interpolated with cubic splines, and the
+/// embedding energy is extrapolated linearly past the tabulated densities.
+///
+/// Elements are assigned to atom types with `set_coeff(i, i, &name)`.
+pub struct EAM {
+    cutoff: f64,
+    elements: Vec<EAMElement>,
+    pair_dr: f64,
+    /// `r phi(r)` between each pair of elements, tabulated at distances `k pair_dr`,
+    /// ordered as the lower triangle `00, 10, 11, 20, ...`
+    r_phi: Vec<Vec<f64>>,
+    element_splines: Vec<ElementSplines>,
+    /// Splines of `r phi(r)`, indexed by both elements
+    pair_splines: Vec<UniformSpline>,
+    num_types: usize,
+    type_elements: Vec<Option<usize>>,
+    /// Derivative of the embedding energy of each atom, including ghost atoms
+    fp: Vec<f64>,
+    /// Embedding energy of each owned atom
+    embedding_energies: Vec<f64>,
+}
+impl EAM {
+    fn new(cutoff: f64, elements: Vec<EAMElement>, pair_dr: f64, r_phi: Vec<Vec<f64>>) -> Self {
+        let mut eam = Self {
+            cutoff,
+            elements,
+            pair_dr,
+            r_phi,
+            element_splines: Vec::new(),
+            pair_splines: Vec::new(),
+            num_types: 0,
+            type_elements: Vec::new(),
+            fp: Vec::new(),
+            embedding_energies: Vec::new(),
+        };
+        eam.build_splines();
+        eam
+    }
+    /// Read a DYNAMO `setfl` file, as used by LAMMPS `pair_style eam/alloy`.
+    ///
+    /// After three comment lines, the file gives the number of elements and their names,
+    /// then `Nrho drho Nr dr cutoff`. For each element, a line with its atomic number,
+    /// mass, lattice constant and lattice type is followed by `F(rho)` at `Nrho`
+    /// densities and `rho(r)` at `Nr` distances, both spaced from zero. Last come
+    /// `r phi(r)` for each pair of elements `i >= j`, in the order `00, 10, 11, 20, ...`.
+    ///
+    /// ```rust,no_run
+    /// use jmd::{atom_type::Basic, atomic::EAM, prelude::*};
+    ///
+    /// fn run(mut sim: Simulation<Basic, EAM>) {
+    ///     let eam = EAM::from_setfl("CuNi.eam.alloy");
+    ///     sim.set_atom_types(vec![Basic::new(eam.mass("Cu")), Basic::new(eam.mass("Ni"))]);
+    ///     sim.set_atomic_potential(eam);
+    ///     sim.set_atomic_coeff(0, 0, &"Cu".to_string());
+    ///     sim.set_atomic_coeff(1, 1, &"Ni".to_string());
+    /// }
+    /// ```
+    pub fn from_setfl(path: &str) -> Self {
+        let contents = fs::read_to_string(path)
+            .unwrap_or_else(|_| panic!("Could not read setfl file {}", path));
+        parse_setfl(&contents)
+    }
+    /// Read DYNAMO `funcfl` files, as used by LAMMPS `pair_style eam`, each holding one
+    /// element named by its file path.
+    ///
+    /// After a comment line, each file gives the atomic number, mass, lattice constant
+    /// and lattice type of the element, then `Nrho drho Nr dr cutoff`, followed by
+    /// `F(rho)` at `Nrho` densities, the effective charge `Z(r)` at `Nr` distances and
+    /// `rho(r)` at `Nr` distances, all spaced from zero. The pair potential between two
+    /// elements is `Zi(r) Zj(r) / r`, converted from Hartree Bohr to eV Angstrom, and
+    /// the cutoff is the largest of the files.
+    pub fn from_funcfl(paths: &[&str]) -> Self {
+        let elements = paths
+            .iter()
+            .map(|&path| {
+                let contents = fs::read_to_string(path)
+                    .unwrap_or_else(|_| panic!("Could not read funcfl file {}", path));
+                parse_funcfl(path, &contents)
+            })
+            .collect();
+        combine_funcfl(elements)
+    }
+    /// The names of the elements, in the order of the file
+    pub fn elements(&self) -> Vec<&str> {
+        self.elements.iter().map(|e| e.name.as_str()).collect()
+    }
+    /// The mass of the given element, as given in the file
+    pub fn mass(&self, element: &str) -> f64 {
+        self.elements[self.element_idx(element)].mass
+    }
+    fn element_idx(&self, element: &str) -> usize {
+        self.elements
+            .iter()
+            .position(|e| e.name == element)
+            .unwrap_or_else(|| panic!("Element {} is not in the EAM file", element))
+    }
+    fn build_splines(&mut self) {
+        let ends = [None, None];
+        self.element_splines = self
+            .elements
+            .iter()
+            .map(|e| ElementSplines {
+                embedding: UniformSpline::from_values(0.0, e.drho, e.embedding.clone(), ends),
+                rho_max: e.drho * (e.embedding.len() - 1) as f64,
+                density: UniformSpline::from_values(0.0, e.dr, e.density.clone(), ends),
+            })
+            .collect();
+        let n = self.elements.len();
+        self.pair_splines = (0..n * n)
+            .map(|idx| {
+                let [i, j] = [(idx / n).max(idx % n), (idx / n).min(idx % n)];
+                let values = self.r_phi[i * (i + 1) / 2 + j].clone();
+                UniformSpline::from_values(0.0, self.pair_dr, values, ends)
+            })
+            .collect();
+    }
+    /// The embedding energy of an element and its derivative at the given density
+    fn embed(&self, element: usize, rho: f64) -> [f64; 2] {
+        let splines = &self.element_splines[element];
+        let rho_max = splines.rho_max;
+        if rho > rho_max {
+            let fp = splines.embedding.derivative(rho_max);
+            [splines.embedding.value(rho_max) + fp * (rho - rho_max), fp]
+        } else {
+            [
+                splines.embedding.value(rho),
+                splines.embedding.derivative(rho),
+            ]
+        }
+    }
+    /// The pair energy between two elements and its derivative at the given distance
+    fn phi(&self, elementi: usize, elementj: usize, r: f64) -> [f64; 2] {
+        let spline = &self.pair_splines[elementi * self.elements.len() + elementj];
+        let phi = spline.value(r) / r;
+        [phi, (spline.derivative(r) - phi) / r]
+    }
+    /// The magnitude of the force between two atoms divided by their distance, positive
+    /// when repulsive
+    fn force_over_r(&self, i: usize, j: usize, r: f64, elementi: usize, elementj: usize) -> f64 {
+        let densityi = &self.element_splines[elementi].density;
+        let densityj = &self.element_splines[elementj].density;
+        let dphi = self.phi(elementi, elementj, r)[1];
+        -(self.fp[i] * densityj.derivative(r) + self.fp[j] * densityi.derivative(r) + dphi) / r
+    }
+    fn check_per_atom_values<T: AtomType>(&self, atoms: &Atoms<T>) {
+        assert_eq!(
+            self.fp.len(),
+            atoms.num_total_atoms(),
+            "Per-atom values should be computed for the current atoms"
+        );
+    }
+    /// The pairs of `for_each_pair` within the cutoff, passed with the distance rather than
+    /// its square and with the elements of the atoms
+    fn for_each_pair<T: AtomType>(
+        &self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+        mut f: impl FnMut(usize, usize, [f64; 3], f64, usize, usize),
+    ) {
+        let cutoff2 = self.cutoff * self.cutoff;
+        for_each_pair(atoms, neighbor_list, |i, j, r, r2| {
+            if r2 >= cutoff2 {
+                return;
+            }
+            let elementi = self.type_elements[atoms.types[i]].expect("Element should be set");
+            let elementj = self.type_elements[atoms.types[j]].expect("Element should be set");
+            f(i, j, r, r2.sqrt(), elementi, elementj);
+        });
+    }
+}
+
+impl<T: AtomType> AtomicPotentialTrait<T> for EAM {
+    /// The name of the element of a type
+    type Coeff = String;
+    fn new() -> Self {
+        Self::new(1.0, Vec::new(), 1.0, Vec::new())
+    }
+    fn cutoff_distance(&self) -> f64 {
+        self.cutoff
+    }
+    /// Sum the densities at each atom, including the contributions of pairs listed on
+    /// other processes, and communicate the derivatives of the embedding energies to the
+    /// ghost atoms for the forces
+    fn compute_per_atom_values(
+        &mut self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+        _container: &Container,
+        comm: &dyn Comm,
+    ) {
+        let mut rho = vec![0.0; atoms.num_total_atoms()];
+        self.for_each_pair(atoms, neighbor_list, |i, j, _, r, elementi, elementj| {
+            rho[i] += self.element_splines[elementj].density.value(r);
+            rho[j] += self.element_splines[elementi].density.value(r);
+        });
+        comm.reverse_comm(&mut rho);
+
+        let mut fp = vec![0.0; atoms.num_total_atoms()];
+        let mut embedding_energies = vec![0.0; atoms.nlocal];
+        for i in 0..atoms.nlocal {
+            let element = self.type_elements[atoms.types[i]].expect("Element should be set");
+            [embedding_energies[i], fp[i]] = self.embed(element, rho[i]);
+        }
+        comm.forward_comm(&mut fp);
+        self.fp = fp;
+        self.embedding_energies = embedding_energies;
+    }
+    fn compute_forces(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> Vec<[f64; 3]> {
+        self.check_per_atom_values(atoms);
+        let mut forces = vec![[0.0; 3]; atoms.num_total_atoms()];
+        self.for_each_pair(atoms, neighbor_list, |i, j, r, dist, elementi, elementj| {
+            let f_over_r = self.force_over_r(i, j, dist, elementi, elementj);
+            for k in 0..3 {
+                forces[i][k] += r[k] * f_over_r;
+                forces[j][k] -= r[k] * f_over_r;
+            }
+        });
+        forces
+    }
+    fn compute_potential_energy(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> f64 {
+        self.check_per_atom_values(atoms);
+        let mut energy: f64 = self.embedding_energies.iter().sum();
+        self.for_each_pair(atoms, neighbor_list, |_, _, _, dist, elementi, elementj| {
+            energy += self.phi(elementi, elementj, dist)[0];
+        });
+        energy
+    }
+    fn compute_virial(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> [f64; 6] {
+        self.check_per_atom_values(atoms);
+        let mut virial = [0.0; 6];
+        self.for_each_pair(atoms, neighbor_list, |i, j, r, dist, elementi, elementj| {
+            let pair = pair_virial(r, self.force_over_r(i, j, dist, elementi, elementj));
+            for k in 0..6 {
+                virial[k] += pair[k];
+            }
+        });
+        virial
+    }
+    /// The embedding energy of each owned atom plus half the pair energy of each pair
+    fn compute_per_atom_energy(
+        &self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+    ) -> Option<Vec<f64>> {
+        self.check_per_atom_values(atoms);
+        let mut energies = vec![0.0; atoms.num_total_atoms()];
+        energies[..atoms.nlocal].copy_from_slice(&self.embedding_energies);
+        self.for_each_pair(atoms, neighbor_list, |i, j, _, dist, elementi, elementj| {
+            let half_energy = 0.5 * self.phi(elementi, elementj, dist)[0];
+            energies[i] += half_energy;
+            energies[j] += half_energy;
+        });
+        Some(energies)
+    }
+    fn compute_per_atom_virial(
+        &self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+    ) -> Option<Vec<[f64; 6]>> {
+        self.check_per_atom_values(atoms);
+        let mut virials = vec![[0.0; 6]; atoms.num_total_atoms()];
+        self.for_each_pair(atoms, neighbor_list, |i, j, r, dist, elementi, elementj| {
+            let half_f_over_r = 0.5 * self.force_over_r(i, j, dist, elementi, elementj);
+            let pair = pair_virial(r, half_f_over_r);
+            for k in 0..6 {
+                virials[i][k] += pair[k];
+                virials[j][k] += pair[k];
+            }
+        });
+        Some(virials)
+    }
+    fn num_types(&self) -> usize {
+        self.num_types
+    }
+    fn set_num_types(&mut self, num_types: usize) {
+        self.type_elements.resize(num_types, None);
+        self.num_types = num_types;
+    }
+    fn all_set(&self) -> bool {
+        self.type_elements.iter().all(|e| e.is_some())
+    }
+    /// Elements are assigned per type, so both type indices should be the same
+    fn set_coeff(&mut self, typei: usize, typej: usize, coeff: &Self::Coeff) {
+        assert!(
+            typei < self.num_types && typej < self.num_types,
+            "Type indices should be less than the number of types (0-indexed)"
+        );
+        assert_eq!(
+            typei, typej,
+            "EAM elements are assigned per type, so both type indices should be the same"
+        );
+        self.type_elements[typei] = Some(self.element_idx(coeff));
+    }
+    fn write_restart(&self, writer: &mut RestartWriter) -> io::Result<()> {
+        writer.write_f64(self.cutoff)?;
+        writer.write_usize(self.elements.len())?;
+        for element in &self.elements {
+            writer.write_string(&element.name)?;
+            writer.write_f64(element.mass)?;
+            writer.write_f64(element.drho)?;
+            writer.write_f64s(&element.embedding)?;
+            writer.write_f64(element.dr)?;
+            writer.write_f64s(&element.density)?;
+        }
+        writer.write_f64(self.pair_dr)?;
+        for r_phi in &self.r_phi {
+            writer.write_f64s(r_phi)?;
+        }
+        writer.write_usize(self.num_types)?;
+        for element in &self.type_elements {
+            writer.write_bool(element.is_some())?;
+            writer.write_usize(element.unwrap_or(0))?;
+        }
+        Ok(())
+    }
+    fn read_restart(&mut self, reader: &mut RestartReader) -> io::Result<()> {
+        let cutoff = reader.read_f64()?;
+        let num_elements = reader.read_usize()?;
+        let elements = (0..num_elements)
+            .map(|_| {
+                Ok(EAMElement {
+                    name: reader.read_string()?,
+                    mass: reader.read_f64()?,
+                    drho: reader.read_f64()?,
+                    embedding: reader.read_f64s()?,
+                    dr: reader.read_f64()?,
+                    density: reader.read_f64s()?,
+                })
+            })
+            .collect::<io::Result<Vec<EAMElement>>>()?;
+        let pair_dr = reader.read_f64()?;
+        let r_phi = (0..num_elements * (num_elements + 1) / 2)
+            .map(|_| reader.read_f64s())
+            .collect::<io::Result<Vec<Vec<f64>>>>()?;
+        *self = Self::new(cutoff, elements, pair_dr, r_phi);
+        self.num_types = reader.read_usize()?;
+        self.type_elements = (0..self.num_types)
+            .map(|_| {
+                let is_set = reader.read_bool()?;
+                let element = reader.read_usize()?;
+                Ok(is_set.then_some(element))
+            })
+            .collect::<io::Result<Vec<Option<usize>>>>()?;
+        Ok(())
+    }
+}
+
+/// Whitespace-separated values of a DYNAMO file, read in order
+struct Fields<'a> {
+    fields: std::str::SplitWhitespace<'a>,
+    file: &'a str,
+}
+impl<'a> Fields<'a> {
+    fn next<F: std::str::FromStr>(&mut self) -> F {
+        let field = self
+            .fields
+            .next()
+            .unwrap_or_else(|| panic!("Unexpected end of {} file", self.file));
+        field
+            .parse()
+            .unwrap_or_else(|_| panic!("Invalid value {} in {} file", field, self.file))
+    }
+    fn take(&mut self, n: usize) -> Vec<f64> {
+        (0..n).map(|_| self.next()).collect()
+    }
+    /// The grid `Nrho drho Nr dr cutoff`
+    fn grid(&mut self) -> (usize, f64, usize, f64, f64) {
+        let (nrho, drho, nr, dr, cutoff) = (
+            self.next(),
+            self.next(),
+            self.next(),
+            self.next(),
+            self.next(),
+        );
+        assert!(
+            nrho >= 2 && nr >= 2,
+            "{} files should tabulate at least 2 densities and distances",
+            self.file
+        );
+        (nrho, drho, nr, dr, cutoff)
+    }
+    /// The line of atomic number, mass, lattice constant and lattice type, returning the
+    /// mass
+    fn element_mass(&mut self) -> f64 {
+        let _number: f64 = self.next();
+        let mass = self.next();
+        let _lattice_constant: f64 = self.next();
+        let _lattice_type: String = self.next();
+        mass
+    }
+}
+
+fn parse_setfl(contents: &str) -> EAM {
+    let body: String = contents.lines().skip(3).flat_map(|l| [l, "\n"]).collect();
+    let mut fields = Fields {
+        fields: body.split_whitespace(),
+        file: "setfl",
+    };
+    let num_elements: usize = fields.next();
+    let names: Vec<String> = (0..num_elements).map(|_| fields.next()).collect();
+    let (nrho, drho, nr, dr, cutoff) = fields.grid();
+    let elements = names
+        .into_iter()
+        .map(|name| {
+            let mass = fields.element_mass();
+            EAMElement {
+                name,
+                mass,
+                drho,
+                embedding: fields.take(nrho),
+                dr,
+                density: fields.take(nr),
+            }
+        })
+        .collect();
+    let r_phi = (0..num_elements * (num_elements + 1) / 2)
+        .map(|_| fields.take(nr))
+        .collect();
+    EAM::new(cutoff, elements, dr, r_phi)
+}
+
+fn parse_funcfl(name: &str, contents: &str) -> FuncflElement {
+    let mut fields = Fields {
+        fields: contents
+            .split_once('\n')
+            .map_or("", |(_, body)| body)
+            .split_whitespace(),
+        file: "funcfl",
+    };
+    let mass = fields.element_mass();
+    let (nrho, drho, nr, dr, cutoff) = fields.grid();
+    let embedding = fields.take(nrho);
+    let charge = fields.take(nr);
+    FuncflElement {
+        element: EAMElement {
+            name: name.to_string(),
+            mass,
+            drho,
+            embedding,
+            dr,
+            density: fields.take(nr),
+        },
+        charge,
+        cutoff,
+    }
+}
+
+/// Combine elements read from `funcfl` files, tabulating the pair potentials between
+/// them at the finest spacing of distances up to the largest cutoff
+fn combine_funcfl(funcfl: Vec<FuncflElement>) -> EAM {
+    assert!(
+        !funcfl.is_empty(),
+        "At least one funcfl file should be given"
+    );
+    let cutoff = funcfl.iter().map(|f| f.cutoff).fold(0.0, f64::max);
+    let dr = funcfl.iter().map(|f| f.element.dr).fold(f64::MAX, f64::min);
+    let nr = (cutoff / dr).ceil() as usize + 1;
+    let charges: Vec<UniformSpline> = funcfl
+        .iter()
+        .map(|f| UniformSpline::from_values(0.0, f.element.dr, f.charge.clone(), [None, None]))
+        .collect();
+    // The charge is zero past the tabulated distances
+    let charge = |i: usize, r: f64| {
+        let r_max = funcfl[i].element.dr * (funcfl[i].charge.len() - 1) as f64;
+        if r > r_max {
+            0.0
+        } else {
+            charges[i].value(r)
+        }
+    };
+    let mut r_phi = Vec::new();
+    for i in 0..funcfl.len() {
+        for j in 0..=i {
+            r_phi.push(
+                (0..nr)
+                    .map(|k| {
+                        let r = k as f64 * dr;
+                        HARTREE_BOHR * charge(i, r) * charge(j, r)
+                    })
+                    .collect(),
+            );
+        }
+    }
+    let elements = funcfl.into_iter().map(|f| f.element).collect();
+    EAM::new(cutoff, elements, dr, r_phi)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{
+        atom_type::Basic,
+        atomic::tests::{check_forces, energy},
+        lattice::{Lattice, FCC},
+        region::Rect,
+        simulation::{
+            tests::{assert_forces_agree, gather_forces, push_forces, run_threads, Forces},
+            Simulation,
+        },
+    };
+
+    fn embedding(element: usize, rho: f64) -> f64 {
+        -(1.0 + element as f64) * rho.sqrt() + 0.1 * rho * rho
+    }
+    fn density(element: usize, r: f64) -> f64 {
+        (1.0 + 0.5 * element as f64) * (-2.0 * (r - 1.0)).exp() * (3.0 - r).powi(2)
+    }
+    fn r_phi(elements: usize, r: f64) -> f64 {
+        let e = (-1.5 * (r - 1.2)).exp();
+        (1.0 + 0.2 * elements as f64) * r * e * (e - 2.0) * (3.0 - r).powi(2)
+    }
+
+    /// A setfl file with elements A and B tabulated from the functions above
+    fn setfl() -> String {
+        let values = |f: &dyn Fn(f64) -> f64, n: usize, dx: f64| {
+            (0..n)
+                .map(|k| format!("{:e}", f(k as f64 * dx)))
+                .collect::<Vec<String>>()
+                .join("\n")
+        };
+        let mut contents =
+            String::from("comment\ncomment\ncomment\n2 A B\n600 0.01 400 0.01 3.0\n");
+        for element in 0..2 {
+            contents += &format!("{} 1.5 3.6 fcc\n", element + 1);
+            contents += &values(&|rho| embedding(element, rho), 600, 0.01);
+            contents += "\n";
+            contents += &values(&|r| density(element, r), 400, 0.01);
+            contents += "\n";
+        }
+        for elements in 0..3 {
+            contents += &values(&|r| r_phi(elements, r), 400, 0.01);
+            contents += "\n";
+        }
+        contents
+    }
+
+    #[test]
+    fn test_files() {
+        let eam = parse_setfl(&setfl());
+        assert_eq!(eam.elements(), vec!["A", "B"]);
+        assert_eq!(eam.mass("B"), 1.5);
+        assert_eq!(eam.cutoff, 3.0);
+        for r in [1.0, 1.37, 2.5] {
+            assert!((eam.phi(1, 0, r)[0] - r_phi(1, r) / r).abs() < 1e-6);
+            assert!((eam.phi(1, 1, r)[0] - r_phi(2, r) / r).abs() < 1e-6);
+            let spline = &eam.element_splines[1].density;
+            assert!((spline.value(r) - density(1, r)).abs() < 1e-6);
+        }
+        // Embedding energies are extrapolated linearly past the tabulated densities
+        let [e, fp] = eam.embed(0, 6.0);
+        let [e_max, fp_max] = eam.embed(0, 5.99);
+        assert!((fp - fp_max).abs() < 1e-6 && (e - e_max - 0.01 * fp).abs() < 1e-6);
+
+        let mut funcfl = String::from("comment\n29 63.55 3.615 FCC\n3 0.5 4 0.5 1.5\n");
+        funcfl += "-1.0 -2.0 -2.5\n1.0 0.8\n0.6 0.4\n0.3 0.2 0.1 0.0\n";
+        let eam = combine_funcfl(vec![parse_funcfl("Cu", &funcfl)]);
+        assert_eq!(eam.elements(), vec!["Cu"]);
+        assert_eq!(eam.mass("Cu"), 63.55);
+        assert!((eam.phi(0, 0, 1.0)[0] - HARTREE_BOHR * 0.36).abs() < 1e-12);
+        assert!((eam.embed(0, 0.5)[0] + 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_forces() {
+        let mut eam = parse_setfl(&setfl());
+        <EAM as AtomicPotentialTrait<Basic>>::set_num_types(&mut eam, 2);
+        <EAM as AtomicPotentialTrait<Basic>>::set_coeff(&mut eam, 0, 0, &"A".to_string());
+        <EAM as AtomicPotentialTrait<Basic>>::set_coeff(&mut eam, 1, 1, &"B".to_string());
+        check_forces(&mut eam, false, energy);
+    }
+
+    static FORCES: Mutex<Vec<Forces>> = Mutex::new(Vec::new());
+
+    /// The derivatives of the embedding energies are communicated to ghost atoms on other
+    /// processes, so the forces do not depend on the number of processes
+    #[test]
+    fn test_threads() {
+        fn run(mut sim: Simulation<Basic, EAM>) {
+            let lattice = FCC::new(2.0);
+            let rect = Rect::from_lattice(&lattice, [4; 3]);
+            let (_, coords) = lattice.coords_within_region(&rect, &[0.0; 3]);
+            // Deterministic pseudo-random displacements into the box, so that the forces
+            // are nonzero
+            let coords: Vec<_> = coords
+                .iter()
+                .enumerate()
+                .map(|(id, c)| {
+                    [0, 1, 2].map(|k| c[k] + 0.05 * (1.0 + ((id * 7 + k * 13) as f64).sin()))
+                })
+                .collect();
+            let eam = parse_setfl(&setfl());
+            sim.set_atom_types(vec![Basic::new(eam.mass("A")), Basic::new(eam.mass("B"))]);
+            sim.set_atomic_potential(eam);
+            sim.set_atomic_coeff(0, 0, &"A".to_string());
+            sim.set_atomic_coeff(1, 1, &"B".to_string());
+            sim.set_container(Container::from_rect_periodic(rect));
+            sim.add_atoms((0..coords.len()).map(|i| i % 3 % 2).collect(), coords);
+            sim.set_nl_skin_distance(0.3);
+            sim.run(0);
+            push_forces(&sim, &FORCES);
+        }
+        let runs: Vec<_> = run_threads(&[1, 8], run, &FORCES)
+            .into_iter()
+            .map(gather_forces)
+            .collect();
+        assert_eq!(runs[0].2.len(), 256);
+        assert!(runs[0].2.iter().flatten().any(|f| f.abs() > 0.1));
+        assert_forces_agree(&runs[1], &runs[0], 1e-10);
+    }
+}
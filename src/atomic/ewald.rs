@@ -0,0 +1,452 @@
+use std::f64::consts::PI;
+
+use super::{
+    coulomb::{for_each_charged_pair, reciprocal_vectors, screened, splitting_parameter},
+    *,
+};
+use crate::{region::Region, utils::Axis};
+
+/// A reciprocal lattice vector of the Ewald sum
+#[derive(Clone, Debug)]
+struct KVector {
+    /// Multiples of the reciprocal box vectors
+    n: [i32; 3],
+    k: [f64; 3],
+    /// `4 pi / V exp(-k^2 / 4 alpha^2) / k^2`, for the sum over half of the vectors
+    factor: f64,
+    /// Factor of the virial of the vector, ordered as the virial
+    virial: [f64; 6],
+}
+
+/// Coulomb potential between the charges of the atoms of a box periodic along every
+/// axis, by Ewald summation as with LAMMPS `pair_style coul/long` and `kspace_style
+/// ewald`. The interactions are split into a real-space sum of `erfc(alpha r) / r` over
+/// pairs within the cutoff and a reciprocal-space sum over the structure factors of the
+/// whole box, whose cost grows faster than the number of atoms, so it is suited to
+/// small systems.
+///
+/// The splitting parameter `alpha` and the number of reciprocal vectors are chosen, as
+/// in LAMMPS, so that the root-mean-square error in the forces is about the given
+/// accuracy relative to the force between two unit charges a unit distance apart. They
+/// are chosen on the first force computation and again if the number of atoms or the
+/// sum of squared charges change. A system with a net charge is neutralized by a
+/// uniform background charge.
+///
+/// The potential has no per-type coefficients. The Coulomb constant is 1 unless set.
+///
+/// ```rust
+/// use jmd::atomic::{Ewald, COULOMB_METAL};
+///
+/// let mut ewald = Ewald::new(10.0, 1e-5);
+/// ewald.set_coulomb_constant(COULOMB_METAL);
+/// ```
+pub struct Ewald {
+    num_types: usize,
+    cutoff: f64,
+    accuracy: f64,
+    coulomb_constant: f64,
+    /// The number of atoms and sum of squared charges that `alpha` and `kmax` were
+    /// chosen for
+    tuned_for: Option<(usize, f64)>,
+    alpha: f64,
+    /// The largest multiple of each reciprocal box vector
+    kmax: [usize; 3],
+    /// Reciprocal box vectors
+    reciprocal: [[f64; 3]; 3],
+    volume: f64,
+    total_charge: f64,
+    /// Half of the reciprocal lattice vectors within the cutoff, with the other half
+    /// given by their negatives
+    kvectors: Vec<KVector>,
+    /// Sum of `q exp(i k . r)` over all atoms for each vector, as real and imaginary parts
+    structure_factors: Vec<[f64; 2]>,
+}
+impl Ewald {
+    /// The potential with the given real-space cutoff and relative accuracy of the forces
+    pub fn new(cutoff: f64, accuracy: f64) -> Self {
+        assert!(cutoff > 0.0, "Cutoff should be positive, found {}", cutoff);
+        assert!(
+            accuracy > 0.0 && accuracy < 1.0,
+            "Accuracy should be between 0 and 1, found {}",
+            accuracy
+        );
+        Self {
+            num_types: 0,
+            cutoff,
+            accuracy,
+            coulomb_constant: 1.0,
+            tuned_for: None,
+            alpha: 0.0,
+            kmax: [0; 3],
+            reciprocal: [[0.0; 3]; 3],
+            volume: 0.0,
+            total_charge: 0.0,
+            kvectors: Vec::new(),
+            structure_factors: Vec::new(),
+        }
+    }
+    /// Set the constant `1 / (4 pi epsilon_0)` converting products of charges over
+    /// distances to energies, such as `COULOMB_METAL`
+    pub fn set_coulomb_constant(&mut self, coulomb_constant: f64) {
+        self.coulomb_constant = coulomb_constant;
+    }
+    /// The splitting parameter, chosen on the first force computation
+    pub fn alpha(&self) -> f64 {
+        self.alpha
+    }
+    /// The largest multiple of each reciprocal box vector, chosen with `alpha`
+    pub fn kmax(&self) -> [usize; 3] {
+        self.kmax
+    }
+
+    /// Choose the splitting parameter and the number of reciprocal vectors for the given
+    /// accuracy, from the estimates of the errors in the real-space and reciprocal-space
+    /// forces of Kolafa and Perram
+    fn tune(&mut self, num_atoms: usize, q2: f64, container: &Container) {
+        let n = num_atoms as f64;
+        let volume = container.rect().volume();
+        self.alpha = splitting_parameter(self.accuracy, self.cutoff, num_atoms, q2, volume);
+        let alpha = self.alpha;
+        let rms = |kmax: usize, length: f64| {
+            let k = kmax as f64;
+            2.0 * q2 * alpha / length
+                * (1.0 / (PI * k * n)).sqrt()
+                * (-PI * PI * k * k / (alpha * alpha * length * length)).exp()
+        };
+        self.kmax = container.face_spacings().map(|length| {
+            let mut kmax = 1;
+            while rms(kmax, length) > self.accuracy {
+                kmax += 1;
+            }
+            kmax
+        });
+        self.tuned_for = Some((num_atoms, q2));
+    }
+    /// Set the reciprocal vectors of the current box, within the sphere holding the
+    /// largest multiples of the reciprocal box vectors
+    fn set_kvectors(&mut self, container: &Container) {
+        self.volume = container.rect().volume();
+        self.reciprocal = reciprocal_vectors(container);
+        let norm2 = |v: [f64; 3]| v[0] * v[0] + v[1] * v[1] + v[2] * v[2];
+        let k2_max = (0..3)
+            .map(|m| norm2(self.reciprocal[m]) * (self.kmax[m] * self.kmax[m]) as f64)
+            .fold(0.0, f64::max)
+            * 1.00001;
+
+        let [k0, k1, k2] = self.kmax.map(|k| k as i32);
+        let alpha2 = self.alpha * self.alpha;
+        self.kvectors.clear();
+        for n0 in 0..=k0 {
+            for n1 in -k1..=k1 {
+                for n2 in -k2..=k2 {
+                    // Only one of each pair of opposite vectors
+                    if n0 == 0 && (n1 < 0 || (n1 == 0 && n2 <= 0)) {
+                        continue;
+                    }
+                    let n = [n0, n1, n2];
+                    let k: [f64; 3] = [0, 1, 2]
+                        .map(|j| (0..3).map(|m| n[m] as f64 * self.reciprocal[m][j]).sum());
+                    let k2 = norm2(k);
+                    if k2 > k2_max {
+                        continue;
+                    }
+                    let c = 2.0 * (1.0 / k2 + 0.25 / alpha2);
+                    self.kvectors.push(KVector {
+                        n,
+                        k,
+                        factor: 4.0 * PI / self.volume * (-0.25 * k2 / alpha2).exp() / k2,
+                        virial: [
+                            1.0 - c * k[0] * k[0],
+                            1.0 - c * k[1] * k[1],
+                            1.0 - c * k[2] * k[2],
+                            -c * k[0] * k[1],
+                            -c * k[0] * k[2],
+                            -c * k[1] * k[2],
+                        ],
+                    });
+                }
+            }
+        }
+    }
+    /// Call the function with the index of each owned charged atom, and `exp(i k . r)`
+    /// of the atom for each reciprocal vector, as real and imaginary parts
+    fn for_each_atom_phases<T: AtomType>(
+        &self,
+        atoms: &Atoms<T>,
+        mut f: impl FnMut(usize, &[[f64; 2]]),
+    ) {
+        let mut phases = vec![[0.0; 2]; self.kvectors.len()];
+        for i in 0..atoms.nlocal {
+            if atoms.charges[i] == 0.0 {
+                continue;
+            }
+            let position = atoms.positions[i];
+            // exp(i n b_m . r) for n from 0 to the largest multiple, along each m
+            let powers: Vec<Vec<[f64; 2]>> = (0..3)
+                .map(|m| {
+                    let b = self.reciprocal[m];
+                    let theta = b[0] * position[0] + b[1] * position[1] + b[2] * position[2];
+                    let unit = [theta.cos(), theta.sin()];
+                    let mut powers = vec![[1.0, 0.0]];
+                    for n in 0..self.kmax[m] {
+                        powers.push(complex_mul(powers[n], unit));
+                    }
+                    powers
+                })
+                .collect();
+            let power = |m: usize, n: i32| {
+                let [re, im] = powers[m][n.unsigned_abs() as usize];
+                if n < 0 {
+                    [re, -im]
+                } else {
+                    [re, im]
+                }
+            };
+            for (phase, kvector) in phases.iter_mut().zip(&self.kvectors) {
+                let [n0, n1, n2] = kvector.n;
+                *phase = complex_mul(complex_mul(power(0, n0), power(1, n1)), power(2, n2));
+            }
+            f(i, &phases);
+        }
+    }
+    fn check_per_atom_values(&self) {
+        assert!(
+            self.tuned_for.is_some() && self.structure_factors.len() == self.kvectors.len(),
+            "Per-atom values should be computed before the forces"
+        );
+    }
+    /// Each reciprocal vector with the real and imaginary parts of `exp(-i k . r) S(k)`,
+    /// for an atom with the given phases
+    fn terms<'a>(
+        &'a self,
+        phases: &'a [[f64; 2]],
+    ) -> impl Iterator<Item = (&'a KVector, [f64; 2])> + 'a {
+        self.kvectors
+            .iter()
+            .zip(&self.structure_factors)
+            .zip(phases)
+            .map(|((kvector, s), p)| {
+                (
+                    kvector,
+                    [p[0] * s[0] + p[1] * s[1], p[0] * s[1] - p[1] * s[0]],
+                )
+            })
+    }
+    /// The energy of each owned atom in the reciprocal-space sum, with the self energy of
+    /// its charge and its share of the energy of the neutralizing background
+    fn reciprocal_energy(&self, q: f64, phases: &[[f64; 2]]) -> f64 {
+        let energy: f64 = self
+            .terms(phases)
+            .map(|(kvector, [re, _])| kvector.factor * re)
+            .sum();
+        self.coulomb_constant * (q * (energy - q * self.alpha / PI.sqrt()) + self.background(q))
+    }
+    /// The share of the energy of the neutralizing background of an atom with the given
+    /// charge
+    fn background(&self, q: f64) -> f64 {
+        -PI * q * self.total_charge / (2.0 * self.volume * self.alpha * self.alpha)
+    }
+}
+
+fn complex_mul(a: [f64; 2], b: [f64; 2]) -> [f64; 2] {
+    [a[0] * b[0] - a[1] * b[1], a[0] * b[1] + a[1] * b[0]]
+}
+
+impl<T: AtomType> AtomicPotentialTrait<T> for Ewald {
+    /// No coefficients are needed
+    type Coeff = ();
+    fn new() -> Self {
+        Self::new(1.0, 1e-5)
+    }
+    fn cutoff_distance(&self) -> f64 {
+        self.cutoff
+    }
+    /// Choose the splitting parameter if needed, and sum the structure factors over all
+    /// processes
+    fn compute_per_atom_values(
+        &mut self,
+        atoms: &Atoms<T>,
+        _neighbor_list: &NeighborList,
+        container: &Container,
+        comm: &dyn Comm,
+    ) {
+        assert!(
+            [Axis::X, Axis::Y, Axis::Z]
+                .iter()
+                .all(|&axis| container.is_periodic(axis)),
+            "Ewald summation needs a box periodic along every axis"
+        );
+        let charges = &atoms.charges[..atoms.nlocal];
+        let mut sums = [charges.iter().map(|q| q * q).sum(), charges.iter().sum()];
+        comm.sum(&mut sums);
+        let [q2, total_charge] = sums;
+        let num_atoms = atoms.num_atoms_global;
+        let retune = match self.tuned_for {
+            Some((n, tuned_q2)) => n != num_atoms || (q2 - tuned_q2).abs() > 1e-10 * q2,
+            None => true,
+        };
+        if retune {
+            self.tune(num_atoms, q2, container);
+        }
+        self.total_charge = total_charge;
+        self.set_kvectors(container);
+
+        let mut structure_factors = vec![0.0; 2 * self.kvectors.len()];
+        self.for_each_atom_phases(atoms, |i, phases| {
+            let q = atoms.charges[i];
+            for (s, phase) in structure_factors.chunks_exact_mut(2).zip(phases) {
+                s[0] += q * phase[0];
+                s[1] += q * phase[1];
+            }
+        });
+        comm.sum(&mut structure_factors);
+        self.structure_factors = structure_factors
+            .chunks_exact(2)
+            .map(|s| [s[0], s[1]])
+            .collect();
+    }
+    fn compute_forces(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> Vec<[f64; 3]> {
+        self.check_per_atom_values();
+        let mut forces = vec![[0.0; 3]; atoms.num_total_atoms()];
+        for_each_charged_pair(atoms, neighbor_list, self.cutoff, |i, j, r, dist, qq| {
+            let f_over_r = self.coulomb_constant * qq * screened(self.alpha, dist)[1] / dist;
+            for k in 0..3 {
+                forces[i][k] += r[k] * f_over_r;
+                forces[j][k] -= r[k] * f_over_r;
+            }
+        });
+        self.for_each_atom_phases(atoms, |i, phases| {
+            let prefactor = 2.0 * self.coulomb_constant * atoms.charges[i];
+            for (kvector, [_, im]) in self.terms(phases) {
+                let f = -prefactor * kvector.factor * im;
+                for (force, k) in forces[i].iter_mut().zip(kvector.k) {
+                    *force += f * k;
+                }
+            }
+        });
+        forces
+    }
+    fn compute_potential_energy(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> f64 {
+        self.compute_per_atom_energy(atoms, neighbor_list)
+            .expect("Per-atom energies are supported")
+            .iter()
+            .sum()
+    }
+    fn compute_virial(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> [f64; 6] {
+        let virials = self
+            .compute_per_atom_virial(atoms, neighbor_list)
+            .expect("Per-atom virials are supported");
+        [0, 1, 2, 3, 4, 5].map(|k| virials.iter().map(|v| v[k]).sum())
+    }
+    /// The reciprocal-space energy of each reciprocal vector is split between the owned
+    /// atoms by their contributions to its structure factor, and the self energies and
+    /// the energy of the neutralizing background are given to the owned atoms
+    fn compute_per_atom_energy(
+        &self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+    ) -> Option<Vec<f64>> {
+        self.check_per_atom_values();
+        let mut energies = vec![0.0; atoms.num_total_atoms()];
+        for_each_charged_pair(atoms, neighbor_list, self.cutoff, |i, j, _, dist, qq| {
+            let half_energy = 0.5 * self.coulomb_constant * qq * screened(self.alpha, dist)[0];
+            energies[i] += half_energy;
+            energies[j] += half_energy;
+        });
+        self.for_each_atom_phases(atoms, |i, phases| {
+            energies[i] += self.reciprocal_energy(atoms.charges[i], phases);
+        });
+        Some(energies)
+    }
+    /// The reciprocal-space virial is split between the owned atoms as the energy
+    fn compute_per_atom_virial(
+        &self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+    ) -> Option<Vec<[f64; 6]>> {
+        self.check_per_atom_values();
+        let mut virials = vec![[0.0; 6]; atoms.num_total_atoms()];
+        for_each_charged_pair(atoms, neighbor_list, self.cutoff, |i, j, r, dist, qq| {
+            let f_over_r = self.coulomb_constant * qq * screened(self.alpha, dist)[1] / dist;
+            let pair = pair_virial(r, 0.5 * f_over_r);
+            for k in 0..6 {
+                virials[i][k] += pair[k];
+                virials[j][k] += pair[k];
+            }
+        });
+        self.for_each_atom_phases(atoms, |i, phases| {
+            let q = atoms.charges[i];
+            for (kvector, [re, _]) in self.terms(phases) {
+                let energy = self.coulomb_constant * q * kvector.factor * re;
+                for (v, c) in virials[i].iter_mut().zip(kvector.virial) {
+                    *v += energy * c;
+                }
+            }
+            // The background energy is inversely proportional to the volume
+            let background = self.coulomb_constant * self.background(q);
+            for v in &mut virials[i][..3] {
+                *v += background;
+            }
+        });
+        Some(virials)
+    }
+    fn num_types(&self) -> usize {
+        self.num_types
+    }
+    fn set_num_types(&mut self, num_types: usize) {
+        self.num_types = num_types;
+    }
+    fn all_set(&self) -> bool {
+        true
+    }
+    fn set_coeff(&mut self, _typei: usize, _typej: usize, _coeff: &Self::Coeff) {}
+    /// The splitting parameter is chosen again after reading
+    fn write_restart(&self, writer: &mut RestartWriter) -> io::Result<()> {
+        writer.write_f64(self.cutoff)?;
+        writer.write_f64(self.accuracy)?;
+        writer.write_f64(self.coulomb_constant)
+    }
+    fn read_restart(&mut self, reader: &mut RestartReader) -> io::Result<()> {
+        self.cutoff = reader.read_f64()?;
+        self.accuracy = reader.read_f64()?;
+        self.coulomb_constant = reader.read_f64()?;
+        self.tuned_for = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atomic::{
+        coulomb::tests::{check_forces, rock_salt, MADELUNG_NACL},
+        tests::{energy, owned_forces},
+    };
+
+    #[test]
+    fn test_madelung() {
+        let mut ewald = Ewald::new(3.5, 1e-6);
+        let (atoms, container, nl) = rock_salt(2, 3.5, false, [0.0; 3]);
+        let total = energy(&mut ewald, &atoms, &container, &nl);
+        let per_ion = total / atoms.nlocal as f64;
+        assert!(
+            (per_ion + 0.5 * MADELUNG_NACL).abs() < 1e-5,
+            "Energy per ion {} should be {}",
+            per_ion,
+            -0.5 * MADELUNG_NACL
+        );
+        let forces = owned_forces(&ewald, &atoms, &nl);
+        assert!(forces.iter().flatten().all(|f| f.abs() < 1e-5));
+
+        // The same energy with most of the sum in reciprocal space
+        let mut ewald = Ewald::new(1.5, 1e-8);
+        let (atoms, container, nl) = rock_salt(2, 1.5, false, [0.0; 3]);
+        let other = energy(&mut ewald, &atoms, &container, &nl);
+        assert!((other - total).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_forces() {
+        check_forces(&mut Ewald::new(3.5, 1e-12), 2);
+    }
+}
@@ -7,23 +7,23 @@ pub struct LJCutCoeff {
     rcut: f64,
     sigma6: f64,
     rcut2: f64,
-    prefactor: f64,  // = -24 epsilon * sigma^6
-    correction: f64, // currently, only shift is supported
+    prefactor: f64, // = 24 epsilon * sigma^6
+    shift: f64,     // subtracted from the energy, set by `truncated`
+    /// Squared inner distance and denominator of the switching function, if any
+    switch: Option<[f64; 2]>,
 }
 impl LJCutCoeff {
     pub fn new(sigma: f64, epsilon: f64, rcut: f64) -> Self {
         let sigma6 = sigma * sigma * sigma * sigma * sigma * sigma;
-        let rcut2 = rcut * rcut;
-        let rcut6 = rcut2 * rcut2 * rcut2;
-        let correction = 2.0 * epsilon * sigma6 / rcut6 * (sigma6 / rcut6 - 1.0);
         Self {
             sigma,
             epsilon,
             rcut,
             rcut2: rcut * rcut,
             sigma6,
-            prefactor: -24.0 * epsilon * sigma6,
-            correction,
+            prefactor: 24.0 * epsilon * sigma6,
+            shift: 0.0,
+            switch: None,
         }
     }
     pub fn sigma(&self) -> f64 {
@@ -35,6 +35,135 @@ impl LJCutCoeff {
     pub fn rcut(&self) -> f64 {
         self.rcut
     }
+    /// The coefficient with the shift or switching of the given truncation scheme
+    fn truncated(self, truncation: Truncation) -> Self {
+        let unshifted = Self {
+            shift: 0.0,
+            switch: None,
+            ..self
+        };
+        match truncation {
+            Truncation::Unshifted | Truncation::Tail => unshifted,
+            Truncation::Shifted => Self {
+                shift: unshifted.unshifted_energy(self.rcut2),
+                ..unshifted
+            },
+            Truncation::Switched(inner) => {
+                let inner2 = inner * inner;
+                let denominator = (self.rcut2 - inner2).powi(3);
+                Self {
+                    switch: Some([inner2, denominator]),
+                    ..unshifted
+                }
+            }
+        }
+    }
+    /// The magnitude of the pair force divided by the distance, given the squared distance
+    fn force_over_r(&self, r2: f64) -> f64 {
+        let r6 = r2 * r2 * r2;
+        let f = self.prefactor / r6 / r2 * (2.0 * self.sigma6 / r6 - 1.0);
+        match self.switch {
+            Some([inner2, denominator]) if r2 > inner2 => {
+                // -d(U S)/dr / r = f S - U (dS/dr) / r
+                let ds = 12.0 * (self.rcut2 - r2) * (r2 - inner2) / denominator;
+                f * self.switching(r2) + self.unshifted_energy(r2) * ds
+            }
+            _ => f,
+        }
+    }
+    /// The pair energy, given the squared distance
+    fn energy(&self, r2: f64) -> f64 {
+        let energy = self.unshifted_energy(r2) - self.shift;
+        match self.switch {
+            Some([inner2, _]) if r2 > inner2 => energy * self.switching(r2),
+            _ => energy,
+        }
+    }
+    fn unshifted_energy(&self, r2: f64) -> f64 {
+        let r6 = r2 * r2 * r2;
+        4.0 * self.epsilon * self.sigma6 / r6 * (self.sigma6 / r6 - 1.0)
+    }
+    /// The XPLOR switching function, going from 1 at the inner distance to 0 at the
+    /// cutoff with zero slope at both ends, given a squared distance between them
+    fn switching(&self, r2: f64) -> f64 {
+        let [inner2, denominator] = self.switch.expect("Switching should be set");
+        let d = self.rcut2 - r2;
+        d * d * (self.rcut2 + 2.0 * r2 - 3.0 * inner2) / denominator
+    }
+    /// The integrals of `r^2 U(r)` and of `-r^3 dU/dr` from the cutoff to infinity
+    fn tail_integrals(&self) -> [f64; 2] {
+        if self.rcut == 0.0 {
+            return [0.0, 0.0];
+        }
+        let rcut3 = self.rcut2 * self.rcut;
+        let rcut9 = rcut3 * rcut3 * rcut3;
+        let repulsive = self.sigma6 * self.sigma6 / (9.0 * rcut9);
+        let attractive = self.sigma6 / (3.0 * rcut3);
+        [
+            4.0 * self.epsilon * (repulsive - attractive),
+            24.0 * self.epsilon * (2.0 * repulsive - attractive),
+        ]
+    }
+    /// The coefficient between two types, mixed from the coefficients of each type with
+    /// itself
+    fn mix(&self, other: &Self, mixing: Mixing) -> Self {
+        let sqrt_epsilon = (self.epsilon * other.epsilon).sqrt();
+        match mixing {
+            Mixing::Arithmetic => LJCutCoeff::new(
+                0.5 * (self.sigma + other.sigma),
+                sqrt_epsilon,
+                0.5 * (self.rcut + other.rcut),
+            ),
+            Mixing::Geometric => LJCutCoeff::new(
+                (self.sigma * other.sigma).sqrt(),
+                sqrt_epsilon,
+                (self.rcut * other.rcut).sqrt(),
+            ),
+            Mixing::Sixthpower => {
+                let sigma3 = self.sigma.powi(3) * other.sigma.powi(3);
+                let sum_sigma6 = self.sigma6 + other.sigma6;
+                let epsilon = if sum_sigma6 == 0.0 {
+                    0.0
+                } else {
+                    2.0 * sqrt_epsilon * sigma3 / sum_sigma6
+                };
+                LJCutCoeff::new(
+                    (0.5 * sum_sigma6).powf(1.0 / 6.0),
+                    epsilon,
+                    (0.5 * (self.rcut.powi(6) + other.rcut.powi(6))).powf(1.0 / 6.0),
+                )
+            }
+        }
+    }
+}
+
+/// Rule for the coefficients between two different types, given those of each type with
+/// itself, as in LAMMPS `pair_modify mix`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mixing {
+    /// Lorentz–Berthelot: arithmetic mean of sigma and the cutoff, geometric mean of
+    /// epsilon
+    Arithmetic,
+    /// Geometric mean of sigma, epsilon and the cutoff
+    Geometric,
+    /// Waldman–Hagler: sixth-power mean of sigma and the cutoff, with epsilon scaled by
+    /// `2 sigma_i^3 sigma_j^3 / (sigma_i^6 + sigma_j^6)`
+    Sixthpower,
+}
+
+/// How the potential is brought to zero at the cutoff
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Truncation {
+    /// Cut at the cutoff, so that the energy jumps to zero there
+    Unshifted,
+    /// Shifted by the energy at the cutoff, so that the energy is continuous
+    Shifted,
+    /// Unshifted, with analytic corrections to the energy and pressure for the
+    /// interactions beyond the cutoff, assuming a uniform density of each type
+    Tail,
+    /// Multiplied by the XPLOR (CHARMM) switching function between the given inner
+    /// distance and the cutoff, so that both the energy and the forces are continuous
+    Switched(f64),
 }
 
 /// Lennard-Jones 12-6 potential
@@ -43,6 +172,8 @@ pub struct LJCut {
     force_cutoff: f64,
     coeffs: Vec<LJCutCoeff>,
     coeff_set: Vec<bool>,
+    mixing: Option<Mixing>,
+    truncation: Truncation,
 }
 impl LJCut {
     pub fn new(force_cutoff: f64) -> Self {
@@ -56,6 +187,8 @@ impl LJCut {
             force_cutoff,
             coeffs: Vec::new(),
             coeff_set: Vec::new(),
+            mixing: None,
+            truncation: Truncation::Shifted,
         }
     }
     pub fn set_global_cutoff(&mut self, cutoff: f64) {
@@ -66,107 +199,114 @@ impl LJCut {
         );
         self.force_cutoff = cutoff;
     }
-    fn default_coeff() -> LJCutCoeff {
-        LJCutCoeff::new(0.0, 0.0, 0.0)
+    /// Fill the coefficients of pairs of different types that are not set with
+    /// `set_coeff` using the given mixing rule, from the coefficients of each type with
+    /// itself. A pair with only the reverse order set is copied from it instead.
+    ///
+    /// ```rust
+    /// use jmd::{atom_type::Basic, atomic::{LJCut, LJCutCoeff, Mixing}, prelude::*};
+    ///
+    /// fn run(mut sim: Simulation<Basic, LJCut>) {
+    ///     sim.set_atom_types(vec![Basic::new(1.0), Basic::new(2.0)]);
+    ///     let mut lj = LJCut::new(3.0);
+    ///     lj.set_mixing(Mixing::Arithmetic);
+    ///     sim.set_atomic_potential(lj);
+    ///     sim.set_atomic_coeff(0, 0, &LJCutCoeff::new(1.0, 1.0, 2.5));
+    ///     sim.set_atomic_coeff(1, 1, &LJCutCoeff::new(1.2, 0.5, 3.0));
+    /// }
+    /// ```
+    pub fn set_mixing(&mut self, mixing: Mixing) {
+        self.mixing = Some(mixing);
+        self.update_coeffs();
     }
-}
-
-impl<T: AtomType> AtomicPotentialTrait<T> for LJCut {
-    type Coeff = LJCutCoeff;
-    fn new() -> Self {
-        Self::new(1.0)
+    pub fn mixing(&self) -> Option<Mixing> {
+        self.mixing
     }
-    fn cutoff_distance(&self) -> f64 {
-        self.force_cutoff
+    /// Set how the potential is brought to zero at the cutoff of each pair of types
+    /// (shifted by default). With switching, the inner distance should be less than
+    /// every cutoff.
+    ///
+    /// ```rust
+    /// use jmd::atomic::{LJCut, Truncation};
+    ///
+    /// let mut lj = LJCut::new(2.5);
+    /// lj.set_truncation(Truncation::Tail);
+    /// lj.set_truncation(Truncation::Switched(2.0));
+    /// ```
+    pub fn set_truncation(&mut self, truncation: Truncation) {
+        if let Truncation::Switched(inner) = truncation {
+            assert!(
+                inner > 0.0,
+                "Inner switching distance should be positive, found {}",
+                inner
+            );
+        }
+        self.truncation = truncation;
+        self.update_coeffs();
     }
-    // TODO: check that forces are not double counted
-    // should be newton-pair full, not half, because half neighbor list
-    fn compute_forces(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> Vec<[f64; 3]> {
-        let mut forces: Vec<[f64; 3]> = Vec::new();
-        forces.resize(atoms.num_total_atoms(), [0.0, 0.0, 0.0]);
-        for i in 0..atoms.nlocal {
-            let typei = &atoms.types[i];
-            let posi = &atoms.positions[i];
-
-            for j in &neighbor_list.neighbors()[i] {
-                if atoms.ids[i] == atoms.ids[*j] {
-                    continue;
-                }
-                // U(r) = 4 eps ((sig/r)^12 - (sig/r)^6) - const
-                // f(r) = dU/dr = dU/d(r^2) d(r^2)/dr
-                // f(r) = -24 r eps / r^2 (2(sig/r)^12 - (sig/r)^6)
-
-                // If r_i = (0, 0) and r_j = (sig, 0), then the
-                // force should be repulsive, ie., f_i ~ (-1, 0), f_j ~ (1, 0)
-                // f(r_ij) = r_ij * -24 eps / sig^2, so if r_ij = r_j - r_i = (sig, 0),
-                // then f_i = f(r_ij) and f_j = -f(r_ij)
-
-                let typej = &atoms.types[*j];
-                let posj = &atoms.positions[*j];
-
-                let idx = <Self as AtomicPotentialTrait<T>>::type_idx(&self, *typei, *typej);
-                let coeff = self.coeffs[idx];
-                let r = [posi[0] - posj[0], posi[1] - posj[1], posi[2] - posj[2]];
-                let r2 = r[0] * r[0] + r[1] * r[1] + r[2] * r[2];
-
-                if r2 > coeff.rcut2 {
-                    continue;
-                }
-
-                let r6 = r2 * r2 * r2;
-
-                let f_mag = coeff.prefactor / r6 / r2 * (2.0 * coeff.sigma6 / r6 - 1.0);
-                forces[i] = [r[0] * f_mag, r[1] * f_mag, r[2] * f_mag];
-                forces[*j] = [-r[0] * f_mag, -r[1] * f_mag, -r[2] * f_mag];
-            }
+    pub fn truncation(&self) -> Truncation {
+        self.truncation
+    }
+    fn default_coeff() -> LJCutCoeff {
+        LJCutCoeff::new(0.0, 0.0, 0.0)
+    }
+    /// The coefficient filling the unset pair of types, if it can be filled
+    fn mixed_coeff(&self, typei: usize, typej: usize) -> Option<LJCutCoeff> {
+        let mixing = self.mixing?;
+        let n = self.num_types;
+        if self.coeff_set[typej * n + typei] {
+            return Some(self.coeffs[typej * n + typei]);
         }
-
-        forces
+        let [ii, jj] = [typei * n + typei, typej * n + typej];
+        (self.coeff_set[ii] && self.coeff_set[jj])
+            .then(|| self.coeffs[ii].mix(&self.coeffs[jj], mixing))
     }
-    fn compute_potential_energy(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> f64 {
-        let mut energy = 0.0;
-
-        for i in 0..atoms.nlocal {
-            let typei = &atoms.types[i];
-            let posi = &atoms.positions[i];
-
-            for j in &neighbor_list.neighbors()[i] {
-                if atoms.ids[i] == atoms.ids[*j] {
+    /// Refill the coefficients of all unset pairs that can be mixed, and apply the
+    /// truncation to all coefficients
+    fn update_coeffs(&mut self) {
+        let n = self.num_types;
+        for typei in 0..n {
+            for typej in 0..n {
+                let idx = typei * n + typej;
+                let coeff = if self.coeff_set[idx] {
+                    self.coeffs[idx]
+                } else if let Some(coeff) = self.mixed_coeff(typei, typej) {
+                    coeff
+                } else {
                     continue;
+                };
+                if let Truncation::Switched(inner) = self.truncation {
+                    assert!(
+                        inner < coeff.rcut,
+                        "Inner switching distance {} should be less than the cutoff {} of \
+                         types {} and {}",
+                        inner,
+                        coeff.rcut,
+                        typei,
+                        typej
+                    );
                 }
-                // U(r) = 4 eps ((sig/r)^12 - (sig/r)^6) - const
-                // f(r) = dU/dr = dU/d(r^2) d(r^2)/dr
-                // f(r) = -24 r eps / r^2 (2(sig/r)^12 - (sig/r)^6)
-
-                // If r_i = (0, 0) and r_j = (sig, 0), then the
-                // force should be repulsive, ie., f_i ~ (-1, 0), f_j ~ (1, 0)
-                // f(r_ij) = r_ij * -24 eps / sig^2, so if r_ij = r_j - r_i = (sig, 0),
-                // then f_i = f(r_ij) and f_j = -f(r_ij)
-
-                let typej = &atoms.types[*j];
-                let posj = &atoms.positions[*j];
-
-                let idx = <Self as AtomicPotentialTrait<T>>::type_idx(&self, *typei, *typej);
-                let coeff = self.coeffs[idx];
-                let r = [posi[0] - posj[0], posi[1] - posj[1], posi[2] - posj[2]];
-                let r2 = r[0] * r[0] + r[1] * r[1] + r[2] * r[2];
-
-                if r2 > coeff.rcut2 {
-                    continue;
-                }
-
-                let r6 = r2 * r2 * r2;
-
-                energy += 4.0 * coeff.epsilon * coeff.sigma6 / r6 * (coeff.sigma6 / r6 - 1.0)
-                    - coeff.correction;
+                self.coeffs[idx] = coeff.truncated(self.truncation);
             }
         }
-        energy
     }
-    fn num_types(&self) -> usize {
-        self.num_types
+    /// The pairs of `for_each_pair` within the cutoff of their coefficient, passed along
+    /// with the coefficient
+    fn for_each_pair<T: AtomType>(
+        &self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+        mut f: impl FnMut(usize, usize, [f64; 3], f64, &LJCutCoeff),
+    ) {
+        for_each_pair(atoms, neighbor_list, |i, j, r, r2| {
+            let coeff = &self.coeffs[self.num_types * atoms.types[i] + atoms.types[j]];
+            if r2 <= coeff.rcut2 {
+                f(i, j, r, r2, coeff);
+            }
+        });
     }
-    fn set_num_types(&mut self, num_types: usize) {
+    /// Resize the coefficients for a new number of types, keeping those that are set
+    fn resize_types(&mut self, num_types: usize) {
         if self.num_types == num_types {
             return;
         }
@@ -225,8 +365,108 @@ impl<T: AtomType> AtomicPotentialTrait<T> for LJCut {
             self.coeff_set.resize(new_len, false);
         }
     }
+}
+
+impl<T: AtomType> AtomicPotentialTrait<T> for LJCut {
+    type Coeff = LJCutCoeff;
+    fn new() -> Self {
+        Self::new(1.0)
+    }
+    fn cutoff_distance(&self) -> f64 {
+        self.force_cutoff
+    }
+    // TODO: check that forces are not double counted
+    // should be newton-pair full, not half, because half neighbor list
+    fn compute_forces(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> Vec<[f64; 3]> {
+        let mut forces = vec![[0.0; 3]; atoms.num_total_atoms()];
+        self.for_each_pair(atoms, neighbor_list, |i, j, r, r2, coeff| {
+            // U(r) = 4 eps ((sig/r)^12 - (sig/r)^6) - const
+            // f(r) = -dU/dr = -dU/d(r^2) d(r^2)/dr
+            // f(r) = 24 r eps / r^2 (2(sig/r)^12 - (sig/r)^6)
+
+            // If r_i = (0, 0) and r_j = (sig, 0), then the
+            // force should be repulsive, ie., f_i ~ (-1, 0), f_j ~ (1, 0)
+            // f(r_ij) = r_ij * 24 eps / sig^2, so if r_ij = r_i - r_j = (-sig, 0),
+            // then f_i = f(r_ij) and f_j = -f(r_ij)
+            let f_mag = coeff.force_over_r(r2);
+            for k in 0..3 {
+                forces[i][k] += r[k] * f_mag;
+                forces[j][k] -= r[k] * f_mag;
+            }
+        });
+        forces
+    }
+    fn compute_potential_energy(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> f64 {
+        let mut energy = 0.0;
+        self.for_each_pair(atoms, neighbor_list, |_, _, _, r2, coeff| {
+            energy += coeff.energy(r2);
+        });
+        energy
+    }
+    fn compute_virial(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> [f64; 6] {
+        let mut virial = [0.0; 6];
+        self.for_each_pair(atoms, neighbor_list, |_, _, r, r2, coeff| {
+            let pair = pair_virial(r, coeff.force_over_r(r2));
+            for k in 0..6 {
+                virial[k] += pair[k];
+            }
+        });
+        virial
+    }
+    fn compute_per_atom_energy(
+        &self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+    ) -> Option<Vec<f64>> {
+        let mut energies = vec![0.0; atoms.num_total_atoms()];
+        self.for_each_pair(atoms, neighbor_list, |i, j, _, r2, coeff| {
+            let half_energy = 0.5 * coeff.energy(r2);
+            energies[i] += half_energy;
+            energies[j] += half_energy;
+        });
+        Some(energies)
+    }
+    fn compute_per_atom_virial(
+        &self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+    ) -> Option<Vec<[f64; 6]>> {
+        let mut virials = vec![[0.0; 6]; atoms.num_total_atoms()];
+        self.for_each_pair(atoms, neighbor_list, |i, j, r, r2, coeff| {
+            let pair = pair_virial(r, 0.5 * coeff.force_over_r(r2));
+            for k in 0..6 {
+                virials[i][k] += pair[k];
+                virials[j][k] += pair[k];
+            }
+        });
+        Some(virials)
+    }
+    fn tail_correction(&self, type_counts: &[usize], volume: f64) -> [f64; 2] {
+        if self.truncation != Truncation::Tail {
+            return [0.0, 0.0];
+        }
+        let mut integrals = [0.0, 0.0];
+        for (typei, &counti) in type_counts.iter().enumerate() {
+            for (typej, &countj) in type_counts.iter().enumerate() {
+                let idx = <Self as AtomicPotentialTrait<T>>::type_idx(self, typei, typej);
+                let pair = self.coeffs[idx].tail_integrals();
+                for k in 0..2 {
+                    integrals[k] += (counti * countj) as f64 * pair[k];
+                }
+            }
+        }
+        integrals.map(|x| 2.0 * std::f64::consts::PI * x / volume)
+    }
+    fn num_types(&self) -> usize {
+        self.num_types
+    }
+    fn set_num_types(&mut self, num_types: usize) {
+        self.resize_types(num_types);
+        self.update_coeffs();
+    }
     fn all_set(&self) -> bool {
-        self.coeff_set.iter().all(|&x| x)
+        let n = self.num_types;
+        (0..n * n).all(|k| self.coeff_set[k] || self.mixed_coeff(k / n, k % n).is_some())
     }
     fn set_coeff(&mut self, typei: usize, typej: usize, coeff: &Self::Coeff) {
         assert!(
@@ -237,5 +477,233 @@ impl<T: AtomType> AtomicPotentialTrait<T> for LJCut {
         let index = <Self as AtomicPotentialTrait<T>>::type_idx(self, typei, typej);
         self.coeff_set[index] = true;
         self.coeffs[index] = coeff.clone();
+        self.update_coeffs();
+    }
+    fn write_restart(&self, writer: &mut RestartWriter) -> io::Result<()> {
+        writer.write_f64(self.force_cutoff)?;
+        writer.write_usize(self.num_types)?;
+        writer.write_usize(match self.mixing {
+            None => 0,
+            Some(Mixing::Arithmetic) => 1,
+            Some(Mixing::Geometric) => 2,
+            Some(Mixing::Sixthpower) => 3,
+        })?;
+        let (truncation, inner) = match self.truncation {
+            Truncation::Unshifted => (0, 0.0),
+            Truncation::Shifted => (1, 0.0),
+            Truncation::Tail => (2, 0.0),
+            Truncation::Switched(inner) => (3, inner),
+        };
+        writer.write_usize(truncation)?;
+        writer.write_f64(inner)?;
+        writer.write_bools(&self.coeff_set)?;
+        for coeff in &self.coeffs {
+            writer.write_f64(coeff.sigma)?;
+            writer.write_f64(coeff.epsilon)?;
+            writer.write_f64(coeff.rcut)?;
+        }
+        Ok(())
+    }
+    fn read_restart(&mut self, reader: &mut RestartReader) -> io::Result<()> {
+        self.force_cutoff = reader.read_f64()?;
+        self.num_types = reader.read_usize()?;
+        self.mixing = match reader.read_usize()? {
+            0 => None,
+            1 => Some(Mixing::Arithmetic),
+            2 => Some(Mixing::Geometric),
+            3 => Some(Mixing::Sixthpower),
+            m => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid mixing rule {}", m),
+                ))
+            }
+        };
+        let truncation = reader.read_usize()?;
+        let inner = reader.read_f64()?;
+        self.truncation = match truncation {
+            0 => Truncation::Unshifted,
+            1 => Truncation::Shifted,
+            2 => Truncation::Tail,
+            3 => Truncation::Switched(inner),
+            t => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Invalid truncation {}", t),
+                ))
+            }
+        };
+        self.coeff_set = reader.read_bools()?;
+        self.coeffs = (0..self.coeff_set.len())
+            .map(|_| {
+                let sigma = reader.read_f64()?;
+                let epsilon = reader.read_f64()?;
+                let rcut = reader.read_f64()?;
+                Ok(LJCutCoeff::new(sigma, epsilon, rcut))
+            })
+            .collect::<io::Result<Vec<LJCutCoeff>>>()?;
+        self.update_coeffs();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        atom_type::Basic,
+        atomic::tests::check_forces,
+        container::{Container, BC},
+    };
+
+    fn setup(positions: Vec<[f64; 3]>) -> (LJCut, Atoms<Basic>, NeighborList) {
+        let mut lj = LJCut::new(2.5);
+        <LJCut as AtomicPotentialTrait<Basic>>::set_num_types(&mut lj, 1);
+        <LJCut as AtomicPotentialTrait<Basic>>::set_coeff(
+            &mut lj,
+            0,
+            0,
+            &LJCutCoeff::new(1.0, 1.0, 2.5),
+        );
+
+        let n = positions.len();
+        let mut atoms: Atoms<Basic> = Atoms::new();
+        atoms.atom_types = vec![Basic::new(1.0)];
+        atoms.ids = (0..n).collect();
+        atoms.types = vec![0; n];
+        atoms.positions = positions;
+        atoms.velocities = vec![[0.0; 3]; n];
+        atoms.nlocal = n;
+
+        let container = Container::new(0.0, 10.0, 0.0, 10.0, 0.0, 10.0, BC::PP, BC::PP, BC::PP);
+        let mut nl = NeighborList::new(&container, 2.5, 0.3);
+        nl.update(&atoms.positions, 3);
+        (lj, atoms, nl)
+    }
+
+    #[test]
+    fn test_close_pair_repels() {
+        let (lj, atoms, nl) = setup(vec![[5.0, 5.0, 5.0], [5.9, 5.0, 5.0]]);
+        let forces = lj.compute_forces(&atoms, &nl);
+        assert!(forces[0][0] < 0.0 && forces[1][0] > 0.0);
+        assert!((forces[0][0] + forces[1][0]).abs() < 1e-12);
+    }
+
+    /// The forces of all pairs add up on atoms with several neighbors, and match central
+    /// finite differences of the energy
+    #[test]
+    fn test_forces_add_up() {
+        let mut lj = LJCut::new(2.5);
+        <LJCut as AtomicPotentialTrait<Basic>>::set_num_types(&mut lj, 2);
+        for (i, j, sigma) in [(0, 0, 1.0), (0, 1, 1.1), (1, 1, 1.2)] {
+            let coeff = LJCutCoeff::new(sigma, 1.0, 2.5);
+            <LJCut as AtomicPotentialTrait<Basic>>::set_coeff(&mut lj, i, j, &coeff);
+        }
+        check_forces(&mut lj, false, |lj, atoms, _, nl| {
+            lj.compute_potential_energy(atoms, nl)
+        });
+    }
+
+    #[test]
+    fn test_per_atom_sums_match_totals() {
+        let (lj, atoms, nl) = setup(vec![[5.0, 5.0, 5.0], [6.1, 5.2, 5.0], [5.3, 6.0, 5.9]]);
+
+        let energy = lj.compute_potential_energy(&atoms, &nl);
+        let energies = lj.compute_per_atom_energy(&atoms, &nl).unwrap();
+        assert!((energies.iter().sum::<f64>() - energy).abs() < 1e-12);
+
+        let virial = lj.compute_virial(&atoms, &nl);
+        let virials = lj.compute_per_atom_virial(&atoms, &nl).unwrap();
+        for k in 0..6 {
+            let sum: f64 = virials.iter().map(|v| v[k]).sum();
+            assert!((sum - virial[k]).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_mixing() {
+        let mut lj = LJCut::new(3.0);
+        <LJCut as AtomicPotentialTrait<Basic>>::set_num_types(&mut lj, 3);
+        let set = |lj: &mut LJCut, i, j, coeff: LJCutCoeff| {
+            <LJCut as AtomicPotentialTrait<Basic>>::set_coeff(lj, i, j, &coeff)
+        };
+        let all_set = |lj: &LJCut| <LJCut as AtomicPotentialTrait<Basic>>::all_set(lj);
+        let coeff = |lj: &LJCut, i: usize, j: usize| lj.coeffs[3 * i + j];
+
+        set(&mut lj, 0, 0, LJCutCoeff::new(1.0, 1.0, 2.5));
+        set(&mut lj, 1, 1, LJCutCoeff::new(2.0, 0.25, 3.0));
+        set(&mut lj, 2, 2, LJCutCoeff::new(1.5, 0.5, 2.0));
+        set(&mut lj, 0, 2, LJCutCoeff::new(0.9, 0.8, 2.2));
+        assert!(!all_set(&lj));
+
+        lj.set_mixing(Mixing::Arithmetic);
+        assert!(all_set(&lj));
+        for (i, j) in [(0, 1), (1, 0)] {
+            let c = coeff(&lj, i, j);
+            assert_eq!([c.sigma(), c.epsilon(), c.rcut()], [1.5, 0.5, 2.75]);
+        }
+        // Explicitly set pairs are kept, and copied to the reverse order
+        for (i, j) in [(0, 2), (2, 0)] {
+            let c = coeff(&lj, i, j);
+            assert_eq!([c.sigma(), c.epsilon(), c.rcut()], [0.9, 0.8, 2.2]);
+        }
+
+        lj.set_mixing(Mixing::Geometric);
+        let c = coeff(&lj, 0, 1);
+        assert!((c.sigma() - 2f64.sqrt()).abs() < 1e-12);
+        assert!((c.rcut() - 7.5f64.sqrt()).abs() < 1e-12);
+
+        lj.set_mixing(Mixing::Sixthpower);
+        let c = coeff(&lj, 1, 0);
+        assert!((c.sigma() - 32.5f64.powf(1.0 / 6.0)).abs() < 1e-12);
+        assert!((c.epsilon() - 2.0 * 0.5 * 8.0 / 65.0).abs() < 1e-12);
+
+        // Resetting a diagonal coefficient updates the mixed pairs
+        set(&mut lj, 0, 0, LJCutCoeff::new(2.0, 0.25, 3.0));
+        let c = coeff(&lj, 0, 1);
+        assert!((c.sigma() - 2.0).abs() < 1e-12 && (c.epsilon() - 0.25).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_truncation() {
+        let coeff = LJCutCoeff::new(1.1, 0.9, 2.5);
+        let rcut2 = 2.5 * 2.5;
+
+        let shifted = coeff.truncated(Truncation::Shifted);
+        assert!(shifted.energy(rcut2).abs() < 1e-14);
+        assert_eq!(shifted.force_over_r(1.5), coeff.force_over_r(1.5));
+
+        // The switched energy and force go smoothly to zero at the cutoff, and the force
+        // is the derivative of the energy throughout
+        let switched = coeff.truncated(Truncation::Switched(2.0));
+        assert_eq!(switched.energy(3.0), coeff.energy(3.0));
+        assert!(switched.energy(rcut2 - 1e-9).abs() < 1e-12);
+        assert!(switched.force_over_r(rcut2 - 1e-9).abs() < 1e-8);
+        for r in [1.2, 1.9, 2.05, 2.3, 2.45] {
+            let h = 1e-6;
+            let derivative = (switched.energy((r + h) * (r + h))
+                - switched.energy((r - h) * (r - h)))
+                / (2.0 * h);
+            assert!((switched.force_over_r(r * r) * r + derivative).abs() < 1e-6);
+        }
+
+        // Analytic tail corrections for a single type, in reduced units
+        let mut lj = LJCut::new(2.5);
+        <LJCut as AtomicPotentialTrait<Basic>>::set_num_types(&mut lj, 1);
+        <LJCut as AtomicPotentialTrait<Basic>>::set_coeff(&mut lj, 0, 0, &coeff);
+        let tail = |lj: &LJCut| {
+            <LJCut as AtomicPotentialTrait<Basic>>::tail_correction(lj, &[800], 1000.0)
+        };
+        assert_eq!(tail(&lj), [0.0, 0.0]);
+        lj.set_truncation(Truncation::Tail);
+        let [energy, virial] = tail(&lj);
+        let [rho, sigma3, ratio3] = [0.8, 1.1f64.powi(3), (1.1f64 / 2.5).powi(3)];
+        let pi = std::f64::consts::PI;
+        let expected_energy =
+            8.0 / 3.0 * pi * 800.0 * rho * 0.9 * sigma3 * (ratio3.powi(3) / 3.0 - ratio3);
+        let expected_pressure =
+            16.0 / 3.0 * pi * rho * rho * 0.9 * sigma3 * (2.0 / 3.0 * ratio3.powi(3) - ratio3);
+        assert!((energy - expected_energy).abs() < 1e-10);
+        assert!((virial / (3.0 * 1000.0) - expected_pressure).abs() < 1e-12);
     }
 }
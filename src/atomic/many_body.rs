@@ -0,0 +1,343 @@
+use std::fs;
+
+use super::*;
+
+/// Parameters of a many-body potential for a triplet of elements, as given by an entry
+/// of a LAMMPS potential file
+pub(super) trait ElementParams: Clone {
+    /// The number of values after the three element names of an entry
+    const NUM_VALUES: usize;
+    fn from_values(values: &[f64]) -> Self;
+    fn values(&self) -> Vec<f64>;
+    /// The distance beyond which the first element is not affected by the others
+    fn cutoff(&self) -> f64;
+}
+
+/// The parameters of each triplet of elements of a many-body potential, and the element
+/// of each atom type
+#[derive(Clone, Debug)]
+pub(super) struct Elements<P: ElementParams> {
+    names: Vec<String>,
+    /// Parameters indexed by the three elements
+    params: Vec<Option<P>>,
+    type_elements: Vec<Option<usize>>,
+}
+impl<P: ElementParams> Elements<P> {
+    pub(super) fn new() -> Self {
+        Self {
+            names: Vec::new(),
+            params: Vec::new(),
+            type_elements: Vec::new(),
+        }
+    }
+    /// Read a LAMMPS potential file of the given kind, as in `parse`
+    pub(super) fn from_file(path: &str, kind: &str) -> Self {
+        let contents = fs::read_to_string(path)
+            .unwrap_or_else(|_| panic!("Could not read {} file {}", kind, path));
+        Self::parse(&contents, kind)
+    }
+    /// Parse the entries of a LAMMPS potential file, each of three element names followed
+    /// by `P::NUM_VALUES` values, possibly over several lines, with comments after `#`.
+    /// Elements are numbered in order of appearance, and later entries for the same
+    /// triplet replace earlier ones.
+    pub(super) fn parse(contents: &str, kind: &str) -> Self {
+        let fields: Vec<&str> = contents
+            .lines()
+            .flat_map(|l| l.split('#').next().unwrap_or("").split_whitespace())
+            .collect();
+        let entry_len = 3 + P::NUM_VALUES;
+        assert!(
+            fields.len().is_multiple_of(entry_len),
+            "Entries of {} files should have {} fields",
+            kind,
+            entry_len
+        );
+
+        let mut names: Vec<String> = Vec::new();
+        let mut entries: Vec<([usize; 3], P)> = Vec::new();
+        for entry in fields.chunks_exact(entry_len) {
+            let elements = [0, 1, 2].map(|k| {
+                names.iter().position(|n| n == entry[k]).unwrap_or_else(|| {
+                    names.push(entry[k].to_string());
+                    names.len() - 1
+                })
+            });
+            let values: Vec<f64> = entry[3..]
+                .iter()
+                .map(|v| {
+                    v.parse()
+                        .unwrap_or_else(|_| panic!("Invalid value {} in {} file", v, kind))
+                })
+                .collect();
+            entries.push((elements, P::from_values(&values)));
+        }
+
+        let n = names.len();
+        let mut params = vec![None; n * n * n];
+        for ([i, j, k], p) in entries {
+            params[(i * n + j) * n + k] = Some(p);
+        }
+        Self {
+            names,
+            params,
+            type_elements: Vec::new(),
+        }
+    }
+    /// The largest cutoff of any entry
+    pub(super) fn cutoff(&self) -> f64 {
+        self.params
+            .iter()
+            .flatten()
+            .map(|p| p.cutoff())
+            .fold(0.0, f64::max)
+    }
+    /// The element of the given atom type
+    pub(super) fn element(&self, type_: usize) -> usize {
+        self.type_elements[type_].expect("Element should be set")
+    }
+    pub(super) fn params(&self, i: usize, j: usize, k: usize) -> &P {
+        let n = self.names.len();
+        self.params[(i * n + j) * n + k]
+            .as_ref()
+            .expect("Parameters should be set")
+    }
+    pub(super) fn num_types(&self) -> usize {
+        self.type_elements.len()
+    }
+    pub(super) fn set_num_types(&mut self, num_types: usize) {
+        self.type_elements.resize(num_types, None);
+    }
+    /// Whether every type has an element, and there are parameters for every triplet of
+    /// those elements
+    pub(super) fn all_set(&self) -> bool {
+        let n = self.names.len();
+        self.type_elements.iter().all(|e| e.is_some())
+            && self.type_elements.iter().flatten().all(|&i| {
+                self.type_elements.iter().flatten().all(|&j| {
+                    self.type_elements
+                        .iter()
+                        .flatten()
+                        .all(|&k| self.params[(i * n + j) * n + k].is_some())
+                })
+            })
+    }
+    pub(super) fn set_coeff(&mut self, typei: usize, typej: usize, element: &str) {
+        assert!(
+            typei < self.num_types() && typej < self.num_types(),
+            "Type indices should be less than the number of types (0-indexed)"
+        );
+        assert_eq!(
+            typei, typej,
+            "Elements are assigned per type, so both type indices should be the same"
+        );
+        let idx = self
+            .names
+            .iter()
+            .position(|n| n == element)
+            .unwrap_or_else(|| panic!("Element {} is not in the potential file", element));
+        self.type_elements[typei] = Some(idx);
+    }
+    pub(super) fn write_restart(&self, writer: &mut RestartWriter) -> io::Result<()> {
+        writer.write_usize(self.names.len())?;
+        for name in &self.names {
+            writer.write_string(name)?;
+        }
+        for p in &self.params {
+            writer.write_bool(p.is_some())?;
+            writer.write_f64s(&p.as_ref().map_or(Vec::new(), |p| p.values()))?;
+        }
+        writer.write_usize(self.type_elements.len())?;
+        for element in &self.type_elements {
+            writer.write_bool(element.is_some())?;
+            writer.write_usize(element.unwrap_or(0))?;
+        }
+        Ok(())
+    }
+    pub(super) fn read_restart(&mut self, reader: &mut RestartReader) -> io::Result<()> {
+        let n = reader.read_usize()?;
+        self.names = (0..n)
+            .map(|_| reader.read_string())
+            .collect::<io::Result<Vec<String>>>()?;
+        self.params = (0..n * n * n)
+            .map(|_| {
+                let is_set = reader.read_bool()?;
+                let values = reader.read_f64s()?;
+                Ok(is_set.then(|| P::from_values(&values)))
+            })
+            .collect::<io::Result<Vec<Option<P>>>>()?;
+        let num_types = reader.read_usize()?;
+        self.type_elements = (0..num_types)
+            .map(|_| {
+                let is_set = reader.read_bool()?;
+                let element = reader.read_usize()?;
+                Ok(is_set.then_some(element))
+            })
+            .collect::<io::Result<Vec<Option<usize>>>>()?;
+        Ok(())
+    }
+}
+
+/// A neighbor of an owned atom within the cutoff of a many-body potential
+pub(super) struct Neighbor {
+    pub(super) idx: usize,
+    pub(super) element: usize,
+    /// Separation from the owned atom to the neighbor
+    pub(super) del: [f64; 3],
+    pub(super) r: f64,
+}
+
+/// The neighbors of an owned atom within the given distance, from a full neighbor list
+pub(super) fn neighbors_within<T: AtomType, P: ElementParams>(
+    atoms: &Atoms<T>,
+    neighbor_list: &NeighborList,
+    elements: &Elements<P>,
+    i: usize,
+    cutoff: f64,
+) -> Vec<Neighbor> {
+    let posi = &atoms.positions[i];
+    neighbor_list.neighbors()[i]
+        .iter()
+        .filter(|&&j| atoms.ids[i] != atoms.ids[j])
+        .filter_map(|&j| {
+            let posj = &atoms.positions[j];
+            let del = [posj[0] - posi[0], posj[1] - posi[1], posj[2] - posi[2]];
+            let r = (del[0] * del[0] + del[1] * del[1] + del[2] * del[2]).sqrt();
+            (r < cutoff).then(|| Neighbor {
+                idx: j,
+                element: elements.element(atoms.types[j]),
+                del,
+                r,
+            })
+        })
+        .collect()
+}
+
+/// The gradients of the cosine of the angle between two neighbors of an atom, with
+/// respect to the positions of the first and second neighbor
+pub(super) fn cos_gradients(j: &Neighbor, k: &Neighbor, cos: f64) -> [[f64; 3]; 2] {
+    let rjk = j.r * k.r;
+    [
+        [0, 1, 2].map(|m| k.del[m] / rjk - cos * j.del[m] / (j.r * j.r)),
+        [0, 1, 2].map(|m| j.del[m] / rjk - cos * k.del[m] / (k.r * k.r)),
+    ]
+}
+
+/// The cosine of the angle between two neighbors of an atom
+pub(super) fn cos_angle(j: &Neighbor, k: &Neighbor) -> f64 {
+    (j.del[0] * k.del[0] + j.del[1] * k.del[1] + j.del[2] * k.del[2]) / (j.r * k.r)
+}
+
+/// An atom of a term of a many-body potential other than its central atom
+pub(super) struct TermAtom {
+    pub(super) idx: usize,
+    /// Separation from the central atom
+    pub(super) del: [f64; 3],
+    pub(super) force: [f64; 3],
+}
+
+/// A many-body potential given as a sum of terms centered on owned atoms, each counted
+/// once over all processes
+pub(super) trait ManyBody {
+    /// Call the function with the central atom, the energy and the other atoms of each
+    /// term, with the forces on them. The force on the central atom balances those on the
+    /// others.
+    fn for_each_term<T: AtomType>(
+        &self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+        f: impl FnMut(usize, f64, &[TermAtom]),
+    );
+}
+
+pub(super) fn forces<T: AtomType>(
+    potential: &impl ManyBody,
+    atoms: &Atoms<T>,
+    neighbor_list: &NeighborList,
+) -> Vec<[f64; 3]> {
+    let mut forces = vec![[0.0; 3]; atoms.num_total_atoms()];
+    potential.for_each_term(atoms, neighbor_list, |i, _, others| {
+        for other in others {
+            for (k, &f) in other.force.iter().enumerate() {
+                forces[other.idx][k] += f;
+                forces[i][k] -= f;
+            }
+        }
+    });
+    forces
+}
+
+pub(super) fn potential_energy<T: AtomType>(
+    potential: &impl ManyBody,
+    atoms: &Atoms<T>,
+    neighbor_list: &NeighborList,
+) -> f64 {
+    let mut energy = 0.0;
+    potential.for_each_term(atoms, neighbor_list, |_, e, _| energy += e);
+    energy
+}
+
+/// The virial of a term, which is the sum over its other atoms of their separation from
+/// the central atom times the force on them
+fn term_virial(others: &[TermAtom]) -> [f64; 6] {
+    let mut virial = [0.0; 6];
+    for other in others {
+        let [d, f] = [other.del, other.force];
+        let pair = [
+            d[0] * f[0],
+            d[1] * f[1],
+            d[2] * f[2],
+            d[0] * f[1],
+            d[0] * f[2],
+            d[1] * f[2],
+        ];
+        for k in 0..6 {
+            virial[k] += pair[k];
+        }
+    }
+    virial
+}
+
+pub(super) fn virial<T: AtomType>(
+    potential: &impl ManyBody,
+    atoms: &Atoms<T>,
+    neighbor_list: &NeighborList,
+) -> [f64; 6] {
+    let mut virial = [0.0; 6];
+    potential.for_each_term(atoms, neighbor_list, |_, _, others| {
+        let term = term_virial(others);
+        for k in 0..6 {
+            virial[k] += term[k];
+        }
+    });
+    virial
+}
+
+/// The energy of each atom, with the energy of each term given to its central atom
+pub(super) fn per_atom_energy<T: AtomType>(
+    potential: &impl ManyBody,
+    atoms: &Atoms<T>,
+    neighbor_list: &NeighborList,
+) -> Vec<f64> {
+    let mut energies = vec![0.0; atoms.num_total_atoms()];
+    potential.for_each_term(atoms, neighbor_list, |i, e, _| energies[i] += e);
+    energies
+}
+
+/// The virial of each atom, with the virial of each term split evenly between its atoms
+pub(super) fn per_atom_virial<T: AtomType>(
+    potential: &impl ManyBody,
+    atoms: &Atoms<T>,
+    neighbor_list: &NeighborList,
+) -> Vec<[f64; 6]> {
+    let mut virials = vec![[0.0; 6]; atoms.num_total_atoms()];
+    potential.for_each_term(atoms, neighbor_list, |i, _, others| {
+        let term = term_virial(others);
+        let share = 1.0 / (others.len() + 1) as f64;
+        for idx in others.iter().map(|o| o.idx).chain([i]) {
+            for k in 0..6 {
+                virials[idx][k] += term[k] * share;
+            }
+        }
+    });
+    virials
+}
@@ -0,0 +1,475 @@
+use std::sync::Arc;
+
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+
+use super::Comm;
+use crate::utils::Axis;
+
+/// The largest supported order of charge assignment
+pub(super) const MAX_ORDER: usize = 7;
+
+/// The values `M(f + j)` of the cardinal B-spline of the given order, which is nonzero
+/// between 0 and the order, for `j` from 0 up to the order
+pub(super) fn b_spline(order: usize, f: f64) -> [f64; 2 * MAX_ORDER] {
+    let mut values = [0.0; 2 * MAX_ORDER];
+    values[0] = 1.0;
+    for n in 2..=order {
+        // Descending, so that the values of the previous order are used on the right
+        for j in (0..n).rev() {
+            let x = f + j as f64;
+            let below = if j > 0 { values[j - 1] } else { 0.0 };
+            values[j] = (x * values[j] + (n as f64 - x) * below) / (n - 1) as f64;
+        }
+    }
+    values
+}
+
+/// The first and past-the-last point owned along an axis by the process at the given
+/// position, out of the given number of points and processes
+fn owned_range(num_points: usize, num_procs: usize, position: usize) -> [i64; 2] {
+    let first = |c: usize| (c * num_points).div_ceil(num_procs) as i64;
+    [first(position), first(position + 1)]
+}
+
+/// A periodic grid of points along the box vectors, split between the processes like
+/// their subdomains. Each process owns the points within its subdomain in fractional
+/// coordinates, and holds ghost points around them for the points near its atoms that
+/// its neighbors own.
+///
+/// Values at the points held by a process are stored with the first axis varying
+/// fastest, in arrays over either the owned points or all points including ghosts.
+pub(super) struct Mesh {
+    size: [usize; 3],
+    procs: [usize; 3],
+    position: [usize; 3],
+    /// Order of the stencils of the atoms, which spread over as many points along each axis
+    order: usize,
+    /// How far, in grid spacings, atoms may be outside the subdomain of their process
+    drift: [f64; 3],
+    /// First and past-the-last owned point along each axis
+    owned: [[i64; 2]; 3],
+    /// First and past-the-last point along each axis, including ghost points
+    ghost: [[i64; 2]; 3],
+    /// Forward and inverse transforms along each axis
+    ffts: [[Arc<dyn Fft<f64>>; 2]; 3],
+}
+impl Mesh {
+    /// The grid with the given number of points along each box vector, split between
+    /// the processes, with no ghost points until `set_stencils`
+    pub(super) fn new(size: [usize; 3], comm: &dyn Comm) -> Self {
+        let procs = comm.procs();
+        let position = comm.position();
+        for k in 0..3 {
+            assert!(
+                size[k] >= procs[k],
+                "Grid of {} points should have at least one point per process along {:?}, \
+                 try using fewer threads",
+                size[k],
+                [Axis::X, Axis::Y, Axis::Z][k]
+            );
+        }
+        let mut planner = FftPlanner::new();
+        let owned = [0, 1, 2].map(|k| owned_range(size[k], procs[k], position[k]));
+        Self {
+            size,
+            procs,
+            position,
+            order: 1,
+            drift: [0.0; 3],
+            owned,
+            ghost: owned,
+            ffts: size.map(|n| [planner.plan_fft_forward(n), planner.plan_fft_inverse(n)]),
+        }
+    }
+    pub(super) fn size(&self) -> [usize; 3] {
+        self.size
+    }
+    /// Set the order of the stencils, and how far in fractional coordinates atoms may be
+    /// outside the subdomain of their process, which determine the ghost points
+    pub(super) fn set_stencils(&mut self, order: usize, drift: [f64; 3]) {
+        self.order = order;
+        self.drift = [0, 1, 2].map(|k| drift[k] * self.size[k] as f64);
+        self.ghost = [0, 1, 2].map(|k| self.ghost_range(k, self.position[k]));
+        for k in 0..3 {
+            let [lo, hi] = self.owned[k];
+            assert!(
+                [false, true]
+                    .iter()
+                    .all(|&above| self.neighbor_ghosts(k, above) <= hi - lo),
+                "Subdomain along {:?} should be wider than the charge assignment stencils, \
+                 try using fewer threads",
+                [Axis::X, Axis::Y, Axis::Z][k]
+            );
+        }
+    }
+    /// The first and past-the-last point along an axis that the atoms of the process at
+    /// the given position spread to
+    fn ghost_range(&self, k: usize, position: usize) -> [i64; 2] {
+        let [lo, hi] =
+            [position, position + 1].map(|c| (c * self.size[k]) as f64 / self.procs[k] as f64);
+        let half = 0.5 * self.order as f64;
+        [
+            (lo - self.drift[k] + half).floor() as i64 - self.order as i64 + 1,
+            (hi + self.drift[k] + half).floor() as i64 + 1,
+        ]
+    }
+    /// The number of ghost points that the neighboring process above along an axis holds
+    /// below its owned points, or that the one below holds above its owned points, which
+    /// are copies of points owned by this process
+    fn neighbor_ghosts(&self, k: usize, above: bool) -> i64 {
+        let p = self.procs[k];
+        let c = if above {
+            (self.position[k] + 1) % p
+        } else {
+            (self.position[k] + p - 1) % p
+        };
+        let [lo, hi] = owned_range(self.size[k], p, c);
+        let [ghost_lo, ghost_hi] = self.ghost_range(k, c);
+        if above {
+            lo - ghost_lo
+        } else {
+            ghost_hi - hi
+        }
+    }
+    /// The number of owned points
+    pub(super) fn num_owned(&self) -> usize {
+        self.owned
+            .iter()
+            .map(|[lo, hi]| (hi - lo) as usize)
+            .product()
+    }
+    /// The number of points including ghost points
+    pub(super) fn num_ghosted(&self) -> usize {
+        self.ghost
+            .iter()
+            .map(|[lo, hi]| (hi - lo) as usize)
+            .product()
+    }
+    fn ghosted_index(&self, point: [i64; 3]) -> usize {
+        let [[x0, x1], [y0, y1], [z0, _]] = self.ghost;
+        (((point[2] - z0) * (y1 - y0) + point[1] - y0) * (x1 - x0) + point[0] - x0) as usize
+    }
+    /// The owned points, in order
+    pub(super) fn owned_points(&self) -> impl Iterator<Item = [i64; 3]> + '_ {
+        let [[x0, x1], [y0, y1], [z0, z1]] = self.owned;
+        (z0..z1).flat_map(move |z| (y0..y1).flat_map(move |y| (x0..x1).map(move |x| [x, y, z])))
+    }
+    /// The index among all points including ghosts of each owned point, in order
+    pub(super) fn owned_indices(&self) -> Vec<usize> {
+        self.owned_points().map(|p| self.ghosted_index(p)).collect()
+    }
+    /// Call the function with the index among all points including ghosts and the weight
+    /// of each point of the stencil of an atom at the given fractional coordinates, whose
+    /// weights are those of cardinal B-splines centered on the atom and add up to 1
+    pub(super) fn for_each_stencil_point(&self, lamda: [f64; 3], mut f: impl FnMut(usize, f64)) {
+        let order = self.order;
+        let mut first = [0; 3];
+        let mut weights = [[0.0; 2 * MAX_ORDER]; 3];
+        for k in 0..3 {
+            let u = lamda[k] * self.size[k] as f64 + 0.5 * order as f64;
+            let base = u.floor();
+            // The weight of point `base - j` is `M(u - base + j)`
+            weights[k] = b_spline(order, u - base);
+            weights[k][..order].reverse();
+            first[k] = base as i64 - order as i64 + 1;
+            assert!(
+                self.ghost[k][0] <= first[k] && first[k] + order as i64 <= self.ghost[k][1],
+                "Atom at {:?} in fractional coordinates is too far from the subdomain of its \
+                 process, the neighbor list should be rebuilt more often",
+                lamda
+            );
+        }
+        let [[x0, x1], [y0, y1], _] = self.ghost;
+        let [nx, ny] = [(x1 - x0) as usize, (y1 - y0) as usize];
+        let start = self.ghosted_index(first);
+        for (c, wz) in weights[2][..order].iter().enumerate() {
+            for (b, wy) in weights[1][..order].iter().enumerate() {
+                let row = start + (c * ny + b) * nx;
+                for (a, wx) in weights[0][..order].iter().enumerate() {
+                    f(row + a, wx * wy * wz);
+                }
+            }
+        }
+    }
+
+    /// The indices among all points including ghosts of the points within the given
+    /// range along an axis, over all points including ghosts along the other axes
+    fn plane_indices(&self, k: usize, range: [i64; 2]) -> Vec<usize> {
+        let mut bounds = self.ghost;
+        bounds[k] = range;
+        let [[x0, x1], [y0, y1], [z0, z1]] = bounds;
+        (z0..z1)
+            .flat_map(|z| (y0..y1).flat_map(move |y| (x0..x1).map(move |x| [x, y, z])))
+            .map(|p| self.ghosted_index(p))
+            .collect()
+    }
+    fn pack(&self, values: &[f64], stride: usize, k: usize, range: [i64; 2]) -> Vec<f64> {
+        self.plane_indices(k, range)
+            .into_iter()
+            .flat_map(|i| values[stride * i..stride * (i + 1)].iter().copied())
+            .collect()
+    }
+    fn unpack(
+        &self,
+        values: &mut [f64],
+        stride: usize,
+        k: usize,
+        range: [i64; 2],
+        received: Vec<f64>,
+        add: bool,
+    ) {
+        let indices = self.plane_indices(k, range);
+        assert_eq!(received.len(), stride * indices.len(), "Invalid message");
+        for (&i, new) in indices.iter().zip(received.chunks_exact(stride)) {
+            for (v, nv) in values[stride * i..stride * (i + 1)].iter_mut().zip(new) {
+                if add {
+                    *v += nv;
+                } else {
+                    *v = *nv;
+                }
+            }
+        }
+    }
+    /// Add the values at ghost points to those at the points they are copies of, owned by
+    /// the neighboring processes. The values are given over all points including ghosts,
+    /// with `stride` values per point.
+    ///
+    /// Axes are handled in reverse order, with planes spanning the ghost points along the
+    /// other axes, so that values at ghost points across edges and corners are passed on.
+    pub(super) fn reverse_comm(&self, comm: &dyn Comm, values: &mut [f64], stride: usize) {
+        for axis in [Axis::Z, Axis::Y, Axis::X] {
+            let k = axis.index();
+            let [[lo, hi], [ghost_lo, ghost_hi]] = [self.owned[k], self.ghost[k]];
+            comm.send(
+                self.pack(values, stride, k, [ghost_lo, lo]),
+                axis.direction(false),
+            );
+            comm.send(
+                self.pack(values, stride, k, [hi, ghost_hi]),
+                axis.direction(true),
+            );
+            let num_above = self.neighbor_ghosts(k, true);
+            let received = comm.receive(axis.direction(false));
+            self.unpack(values, stride, k, [hi - num_above, hi], received, true);
+            let num_below = self.neighbor_ghosts(k, false);
+            let received = comm.receive(axis.direction(true));
+            self.unpack(values, stride, k, [lo, lo + num_below], received, true);
+        }
+    }
+    /// Overwrite the values at ghost points with those at the points they are copies of,
+    /// owned by the neighboring processes. The values are given over all points including
+    /// ghosts, with `stride` values per point.
+    pub(super) fn forward_comm(&self, comm: &dyn Comm, values: &mut [f64], stride: usize) {
+        for axis in [Axis::X, Axis::Y, Axis::Z] {
+            let k = axis.index();
+            let [[lo, hi], [ghost_lo, ghost_hi]] = [self.owned[k], self.ghost[k]];
+            let num_below = self.neighbor_ghosts(k, false);
+            comm.send(
+                self.pack(values, stride, k, [lo, lo + num_below]),
+                axis.direction(false),
+            );
+            let num_above = self.neighbor_ghosts(k, true);
+            comm.send(
+                self.pack(values, stride, k, [hi - num_above, hi]),
+                axis.direction(true),
+            );
+            let received = comm.receive(axis.direction(false));
+            self.unpack(values, stride, k, [hi, ghost_hi], received, false);
+            let received = comm.receive(axis.direction(true));
+            self.unpack(values, stride, k, [ghost_lo, lo], received, false);
+        }
+    }
+
+    /// The index among the owned points of the first point of each line of owned points
+    /// along an axis, and the distance between the indices of consecutive points of a line
+    fn line_starts(&self, k: usize) -> (Vec<usize>, usize) {
+        let lengths = self.owned.map(|[lo, hi]| (hi - lo) as usize);
+        let strides = [1, lengths[0], lengths[0] * lengths[1]];
+        let [a, b] = match k {
+            0 => [1, 2],
+            1 => [0, 2],
+            _ => [0, 1],
+        };
+        let starts = (0..lengths[b])
+            .flat_map(|j| (0..lengths[a]).map(move |i| i * strides[a] + j * strides[b]))
+            .collect();
+        (starts, strides[k])
+    }
+    /// Fourier transform the values at the owned points of each field over the whole grid,
+    /// in place, with `exp(-2 pi i m . n / N)` for the forward transform and its conjugate
+    /// for the unnormalized inverse. The grid is transformed along one axis at a time,
+    /// with the processes along the axis exchanging their parts of the lines of points,
+    /// so that each transforms whole lines, and then exchanging the results back.
+    pub(super) fn fft(&self, comm: &dyn Comm, fields: &mut [Vec<Complex<f64>>], inverse: bool) {
+        for axis in [Axis::X, Axis::Y, Axis::Z] {
+            let k = axis.index();
+            let [n, p, me] = [self.size[k], self.procs[k], self.position[k]];
+            let segments: Vec<[usize; 2]> = (0..p)
+                .map(|c| owned_range(n, p, c).map(|i| i as usize))
+                .collect();
+            let (starts, stride) = self.line_starts(k);
+            let length = segments[me][1] - segments[me][0];
+            // The lines transformed by each process along the axis
+            let group = |c: usize| &starts[starts.len() * c / p..starts.len() * (c + 1) / p];
+
+            let chunks = (0..p)
+                .map(|c| {
+                    let mut chunk = Vec::new();
+                    for field in fields.iter() {
+                        for &start in group(c) {
+                            for t in 0..length {
+                                let value = field[start + t * stride];
+                                chunk.extend([value.re, value.im]);
+                            }
+                        }
+                    }
+                    chunk
+                })
+                .collect();
+            let mut lines = vec![Complex::default(); fields.len() * group(me).len() * n];
+            for (chunk, [lo, hi]) in all_to_all(comm, axis, chunks).iter().zip(&segments) {
+                for (line, values) in lines
+                    .chunks_exact_mut(n)
+                    .zip(chunk.chunks_exact(2 * (hi - lo)))
+                {
+                    for (value, v) in line[*lo..*hi].iter_mut().zip(values.chunks_exact(2)) {
+                        *value = Complex::new(v[0], v[1]);
+                    }
+                }
+            }
+            if !lines.is_empty() {
+                self.ffts[k][inverse as usize].process(&mut lines);
+            }
+
+            let chunks = segments
+                .iter()
+                .map(|&[lo, hi]| {
+                    lines
+                        .chunks_exact(n)
+                        .flat_map(|line| line[lo..hi].iter().flat_map(|v| [v.re, v.im]))
+                        .collect()
+                })
+                .collect();
+            for (c, chunk) in all_to_all(comm, axis, chunks).into_iter().enumerate() {
+                let mut values = chunk.chunks_exact(2);
+                for field in fields.iter_mut() {
+                    for &start in group(c) {
+                        for t in 0..length {
+                            let v = values.next().expect("Invalid message");
+                            field[start + t * stride] = Complex::new(v[0], v[1]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Exchange values between the processes along an axis of a periodic box, passing them
+/// on upward around the ring of processes. Given the values for each process along the
+/// axis in order of position, returns the values from each.
+fn all_to_all(comm: &dyn Comm, axis: Axis, mut chunks: Vec<Vec<f64>>) -> Vec<Vec<f64>> {
+    let p = chunks.len();
+    let me = comm.position()[axis.index()];
+    let up = axis.direction(true);
+    let mut received = vec![Vec::new(); p];
+    received[me] = std::mem::take(&mut chunks[me]);
+    // The values in transit for the processes 1, 2, ... positions further up
+    let mut transit: Vec<Vec<f64>> = (1..p)
+        .map(|d| std::mem::take(&mut chunks[(me + d) % p]))
+        .collect();
+    for step in 1..p {
+        for values in transit.drain(..) {
+            comm.send(values, up);
+        }
+        transit = (step..p).map(|_| comm.receive(up)).collect();
+        // The first values are for this process, from the one `step` positions down
+        received[(me + p - step) % p] = transit.remove(0);
+    }
+    received
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use super::*;
+    use crate::atomic::tests::NoComm;
+
+    #[test]
+    fn test_b_spline() {
+        for order in 2..=MAX_ORDER {
+            let values = b_spline(order, 0.3);
+            assert!((values.iter().sum::<f64>() - 1.0).abs() < 1e-14);
+            assert!(values[order..].iter().all(|&v| v == 0.0));
+            // Symmetric about the center
+            let mirrored = b_spline(order, 0.7);
+            for j in 0..order {
+                assert!((values[j] - mirrored[order - 1 - j]).abs() < 1e-14);
+            }
+        }
+        assert_eq!(b_spline(2, 0.25)[..2], [0.25, 0.75]);
+    }
+
+    #[test]
+    fn test_fft() {
+        let size = [4, 3, 5];
+        let mesh = Mesh::new(size, &NoComm::default());
+        let values: Vec<Complex<f64>> = (0..mesh.num_owned())
+            .map(|i| Complex::new((i as f64).sin(), (i as f64 * 0.3).cos()))
+            .collect();
+        let mut fields = vec![values.clone(), values.clone()];
+        mesh.fft(&NoComm::default(), &mut fields, false);
+        let points: Vec<[i64; 3]> = mesh.owned_points().collect();
+        for (m, value) in points.iter().zip(&fields[0]) {
+            let expected: Complex<f64> = points
+                .iter()
+                .zip(&values)
+                .map(|(r, v)| {
+                    let phase: f64 = (0..3).map(|k| (m[k] * r[k]) as f64 / size[k] as f64).sum();
+                    v * Complex::from_polar(1.0, -2.0 * PI * phase)
+                })
+                .sum();
+            assert!((value - expected).norm() < 1e-10);
+        }
+        assert_eq!(fields[0], fields[1]);
+
+        mesh.fft(&NoComm::default(), &mut fields, true);
+        let n = mesh.num_owned() as f64;
+        for (value, original) in fields[0].iter().zip(&values) {
+            assert!((value / n - original).norm() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_ghost_comm() {
+        let mut mesh = Mesh::new([6, 6, 6], &NoComm::default());
+        mesh.set_stencils(3, [0.05; 3]);
+        let comm = NoComm::default();
+        // Spreading unit values over a stencil reaching across the box boundary adds up
+        let mut values = vec![0.0; mesh.num_ghosted()];
+        mesh.for_each_stencil_point([0.99, 0.0, 0.5], |i, w| values[i] += w);
+        mesh.reverse_comm(&comm, &mut values, 1);
+        let owned: Vec<f64> = mesh.owned_indices().iter().map(|&i| values[i]).collect();
+        assert!((owned.iter().sum::<f64>() - 1.0).abs() < 1e-14);
+
+        // Ghost points take the values of the owned points they are periodic images of
+        let mut values = vec![0.0; 2 * mesh.num_ghosted()];
+        for (i, p) in mesh.owned_indices().into_iter().zip(mesh.owned_points()) {
+            values[2 * i] = (p[0] + 10 * p[1] + 100 * p[2]) as f64;
+            values[2 * i + 1] = -1.0;
+        }
+        mesh.forward_comm(&comm, &mut values, 2);
+        let [[x0, x1], [y0, y1], [z0, z1]] = mesh.ghost;
+        for z in z0..z1 {
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let i = mesh.ghosted_index([x, y, z]);
+                    let [x, y, z] = [x, y, z].map(|m| m.rem_euclid(6));
+                    assert_eq!(values[2 * i], (x + 10 * y + 100 * z) as f64);
+                    assert_eq!(values[2 * i + 1], -1.0);
+                }
+            }
+        }
+    }
+}
@@ -1,12 +1,70 @@
-use crate::{atom_type::AtomType, atoms::Atoms, neighbor::NeighborList};
+use std::io;
 
+use crate::{
+    atom_type::AtomType,
+    atoms::Atoms,
+    container::Container,
+    neighbor::NeighborList,
+    output::{RestartReader, RestartWriter},
+    utils::Direction,
+};
+
+mod born_mayer;
+mod buckingham;
+mod coul_dsf;
+mod coulomb;
+mod eam;
+mod ewald;
 mod ljcut;
+mod many_body;
+mod mesh;
+mod morse;
 mod none;
+mod pair;
+mod pppm;
+mod stillinger_weber;
+mod table;
+mod tersoff;
 
-pub use ljcut::{LJCut, LJCutCoeff};
+pub use born_mayer::{BornMayer, BornMayerCoeff};
+pub use buckingham::{Buckingham, BuckinghamCoeff};
+pub use coul_dsf::{CoulDSF, CoulShift};
+pub use coulomb::COULOMB_METAL;
+pub use eam::EAM;
+pub use ewald::Ewald;
+pub use ljcut::{LJCut, LJCutCoeff, Mixing, Truncation};
+pub use morse::{Morse, MorseCoeff};
 pub use none::None_;
+pub use pair::{Pair, PairCoeff};
+pub use pppm::PPPM;
+pub use stillinger_weber::StillingerWeber;
+pub use table::{Table, TableCoeff};
+pub use tersoff::Tersoff;
+
+/// Communication between processes while computing per-atom values. Per-atom values are
+/// indexed like the atoms, including ghost atoms.
+pub trait Comm {
+    /// Add the values of ghost atoms to those of the owned atoms they are copies of
+    fn reverse_comm(&self, values: &mut [f64]);
+    /// Overwrite the values of ghost atoms with those of the owned atoms they are copies of
+    fn forward_comm(&self, values: &mut [f64]);
+    /// Sum each value over all processes, which should all give the same number of values
+    fn sum(&self, values: &mut [f64]);
+    /// The number of processes along each axis, which split the box evenly in fractional
+    /// coordinates
+    fn procs(&self) -> [usize; 3];
+    /// The position of this process along each axis
+    fn position(&self) -> [usize; 3];
+    /// Send values to the neighboring process in the given direction, which is this
+    /// process itself when it is the only one along the axis of a periodic box
+    fn send(&self, values: Vec<f64>, direction: Direction);
+    /// Receive the next values sent in the given direction, i.e., from the neighboring
+    /// process in the opposite direction
+    fn receive(&self, direction: Direction) -> Vec<f64>;
+}
 
-/// Trait for pairwise atomic potentials
+/// Trait for atomic potentials: pair potentials, many-body potentials such as EAM,
+/// Stillinger-Weber and Tersoff, and long-range electrostatics such as Ewald and PPPM
 pub trait AtomicPotentialTrait<T: AtomType> {
     type Coeff;
 
@@ -15,6 +73,27 @@ pub trait AtomicPotentialTrait<T: AtomType> {
     /// Get the maximum distance for effective interaction
     fn cutoff_distance(&self) -> f64;
 
+    /// Whether the potential needs full neighbor lists, which list each pair under both
+    /// atoms and include every ghost atom within range, rather than half lists. False by
+    /// default.
+    fn full_neighbor_list(&self) -> bool {
+        false
+    }
+
+    /// Compute per-atom values that the forces, energies and virials depend on, such as
+    /// the electron densities of many-body potentials or the structure factors of
+    /// long-range electrostatics, communicating them between processes midway where
+    /// needed. Called by every process before each force computation, with the other
+    /// compute methods using the values until the next call. Does nothing by default.
+    fn compute_per_atom_values(
+        &mut self,
+        _atoms: &Atoms<T>,
+        _neighbor_list: &NeighborList,
+        _container: &Container,
+        _comm: &dyn Comm,
+    ) {
+    }
+
     /// Compute the pairwise force given a configuration of atoms
     fn compute_forces(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> Vec<[f64; 3]>;
 
@@ -24,10 +103,234 @@ pub trait AtomicPotentialTrait<T: AtomType> {
     fn num_types(&self) -> usize;
     fn compute_potential_energy(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> f64;
 
+    /// Compute the virial tensor `sum r_ij (x) f_ij` over the pairs of owned atoms,
+    /// ordered as `[xx, yy, zz, xy, xz, yz]`
+    fn compute_virial(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> [f64; 6];
+
+    /// Compute the potential energy of each atom, including ghost atoms, with the energy
+    /// of each pair split evenly between its atoms. Returns `None` if unsupported.
+    fn compute_per_atom_energy(
+        &self,
+        _atoms: &Atoms<T>,
+        _neighbor_list: &NeighborList,
+    ) -> Option<Vec<f64>> {
+        None
+    }
+
+    /// Compute the virial tensor of each atom, including ghost atoms, ordered as in
+    /// `compute_virial`, with the virial of each pair split evenly between its atoms.
+    /// Returns `None` if unsupported.
+    fn compute_per_atom_virial(
+        &self,
+        _atoms: &Atoms<T>,
+        _neighbor_list: &NeighborList,
+    ) -> Option<Vec<[f64; 6]>> {
+        None
+    }
+
+    /// Long-range corrections for the interactions beyond the cutoff, given the number of
+    /// atoms of each type over all processes and the volume, assuming a uniform density.
+    /// Returns the corrections to the energy and to the trace of the virial, which are
+    /// zero by default.
+    fn tail_correction(&self, _type_counts: &[usize], _volume: f64) -> [f64; 2] {
+        [0.0, 0.0]
+    }
+
     fn type_idx(&self, typei: usize, typej: usize) -> usize {
         self.num_types() * typei + typej
     }
 
     fn all_set(&self) -> bool;
     fn set_coeff(&mut self, typei: usize, typej: usize, coeff: &Self::Coeff);
+
+    /// Write the settings and coefficients of the potential to a restart file
+    fn write_restart(&self, writer: &mut RestartWriter) -> io::Result<()>;
+    /// Read the settings and coefficients written by `write_restart`
+    fn read_restart(&mut self, reader: &mut RestartReader) -> io::Result<()>;
+}
+
+/// The virial of a pair with the given separation and force divided by distance, ordered
+/// as in `AtomicPotentialTrait::compute_virial`
+pub(super) fn pair_virial(r: [f64; 3], f_over_r: f64) -> [f64; 6] {
+    [
+        r[0] * r[0] * f_over_r,
+        r[1] * r[1] * f_over_r,
+        r[2] * r[2] * f_over_r,
+        r[0] * r[1] * f_over_r,
+        r[0] * r[2] * f_over_r,
+        r[1] * r[2] * f_over_r,
+    ]
+}
+
+/// Call the function with the indices, separation and squared distance of each pair of
+/// an owned atom and a neighbor, skipping the images of the atom itself
+pub(super) fn for_each_pair<T: AtomType>(
+    atoms: &Atoms<T>,
+    neighbor_list: &NeighborList,
+    mut f: impl FnMut(usize, usize, [f64; 3], f64),
+) {
+    for i in 0..atoms.nlocal {
+        let posi = &atoms.positions[i];
+        for &j in &neighbor_list.neighbors()[i] {
+            if atoms.ids[i] == atoms.ids[j] {
+                continue;
+            }
+            let posj = &atoms.positions[j];
+            let r = [posi[0] - posj[0], posi[1] - posj[1], posi[2] - posj[2]];
+            f(i, j, r, r[0] * r[0] + r[1] * r[1] + r[2] * r[2]);
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{cell::RefCell, collections::VecDeque};
+
+    use super::*;
+    use crate::{atom_type::Basic, container::BC};
+
+    /// Communication within a single process, whose ghost atoms are its periodic images
+    /// and whose neighbor in each direction is itself
+    #[derive(Default)]
+    pub(crate) struct NoComm {
+        sent: RefCell<VecDeque<(Direction, Vec<f64>)>>,
+    }
+    impl Comm for NoComm {
+        fn reverse_comm(&self, _values: &mut [f64]) {}
+        fn forward_comm(&self, _values: &mut [f64]) {}
+        fn sum(&self, _values: &mut [f64]) {}
+        fn procs(&self) -> [usize; 3] {
+            [1; 3]
+        }
+        fn position(&self) -> [usize; 3] {
+            [0; 3]
+        }
+        fn send(&self, values: Vec<f64>, direction: Direction) {
+            self.sent.borrow_mut().push_back((direction, values));
+        }
+        fn receive(&self, direction: Direction) -> Vec<f64> {
+            let mut sent = self.sent.borrow_mut();
+            let i = sent
+                .iter()
+                .position(|(d, _)| *d == direction)
+                .expect("Values should have been sent");
+            sent.remove(i).expect("Index should be valid").1
+        }
+    }
+
+    /// The potential energy, after computing the per-atom values within a single process
+    pub(crate) fn energy<A: AtomicPotentialTrait<Basic>>(
+        potential: &mut A,
+        atoms: &Atoms<Basic>,
+        container: &Container,
+        nl: &NeighborList,
+    ) -> f64 {
+        potential.compute_per_atom_values(atoms, nl, container, &NoComm::default());
+        potential.compute_potential_energy(atoms, nl)
+    }
+
+    /// The force on each owned atom, including those on its ghost copies
+    pub(crate) fn owned_forces<A: AtomicPotentialTrait<Basic>>(
+        potential: &A,
+        atoms: &Atoms<Basic>,
+        nl: &NeighborList,
+    ) -> Vec<[f64; 3]> {
+        let forces = potential.compute_forces(atoms, nl);
+        let mut owned = forces[..atoms.nlocal].to_vec();
+        for j in atoms.nlocal..atoms.num_total_atoms() {
+            for k in 0..3 {
+                owned[atoms.ids[j]][k] += forces[j][k];
+            }
+        }
+        owned
+    }
+
+    /// Check the forces on a cluster of four atoms of types 0 and 1 in a periodic box,
+    /// with a full neighbor list if asked for, as in `check_finite_differences`
+    pub(crate) fn check_forces<A: AtomicPotentialTrait<Basic>>(
+        potential: &mut A,
+        full: bool,
+        energy: impl FnMut(&mut A, &Atoms<Basic>, &Container, &NeighborList) -> f64,
+    ) {
+        let mut atoms: Atoms<Basic> = Atoms::new();
+        atoms.atom_types = vec![Basic::new(1.0), Basic::new(1.0)];
+        atoms.ids = vec![0, 1, 2, 3];
+        atoms.types = vec![0, 1, 1, 0];
+        atoms.positions = vec![
+            [5.0, 5.0, 5.0],
+            [6.3, 5.2, 5.0],
+            [5.3, 6.4, 5.9],
+            [6.1, 6.2, 4.1],
+        ];
+        atoms.velocities = vec![[0.0; 3]; 4];
+        atoms.nlocal = 4;
+        let container = Container::new(0.0, 10.0, 0.0, 10.0, 0.0, 10.0, BC::PP, BC::PP, BC::PP);
+        let mut nl = NeighborList::new(&container, potential.cutoff_distance(), 0.3);
+        nl.set_full(full);
+        nl.update(&atoms.positions, 4);
+        check_finite_differences(
+            potential,
+            &mut atoms,
+            &container,
+            &nl,
+            &[0, 1, 2, 3],
+            energy,
+        );
+    }
+
+    /// Check that the per-atom energies and virials add up to the totals, where supported,
+    /// and the forces on the atoms with the given ids against central finite differences
+    /// of the energy, moving the ghost copies of each atom with it
+    pub(crate) fn check_finite_differences<A: AtomicPotentialTrait<Basic>>(
+        potential: &mut A,
+        atoms: &mut Atoms<Basic>,
+        container: &Container,
+        nl: &NeighborList,
+        ids: &[usize],
+        mut energy: impl FnMut(&mut A, &Atoms<Basic>, &Container, &NeighborList) -> f64,
+    ) {
+        let total = energy(potential, atoms, container, nl);
+        // Up to rounding errors relative to the sizes of the terms
+        let check_sum = |terms: &[f64], total: f64| {
+            let tolerance = 1e-12 * terms.iter().map(|t| t.abs()).sum::<f64>().max(1.0);
+            assert!((terms.iter().sum::<f64>() - total).abs() < tolerance);
+        };
+        if let Some(energies) = potential.compute_per_atom_energy(atoms, nl) {
+            check_sum(&energies, total);
+        }
+        if let Some(virials) = potential.compute_per_atom_virial(atoms, nl) {
+            let virial = potential.compute_virial(atoms, nl);
+            for (k, &v) in virial.iter().enumerate() {
+                check_sum(&virials.iter().map(|v| v[k]).collect::<Vec<f64>>(), v);
+            }
+        }
+
+        let forces = owned_forces(potential, atoms, nl);
+        let h = 1e-6;
+        let displace = |atoms: &mut Atoms<Basic>, id: usize, k: usize, d: f64| {
+            for j in 0..atoms.num_total_atoms() {
+                if atoms.ids[j] == id {
+                    atoms.positions[j][k] += d;
+                }
+            }
+        };
+        for &i in ids {
+            for (k, &f) in forces[i].iter().enumerate() {
+                displace(atoms, i, k, h);
+                let plus = energy(potential, atoms, container, nl);
+                displace(atoms, i, k, -2.0 * h);
+                let minus = energy(potential, atoms, container, nl);
+                displace(atoms, i, k, h);
+                let expected = -(plus - minus) / (2.0 * h);
+                assert!(
+                    (f - expected).abs() < 1e-6 * expected.abs().max(1.0),
+                    "Force {} on atom {} along {} should be {}",
+                    f,
+                    i,
+                    k,
+                    expected
+                );
+            }
+        }
+    }
 }
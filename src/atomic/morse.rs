@@ -0,0 +1,51 @@
+use super::*;
+
+/// Morse potential `D0 [exp(-2 alpha (r - r0)) - 2 exp(-alpha (r - r0))]`, with well
+/// depth `D0` at the equilibrium distance `r0`
+pub type Morse = Pair<MorseCoeff>;
+
+#[derive(Clone, Copy, Debug)]
+pub struct MorseCoeff {
+    d0: f64,
+    alpha: f64,
+    r0: f64,
+    rcut: f64,
+}
+impl MorseCoeff {
+    pub fn new(d0: f64, alpha: f64, r0: f64, rcut: f64) -> Self {
+        Self {
+            d0,
+            alpha,
+            r0,
+            rcut,
+        }
+    }
+    pub fn d0(&self) -> f64 {
+        self.d0
+    }
+    pub fn alpha(&self) -> f64 {
+        self.alpha
+    }
+    pub fn r0(&self) -> f64 {
+        self.r0
+    }
+}
+impl PairCoeff for MorseCoeff {
+    fn rcut(&self) -> f64 {
+        self.rcut
+    }
+    fn energy(&self, r: f64) -> f64 {
+        let e = (-self.alpha * (r - self.r0)).exp();
+        self.d0 * e * (e - 2.0)
+    }
+    fn force(&self, r: f64) -> f64 {
+        let e = (-self.alpha * (r - self.r0)).exp();
+        2.0 * self.alpha * self.d0 * e * (e - 1.0)
+    }
+    fn to_params(&self) -> Vec<f64> {
+        vec![self.d0, self.alpha, self.r0, self.rcut]
+    }
+    fn from_params(params: &[f64]) -> Self {
+        Self::new(params[0], params[1], params[2], params[3])
+    }
+}
@@ -22,8 +22,31 @@ impl<T: AtomType> AtomicPotentialTrait<T> for None_ {
     fn compute_potential_energy(&self, _atoms: &Atoms<T>, _neighbor_list: &NeighborList) -> f64 {
         0.0
     }
+    fn compute_virial(&self, _atoms: &Atoms<T>, _neighbor_list: &NeighborList) -> [f64; 6] {
+        [0.0; 6]
+    }
+    fn compute_per_atom_energy(
+        &self,
+        atoms: &Atoms<T>,
+        _neighbor_list: &NeighborList,
+    ) -> Option<Vec<f64>> {
+        Some(vec![0.0; atoms.num_total_atoms()])
+    }
+    fn compute_per_atom_virial(
+        &self,
+        atoms: &Atoms<T>,
+        _neighbor_list: &NeighborList,
+    ) -> Option<Vec<[f64; 6]>> {
+        Some(vec![[0.0; 6]; atoms.num_total_atoms()])
+    }
     fn all_set(&self) -> bool {
         true
     }
     fn set_coeff(&mut self, _typei: usize, _typej: usize, _coeff: &Self::Coeff) {}
+    fn write_restart(&self, _writer: &mut RestartWriter) -> io::Result<()> {
+        Ok(())
+    }
+    fn read_restart(&mut self, _reader: &mut RestartReader) -> io::Result<()> {
+        Ok(())
+    }
 }
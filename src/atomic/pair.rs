@@ -0,0 +1,250 @@
+use super::*;
+
+/// Coefficients between a pair of atom types of a potential that depends only on the
+/// distance between the atoms
+pub trait PairCoeff: Clone {
+    /// The distance beyond which the pair does not interact
+    fn rcut(&self) -> f64;
+    /// The unshifted pair energy at the given distance
+    fn energy(&self, r: f64) -> f64;
+    /// The magnitude of the pair force at the given distance, i.e., the negative
+    /// derivative of the energy, positive when repulsive
+    fn force(&self, r: f64) -> f64;
+    /// The parameters of the coefficient, to be written to restart files
+    fn to_params(&self) -> Vec<f64>;
+    /// The coefficient with the parameters given by `to_params`
+    fn from_params(params: &[f64]) -> Self;
+}
+
+/// Pair potential with coefficients of type `C` between each pair of types, shifted so
+/// that the energy is zero at the cutoff by default
+pub struct Pair<C: PairCoeff> {
+    num_types: usize,
+    force_cutoff: f64,
+    coeffs: Vec<Option<C>>,
+    shift: bool,
+}
+impl<C: PairCoeff> Pair<C> {
+    pub fn new(force_cutoff: f64) -> Self {
+        assert!(
+            force_cutoff > 0.0,
+            "Force cutoff should be positive, found {}",
+            force_cutoff
+        );
+        Self {
+            num_types: 0,
+            force_cutoff,
+            coeffs: Vec::new(),
+            shift: true,
+        }
+    }
+    /// Whether the energy of each pair is shifted by its value at the cutoff, so that it
+    /// is continuous there
+    pub fn set_shift(&mut self, shift: bool) {
+        self.shift = shift;
+    }
+    /// The pair energy at the given distance, shifted if set
+    fn energy(&self, coeff: &C, r: f64) -> f64 {
+        if self.shift {
+            coeff.energy(r) - coeff.energy(coeff.rcut())
+        } else {
+            coeff.energy(r)
+        }
+    }
+    /// The pairs of `for_each_pair` within the cutoff of their coefficient, passed with the
+    /// distance rather than its square and with the coefficient
+    fn for_each_pair<T: AtomType>(
+        &self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+        mut f: impl FnMut(usize, usize, [f64; 3], f64, &C),
+    ) {
+        for_each_pair(atoms, neighbor_list, |i, j, r, r2| {
+            let idx = self.num_types * atoms.types[i] + atoms.types[j];
+            let coeff = self.coeffs[idx]
+                .as_ref()
+                .expect("Coefficient should be set");
+            if r2 < coeff.rcut() * coeff.rcut() {
+                f(i, j, r, r2.sqrt(), coeff);
+            }
+        });
+    }
+}
+
+impl<T: AtomType, C: PairCoeff> AtomicPotentialTrait<T> for Pair<C> {
+    type Coeff = C;
+    fn new() -> Self {
+        Self::new(1.0)
+    }
+    fn cutoff_distance(&self) -> f64 {
+        self.force_cutoff
+    }
+    fn compute_forces(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> Vec<[f64; 3]> {
+        let mut forces = vec![[0.0; 3]; atoms.num_total_atoms()];
+        self.for_each_pair(atoms, neighbor_list, |i, j, r, dist, coeff| {
+            let f_over_r = coeff.force(dist) / dist;
+            for k in 0..3 {
+                forces[i][k] += r[k] * f_over_r;
+                forces[j][k] -= r[k] * f_over_r;
+            }
+        });
+        forces
+    }
+    fn compute_potential_energy(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> f64 {
+        let mut energy = 0.0;
+        self.for_each_pair(atoms, neighbor_list, |_, _, _, dist, coeff| {
+            energy += self.energy(coeff, dist);
+        });
+        energy
+    }
+    fn compute_virial(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> [f64; 6] {
+        let mut virial = [0.0; 6];
+        self.for_each_pair(atoms, neighbor_list, |_, _, r, dist, coeff| {
+            let pair = pair_virial(r, coeff.force(dist) / dist);
+            for k in 0..6 {
+                virial[k] += pair[k];
+            }
+        });
+        virial
+    }
+    fn compute_per_atom_energy(
+        &self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+    ) -> Option<Vec<f64>> {
+        let mut energies = vec![0.0; atoms.num_total_atoms()];
+        self.for_each_pair(atoms, neighbor_list, |i, j, _, dist, coeff| {
+            let half_energy = 0.5 * self.energy(coeff, dist);
+            energies[i] += half_energy;
+            energies[j] += half_energy;
+        });
+        Some(energies)
+    }
+    fn compute_per_atom_virial(
+        &self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+    ) -> Option<Vec<[f64; 6]>> {
+        let mut virials = vec![[0.0; 6]; atoms.num_total_atoms()];
+        self.for_each_pair(atoms, neighbor_list, |i, j, r, dist, coeff| {
+            let pair = pair_virial(r, 0.5 * coeff.force(dist) / dist);
+            for k in 0..6 {
+                virials[i][k] += pair[k];
+                virials[j][k] += pair[k];
+            }
+        });
+        Some(virials)
+    }
+    fn num_types(&self) -> usize {
+        self.num_types
+    }
+    fn set_num_types(&mut self, num_types: usize) {
+        let old = self.num_types;
+        let mut coeffs = vec![None; num_types * num_types];
+        for i in 0..old.min(num_types) {
+            for j in 0..old.min(num_types) {
+                coeffs[i * num_types + j] = self.coeffs[i * old + j].take();
+            }
+        }
+        self.num_types = num_types;
+        self.coeffs = coeffs;
+    }
+    fn all_set(&self) -> bool {
+        self.coeffs.iter().all(|c| c.is_some())
+    }
+    /// The coefficient is set for both orders of the types
+    fn set_coeff(&mut self, typei: usize, typej: usize, coeff: &Self::Coeff) {
+        assert!(
+            typei < self.num_types && typej < self.num_types,
+            "Type indices should be less than the number of types (0-indexed)"
+        );
+        assert!(
+            coeff.rcut() <= self.force_cutoff,
+            "Cutoff distance {} should be at most the global cutoff {}",
+            coeff.rcut(),
+            self.force_cutoff
+        );
+        self.coeffs[self.num_types * typei + typej] = Some(coeff.clone());
+        self.coeffs[self.num_types * typej + typei] = Some(coeff.clone());
+    }
+    fn write_restart(&self, writer: &mut RestartWriter) -> io::Result<()> {
+        writer.write_f64(self.force_cutoff)?;
+        writer.write_bool(self.shift)?;
+        writer.write_usize(self.num_types)?;
+        for coeff in &self.coeffs {
+            writer.write_bool(coeff.is_some())?;
+            if let Some(coeff) = coeff {
+                writer.write_f64s(&coeff.to_params())?;
+            }
+        }
+        Ok(())
+    }
+    fn read_restart(&mut self, reader: &mut RestartReader) -> io::Result<()> {
+        self.force_cutoff = reader.read_f64()?;
+        self.shift = reader.read_bool()?;
+        self.num_types = reader.read_usize()?;
+        self.coeffs = (0..self.num_types * self.num_types)
+            .map(|_| {
+                Ok(if reader.read_bool()? {
+                    Some(C::from_params(&reader.read_f64s()?))
+                } else {
+                    None
+                })
+            })
+            .collect::<io::Result<Vec<Option<C>>>>()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        atom_type::Basic,
+        atomic::{tests::check_forces, BornMayerCoeff, BuckinghamCoeff, MorseCoeff},
+    };
+
+    /// Check that the shifted energy is zero at the cutoff, and the forces on a small
+    /// cluster of two types against central finite differences of the energy
+    fn check_pair_forces<C: PairCoeff>(coeffs: [C; 3]) {
+        let mut pair: Pair<C> = Pair::new(3.0);
+        for coeff in &coeffs {
+            assert!(pair.energy(coeff, coeff.rcut()).abs() < 1e-12);
+        }
+
+        <Pair<C> as AtomicPotentialTrait<Basic>>::set_num_types(&mut pair, 2);
+        for (coeff, [i, j]) in coeffs.iter().zip([[0, 0], [0, 1], [1, 1]]) {
+            <Pair<C> as AtomicPotentialTrait<Basic>>::set_coeff(&mut pair, i, j, coeff);
+        }
+        check_forces(&mut pair, false, |pair, atoms, _, nl| {
+            pair.compute_potential_energy(atoms, nl)
+        });
+    }
+
+    #[test]
+    fn test_morse_forces() {
+        check_pair_forces([
+            MorseCoeff::new(1.0, 2.0, 1.2, 2.5),
+            MorseCoeff::new(0.5, 1.5, 1.4, 3.0),
+            MorseCoeff::new(2.0, 1.0, 1.1, 2.8),
+        ]);
+    }
+
+    #[test]
+    fn test_buckingham_forces() {
+        check_pair_forces([
+            BuckinghamCoeff::new(1000.0, 0.3, 5.0, 2.5),
+            BuckinghamCoeff::new(1500.0, 0.25, 2.0, 3.0),
+            BuckinghamCoeff::new(800.0, 0.35, 10.0, 2.8),
+        ]);
+    }
+
+    #[test]
+    fn test_born_mayer_forces() {
+        check_pair_forces([
+            BornMayerCoeff::new(10.0, 0.3, 1.0, 5.0, 1.0, 2.5),
+            BornMayerCoeff::new(5.0, 0.25, 1.2, 2.0, 0.5, 3.0),
+            BornMayerCoeff::new(8.0, 0.35, 0.9, 0.0, 0.0, 2.8),
+        ]);
+    }
+}
@@ -0,0 +1,799 @@
+use std::f64::consts::PI;
+
+use rustfft::num_complex::Complex;
+
+use super::{
+    coulomb::{for_each_charged_pair, reciprocal_vectors, screened, splitting_parameter},
+    mesh::{b_spline, Mesh, MAX_ORDER},
+    *,
+};
+use crate::{region::Region, utils::Axis};
+
+/// Coefficients of the estimate of the root-mean-square error in the forces of
+/// particle-mesh Ewald sums with ik differentiation, for each order of charge assignment,
+/// from Deserno and Holm
+const ERROR_COEFFS: [&[f64]; MAX_ORDER + 1] = [
+    &[],
+    &[2.0 / 3.0],
+    &[1.0 / 50.0, 5.0 / 294.0],
+    &[1.0 / 588.0, 7.0 / 1440.0, 21.0 / 3872.0],
+    &[
+        1.0 / 4320.0,
+        3.0 / 1936.0,
+        7601.0 / 2271360.0,
+        143.0 / 28800.0,
+    ],
+    &[
+        1.0 / 23232.0,
+        7601.0 / 13628160.0,
+        143.0 / 69120.0,
+        517231.0 / 106536960.0,
+        106640677.0 / 11737571328.0,
+    ],
+    &[
+        691.0 / 68140800.0,
+        13.0 / 57600.0,
+        47021.0 / 35512320.0,
+        9694607.0 / 2095994880.0,
+        733191589.0 / 59609088000.0,
+        326190917.0 / 11700633600.0,
+    ],
+    &[
+        1.0 / 345600.0,
+        3617.0 / 35512320.0,
+        745739.0 / 838397952.0,
+        56399353.0 / 12773376000.0,
+        25091609.0 / 1560084480.0,
+        1755948832039.0 / 36229939200000.0,
+        4887769399.0 / 37838389248.0,
+    ],
+];
+
+/// The signed frequency of a grid index out of the given number of points, from `-n / 2`
+/// up to below `n / 2`
+fn frequency(m: i64, n: usize) -> i64 {
+    if 2 * m < n as i64 {
+        m
+    } else {
+        m - n as i64
+    }
+}
+
+/// The smallest number of grid points, at least the given one, with no prime factors
+/// other than 2, 3 and 5, for which the transforms are fastest
+fn factorable(mut n: usize) -> usize {
+    loop {
+        let mut m = n;
+        for p in [2, 3, 5] {
+            while m.is_multiple_of(p) {
+                m /= p;
+            }
+        }
+        if m == 1 {
+            return n;
+        }
+        n += 1;
+    }
+}
+
+/// Coulomb potential between the charges of the atoms of a box periodic along every
+/// axis, by particle-particle particle-mesh (PPPM) Ewald summation as with LAMMPS
+/// `pair_style coul/long` and `kspace_style pppm`. The interactions are split as in
+/// `Ewald`, with the reciprocal-space sum computed on a grid: the charges are spread
+/// over the grid points around each atom, the electric field is found by fast Fourier
+/// transforms with the optimal influence function of Hockney and Eastwood, and
+/// interpolated back to the atoms. The cost grows as `N log N` with the number of atoms.
+///
+/// The grid is split between the processes along their subdomains, exchanging ghost
+/// points with their neighbors, and is transformed one axis at a time by the processes
+/// along it. The splitting parameter `alpha` and the number of grid points along each box
+/// vector are chosen, as in LAMMPS, so that the root-mean-square errors in the
+/// real-space and reciprocal-space forces are equal and about the given accuracy relative
+/// to the force between two unit charges a unit distance apart. They are chosen on the
+/// first force computation and again if the number of atoms or the sum of squared charges
+/// change. A system with a net charge is neutralized by a uniform background charge.
+///
+/// Forces are found by differentiating in reciprocal space, so they conserve momentum
+/// but are not exactly the derivatives of the energy. Per-atom virials are not supported.
+///
+/// The potential has no per-type coefficients. The Coulomb constant is 1 unless set.
+///
+/// ```rust
+/// use jmd::atomic::{PPPM, COULOMB_METAL};
+///
+/// let mut pppm = PPPM::new(10.0, 1e-5);
+/// pppm.set_coulomb_constant(COULOMB_METAL);
+/// pppm.set_order(7);
+/// ```
+pub struct PPPM {
+    num_types: usize,
+    cutoff: f64,
+    accuracy: f64,
+    order: usize,
+    coulomb_constant: f64,
+    /// The number of atoms and sum of squared charges that `alpha` and the grid were
+    /// chosen for
+    tuned_for: Option<(usize, f64)>,
+    alpha: f64,
+    grid: [usize; 3],
+    mesh: Option<Mesh>,
+    /// The box that the influence function was computed for, as its origin and
+    /// reciprocal box vectors
+    origin: [f64; 3],
+    reciprocal: [[f64; 3]; 3],
+    volume: f64,
+    total_charge: f64,
+    /// Influence function at the owned points of the transformed grid
+    influence: Vec<f64>,
+    /// Electric field and potential at the points held by this process, including ghost
+    /// points, due to the charges of the reciprocal-space sum
+    fields: Vec<[f64; 4]>,
+    /// Reciprocal-space virial of the owned points of the transformed grid, with the
+    /// virial of the neutralizing background for the owned atoms
+    kspace_virial: [f64; 6],
+}
+impl PPPM {
+    /// The potential with the given real-space cutoff and relative accuracy of the
+    /// forces, with charge assignment of order 5
+    pub fn new(cutoff: f64, accuracy: f64) -> Self {
+        assert!(cutoff > 0.0, "Cutoff should be positive, found {}", cutoff);
+        assert!(
+            accuracy > 0.0 && accuracy < 1.0,
+            "Accuracy should be between 0 and 1, found {}",
+            accuracy
+        );
+        Self {
+            num_types: 0,
+            cutoff,
+            accuracy,
+            order: 5,
+            coulomb_constant: 1.0,
+            tuned_for: None,
+            alpha: 0.0,
+            grid: [0; 3],
+            mesh: None,
+            origin: [0.0; 3],
+            reciprocal: [[0.0; 3]; 3],
+            volume: 0.0,
+            total_charge: 0.0,
+            influence: Vec::new(),
+            fields: Vec::new(),
+            kspace_virial: [0.0; 6],
+        }
+    }
+    /// Set the number of grid points each charge is spread over along each axis, from 2
+    /// to 7. Higher orders need fewer grid points for the same accuracy.
+    pub fn set_order(&mut self, order: usize) {
+        assert!(
+            (2..=MAX_ORDER).contains(&order),
+            "Order should be from 2 to {}, found {}",
+            MAX_ORDER,
+            order
+        );
+        self.order = order;
+        self.tuned_for = None;
+    }
+    pub fn order(&self) -> usize {
+        self.order
+    }
+    /// Set the constant `1 / (4 pi epsilon_0)` converting products of charges over
+    /// distances to energies, such as `COULOMB_METAL`
+    pub fn set_coulomb_constant(&mut self, coulomb_constant: f64) {
+        self.coulomb_constant = coulomb_constant;
+    }
+    /// The splitting parameter, chosen on the first force computation
+    pub fn alpha(&self) -> f64 {
+        self.alpha
+    }
+    /// The number of grid points along each box vector, chosen with `alpha`
+    pub fn grid(&self) -> [usize; 3] {
+        self.grid
+    }
+
+    /// Estimate of the root-mean-square error in the reciprocal-space forces, for the
+    /// given splitting parameter and grid spacings along each box vector
+    fn kspace_error(&self, alpha: f64, spacings: [f64; 3], num_atoms: usize, q2: f64) -> f64 {
+        if num_atoms == 0 {
+            return 0.0;
+        }
+        let n = num_atoms as f64;
+        let lengths = self.face_lengths();
+        let errors = [0, 1, 2].map(|k| {
+            let ha = spacings[k] * alpha;
+            let sum: f64 = ERROR_COEFFS[self.order]
+                .iter()
+                .enumerate()
+                .map(|(m, c)| c * ha.powi(2 * m as i32))
+                .sum();
+            let length = lengths[k];
+            q2 * ha.powi(self.order as i32) * (alpha * length * (2.0 * PI).sqrt() * sum / n).sqrt()
+                / (length * length)
+        });
+        (errors.iter().map(|e| e * e).sum::<f64>() / 3.0).sqrt()
+    }
+    /// Estimate of the root-mean-square error in the real-space forces, from Kolafa and
+    /// Perram
+    fn rspace_error(&self, alpha: f64, num_atoms: usize, q2: f64) -> f64 {
+        2.0 * q2 * (-alpha * alpha * self.cutoff * self.cutoff).exp()
+            / (num_atoms as f64 * self.cutoff * self.volume).sqrt()
+    }
+    /// The distance between each pair of opposite faces of the box
+    fn face_lengths(&self) -> [f64; 3] {
+        self.reciprocal
+            .map(|b| 2.0 * PI / (b[0] * b[0] + b[1] * b[1] + b[2] * b[2]).sqrt())
+    }
+    /// Choose the splitting parameter and the grid for the given accuracy. The grid is
+    /// refined until the estimated reciprocal-space error is within the accuracy, rounded
+    /// up to sizes with small prime factors, and the splitting parameter is then adjusted
+    /// so that the real-space and reciprocal-space errors are equal.
+    fn tune(&mut self, num_atoms: usize, q2: f64) {
+        let lengths = self.face_lengths();
+        self.alpha = splitting_parameter(self.accuracy, self.cutoff, num_atoms, q2, self.volume);
+        let mut h = 4.0 / self.alpha;
+        let mut count = 0;
+        loop {
+            self.grid = lengths.map(|l| ((l / h) as usize).max(2));
+            if self.kspace_error(self.alpha, [h; 3], num_atoms, q2) <= self.accuracy {
+                break;
+            }
+            count += 1;
+            assert!(count <= 500, "Could not choose the grid for the accuracy");
+            h *= 0.95;
+        }
+        // The stencils of the atoms should fit within the grid
+        self.grid = self.grid.map(|n| factorable(n.max(self.order)));
+
+        let spacings = [0, 1, 2].map(|k| lengths[k] / self.grid[k] as f64);
+        let f = |alpha: f64| {
+            self.rspace_error(alpha, num_atoms, q2)
+                - self.kspace_error(alpha, spacings, num_atoms, q2)
+        };
+        let mut alpha = self.alpha;
+        let mut converged = false;
+        for _ in 0..1000 {
+            let value = f(alpha);
+            if value.abs() <= 1e-4 * self.accuracy {
+                converged = true;
+                break;
+            }
+            let step = 1e-6 * alpha;
+            alpha -= value * step / (f(alpha + step) - value);
+        }
+        assert!(
+            converged && alpha > 0.0,
+            "Could not choose the splitting parameter for the accuracy"
+        );
+        self.alpha = alpha;
+        self.tuned_for = Some((num_atoms, q2));
+    }
+    /// Compute the influence function at the owned points of the transformed grid for
+    /// the current box, optimal for ik differentiation. Its denominator, the sum over
+    /// aliases of the squared transforms of the charge assignment, is found exactly
+    /// from the values of the B-spline of twice the order at the integers.
+    fn set_influence(&mut self) {
+        let mesh = self.mesh.as_ref().expect("Mesh should be set up");
+        let size = mesh.size();
+        let order = self.order;
+        let spline = b_spline(2 * order, 0.0);
+        let denominators = size.map(|n| {
+            (0..n)
+                .map(|m| {
+                    (1..2 * order)
+                        .map(|j| {
+                            let shift = j as f64 - order as f64;
+                            spline[j] * (2.0 * PI * shift * m as f64 / n as f64).cos()
+                        })
+                        .sum::<f64>()
+                })
+                .collect::<Vec<f64>>()
+        });
+        let lengths = self.face_lengths();
+        let num_aliases = [0, 1, 2].map(|k| {
+            (self.alpha * lengths[k] / (PI * size[k] as f64) * (-(1e-7f64).ln()).powf(0.25)) as i64
+        });
+        let sinc_power = |f: i64, n: usize| {
+            let x = PI * f as f64 / n as f64;
+            if x == 0.0 {
+                1.0
+            } else {
+                (x.sin() / x).powi(2 * order as i32)
+            }
+        };
+        let alpha2 = self.alpha * self.alpha;
+        let influence = mesh
+            .owned_points()
+            .map(|m| {
+                let f = [0, 1, 2].map(|k| frequency(m[k], size[k]));
+                let k = self.kvector(f);
+                let k2 = dot(k, k);
+                if k2 == 0.0 {
+                    return 0.0;
+                }
+                let mut sum = 0.0;
+                for a in -num_aliases[0]..=num_aliases[0] {
+                    let f0 = f[0] + a * size[0] as i64;
+                    let w0 = sinc_power(f0, size[0]);
+                    for b in -num_aliases[1]..=num_aliases[1] {
+                        let f1 = f[1] + b * size[1] as i64;
+                        let w1 = sinc_power(f1, size[1]);
+                        for c in -num_aliases[2]..=num_aliases[2] {
+                            let f2 = f[2] + c * size[2] as i64;
+                            let w2 = sinc_power(f2, size[2]);
+                            let alias = self.kvector([f0, f1, f2]);
+                            let alias2 = dot(alias, alias);
+                            sum += dot(k, alias) / alias2
+                                * (-0.25 * alias2 / alpha2).exp()
+                                * w0
+                                * w1
+                                * w2;
+                        }
+                    }
+                }
+                let denominator = (0..3)
+                    .map(|k| denominators[k][m[k] as usize])
+                    .product::<f64>();
+                4.0 * PI / k2 * sum / (denominator * denominator)
+            })
+            .collect();
+        self.influence = influence;
+    }
+    /// The reciprocal vector with the given multiples of the reciprocal box vectors
+    fn kvector(&self, f: [i64; 3]) -> [f64; 3] {
+        [0, 1, 2].map(|j| (0..3).map(|m| f[m] as f64 * self.reciprocal[m][j]).sum())
+    }
+    fn to_lamda(&self, position: &[f64; 3]) -> [f64; 3] {
+        let d = [0, 1, 2].map(|j| position[j] - self.origin[j]);
+        self.reciprocal.map(|b| dot(b, d) / (2.0 * PI))
+    }
+    fn check_per_atom_values(&self) {
+        assert!(
+            self.mesh
+                .as_ref()
+                .is_some_and(|mesh| self.fields.len() == mesh.num_ghosted()),
+            "Per-atom values should be computed before the forces"
+        );
+    }
+    /// The electric field and potential of the reciprocal-space sum at an atom
+    fn interpolate(&self, position: &[f64; 3]) -> [f64; 4] {
+        let mesh = self.mesh.as_ref().expect("Mesh should be set up");
+        let mut values = [0.0; 4];
+        mesh.for_each_stencil_point(self.to_lamda(position), |i, w| {
+            for (v, f) in values.iter_mut().zip(self.fields[i]) {
+                *v += w * f;
+            }
+        });
+        values
+    }
+    /// The share of the energy of the neutralizing background of an atom with the given
+    /// charge
+    fn background(&self, q: f64) -> f64 {
+        -PI * q * self.total_charge / (2.0 * self.volume * self.alpha * self.alpha)
+    }
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+impl<T: AtomType> AtomicPotentialTrait<T> for PPPM {
+    /// No coefficients are needed
+    type Coeff = ();
+    fn new() -> Self {
+        Self::new(1.0, 1e-5)
+    }
+    fn cutoff_distance(&self) -> f64 {
+        self.cutoff
+    }
+    /// Choose the splitting parameter and grid if needed, spread the charges of the owned
+    /// atoms over the grid, and solve for the electric field and potential at the grid
+    /// points
+    fn compute_per_atom_values(
+        &mut self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+        container: &Container,
+        comm: &dyn Comm,
+    ) {
+        assert!(
+            [Axis::X, Axis::Y, Axis::Z]
+                .iter()
+                .all(|&axis| container.is_periodic(axis)),
+            "PPPM needs a box periodic along every axis"
+        );
+        let charges = &atoms.charges[..atoms.nlocal];
+        let mut sums = [charges.iter().map(|q| q * q).sum(), charges.iter().sum()];
+        comm.sum(&mut sums);
+        let [q2, total_charge] = sums;
+        self.total_charge = total_charge;
+        let num_atoms = atoms.num_atoms_global;
+
+        let box_changed = self.origin != container.rect().lo()
+            || self.reciprocal != reciprocal_vectors(container);
+        self.origin = container.rect().lo();
+        self.reciprocal = reciprocal_vectors(container);
+        self.volume = container.rect().volume();
+        let retune = match self.tuned_for {
+            Some((n, tuned_q2)) => n != num_atoms || (q2 - tuned_q2).abs() > 1e-10 * q2,
+            None => true,
+        };
+        if retune {
+            self.tune(num_atoms, q2);
+        }
+        if retune || self.mesh.is_none() {
+            self.mesh = Some(Mesh::new(self.grid, comm));
+        }
+        if retune || box_changed || self.influence.is_empty() {
+            self.set_influence();
+        }
+        let mesh = self.mesh.as_mut().expect("Mesh should be set up");
+        // Atoms move up to half the skin distance out of their subdomains between
+        // neighbor list updates
+        let spacings = container.face_spacings();
+        mesh.set_stencils(
+            self.order,
+            spacings.map(|s| 0.5 * neighbor_list.skin_distance() / s),
+        );
+        let mesh = self.mesh.as_ref().expect("Mesh should be set up");
+
+        let mut density = vec![0.0; mesh.num_ghosted()];
+        for i in 0..atoms.nlocal {
+            let q = atoms.charges[i];
+            if q != 0.0 {
+                mesh.for_each_stencil_point(self.to_lamda(&atoms.positions[i]), |j, w| {
+                    density[j] += q * w
+                });
+            }
+        }
+        mesh.reverse_comm(comm, &mut density, 1);
+        let owned = mesh.owned_indices();
+        let mut transformed = vec![owned.iter().map(|&i| Complex::from(density[i])).collect()];
+        mesh.fft(comm, &mut transformed, false);
+
+        // The Nyquist frequencies are left out of the derivatives, so that the fields are
+        // real
+        let size = mesh.size();
+        let derivative = |m: [i64; 3]| {
+            let f = [0, 1, 2].map(|k| {
+                if 2 * m[k] == size[k] as i64 {
+                    0
+                } else {
+                    frequency(m[k], size[k])
+                }
+            });
+            self.kvector(f)
+        };
+        let alpha2 = self.alpha * self.alpha;
+        let mut virial = [0.0; 6];
+        // The x and y components of the field, and its z component and the potential, as
+        // the real and imaginary parts of the transforms of two real fields
+        let mut fields = [0, 1].map(|_| Vec::with_capacity(mesh.num_owned()));
+        for ((m, &g), &rho) in mesh
+            .owned_points()
+            .zip(&self.influence)
+            .zip(&transformed[0])
+        {
+            let k = self.kvector([0, 1, 2].map(|j| frequency(m[j], size[j])));
+            let k2 = dot(k, k);
+            if k2 > 0.0 {
+                let energy = 0.5 * self.coulomb_constant * g * rho.norm_sqr() / self.volume;
+                let c = 2.0 * (1.0 / k2 + 0.25 / alpha2);
+                let factors = [
+                    1.0 - c * k[0] * k[0],
+                    1.0 - c * k[1] * k[1],
+                    1.0 - c * k[2] * k[2],
+                    -c * k[0] * k[1],
+                    -c * k[0] * k[2],
+                    -c * k[1] * k[2],
+                ];
+                for (v, factor) in virial.iter_mut().zip(factors) {
+                    *v += energy * factor;
+                }
+            }
+            let phi = rho * (g / self.volume);
+            let field = derivative(m).map(|x| phi * Complex::new(0.0, -x));
+            fields[0].push(field[0] + field[1] * Complex::i());
+            fields[1].push(field[2] + phi * Complex::i());
+        }
+        mesh.fft(comm, &mut fields, true);
+
+        let mut values = vec![0.0; 4 * mesh.num_ghosted()];
+        for (j, &i) in owned.iter().enumerate() {
+            let [a, b] = [fields[0][j], fields[1][j]];
+            values[4 * i..4 * i + 4].copy_from_slice(&[a.re, a.im, b.re, b.im]);
+        }
+        mesh.forward_comm(comm, &mut values, 4);
+        self.fields = values
+            .chunks_exact(4)
+            .map(|v| [v[0], v[1], v[2], v[3]])
+            .collect();
+
+        // The background energy is inversely proportional to the volume
+        for &q in charges {
+            let background = self.coulomb_constant * self.background(q);
+            for v in &mut virial[..3] {
+                *v += background;
+            }
+        }
+        self.kspace_virial = virial;
+    }
+    fn compute_forces(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> Vec<[f64; 3]> {
+        self.check_per_atom_values();
+        let mut forces = vec![[0.0; 3]; atoms.num_total_atoms()];
+        for_each_charged_pair(atoms, neighbor_list, self.cutoff, |i, j, r, dist, qq| {
+            let f_over_r = self.coulomb_constant * qq * screened(self.alpha, dist)[1] / dist;
+            for k in 0..3 {
+                forces[i][k] += r[k] * f_over_r;
+                forces[j][k] -= r[k] * f_over_r;
+            }
+        });
+        for (i, force) in forces[..atoms.nlocal].iter_mut().enumerate() {
+            let q = atoms.charges[i];
+            if q != 0.0 {
+                let field = self.interpolate(&atoms.positions[i]);
+                for k in 0..3 {
+                    force[k] += self.coulomb_constant * q * field[k];
+                }
+            }
+        }
+        forces
+    }
+    fn compute_potential_energy(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> f64 {
+        self.compute_per_atom_energy(atoms, neighbor_list)
+            .expect("Per-atom energies are supported")
+            .iter()
+            .sum()
+    }
+    fn compute_virial(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> [f64; 6] {
+        self.check_per_atom_values();
+        let mut virial = self.kspace_virial;
+        for_each_charged_pair(atoms, neighbor_list, self.cutoff, |_, _, r, dist, qq| {
+            let f_over_r = self.coulomb_constant * qq * screened(self.alpha, dist)[1] / dist;
+            for (v, p) in virial.iter_mut().zip(pair_virial(r, f_over_r)) {
+                *v += p;
+            }
+        });
+        virial
+    }
+    /// The reciprocal-space energy of each owned atom is half its charge times the
+    /// potential interpolated from the grid, with the self energy of its charge and its
+    /// share of the energy of the neutralizing background
+    fn compute_per_atom_energy(
+        &self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+    ) -> Option<Vec<f64>> {
+        self.check_per_atom_values();
+        let mut energies = vec![0.0; atoms.num_total_atoms()];
+        for_each_charged_pair(atoms, neighbor_list, self.cutoff, |i, j, _, dist, qq| {
+            let half_energy = 0.5 * self.coulomb_constant * qq * screened(self.alpha, dist)[0];
+            energies[i] += half_energy;
+            energies[j] += half_energy;
+        });
+        for (i, energy) in energies[..atoms.nlocal].iter_mut().enumerate() {
+            let q = atoms.charges[i];
+            if q != 0.0 {
+                let potential = self.interpolate(&atoms.positions[i])[3];
+                *energy += self.coulomb_constant
+                    * (q * (0.5 * potential - q * self.alpha / PI.sqrt()) + self.background(q));
+            }
+        }
+        Some(energies)
+    }
+    fn num_types(&self) -> usize {
+        self.num_types
+    }
+    fn set_num_types(&mut self, num_types: usize) {
+        self.num_types = num_types;
+    }
+    fn all_set(&self) -> bool {
+        true
+    }
+    fn set_coeff(&mut self, _typei: usize, _typej: usize, _coeff: &Self::Coeff) {}
+    /// The splitting parameter and grid are chosen again after reading
+    fn write_restart(&self, writer: &mut RestartWriter) -> io::Result<()> {
+        writer.write_f64(self.cutoff)?;
+        writer.write_f64(self.accuracy)?;
+        writer.write_usize(self.order)?;
+        writer.write_f64(self.coulomb_constant)
+    }
+    fn read_restart(&mut self, reader: &mut RestartReader) -> io::Result<()> {
+        self.cutoff = reader.read_f64()?;
+        self.accuracy = reader.read_f64()?;
+        self.order = reader.read_usize()?;
+        self.coulomb_constant = reader.read_f64()?;
+        self.tuned_for = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{
+        atom_type::Basic,
+        atomic::{
+            coulomb::tests::{rock_salt, MADELUNG_NACL},
+            tests::{energy, owned_forces},
+            Ewald,
+        },
+        region::Rect,
+        simulation::{
+            tests::{assert_forces_agree, gather_forces, push_forces, run_threads, Forces},
+            Simulation,
+        },
+        utils::Types,
+    };
+
+    #[test]
+    fn test_factorable() {
+        assert_eq!(factorable(7), 8);
+        assert_eq!(factorable(11), 12);
+        assert_eq!(factorable(31), 32);
+        assert_eq!(factorable(49), 50);
+    }
+
+    #[test]
+    fn test_madelung() {
+        let mut pppm = PPPM::new(3.5, 1e-5);
+        let (atoms, container, nl) = rock_salt(2, 3.5, false, [0.0; 3]);
+        let per_ion = energy(&mut pppm, &atoms, &container, &nl) / atoms.nlocal as f64;
+        assert!(
+            (per_ion + 0.5 * MADELUNG_NACL).abs() < 1e-4,
+            "Energy per ion {} should be {}",
+            per_ion,
+            -0.5 * MADELUNG_NACL
+        );
+        let forces = owned_forces(&pppm, &atoms, &nl);
+        assert!(forces.iter().flatten().all(|f| f.abs() < 1e-4));
+    }
+
+    /// The energy, forces and virial of a displaced and charged crystal agree with Ewald
+    /// summation to about the accuracy
+    #[test]
+    fn test_against_ewald() {
+        for order in [3, 5, 7] {
+            let accuracy = 1e-5;
+            let mut pppm = PPPM::new(3.5, accuracy);
+            pppm.set_order(order);
+            let mut ewald = Ewald::new(3.5, 1e-10);
+            let (mut atoms, container, nl) = rock_salt(2, 3.5, true, [0.0; 3]);
+            // A net charge, on the ghost copies too
+            atoms.charges[0] += 0.5;
+            for j in atoms.nlocal..atoms.num_total_atoms() {
+                atoms.charges[j] = atoms.charges[atoms.ids[j]];
+            }
+
+            let total = energy(&mut pppm, &atoms, &container, &nl);
+            let expected = energy(&mut ewald, &atoms, &container, &nl);
+            assert!(
+                (total - expected).abs() < 10.0 * accuracy * atoms.nlocal as f64,
+                "Energy {} should be {} with order {}",
+                total,
+                expected,
+                order
+            );
+
+            let forces = owned_forces(&pppm, &atoms, &nl);
+            let expected = owned_forces(&ewald, &atoms, &nl);
+            let rms = (forces
+                .iter()
+                .flatten()
+                .zip(expected.iter().flatten())
+                .map(|(f, e)| (f - e) * (f - e))
+                .sum::<f64>()
+                / atoms.nlocal as f64)
+                .sqrt();
+            assert!(
+                rms < 3.0 * accuracy,
+                "Error in forces {} should be about {} with order {}",
+                rms,
+                accuracy,
+                order
+            );
+
+            let virial = pppm.compute_virial(&atoms, &nl);
+            let expected = ewald.compute_virial(&atoms, &nl);
+            for k in 0..6 {
+                assert!(
+                    (virial[k] - expected[k]).abs() < 10.0 * accuracy * atoms.nlocal as f64,
+                    "Virial {:?} should be {:?} with order {}",
+                    virial,
+                    expected,
+                    order
+                );
+            }
+        }
+    }
+
+    static FORCES: Mutex<Vec<Forces>> = Mutex::new(Vec::new());
+
+    /// A displaced rock salt crystal of 8 cells along each axis with unit nearest-neighbor
+    /// distance, with a net charge
+    fn charged_lattice<A: AtomicPotentialTrait<Basic>>(
+        sim: &mut Simulation<Basic, A>,
+        potential: A,
+    ) {
+        let mut types = Vec::new();
+        let mut coords = Vec::new();
+        for x in 0..8 {
+            for y in 0..8 {
+                for z in 0..8 {
+                    let id = coords.len();
+                    // Deterministic pseudo-random displacements into the box
+                    let site = [x, y, z];
+                    types.push((x + y + z) % 2);
+                    coords.push(
+                        [0, 1, 2].map(|k| {
+                            site[k] as f64 + 0.05 * (1.0 + ((id * 7 + k * 13) as f64).sin())
+                        }),
+                    );
+                }
+            }
+        }
+        sim.set_atom_types(vec![Basic::new(1.0), Basic::new(1.0)]);
+        sim.set_atomic_potential(potential);
+        sim.set_container(Container::from_rect_periodic(Rect::new(
+            0.0, 8.0, 0.0, 8.0, 0.0, 8.0,
+        )));
+        sim.add_atoms(types, coords);
+        sim.group_types("Na", Types::One(0));
+        sim.group_types("Cl", Types::One(1));
+        sim.group_ids("extra", 0..1);
+        sim.set_charge("Na", 1.0);
+        sim.set_charge("Cl", -1.0);
+        sim.set_charge("extra", 1.5);
+        sim.set_nl_skin_distance(0.3);
+        sim.run(0);
+        push_forces(sim, &FORCES);
+    }
+
+    /// The charge grid is split between processes, with its ghost points exchanged and
+    /// its Fourier transform distributed, which should agree with one process and with
+    /// Ewald summation
+    #[test]
+    fn test_threads() {
+        fn run(mut sim: Simulation<Basic, PPPM>) {
+            charged_lattice(&mut sim, PPPM::new(3.5, 1e-5));
+        }
+        fn run_ewald(mut sim: Simulation<Basic, Ewald>) {
+            charged_lattice(&mut sim, Ewald::new(3.5, 1e-10));
+        }
+        let runs: Vec<_> = run_threads(&[1, 2, 8], run, &FORCES)
+            .into_iter()
+            .map(gather_forces)
+            .collect();
+        assert_eq!(runs[0].2.len(), 512);
+        for run in &runs[1..] {
+            assert_forces_agree(run, &runs[0], 1e-9);
+        }
+
+        let (energy, virial, forces) = &runs[0];
+        let expected = gather_forces(run_threads(&[1], run_ewald, &FORCES).remove(0));
+        let tolerance = 10.0 * 1e-5 * 512.0;
+        assert!(
+            (energy - expected.0).abs() < tolerance,
+            "Energy {} should be {}",
+            energy,
+            expected.0
+        );
+        for k in 0..6 {
+            assert!(
+                (virial[k] - expected.1[k]).abs() < tolerance,
+                "Virial {:?} should be {:?}",
+                virial,
+                expected.1
+            );
+        }
+        let rms = (forces
+            .iter()
+            .flatten()
+            .zip(expected.2.iter().flatten())
+            .map(|(f, e)| (f - e) * (f - e))
+            .sum::<f64>()
+            / 512.0)
+            .sqrt();
+        assert!(rms < 3e-5, "Error in forces {} should be about 1e-5", rms);
+    }
+}
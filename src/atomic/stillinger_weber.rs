@@ -0,0 +1,297 @@
+use super::{
+    many_body::{self, cos_angle, cos_gradients, neighbors_within, ElementParams, Elements},
+    many_body::{ManyBody, TermAtom},
+    *,
+};
+
+/// Parameters of a triplet of elements of the Stillinger-Weber potential
+#[derive(Clone, Copy, Debug)]
+struct SWParams {
+    epsilon: f64,
+    sigma: f64,
+    a: f64,
+    lambda: f64,
+    gamma: f64,
+    costheta0: f64,
+    big_a: f64,
+    big_b: f64,
+    p: f64,
+    q: f64,
+    tol: f64,
+}
+impl ElementParams for SWParams {
+    const NUM_VALUES: usize = 11;
+    fn from_values(v: &[f64]) -> Self {
+        Self {
+            epsilon: v[0],
+            sigma: v[1],
+            a: v[2],
+            lambda: v[3],
+            gamma: v[4],
+            costheta0: v[5],
+            big_a: v[6],
+            big_b: v[7],
+            p: v[8],
+            q: v[9],
+            tol: v[10],
+        }
+    }
+    fn values(&self) -> Vec<f64> {
+        vec![
+            self.epsilon,
+            self.sigma,
+            self.a,
+            self.lambda,
+            self.gamma,
+            self.costheta0,
+            self.big_a,
+            self.big_b,
+            self.p,
+            self.q,
+            self.tol,
+        ]
+    }
+    fn cutoff(&self) -> f64 {
+        self.a * self.sigma
+    }
+}
+impl SWParams {
+    /// The two-body energy and its derivative at the given distance
+    fn two_body(&self, r: f64) -> [f64; 2] {
+        let s = self.sigma / r;
+        let [sp, sq] = [s.powf(self.p), s.powf(self.q)];
+        let dr = r - self.cutoff();
+        let ex = self.big_a * self.epsilon * (self.sigma / dr).exp();
+        let radial = self.big_b * sp - sq;
+        let dradial = (-self.p * self.big_b * sp + self.q * sq) / r;
+        [
+            radial * ex,
+            dradial * ex - radial * ex * self.sigma / (dr * dr),
+        ]
+    }
+    /// The radial factor of three-body energies at the given distance, and its derivative
+    fn three_body_radial(&self, r: f64) -> [f64; 2] {
+        let dr = r - self.cutoff();
+        let ex = (self.gamma * self.sigma / dr).exp();
+        [ex, -ex * self.gamma * self.sigma / (dr * dr)]
+    }
+}
+
+/// Stillinger-Weber three-body potential, as with LAMMPS `pair_style sw`, with the
+/// parameters of each triplet of elements read from a LAMMPS `.sw` file
+///
+/// The energy is the sum over pairs of
+/// `A eps (B (sigma/r)^p - (sigma/r)^q) exp(sigma / (r - a sigma))` and over triplets
+/// centered on atom `i` of
+/// `lambda eps (cos theta_jik - cos theta0)^2 exp(gamma sigma / (r_ij - a sigma))
+/// exp(gamma sigma / (r_ik - a sigma))`. Elements are assigned to atom types with
+/// `set_coeff(i, i, &name)`.
+pub struct StillingerWeber {
+    elements: Elements<SWParams>,
+}
+impl StillingerWeber {
+    /// Read a LAMMPS `.sw` file, with entries of three elements followed by `epsilon`,
+    /// `sigma`, `a`, `lambda`, `gamma`, `costheta0`, `A`, `B`, `p`, `q` and `tol`. The
+    /// two-body parameters of elements `i` and `j` are from entry `i j j`, and the
+    /// three-body parameters `lambda`, `epsilon` and `costheta0` of a triplet centered
+    /// on `i` are from entry `i j k`.
+    ///
+    /// ```rust,no_run
+    /// use jmd::{atom_type::Basic, atomic::StillingerWeber, prelude::*};
+    ///
+    /// fn run(mut sim: Simulation<Basic, StillingerWeber>) {
+    ///     sim.set_atomic_potential(StillingerWeber::from_file("Si.sw"));
+    ///     sim.set_atomic_coeff(0, 0, &"Si".to_string());
+    /// }
+    /// ```
+    pub fn from_file(path: &str) -> Self {
+        Self {
+            elements: Elements::from_file(path, "sw"),
+        }
+    }
+}
+
+impl ManyBody for StillingerWeber {
+    fn for_each_term<T: AtomType>(
+        &self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+        mut f: impl FnMut(usize, f64, &[TermAtom]),
+    ) {
+        let cutoff = self.elements.cutoff();
+        for i in 0..atoms.nlocal {
+            let ei = self.elements.element(atoms.types[i]);
+            let neighbors: Vec<_> =
+                neighbors_within(atoms, neighbor_list, &self.elements, i, cutoff)
+                    .into_iter()
+                    .filter(|j| j.r < self.elements.params(ei, j.element, j.element).cutoff())
+                    .collect();
+
+            // Each pair is seen from both of its atoms, so half of its energy is
+            // attributed to each
+            for j in &neighbors {
+                let [e, de] = self.elements.params(ei, j.element, j.element).two_body(j.r);
+                let force = j.del.map(|d| -0.5 * de * d / j.r);
+                let other = TermAtom {
+                    idx: j.idx,
+                    del: j.del,
+                    force,
+                };
+                f(i, 0.5 * e, &[other]);
+            }
+
+            for (a, j) in neighbors.iter().enumerate() {
+                let [ej, dej] = self
+                    .elements
+                    .params(ei, j.element, j.element)
+                    .three_body_radial(j.r);
+                for k in &neighbors[a + 1..] {
+                    let [ek, dek] = self
+                        .elements
+                        .params(ei, k.element, k.element)
+                        .three_body_radial(k.r);
+                    let pijk = self.elements.params(ei, j.element, k.element);
+                    let lambda_epsilon = pijk.lambda * pijk.epsilon;
+                    let cos = cos_angle(j, k);
+                    let delta = cos - pijk.costheta0;
+
+                    let energy = lambda_epsilon * delta * delta * ej * ek;
+                    let de_drj = lambda_epsilon * delta * delta * dej * ek;
+                    let de_drk = lambda_epsilon * delta * delta * ej * dek;
+                    let de_dcos = 2.0 * lambda_epsilon * delta * ej * ek;
+                    let [dcos_j, dcos_k] = cos_gradients(j, k, cos);
+                    let others = [
+                        TermAtom {
+                            idx: j.idx,
+                            del: j.del,
+                            force: [0, 1, 2]
+                                .map(|m| -(de_drj * j.del[m] / j.r + de_dcos * dcos_j[m])),
+                        },
+                        TermAtom {
+                            idx: k.idx,
+                            del: k.del,
+                            force: [0, 1, 2]
+                                .map(|m| -(de_drk * k.del[m] / k.r + de_dcos * dcos_k[m])),
+                        },
+                    ];
+                    f(i, energy, &others);
+                }
+            }
+        }
+    }
+}
+
+impl<T: AtomType> AtomicPotentialTrait<T> for StillingerWeber {
+    /// The name of the element of a type
+    type Coeff = String;
+    fn new() -> Self {
+        Self {
+            elements: Elements::new(),
+        }
+    }
+    /// The largest cutoff `a sigma` of any entry of the file
+    fn cutoff_distance(&self) -> f64 {
+        if self.elements.cutoff() > 0.0 {
+            self.elements.cutoff()
+        } else {
+            1.0
+        }
+    }
+    fn full_neighbor_list(&self) -> bool {
+        true
+    }
+    fn compute_forces(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> Vec<[f64; 3]> {
+        many_body::forces(self, atoms, neighbor_list)
+    }
+    fn compute_potential_energy(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> f64 {
+        many_body::potential_energy(self, atoms, neighbor_list)
+    }
+    fn compute_virial(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> [f64; 6] {
+        many_body::virial(self, atoms, neighbor_list)
+    }
+    /// The energy of each triplet is given to its central atom
+    fn compute_per_atom_energy(
+        &self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+    ) -> Option<Vec<f64>> {
+        Some(many_body::per_atom_energy(self, atoms, neighbor_list))
+    }
+    fn compute_per_atom_virial(
+        &self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+    ) -> Option<Vec<[f64; 6]>> {
+        Some(many_body::per_atom_virial(self, atoms, neighbor_list))
+    }
+    fn num_types(&self) -> usize {
+        self.elements.num_types()
+    }
+    fn set_num_types(&mut self, num_types: usize) {
+        self.elements.set_num_types(num_types);
+    }
+    fn all_set(&self) -> bool {
+        self.elements.all_set()
+    }
+    /// Elements are assigned per type, so both type indices should be the same
+    fn set_coeff(&mut self, typei: usize, typej: usize, coeff: &Self::Coeff) {
+        self.elements.set_coeff(typei, typej, coeff);
+    }
+    fn write_restart(&self, writer: &mut RestartWriter) -> io::Result<()> {
+        self.elements.write_restart(writer)
+    }
+    fn read_restart(&mut self, reader: &mut RestartReader) -> io::Result<()> {
+        self.elements.read_restart(reader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{atom_type::Basic, atomic::tests::check_forces};
+
+    #[test]
+    fn test_forces() {
+        let contents = "
+            # epsilon sigma a lambda gamma costheta0 A B p q tol
+            Si Si Si 2.1683 1.0 1.80 21.0 1.20 -0.333333333333
+                     7.049556277 0.6022245584 4.0 0.0 0.0
+            C C C 1.5 0.9 1.9 18.0 1.1 -0.3 6.5 0.7 4.0 0.0 0.0
+            Si C C 1.8 0.95 1.85 19.0 1.15 -0.32 6.8 0.65 4.0 0.0 0.0
+            C Si Si 1.8 0.95 1.85 19.0 1.15 -0.32 6.8 0.65 4.0 0.0 0.0
+            Si Si C 2.0 1.0 1.8 20.0 1.2 -0.33 7.0 0.6 4.0 0.0 0.0
+            Si C Si 2.0 1.0 1.8 20.0 1.2 -0.33 7.0 0.6 4.0 0.0 0.0
+            C Si C 1.6 0.9 1.9 17.0 1.1 -0.31 6.6 0.7 4.0 0.0 0.0
+            C C Si 1.6 0.9 1.9 17.0 1.1 -0.31 6.6 0.7 4.0 0.0 0.0
+        ";
+        let mut sw = StillingerWeber {
+            elements: Elements::parse(contents, "sw"),
+        };
+        assert!(
+            (<StillingerWeber as AtomicPotentialTrait<Basic>>::cutoff_distance(&sw) - 1.8).abs()
+                < 1e-12
+        );
+        <StillingerWeber as AtomicPotentialTrait<Basic>>::set_num_types(&mut sw, 2);
+        <StillingerWeber as AtomicPotentialTrait<Basic>>::set_coeff(
+            &mut sw,
+            0,
+            0,
+            &"Si".to_string(),
+        );
+        assert!(!<StillingerWeber as AtomicPotentialTrait<Basic>>::all_set(
+            &sw
+        ));
+        <StillingerWeber as AtomicPotentialTrait<Basic>>::set_coeff(
+            &mut sw,
+            1,
+            1,
+            &"C".to_string(),
+        );
+        assert!(<StillingerWeber as AtomicPotentialTrait<Basic>>::all_set(
+            &sw
+        ));
+        check_forces(&mut sw, true, |sw, atoms, _, nl| {
+            sw.compute_potential_energy(atoms, nl)
+        });
+    }
+}
@@ -0,0 +1,462 @@
+use std::fs;
+
+use super::*;
+use crate::utils::{Spline, UniformSpline};
+
+/// The energy and force of a pair of atoms tabulated against their distance, such as a
+/// section of a LAMMPS `pair_style table` file
+#[derive(Clone, Debug)]
+pub struct TableCoeff {
+    r: Vec<f64>,
+    energy: Vec<f64>,
+    force: Vec<f64>,
+    /// Derivatives of the force at the first and last distances, if given
+    fprime: Option<[f64; 2]>,
+    cutoff: f64,
+}
+impl TableCoeff {
+    /// The table of the given energies and forces at increasing positive distances, with
+    /// the last distance as the cutoff
+    pub fn new(r: Vec<f64>, energy: Vec<f64>, force: Vec<f64>) -> Self {
+        assert!(
+            r.len() >= 2 && energy.len() == r.len() && force.len() == r.len(),
+            "Tables should have at least 2 distances, with one energy and force per distance"
+        );
+        assert!(r[0] > 0.0, "Table distances should be positive");
+        assert!(
+            r.windows(2).all(|w| w[0] < w[1]),
+            "Table distances should be increasing"
+        );
+        let cutoff = r[r.len() - 1];
+        Self {
+            r,
+            energy,
+            force,
+            fprime: None,
+            cutoff,
+        }
+    }
+    /// Read the section with the given keyword from a LAMMPS `pair_style table` file.
+    ///
+    /// The line after the keyword gives the number of rows `N`, and optionally distances
+    /// `R lo hi` or `RSQ lo hi` replacing those of the rows, equally spaced in distance or
+    /// in squared distance, and the derivatives of the force at the ends `FPRIME lo hi`.
+    /// Rows hold an index, the distance, the energy and the force. `BITMAP` tables are not
+    /// supported.
+    ///
+    /// ```rust,no_run
+    /// use jmd::{atom_type::Basic, atomic::{Table, TableCoeff}, prelude::*};
+    ///
+    /// fn run(mut sim: Simulation<Basic, Table>) {
+    ///     sim.set_atomic_potential(Table::new(3.0, 2000));
+    ///     let coeff = TableCoeff::from_file("pair.table", "LJ_AA").with_cutoff(2.5);
+    ///     sim.set_atomic_coeff(0, 0, &coeff);
+    /// }
+    /// ```
+    pub fn from_file(path: &str, keyword: &str) -> Self {
+        let contents = fs::read_to_string(path)
+            .unwrap_or_else(|_| panic!("Could not read table file {}", path));
+        parse_table(&contents, keyword)
+    }
+    /// Cut the table off at the given distance, within the tabulated distances
+    pub fn with_cutoff(mut self, cutoff: f64) -> Self {
+        assert!(
+            self.r[0] < cutoff && cutoff <= self.r[self.r.len() - 1],
+            "Cutoff {} should be within the tabulated distances {} to {}",
+            cutoff,
+            self.r[0],
+            self.r[self.r.len() - 1]
+        );
+        self.cutoff = cutoff;
+        self
+    }
+    pub fn cutoff(&self) -> f64 {
+        self.cutoff
+    }
+}
+
+fn parse_table(contents: &str, keyword: &str) -> TableCoeff {
+    let mut lines = contents
+        .lines()
+        .map(|l| l.split('#').next().unwrap_or("").trim())
+        .skip_while(|&l| l != keyword)
+        .skip(1);
+    let params: Vec<&str> = lines
+        .next()
+        .unwrap_or_else(|| panic!("Table {} not found", keyword))
+        .split_whitespace()
+        .collect();
+
+    let mut num_rows = 0;
+    let mut spacing: Option<(&str, f64, f64)> = None;
+    let mut fprime = None;
+    let mut k = 0;
+    while k < params.len() {
+        match params[k] {
+            "N" => {
+                num_rows = parse(params[k + 1]);
+                k += 2;
+            }
+            "R" | "RSQ" => {
+                spacing = Some((params[k], parse(params[k + 1]), parse(params[k + 2])));
+                k += 3;
+            }
+            "FPRIME" => {
+                fprime = Some([parse(params[k + 1]), parse(params[k + 2])]);
+                k += 3;
+            }
+            p => panic!("Unsupported table parameter {} in table {}", p, keyword),
+        }
+    }
+    assert!(
+        num_rows >= 2,
+        "Table {} should have at least 2 rows",
+        keyword
+    );
+
+    let rows: Vec<[f64; 3]> = lines
+        .filter(|l| !l.is_empty())
+        .take(num_rows)
+        .map(|l| {
+            let fields: Vec<&str> = l.split_whitespace().collect();
+            assert!(fields.len() >= 4, "Invalid row {} in table {}", l, keyword);
+            [parse(fields[1]), parse(fields[2]), parse(fields[3])]
+        })
+        .collect();
+    assert_eq!(
+        rows.len(),
+        num_rows,
+        "Table {} should have {} rows",
+        keyword,
+        num_rows
+    );
+
+    let fraction = |i: usize| i as f64 / (num_rows - 1) as f64;
+    let r = match spacing {
+        Some(("R", lo, hi)) => (0..num_rows)
+            .map(|i| lo + (hi - lo) * fraction(i))
+            .collect(),
+        Some((_, lo, hi)) => (0..num_rows)
+            .map(|i| (lo * lo + (hi * hi - lo * lo) * fraction(i)).sqrt())
+            .collect(),
+        None => rows.iter().map(|row| row[0]).collect(),
+    };
+    let mut coeff = TableCoeff::new(
+        r,
+        rows.iter().map(|row| row[1]).collect(),
+        rows.iter().map(|row| row[2]).collect(),
+    );
+    coeff.fprime = fprime;
+    coeff
+}
+
+fn parse<F: std::str::FromStr>(field: &str) -> F {
+    field
+        .parse()
+        .unwrap_or_else(|_| panic!("Invalid value {} in table file", field))
+}
+
+/// A table resampled at equally spaced squared distances, so that the energy and force
+/// are interpolated in constant time without a square root
+#[derive(Clone, Debug)]
+struct PairTable {
+    inner2: f64,
+    cutoff2: f64,
+    energy: UniformSpline,
+    force_over_r: UniformSpline,
+}
+impl PairTable {
+    fn new(coeff: &TableCoeff, num_points: usize) -> Self {
+        let [r, e, f] = [&coeff.r, &coeff.energy, &coeff.force];
+        let n = r.len();
+        // The force is the negative derivative of the energy, and its own derivatives at
+        // the ends are estimated from the first and last rows if not given
+        let fprime = coeff.fprime.unwrap_or([
+            (f[1] - f[0]) / (r[1] - r[0]),
+            (f[n - 1] - f[n - 2]) / (r[n - 1] - r[n - 2]),
+        ]);
+        let energy = Spline::new(r.clone(), e.clone(), [Some(-f[0]), Some(-f[n - 1])]);
+        let force = Spline::new(r.clone(), f.clone(), fprime.map(Some));
+
+        // Derivatives with respect to r^2 are those with respect to r divided by 2 r
+        let [inner, cutoff] = [r[0], coeff.cutoff];
+        let energy_derivative = |r: f64| -force.value(r) / (2.0 * r);
+        let force_over_r_derivative =
+            |r: f64| (force.derivative(r) / r - force.value(r) / (r * r)) / (2.0 * r);
+        Self {
+            inner2: inner * inner,
+            cutoff2: cutoff * cutoff,
+            energy: UniformSpline::from_fn(
+                inner * inner,
+                cutoff * cutoff,
+                num_points,
+                |r2| energy.value(r2.sqrt()),
+                [
+                    Some(energy_derivative(inner)),
+                    Some(energy_derivative(cutoff)),
+                ],
+            ),
+            force_over_r: UniformSpline::from_fn(
+                inner * inner,
+                cutoff * cutoff,
+                num_points,
+                |r2| force.value(r2.sqrt()) / r2.sqrt(),
+                [
+                    Some(force_over_r_derivative(inner)),
+                    Some(force_over_r_derivative(cutoff)),
+                ],
+            ),
+        }
+    }
+    fn check_inner(&self, r2: f64) {
+        assert!(
+            r2 >= self.inner2,
+            "Pair distance {} is below the first tabulated distance {}",
+            r2.sqrt(),
+            self.inner2.sqrt()
+        );
+    }
+}
+
+/// Pair potential interpolated with cubic splines from tables of the energy and force
+/// against distance, as with LAMMPS `pair_style table spline`. Tables are resampled at
+/// equally spaced squared distances when set.
+pub struct Table {
+    num_types: usize,
+    force_cutoff: f64,
+    num_points: usize,
+    coeffs: Vec<Option<TableCoeff>>,
+    tables: Vec<Option<PairTable>>,
+}
+impl Table {
+    /// Tables with cutoffs up to the given global cutoff, resampled at the given number
+    /// of points
+    pub fn new(force_cutoff: f64, num_points: usize) -> Self {
+        assert!(
+            force_cutoff > 0.0,
+            "Force cutoff should be positive, found {}",
+            force_cutoff
+        );
+        assert!(
+            num_points >= 2,
+            "Tables should be resampled at at least 2 points, found {}",
+            num_points
+        );
+        Self {
+            num_types: 0,
+            force_cutoff,
+            num_points,
+            coeffs: Vec::new(),
+            tables: Vec::new(),
+        }
+    }
+    /// The pairs of `for_each_pair` within the cutoff of their table, passed along with the
+    /// table after checking that they are beyond its inner distance
+    fn for_each_pair<T: AtomType>(
+        &self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+        mut f: impl FnMut(usize, usize, [f64; 3], f64, &PairTable),
+    ) {
+        for_each_pair(atoms, neighbor_list, |i, j, r, r2| {
+            let idx = self.num_types * atoms.types[i] + atoms.types[j];
+            let table = self.tables[idx].as_ref().expect("Table should be set");
+            if r2 < table.cutoff2 {
+                table.check_inner(r2);
+                f(i, j, r, r2, table);
+            }
+        });
+    }
+}
+
+impl<T: AtomType> AtomicPotentialTrait<T> for Table {
+    type Coeff = TableCoeff;
+    fn new() -> Self {
+        Self::new(1.0, 1000)
+    }
+    fn cutoff_distance(&self) -> f64 {
+        self.force_cutoff
+    }
+    fn compute_forces(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> Vec<[f64; 3]> {
+        let mut forces = vec![[0.0; 3]; atoms.num_total_atoms()];
+        self.for_each_pair(atoms, neighbor_list, |i, j, r, r2, table| {
+            let f_mag = table.force_over_r.value(r2);
+            for k in 0..3 {
+                forces[i][k] += r[k] * f_mag;
+                forces[j][k] -= r[k] * f_mag;
+            }
+        });
+        forces
+    }
+    fn compute_potential_energy(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> f64 {
+        let mut energy = 0.0;
+        self.for_each_pair(atoms, neighbor_list, |_, _, _, r2, table| {
+            energy += table.energy.value(r2);
+        });
+        energy
+    }
+    fn compute_virial(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> [f64; 6] {
+        let mut virial = [0.0; 6];
+        self.for_each_pair(atoms, neighbor_list, |_, _, r, r2, table| {
+            let pair = pair_virial(r, table.force_over_r.value(r2));
+            for k in 0..6 {
+                virial[k] += pair[k];
+            }
+        });
+        virial
+    }
+    fn compute_per_atom_energy(
+        &self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+    ) -> Option<Vec<f64>> {
+        let mut energies = vec![0.0; atoms.num_total_atoms()];
+        self.for_each_pair(atoms, neighbor_list, |i, j, _, r2, table| {
+            let half_energy = 0.5 * table.energy.value(r2);
+            energies[i] += half_energy;
+            energies[j] += half_energy;
+        });
+        Some(energies)
+    }
+    fn compute_per_atom_virial(
+        &self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+    ) -> Option<Vec<[f64; 6]>> {
+        let mut virials = vec![[0.0; 6]; atoms.num_total_atoms()];
+        self.for_each_pair(atoms, neighbor_list, |i, j, r, r2, table| {
+            let pair = pair_virial(r, 0.5 * table.force_over_r.value(r2));
+            for k in 0..6 {
+                virials[i][k] += pair[k];
+                virials[j][k] += pair[k];
+            }
+        });
+        Some(virials)
+    }
+    fn num_types(&self) -> usize {
+        self.num_types
+    }
+    fn set_num_types(&mut self, num_types: usize) {
+        let old = self.num_types;
+        let mut coeffs = vec![None; num_types * num_types];
+        let mut tables = vec![None; num_types * num_types];
+        for i in 0..old.min(num_types) {
+            for j in 0..old.min(num_types) {
+                coeffs[i * num_types + j] = self.coeffs[i * old + j].take();
+                tables[i * num_types + j] = self.tables[i * old + j].take();
+            }
+        }
+        self.num_types = num_types;
+        self.coeffs = coeffs;
+        self.tables = tables;
+    }
+    fn all_set(&self) -> bool {
+        self.coeffs.iter().all(|c| c.is_some())
+    }
+    /// Tables are symmetric, so the coefficient is set for both orders of the types
+    fn set_coeff(&mut self, typei: usize, typej: usize, coeff: &Self::Coeff) {
+        assert!(
+            typei < self.num_types && typej < self.num_types,
+            "Type indices should be less than the number of types (0-indexed)"
+        );
+        assert!(
+            coeff.cutoff <= self.force_cutoff,
+            "Table cutoff {} should be at most the global cutoff {}",
+            coeff.cutoff,
+            self.force_cutoff
+        );
+        let table = PairTable::new(coeff, self.num_points);
+        for idx in [
+            self.num_types * typei + typej,
+            self.num_types * typej + typei,
+        ] {
+            self.coeffs[idx] = Some(coeff.clone());
+            self.tables[idx] = Some(table.clone());
+        }
+    }
+    fn write_restart(&self, writer: &mut RestartWriter) -> io::Result<()> {
+        writer.write_f64(self.force_cutoff)?;
+        writer.write_usize(self.num_points)?;
+        writer.write_usize(self.num_types)?;
+        for coeff in &self.coeffs {
+            writer.write_bool(coeff.is_some())?;
+            if let Some(coeff) = coeff {
+                writer.write_f64s(&coeff.r)?;
+                writer.write_f64s(&coeff.energy)?;
+                writer.write_f64s(&coeff.force)?;
+                writer.write_bool(coeff.fprime.is_some())?;
+                writer.write_f64s(&coeff.fprime.unwrap_or([0.0; 2]))?;
+                writer.write_f64(coeff.cutoff)?;
+            }
+        }
+        Ok(())
+    }
+    fn read_restart(&mut self, reader: &mut RestartReader) -> io::Result<()> {
+        self.force_cutoff = reader.read_f64()?;
+        self.num_points = reader.read_usize()?;
+        self.num_types = reader.read_usize()?;
+        self.coeffs = (0..self.num_types * self.num_types)
+            .map(|_| {
+                if !reader.read_bool()? {
+                    return Ok(None);
+                }
+                let mut coeff = TableCoeff::new(
+                    reader.read_f64s()?,
+                    reader.read_f64s()?,
+                    reader.read_f64s()?,
+                );
+                let has_fprime = reader.read_bool()?;
+                let fprime = reader.read_f64s()?;
+                coeff.fprime = has_fprime.then_some([fprime[0], fprime[1]]);
+                coeff.cutoff = reader.read_f64()?;
+                Ok(Some(coeff))
+            })
+            .collect::<io::Result<Vec<Option<TableCoeff>>>>()?;
+        self.tables = self
+            .coeffs
+            .iter()
+            .map(|c| c.as_ref().map(|c| PairTable::new(c, self.num_points)))
+            .collect();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lj(r: f64) -> [f64; 2] {
+        let r6 = r.powi(-6);
+        [4.0 * r6 * (r6 - 1.0), 24.0 * r6 * (2.0 * r6 - 1.0) / r]
+    }
+
+    #[test]
+    fn test_table_file() {
+        let mut contents = String::from("# LJ tables\n\nLJ_R\nN 400 R 0.9 3.0\n\n");
+        for i in 0..400 {
+            let r = 0.9 + 2.1 * i as f64 / 399.0;
+            let [e, f] = lj(r);
+            // Distances are replaced by those given by R
+            contents += &format!("{} {} {} {}\n", i + 1, r + 0.5, e, f);
+        }
+        contents += "\nLJ_RSQ\nN 400 RSQ 0.9 3.0 FPRIME 1.0 2.0\n\n";
+        for i in 0..400 {
+            let r = (0.81 + (9.0 - 0.81) * i as f64 / 399.0).sqrt();
+            let [e, f] = lj(r);
+            contents += &format!("{} {} {} {}\n", i + 1, r, e, f);
+        }
+
+        let coeff = parse_table(&contents, "LJ_R").with_cutoff(2.5);
+        assert!((coeff.r[399] - 3.0).abs() < 1e-12 && coeff.fprime.is_none());
+        let rsq = parse_table(&contents, "LJ_RSQ");
+        assert!((rsq.r[399] - 3.0).abs() < 1e-12 && rsq.fprime == Some([1.0, 2.0]));
+
+        let table = PairTable::new(&coeff, 2000);
+        assert_eq!(table.cutoff2, 6.25);
+        for r in [0.9, 0.95, 1.0, 1.12, 1.5, 2.0, 2.49] {
+            let [e, f] = lj(r);
+            assert!((table.energy.value(r * r) - e).abs() < 1e-4 * e.abs().max(1.0));
+            assert!((table.force_over_r.value(r * r) * r - f).abs() < 1e-4 * f.abs().max(1.0));
+        }
+    }
+}
@@ -0,0 +1,339 @@
+use std::f64::consts::PI;
+
+use super::{
+    many_body::{self, cos_angle, cos_gradients, neighbors_within, ElementParams, Elements},
+    many_body::{ManyBody, Neighbor, TermAtom},
+    *,
+};
+
+/// Parameters of a triplet of elements of the Tersoff potential
+#[derive(Clone, Copy, Debug)]
+struct TersoffParams {
+    m: f64,
+    gamma: f64,
+    lambda3: f64,
+    c: f64,
+    d: f64,
+    costheta0: f64,
+    n: f64,
+    beta: f64,
+    lambda2: f64,
+    big_b: f64,
+    big_r: f64,
+    big_d: f64,
+    lambda1: f64,
+    big_a: f64,
+}
+impl ElementParams for TersoffParams {
+    const NUM_VALUES: usize = 14;
+    fn from_values(v: &[f64]) -> Self {
+        assert!(
+            v[0] == 1.0 || v[0] == 3.0,
+            "Tersoff parameter m should be 1 or 3, found {}",
+            v[0]
+        );
+        Self {
+            m: v[0],
+            gamma: v[1],
+            lambda3: v[2],
+            c: v[3],
+            d: v[4],
+            costheta0: v[5],
+            n: v[6],
+            beta: v[7],
+            lambda2: v[8],
+            big_b: v[9],
+            big_r: v[10],
+            big_d: v[11],
+            lambda1: v[12],
+            big_a: v[13],
+        }
+    }
+    fn values(&self) -> Vec<f64> {
+        vec![
+            self.m,
+            self.gamma,
+            self.lambda3,
+            self.c,
+            self.d,
+            self.costheta0,
+            self.n,
+            self.beta,
+            self.lambda2,
+            self.big_b,
+            self.big_r,
+            self.big_d,
+            self.lambda1,
+            self.big_a,
+        ]
+    }
+    fn cutoff(&self) -> f64 {
+        self.big_r + self.big_d
+    }
+}
+impl TersoffParams {
+    /// The cutoff function and its derivative, going smoothly from 1 at `R - D` to 0 at
+    /// `R + D`
+    fn fc(&self, r: f64) -> [f64; 2] {
+        if r < self.big_r - self.big_d {
+            [1.0, 0.0]
+        } else if r > self.big_r + self.big_d {
+            [0.0, 0.0]
+        } else {
+            let x = 0.5 * PI * (r - self.big_r) / self.big_d;
+            [0.5 * (1.0 - x.sin()), -0.25 * PI / self.big_d * x.cos()]
+        }
+    }
+    /// The angular function and its derivative with respect to the cosine of the angle
+    fn g(&self, cos: f64) -> [f64; 2] {
+        let [c2, d2] = [self.c * self.c, self.d * self.d];
+        let h = self.costheta0 - cos;
+        let denominator = d2 + h * h;
+        [
+            self.gamma * (1.0 + c2 / d2 - c2 / denominator),
+            -2.0 * self.gamma * c2 * h / (denominator * denominator),
+        ]
+    }
+    /// The exponential of the difference between two distances, and its derivative
+    fn exp_delta(&self, delta: f64) -> [f64; 2] {
+        if self.m == 3.0 {
+            let x = self.lambda3 * delta;
+            let ex = (x * x * x).exp();
+            [ex, 3.0 * self.lambda3 * x * x * ex]
+        } else {
+            let ex = (self.lambda3 * delta).exp();
+            [ex, self.lambda3 * ex]
+        }
+    }
+    /// The bond order and its derivative with respect to zeta
+    fn bond_order(&self, zeta: f64) -> [f64; 2] {
+        if zeta <= 0.0 {
+            return [1.0, 0.0];
+        }
+        let tmp = (self.beta * zeta).powf(self.n);
+        let b = (1.0 + tmp).powf(-0.5 / self.n);
+        [b, -0.5 * b * tmp / ((1.0 + tmp) * zeta)]
+    }
+}
+
+/// The partial derivatives of a contribution to zeta, with respect to the distances
+/// between the central atom and its two neighbors and to the cosine of their angle
+struct ZetaTerm {
+    value: f64,
+    d_rij: f64,
+    d_rik: f64,
+    d_cos: f64,
+    cos: f64,
+}
+
+/// Tersoff bond-order potential, as with LAMMPS `pair_style tersoff`, with the
+/// parameters of each triplet of elements read from a LAMMPS `.tersoff` file
+///
+/// The energy is half the sum over ordered pairs of
+/// `fc(r_ij) (A exp(-lambda1 r_ij) - b_ij B exp(-lambda2 r_ij))`, with the bond order
+/// `b_ij = (1 + (beta zeta_ij)^n)^(-1/2n)` decreasing with the number of other neighbors
+/// `k` of atom `i` through
+/// `zeta_ij = sum fc(r_ik) g(theta_ijk) exp((lambda3 (r_ij - r_ik))^m)`. Elements are
+/// assigned to atom types with `set_coeff(i, i, &name)`.
+pub struct Tersoff {
+    elements: Elements<TersoffParams>,
+}
+impl Tersoff {
+    /// Read a LAMMPS `.tersoff` file, with entries of three elements followed by `m`,
+    /// `gamma`, `lambda3`, `c`, `d`, `costheta0`, `n`, `beta`, `lambda2`, `B`, `R`, `D`,
+    /// `lambda1` and `A`. The two-body parameters `n`, `beta`, `lambda2`, `B`, `lambda1`
+    /// and `A` of elements `i` and `j`, and their cutoff, are from entry `i j j`, and
+    /// the parameters of the contribution of `k` to `zeta_ij` are from entry `i j k`.
+    ///
+    /// ```rust,no_run
+    /// use jmd::{atom_type::Basic, atomic::Tersoff, prelude::*};
+    ///
+    /// fn run(mut sim: Simulation<Basic, Tersoff>) {
+    ///     sim.set_atomic_potential(Tersoff::from_file("SiC.tersoff"));
+    ///     sim.set_atomic_coeff(0, 0, &"Si".to_string());
+    ///     sim.set_atomic_coeff(1, 1, &"C".to_string());
+    /// }
+    /// ```
+    pub fn from_file(path: &str) -> Self {
+        Self {
+            elements: Elements::from_file(path, "tersoff"),
+        }
+    }
+    /// The contribution of neighbor `k` to `zeta_ij`, with its partial derivatives
+    fn zeta_term(&self, ei: usize, j: &Neighbor, k: &Neighbor) -> ZetaTerm {
+        let p = self.elements.params(ei, j.element, k.element);
+        let [fc, dfc] = p.fc(k.r);
+        let cos = cos_angle(j, k);
+        let [g, dg] = p.g(cos);
+        let [ex, dex] = p.exp_delta(j.r - k.r);
+        ZetaTerm {
+            value: fc * g * ex,
+            d_rij: fc * g * dex,
+            d_rik: dfc * g * ex - fc * g * dex,
+            d_cos: fc * dg * ex,
+            cos,
+        }
+    }
+}
+
+impl ManyBody for Tersoff {
+    fn for_each_term<T: AtomType>(
+        &self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+        mut f: impl FnMut(usize, f64, &[TermAtom]),
+    ) {
+        let cutoff = self.elements.cutoff();
+        let mut others: Vec<TermAtom> = Vec::new();
+        for i in 0..atoms.nlocal {
+            let ei = self.elements.element(atoms.types[i]);
+            let neighbors = neighbors_within(atoms, neighbor_list, &self.elements, i, cutoff);
+            for (a, j) in neighbors.iter().enumerate() {
+                let p = self.elements.params(ei, j.element, j.element);
+                if j.r >= p.cutoff() {
+                    continue;
+                }
+                let zeta_terms: Vec<(&Neighbor, ZetaTerm)> = neighbors
+                    .iter()
+                    .enumerate()
+                    .filter(|&(b, k)| {
+                        b != a && k.r < self.elements.params(ei, j.element, k.element).cutoff()
+                    })
+                    .map(|(_, k)| (k, self.zeta_term(ei, j, k)))
+                    .collect();
+                let zeta: f64 = zeta_terms.iter().map(|(_, z)| z.value).sum();
+                let [b, db] = p.bond_order(zeta);
+
+                let [fc, dfc] = p.fc(j.r);
+                let repulsive = p.big_a * (-p.lambda1 * j.r).exp();
+                let attractive = -p.big_b * (-p.lambda2 * j.r).exp();
+                let energy = 0.5 * fc * (repulsive + b * attractive);
+                let de_dr = 0.5
+                    * (dfc * (repulsive + b * attractive)
+                        - fc * (p.lambda1 * repulsive + b * p.lambda2 * attractive));
+                let de_dzeta = 0.5 * fc * attractive * db;
+
+                others.clear();
+                others.push(TermAtom {
+                    idx: j.idx,
+                    del: j.del,
+                    force: j.del.map(|d| -de_dr * d / j.r),
+                });
+                for (k, z) in &zeta_terms {
+                    let [dcos_j, dcos_k] = cos_gradients(j, k, z.cos);
+                    for (m, force) in others[0].force.iter_mut().enumerate() {
+                        *force -= de_dzeta * (z.d_rij * j.del[m] / j.r + z.d_cos * dcos_j[m]);
+                    }
+                    others.push(TermAtom {
+                        idx: k.idx,
+                        del: k.del,
+                        force: [0, 1, 2]
+                            .map(|m| -de_dzeta * (z.d_rik * k.del[m] / k.r + z.d_cos * dcos_k[m])),
+                    });
+                }
+                f(i, energy, &others);
+            }
+        }
+    }
+}
+
+impl<T: AtomType> AtomicPotentialTrait<T> for Tersoff {
+    /// The name of the element of a type
+    type Coeff = String;
+    fn new() -> Self {
+        Self {
+            elements: Elements::new(),
+        }
+    }
+    /// The largest cutoff `R + D` of any entry of the file
+    fn cutoff_distance(&self) -> f64 {
+        if self.elements.cutoff() > 0.0 {
+            self.elements.cutoff()
+        } else {
+            1.0
+        }
+    }
+    fn full_neighbor_list(&self) -> bool {
+        true
+    }
+    fn compute_forces(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> Vec<[f64; 3]> {
+        many_body::forces(self, atoms, neighbor_list)
+    }
+    fn compute_potential_energy(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> f64 {
+        many_body::potential_energy(self, atoms, neighbor_list)
+    }
+    fn compute_virial(&self, atoms: &Atoms<T>, neighbor_list: &NeighborList) -> [f64; 6] {
+        many_body::virial(self, atoms, neighbor_list)
+    }
+    /// The energy of each bond is given to the atom it is seen from
+    fn compute_per_atom_energy(
+        &self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+    ) -> Option<Vec<f64>> {
+        Some(many_body::per_atom_energy(self, atoms, neighbor_list))
+    }
+    fn compute_per_atom_virial(
+        &self,
+        atoms: &Atoms<T>,
+        neighbor_list: &NeighborList,
+    ) -> Option<Vec<[f64; 6]>> {
+        Some(many_body::per_atom_virial(self, atoms, neighbor_list))
+    }
+    fn num_types(&self) -> usize {
+        self.elements.num_types()
+    }
+    fn set_num_types(&mut self, num_types: usize) {
+        self.elements.set_num_types(num_types);
+    }
+    fn all_set(&self) -> bool {
+        self.elements.all_set()
+    }
+    /// Elements are assigned per type, so both type indices should be the same
+    fn set_coeff(&mut self, typei: usize, typej: usize, coeff: &Self::Coeff) {
+        self.elements.set_coeff(typei, typej, coeff);
+    }
+    fn write_restart(&self, writer: &mut RestartWriter) -> io::Result<()> {
+        self.elements.write_restart(writer)
+    }
+    fn read_restart(&mut self, reader: &mut RestartReader) -> io::Result<()> {
+        self.elements.read_restart(reader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{atom_type::Basic, atomic::tests::check_forces};
+
+    #[test]
+    fn test_forces() {
+        // Parameters scaled to the distances of the test cluster, with bond orders well
+        // below one so that the three-body forces matter
+        let si = "1.0 1.0 1.3 4.0 2.0 -0.6 0.79 0.3 1.7322 471.18 1.5 0.1 2.4799 1830.8";
+        let c = "1.0 1.2 1.5 3.0 1.5 -0.57 0.73 0.4 2.2119 346.7 1.4 0.1 3.4879 1393.6";
+        let mixed = "3.0 0.9 1.4 3.5 1.8 -0.59 0.76 0.35 1.97 395.1 1.45 0.1 2.9839 1597.3";
+        let mut contents = String::new();
+        for (elements, values) in [
+            ("Si Si Si", si),
+            ("C C C", c),
+            ("Si Si C", si),
+            ("Si C Si", mixed),
+            ("Si C C", mixed),
+            ("C Si Si", mixed),
+            ("C Si C", c),
+            ("C C Si", c),
+        ] {
+            contents += &format!("{} {}\n", elements, values);
+        }
+        let mut tersoff = Tersoff {
+            elements: Elements::parse(&contents, "tersoff"),
+        };
+        <Tersoff as AtomicPotentialTrait<Basic>>::set_num_types(&mut tersoff, 2);
+        <Tersoff as AtomicPotentialTrait<Basic>>::set_coeff(&mut tersoff, 0, 0, &"Si".to_string());
+        <Tersoff as AtomicPotentialTrait<Basic>>::set_coeff(&mut tersoff, 1, 1, &"C".to_string());
+        check_forces(&mut tersoff, true, |tersoff, atoms, _, nl| {
+            tersoff.compute_potential_energy(atoms, nl)
+        });
+    }
+}
@@ -1,12 +1,15 @@
 use std::fmt::Debug;
 
-use crate::atom_type::AtomType;
+use crate::{atom_type::AtomType, group::ALL_GROUPBIT};
 
+#[derive(Clone, Copy)]
 pub(crate) struct Atom {
     pub(crate) id: usize,
     pub(crate) type_: usize,
     pub(crate) position: [f64; 3],
     pub(crate) velocity: [f64; 3],
+    pub(crate) mask: u32,
+    pub(crate) charge: f64,
 }
 
 /// Atom properties during simulation, not including forces
@@ -16,6 +19,9 @@ pub struct Atoms<T: AtomType> {
     pub(crate) types: Vec<usize>,
     pub(crate) positions: Vec<[f64; 3]>,
     pub(crate) velocities: Vec<[f64; 3]>,
+    /// Bitmask of the groups each atom belongs to
+    pub(crate) masks: Vec<u32>,
+    pub(crate) charges: Vec<f64>,
     pub(crate) atom_types: Vec<T>,
     pub(crate) nlocal: usize,
     pub(crate) num_atoms_global: usize,
@@ -24,8 +30,8 @@ impl<T: AtomType> Atoms<T> {
     /// Create a new, empty set of atoms
     ///
     /// ```rust
-    /// use jmd;
-    /// let atoms: jmd::Atoms<jmd::atom_type::Basic> = Atoms::new();
+    /// use jmd::{atom_type::Basic, atoms::Atoms};
+    /// let atoms: Atoms<Basic> = Atoms::new();
     /// ```
     pub fn new() -> Self {
         Atoms {
@@ -33,6 +39,8 @@ impl<T: AtomType> Atoms<T> {
             types: Vec::new(),
             positions: Vec::new(),
             velocities: Vec::new(),
+            masks: Vec::new(),
+            charges: Vec::new(),
             atom_types: Vec::new(),
             nlocal: 0,
             num_atoms_global: 0,
@@ -74,6 +82,19 @@ impl<T: AtomType> Atoms<T> {
     pub fn velocities(&self) -> &Vec<[f64; 3]> {
         &self.velocities
     }
+    /// The bitmask of the groups each atom belongs to
+    pub fn masks(&self) -> &Vec<u32> {
+        &self.masks
+    }
+    /// The charge of each atom
+    pub fn charges(&self) -> &Vec<f64> {
+        &self.charges
+    }
+    /// Whether the atom at the given index belongs to the group with the given bit, as
+    /// from `Groups::bit`
+    pub fn in_group(&self, i: usize, groupbit: u32) -> bool {
+        groupbit == ALL_GROUPBIT || self.masks[i] & groupbit != 0
+    }
     /// The mass of a given atom (defined by the atom type)
     pub fn mass(&self, idx: usize) -> f64 {
         self.atom_types[self.types[idx]].mass()
@@ -87,32 +108,76 @@ impl<T: AtomType> Atoms<T> {
         self.atom_types.len()
     }
     /// Increment the position of the atom at the given index by the given increment
-    pub(crate) fn increment_position(&mut self, i: usize, increment: [f64; 3]) {
+    pub fn increment_position(&mut self, i: usize, increment: [f64; 3]) {
         self.positions[i][0] += increment[0];
         self.positions[i][1] += increment[1];
         self.positions[i][2] += increment[2];
     }
     /// Increment the velocity of the atom at the given index by the given increment
-    pub(crate) fn increment_velocity(&mut self, i: usize, increment: [f64; 3]) {
+    pub fn increment_velocity(&mut self, i: usize, increment: [f64; 3]) {
         self.velocities[i][0] += increment[0];
         self.velocities[i][1] += increment[1];
         self.velocities[i][2] += increment[2];
     }
-    pub(crate) fn update_or_push(&mut self, atom: Atom) {
-        let idx = self.ids.iter().position(|id| *id == atom.id);
-        match idx {
-            Some(i) => {
-                self.ids[i] = atom.id;
-                self.types[i] = atom.type_;
-                self.positions[i] = atom.position;
-                self.velocities[i] = atom.velocity;
-            }
-            None => {
-                self.ids.push(atom.id);
-                self.types.push(atom.type_);
-                self.positions.push(atom.position);
-                self.velocities.push(atom.velocity);
-            }
-        };
+    /// Set the velocity of the atom at the given index
+    pub fn set_velocity(&mut self, i: usize, velocity: [f64; 3]) {
+        self.velocities[i] = velocity;
+    }
+    /// A copy of the ids, types, positions, velocities, group masks and charges of the
+    /// owned atoms, without the atom types
+    pub(crate) fn owned_atoms(&self) -> Self {
+        Atoms {
+            ids: self.ids[..self.nlocal].to_vec(),
+            types: self.types[..self.nlocal].to_vec(),
+            positions: self.positions[..self.nlocal].to_vec(),
+            velocities: self.velocities[..self.nlocal].to_vec(),
+            masks: self.masks[..self.nlocal].to_vec(),
+            charges: self.charges[..self.nlocal].to_vec(),
+            atom_types: Vec::new(),
+            nlocal: self.nlocal,
+            num_atoms_global: self.num_atoms_global,
+        }
+    }
+    /// A copy of the owned atoms in the group with the given bit, as by `owned_atoms`
+    pub(crate) fn owned_atoms_in_group(&self, groupbit: u32) -> Self {
+        if groupbit == ALL_GROUPBIT {
+            return self.owned_atoms();
+        }
+        let mut atoms = Atoms::new();
+        (0..self.nlocal)
+            .filter(|&i| self.in_group(i, groupbit))
+            .for_each(|i| atoms.push(self.atom(i)));
+        atoms.nlocal = atoms.ids.len();
+        atoms.num_atoms_global = self.num_atoms_global;
+        atoms
+    }
+    /// A copy of the atom at the given index
+    pub(crate) fn atom(&self, i: usize) -> Atom {
+        Atom {
+            id: self.ids[i],
+            type_: self.types[i],
+            position: self.positions[i],
+            velocity: self.velocities[i],
+            mask: self.masks[i],
+            charge: self.charges[i],
+        }
+    }
+    /// Append an atom after all others, as a ghost atom unless `nlocal` is incremented
+    pub(crate) fn push(&mut self, atom: Atom) {
+        self.ids.push(atom.id);
+        self.types.push(atom.type_);
+        self.positions.push(atom.position);
+        self.velocities.push(atom.velocity);
+        self.masks.push(atom.mask);
+        self.charges.push(atom.charge);
+    }
+    /// Remove all ghost atoms
+    pub(crate) fn truncate_ghosts(&mut self) {
+        self.ids.truncate(self.nlocal);
+        self.types.truncate(self.nlocal);
+        self.positions.truncate(self.nlocal);
+        self.velocities.truncate(self.nlocal);
+        self.masks.truncate(self.nlocal);
+        self.charges.truncate(self.nlocal);
     }
 }
@@ -13,10 +13,23 @@ where
         .map(|v| v[0] * v[0] + v[1] * v[1] + v[2] * v[2])
         .collect()
 }
-pub(super) fn compute<T, A>(simulation: &Simulation<T, A>) -> f64
+/// The contribution of the current process to the mean squared velocity of the group,
+/// which is zero for an empty group
+pub(super) fn compute<T, A>(simulation: &Simulation<T, A>, groupbit: u32) -> f64
 where
     T: AtomType,
     A: AtomicPotentialTrait<T>,
 {
-    vsq(simulation).iter().sum::<f64>() / simulation.atoms.num_atoms_global() as f64
+    let count = simulation.group_count(groupbit);
+    if count == 0 {
+        return 0.0;
+    }
+    let atoms = &simulation.atoms;
+    vsq(simulation)
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| atoms.in_group(i, groupbit))
+        .map(|(_, vsq)| vsq)
+        .sum::<f64>()
+        / count as f64
 }
@@ -0,0 +1,9 @@
+use super::*;
+
+pub(super) fn compute<T, A>(sim: &Simulation<T, A>) -> f64
+where
+    T: AtomType,
+    A: AtomicPotentialTrait<T>,
+{
+    total_energy::compute(sim, ALL_GROUPBIT) + sim.integrator().extended_energy(sim)
+}
@@ -1,6 +1,6 @@
 use super::*;
 
-pub(super) fn compute<T, A>(sim: &Simulation<T, A>) -> f64
+pub(super) fn compute<T, A>(sim: &Simulation<T, A>, groupbit: u32) -> f64
 where
     T: AtomType,
     A: AtomicPotentialTrait<T>,
@@ -8,6 +8,7 @@ where
     0.5 * vsq(sim)
         .iter()
         .enumerate()
+        .filter(|&(i, _)| sim.atoms.in_group(i, groupbit))
         .map(|(i, vsq)| sim.atoms.mass(i) * vsq)
         .sum::<f64>()
 }
@@ -1,26 +1,76 @@
 use crate::{
     atom_type::AtomType,
     atomic::AtomicPotentialTrait,
+    group::ALL_GROUPBIT,
     output::{Operatable, Operation, Value},
     simulation::Simulation,
     traits::Named,
 };
 
 mod avg_vsq;
+mod conserved_energy;
 mod kinetic_energy;
 mod potential_energy;
+mod pressure;
 mod temperature;
 mod total_energy;
 
 use avg_vsq::vsq;
+pub(crate) use pressure::{global_pressure, global_virial_trace};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Compute {
     AvgVsq,
+    ConservedE,
     KineticE,
     PotentialE,
+    Pressure,
+    /// The symmetric pressure tensor, ordered [xx, yy, zz, xy, xz, yz]
+    PressureTensor,
     Temperature,
     TotalE,
+    /// A compute restricted to the atoms of the named group, created with
+    /// `Compute::in_group`
+    Group(Box<Compute>, String),
+}
+impl Compute {
+    /// Restrict the compute to the atoms of the named group. Only per-atom sums and
+    /// averages (`AvgVsq`, `KineticE`, `PotentialE`, `Temperature` and `TotalE`) can be
+    /// restricted, with the potential energy of each pair split evenly between its atoms.
+    pub fn in_group(self, group: &str) -> Self {
+        assert!(
+            matches!(
+                self,
+                Compute::AvgVsq
+                    | Compute::KineticE
+                    | Compute::PotentialE
+                    | Compute::Temperature
+                    | Compute::TotalE
+            ),
+            "Compute {:?} cannot be restricted to a group",
+            self
+        );
+        Compute::Group(Box::new(self), String::from(group))
+    }
+    fn compute_in_group<T, A>(&self, sim: &Simulation<T, A>, groupbit: u32) -> Value
+    where
+        T: AtomType,
+        A: AtomicPotentialTrait<T>,
+    {
+        match self {
+            Compute::AvgVsq => Value::Float(avg_vsq::compute(sim, groupbit)),
+            Compute::ConservedE => Value::Float(conserved_energy::compute(sim)),
+            Compute::KineticE => Value::Float(kinetic_energy::compute(sim, groupbit)),
+            Compute::PotentialE => Value::Float(potential_energy::compute(sim, groupbit)),
+            Compute::Pressure => Value::Float(pressure::compute(sim)),
+            Compute::PressureTensor => Value::Tensor(pressure::compute_tensor(sim)),
+            Compute::Temperature => Value::Float(temperature::compute(sim, groupbit)),
+            Compute::TotalE => Value::Float(total_energy::compute(sim, groupbit)),
+            Compute::Group(compute, group) => {
+                compute.compute_in_group(sim, sim.groups().expect_bit(group))
+            }
+        }
+    }
 }
 impl<T, A> ComputeTrait<T, A> for Compute
 where
@@ -28,23 +78,21 @@ where
     A: AtomicPotentialTrait<T>,
 {
     fn compute(&self, sim: &Simulation<T, A>) -> Value {
-        match self {
-            Compute::AvgVsq => Value::Float(avg_vsq::compute(sim)),
-            Compute::KineticE => Value::Float(kinetic_energy::compute(sim)),
-            Compute::PotentialE => Value::Float(potential_energy::compute(sim)),
-            Compute::Temperature => Value::Float(temperature::compute(sim)),
-            Compute::TotalE => Value::Float(total_energy::compute(sim)),
-        }
+        self.compute_in_group(sim, ALL_GROUPBIT)
     }
 }
 impl Named for Compute {
     fn name(&self) -> &str {
         match self {
             Compute::AvgVsq => "AvgVsq",
+            Compute::ConservedE => "ConservedE",
             Compute::KineticE => "KineticE",
             Compute::PotentialE => "PotentialE",
+            Compute::Pressure => "Pressure",
+            Compute::PressureTensor => "PressureTensor",
             Compute::Temperature => "Temperature",
             Compute::TotalE => "TotalE",
+            Compute::Group(compute, _) => compute.name(),
         }
     }
 }
@@ -52,10 +100,14 @@ impl Operatable for Compute {
     fn op(&self) -> Operation {
         match self {
             Compute::AvgVsq
+            | Compute::ConservedE
             | Compute::KineticE
             | Compute::PotentialE
+            | Compute::Pressure
+            | Compute::PressureTensor
             | Compute::Temperature
             | Compute::TotalE => Operation::Sum,
+            Compute::Group(compute, _) => compute.op(),
         }
     }
 }
@@ -1,10 +1,23 @@
 use super::*;
 
-pub(super) fn compute<T, A>(sim: &Simulation<T, A>) -> f64
+/// The potential energy of the owned atoms, including any long-range correction, or
+/// for a group other than all atoms, with the energy of each pair split evenly between
+/// its atoms and no long-range correction
+pub(super) fn compute<T, A>(sim: &Simulation<T, A>, groupbit: u32) -> f64
 where
     T: AtomType,
     A: AtomicPotentialTrait<T>,
 {
-    sim.atomic_potential()
-        .compute_potential_energy(&sim.atoms, sim.nl())
+    if groupbit == ALL_GROUPBIT {
+        return sim
+            .atomic_potential()
+            .compute_potential_energy(&sim.atoms, sim.nl())
+            + sim.tail_correction()[0];
+    }
+    sim.per_atom_energy()
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| sim.atoms.in_group(i, groupbit))
+        .map(|(_, e)| e)
+        .sum()
 }
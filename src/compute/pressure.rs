@@ -0,0 +1,153 @@
+use super::*;
+use crate::region::Region;
+
+/// The contribution of the current process to the pressure tensor, ordered
+/// [xx, yy, zz, xy, xz, yz], from the kinetic energy, the pair virial and any long-range
+/// correction
+pub(super) fn compute_tensor<T, A>(sim: &Simulation<T, A>) -> [f64; 6]
+where
+    T: AtomType,
+    A: AtomicPotentialTrait<T>,
+{
+    let mut tensor = sim.atomic_potential().compute_virial(&sim.atoms, sim.nl());
+    let tail = sim.tail_correction()[1];
+    (0..3).for_each(|k| tensor[k] += tail / 3.0);
+    for i in 0..sim.nlocal() {
+        let mass = sim.atoms.mass(i);
+        let v = sim.atoms.velocities[i];
+        tensor[0] += mass * v[0] * v[0];
+        tensor[1] += mass * v[1] * v[1];
+        tensor[2] += mass * v[2] * v[2];
+        tensor[3] += mass * v[0] * v[1];
+        tensor[4] += mass * v[0] * v[2];
+        tensor[5] += mass * v[1] * v[2];
+    }
+    let volume = sim.container().rect().volume();
+    tensor.map(|p| p / volume)
+}
+
+/// The contribution of the current process to the scalar pressure
+pub(super) fn compute<T, A>(sim: &Simulation<T, A>) -> f64
+where
+    T: AtomType,
+    A: AtomicPotentialTrait<T>,
+{
+    let tensor = compute_tensor(sim);
+    (tensor[0] + tensor[1] + tensor[2]) / 3.0
+}
+
+/// The trace of the virial of the whole system, including any long-range correction.
+/// Must be called by every process.
+pub(crate) fn global_virial_trace<T, A>(sim: &Simulation<T, A>) -> f64
+where
+    T: AtomType,
+    A: AtomicPotentialTrait<T>,
+{
+    let virial = sim.atomic_potential().compute_virial(&sim.atoms, sim.nl());
+    sim.domain()
+        .sum_float(virial[0] + virial[1] + virial[2] + sim.tail_correction()[1])
+}
+
+/// The instantaneous pressure of the whole system. Must be called by every process.
+pub(crate) fn global_pressure<T, A>(sim: &Simulation<T, A>) -> f64
+where
+    T: AtomType,
+    A: AtomicPotentialTrait<T>,
+{
+    sim.domain().sum_float(compute(sim))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{
+        atom_type::Basic,
+        atomic::{LJCut, LJCutCoeff},
+        container::Container,
+        lattice::{Cubic, Lattice},
+        region::Rect,
+        simulation::tests::run_threads,
+    };
+
+    /// The pressure tensor and scalar pressure summed over all processes, with the number
+    /// of atoms and the volume
+    type Pressures = Mutex<Vec<([f64; 6], f64, usize, f64)>>;
+    static IDEAL_GAS: Pressures = Mutex::new(Vec::new());
+    static TWO_ATOMS: Pressures = Mutex::new(Vec::new());
+
+    fn push_pressures(sim: &Simulation<Basic, LJCut>, results: &Pressures) {
+        let tensor = sim.domain().sum_floats(compute_tensor(sim).to_vec());
+        let pressure = global_pressure(sim);
+        if sim.domain().proc_index() == 0 {
+            results.lock().unwrap().push((
+                [0, 1, 2, 3, 4, 5].map(|k| tensor[k]),
+                pressure,
+                sim.atoms.num_atoms_global(),
+                sim.container().rect().volume(),
+            ));
+        }
+    }
+
+    fn lj(sim: &mut Simulation<Basic, LJCut>, rect: Rect) {
+        sim.set_atom_types(vec![Basic::new(2.0)]);
+        sim.set_atomic_potential(LJCut::new(2.5));
+        sim.set_atomic_coeff(0, 0, &LJCutCoeff::new(1.0, 1.0, 2.5));
+        sim.set_container(Container::from_rect_periodic(rect));
+    }
+
+    #[test]
+    fn test_ideal_gas() {
+        fn run(mut sim: Simulation<Basic, LJCut>) {
+            // Spaced beyond the cutoff, so only the kinetic term contributes
+            let lattice = Cubic::new(3.0);
+            let rect = Rect::from_lattice(&lattice, [4; 3]);
+            let (_, coords) = lattice.coords_within_region(&rect, &[0.0; 3]);
+            lj(&mut sim, rect);
+            sim.add_atoms(vec![0; coords.len()], coords);
+            sim.set_seed(7);
+            sim.set_temperature(1.5);
+            sim.run(0);
+            push_pressures(&sim, &IDEAL_GAS);
+        }
+        for results in run_threads(&[1, 4], run, &IDEAL_GAS) {
+            let (tensor, pressure, natoms, volume) = results[0];
+            let expected = natoms as f64 * 1.5 / volume;
+            assert!(
+                (pressure - expected).abs() < 1e-12,
+                "Pressure {} should be NkT/V = {}",
+                pressure,
+                expected
+            );
+            let trace = tensor[0] + tensor[1] + tensor[2];
+            assert!((trace / 3.0 - pressure).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn test_two_atom_virial() {
+        fn run(mut sim: Simulation<Basic, LJCut>) {
+            lj(&mut sim, Rect::new(0.0, 10.0, 0.0, 10.0, 0.0, 10.0));
+            sim.add_atoms(vec![0, 0], vec![[4.0, 4.0, 4.0], [5.1, 4.6, 3.3]]);
+            sim.run(0);
+            push_pressures(&sim, &TWO_ATOMS);
+        }
+        // The force on the first atom is F(r) d / r with d the separation from the second
+        let d = [-1.1, -0.6, 0.7];
+        let r2: f64 = d.iter().map(|x| x * x).sum();
+        let f_over_r = 24.0 * (2.0 / r2.powi(7) - 1.0 / r2.powi(4));
+        let virial = |a: usize, b: usize| d[a] * d[b] * f_over_r / 1000.0;
+        for results in run_threads(&[1, 2], run, &TWO_ATOMS) {
+            let (tensor, pressure, _, _) = results[0];
+            let expected = [(0, 0), (1, 1), (2, 2), (0, 1), (0, 2), (1, 2)];
+            for (k, &(a, b)) in expected.iter().enumerate() {
+                // Symmetric, with d_a F_b equal to d_b F_a
+                assert!((tensor[k] - virial(a, b)).abs() < 1e-12);
+                assert!((tensor[k] - virial(b, a)).abs() < 1e-12);
+            }
+            let trace = tensor[0] + tensor[1] + tensor[2];
+            assert!((trace / 3.0 - pressure).abs() < 1e-12);
+        }
+    }
+}
@@ -1,9 +1,15 @@
 use super::*;
 
-pub(super) fn compute<T, A>(sim: &Simulation<T, A>) -> f64
+/// The contribution of the current process to the temperature of the group, which is zero
+/// for an empty group
+pub(super) fn compute<T, A>(sim: &Simulation<T, A>, groupbit: u32) -> f64
 where
     T: AtomType,
     A: AtomicPotentialTrait<T>,
 {
-    2.0 / 3.0 * kinetic_energy::compute(sim) / sim.atoms.num_atoms_global() as f64
+    let count = sim.group_count(groupbit);
+    if count == 0 {
+        return 0.0;
+    }
+    2.0 / 3.0 * kinetic_energy::compute(sim, groupbit) / count as f64
 }
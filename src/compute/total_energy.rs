@@ -1,9 +1,9 @@
 use super::*;
 
-pub(super) fn compute<T, A>(sim: &Simulation<T, A>) -> f64
+pub(super) fn compute<T, A>(sim: &Simulation<T, A>, groupbit: u32) -> f64
 where
     T: AtomType,
     A: AtomicPotentialTrait<T>,
 {
-    potential_energy::compute(sim) + kinetic_energy::compute(sim)
+    potential_energy::compute(sim, groupbit) + kinetic_energy::compute(sim, groupbit)
 }
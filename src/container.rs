@@ -9,7 +9,7 @@ use crate::{
 /// F: Fixed boundary
 /// S: Shrink-wrapped boundary
 /// M: Shrink-wrapped boundary with a minimum
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum BC {
     PP,
     FF,
@@ -30,13 +30,61 @@ impl BC {
             _ => false,
         }
     }
+    /// The treatment of the lower and upper sides
+    pub fn sides(&self) -> [Boundary; 2] {
+        use Boundary::*;
+        match self {
+            BC::PP => [Periodic, Periodic],
+            BC::FF => [Fixed, Fixed],
+            BC::FM => [Fixed, MinShrink],
+            BC::FS => [Fixed, Shrink],
+            BC::MF => [MinShrink, Fixed],
+            BC::MM => [MinShrink, MinShrink],
+            BC::MS => [MinShrink, Shrink],
+            BC::SF => [Shrink, Fixed],
+            BC::SM => [Shrink, MinShrink],
+            BC::SS => [Shrink, Shrink],
+        }
+    }
 }
 
-/// Simulation box, represented by a rectangular box and boundary conditions
-#[derive(Debug)]
+/// Treatment of one side of the simulation box.
+///
+/// Atoms that move past a fixed side are lost. Shrink-wrapped sides are moved to the
+/// extent of the atoms each time the neighbor list is built, and minimum shrink-wrapped
+/// sides likewise, but never inside their originally set position.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Boundary {
+    Periodic,
+    Fixed,
+    Shrink,
+    MinShrink,
+}
+impl Boundary {
+    pub fn is_shrink_wrapped(&self) -> bool {
+        matches!(self, Boundary::Shrink | Boundary::MinShrink)
+    }
+}
+
+/// Padding added to shrink-wrapped bounds, relative to the box length, so that the
+/// outermost atoms lie inside the box
+const SHRINK_PADDING: f64 = 1e-4;
+
+/// Simulation box, represented by a rectangular box, tilt factors and boundary conditions.
+///
+/// The box is spanned from its lower corner by the vectors a = (lx, 0, 0),
+/// b = (xy, ly, 0) and c = (xz, yz, lz), as in LAMMPS, where lx, ly and lz are the lengths
+/// of the rectangular box and xy, xz and yz are the tilt factors. The box is orthogonal
+/// if all tilt factors are zero, and triclinic otherwise. Positions within the box have
+/// fractional (lamda) coordinates in [0, 1) along these vectors.
+#[derive(Clone, Debug)]
 pub struct Container {
     rect: Rect,
     bc: [BC; 3],
+    /// Tilt factors xy, xz and yz
+    tilt: [f64; 3],
+    /// Bounds as set by the user, the limits of minimum shrink-wrapped sides
+    shrink_limits: Rect,
 }
 impl Container {
     // Creation
@@ -57,6 +105,8 @@ impl Container {
         Self {
             rect,
             bc: [xbc, ybc, zbc],
+            tilt: [0.0; 3],
+            shrink_limits: rect,
         }
     }
     /// Create a fully periodic container from a given rectangular box
@@ -64,6 +114,8 @@ impl Container {
         Self {
             rect,
             bc: [BC::PP, BC::PP, BC::PP],
+            tilt: [0.0; 3],
+            shrink_limits: rect,
         }
     }
 
@@ -72,10 +124,123 @@ impl Container {
     pub fn is_periodic(&self, axis: Axis) -> bool {
         self.bc[axis.index()].is_periodic()
     }
-    /// A reference to the rectangular box
+    /// The boundary condition along a given axis (X, Y, Z)
+    pub fn boundary_condition(&self, axis: Axis) -> &BC {
+        &self.bc[axis.index()]
+    }
+    /// A reference to the rectangular box, giving the lower corner and the lengths of
+    /// the box
     pub fn rect(&self) -> &Rect {
         &self.rect
     }
+    /// The tilt factors xy, xz and yz
+    pub fn tilt(&self) -> [f64; 3] {
+        self.tilt
+    }
+    /// Check whether any tilt factor is nonzero
+    pub fn is_triclinic(&self) -> bool {
+        self.tilt.iter().any(|&t| t != 0.0)
+    }
+    /// The vectors a, b and c spanning the box
+    pub fn box_vectors(&self) -> [[f64; 3]; 3] {
+        let [lx, ly, lz] = self.rect.lengths();
+        let [xy, xz, yz] = self.tilt;
+        [[lx, 0.0, 0.0], [xy, ly, 0.0], [xz, yz, lz]]
+    }
+    /// The distance between each pair of opposite faces of the box, which equals the
+    /// box length along each axis for an orthogonal box
+    pub fn face_spacings(&self) -> [f64; 3] {
+        let [lx, ly, lz] = self.rect.lengths();
+        let [xy, xz, yz] = self.tilt;
+        // Rows of the inverse of the matrix with the box vectors as columns
+        let rows = [
+            [
+                1.0 / lx,
+                -xy / (lx * ly),
+                (xy * yz - ly * xz) / (lx * ly * lz),
+            ],
+            [0.0, 1.0 / ly, -yz / (ly * lz)],
+            [0.0, 0.0, 1.0 / lz],
+        ];
+        rows.map(|r| 1.0 / (r[0] * r[0] + r[1] * r[1] + r[2] * r[2]).sqrt())
+    }
+    /// Convert a position to fractional (lamda) coordinates along the box vectors
+    pub fn to_lamda(&self, position: &[f64; 3]) -> [f64; 3] {
+        let lo = self.rect.lo();
+        let [lx, ly, lz] = self.rect.lengths();
+        let [xy, xz, yz] = self.tilt;
+        let d = [
+            position[0] - lo[0],
+            position[1] - lo[1],
+            position[2] - lo[2],
+        ];
+        let c = d[2] / lz;
+        let b = (d[1] - c * yz) / ly;
+        let a = (d[0] - b * xy - c * xz) / lx;
+        [a, b, c]
+    }
+    /// Convert fractional (lamda) coordinates along the box vectors to a position
+    pub fn from_lamda(&self, lamda: &[f64; 3]) -> [f64; 3] {
+        let lo = self.rect.lo();
+        let [lx, ly, lz] = self.rect.lengths();
+        let [xy, xz, yz] = self.tilt;
+        [
+            lo[0] + lamda[0] * lx + lamda[1] * xy + lamda[2] * xz,
+            lo[1] + lamda[1] * ly + lamda[2] * yz,
+            lo[2] + lamda[2] * lz,
+        ]
+    }
+    /// Check whether a position is within the box
+    pub fn contains(&self, position: &[f64; 3]) -> bool {
+        self.to_lamda(position)
+            .iter()
+            .all(|&l| (0.0..1.0).contains(&l))
+    }
+    /// Wrap a position into the box along the periodic axes by adding or subtracting box
+    /// vectors
+    pub fn wrap(&self, position: &[f64; 3]) -> [f64; 3] {
+        let mut p = *position;
+        let vectors = self.box_vectors();
+        for axis in [Axis::X, Axis::Y, Axis::Z] {
+            if !self.is_periodic(axis) {
+                continue;
+            }
+            let k = axis.index();
+            let shift = self.to_lamda(&p)[k].floor();
+            if shift != 0.0 {
+                (0..3).for_each(|j| p[j] -= shift * vectors[k][j]);
+                // Rounding can leave a position just below the lower side at the upper one
+                let l = self.to_lamda(&p)[k];
+                if l >= 1.0 {
+                    (0..3).for_each(|j| p[j] -= l * vectors[k][j]);
+                }
+            }
+        }
+        p
+    }
+    /// The shortest periodic image of the separation vector between two positions
+    pub fn minimum_image(&self, delta: &[f64; 3]) -> [f64; 3] {
+        let mut d = *delta;
+        let vectors = self.box_vectors();
+        let lo = self.rect.lo();
+        // Fractional coordinates of the separation, relative to the lower corner
+        let lamda = self.to_lamda(&[d[0] + lo[0], d[1] + lo[1], d[2] + lo[2]]);
+        for axis in [Axis::Z, Axis::Y, Axis::X] {
+            let k = axis.index();
+            if self.is_periodic(axis) {
+                let shift = lamda[k].round();
+                (0..3).for_each(|j| d[j] -= shift * vectors[k][j]);
+            }
+        }
+        d
+    }
+    /// Check whether any side of the box is shrink-wrapped
+    pub fn is_shrink_wrapped(&self) -> bool {
+        self.bc
+            .iter()
+            .flat_map(|bc| bc.sides())
+            .any(|side| side.is_shrink_wrapped())
+    }
 
     // Setters
 
@@ -101,8 +266,163 @@ impl Container {
             );
         }
         self.rect.set_bound(direction, bound);
+        self.shrink_limits.set_bound(direction, bound);
+    }
+    /// Scale the box about its center by the given factor along each axis
+    pub fn scale(&mut self, factors: [f64; 3]) {
+        assert!(
+            factors.iter().all(|&f| f > 0.0),
+            "Scale factors should be positive, found {:?}",
+            factors
+        );
+        let center = self.rect.center();
+        let scale_rect = |rect: &Rect| {
+            let lo = rect.lo();
+            let hi = rect.hi();
+            let scaled = |k: usize, x: f64| center[k] + (x - center[k]) * factors[k];
+            Rect::new(
+                scaled(0, lo[0]),
+                scaled(0, hi[0]),
+                scaled(1, lo[1]),
+                scaled(1, hi[1]),
+                scaled(2, lo[2]),
+                scaled(2, hi[2]),
+            )
+        };
+        self.rect = scale_rect(&self.rect);
+        self.shrink_limits = scale_rect(&self.shrink_limits);
+        self.tilt = [
+            self.tilt[0] * factors[0],
+            self.tilt[1] * factors[0],
+            self.tilt[2] * factors[1],
+        ];
+    }
+    /// Move the shrink-wrapped sides to the given extent `[lo, hi]` of the atoms along
+    /// each axis, in fractional (lamda) coordinates of the current box, padded slightly.
+    /// Minimum shrink-wrapped sides are not moved inside their originally set position,
+    /// and other sides are unchanged.
+    pub(crate) fn shrink_wrap(&mut self, extents: [[f64; 2]; 3]) {
+        let old_rect = self.rect;
+        for axis in [Axis::X, Axis::Y, Axis::Z] {
+            let k = axis.index();
+            let [lo, hi] = extents[k];
+            if lo > hi {
+                // No atoms
+                continue;
+            }
+            let length = old_rect.get_length(axis);
+            let padding = SHRINK_PADDING * length;
+            let [old_lo, old_hi] = old_rect.get_bounds(axis);
+            let [lo, hi] = [old_lo + lo * length, old_lo + hi * length];
+            let [lo_side, hi_side] = self.bc[k].sides();
+            let [lo_limit, hi_limit] = self.shrink_limits.get_bounds(axis);
+            let new_lo = match lo_side {
+                Boundary::Shrink => lo - padding,
+                Boundary::MinShrink => (lo - padding).min(lo_limit),
+                _ => old_lo,
+            };
+            let new_hi = match hi_side {
+                Boundary::Shrink => hi + padding,
+                Boundary::MinShrink => (hi + padding).max(hi_limit),
+                _ => old_hi,
+            };
+            assert!(
+                new_lo < new_hi,
+                "Shrink-wrapped bounds along {:?} ({}, {}) should not cross",
+                axis,
+                new_lo,
+                new_hi
+            );
+            self.rect.set_bound(axis.direction(false), new_lo);
+            self.rect.set_bound(axis.direction(true), new_hi);
+        }
     }
     pub fn set_boundary_condition(&mut self, axis: Axis, bc: BC) {
         self.bc[axis.index()] = bc;
+        self.check_tilt();
+    }
+    /// Set the tilt factors xy, xz and yz, making the box triclinic if any are nonzero.
+    ///
+    /// Each tilt factor should be at most half the box length along its first axis, and
+    /// the axis of the box vector it tilts (y for xy, z for xz and yz) should be periodic.
+    ///
+    /// ```rust
+    /// use jmd::prelude::*;
+    ///
+    /// let mut container = Container::new(0.0, 4.0, 0.0, 3.0, 0.0, 2.0, BC::PP, BC::PP, BC::PP);
+    /// container.set_tilt(1.0, 0.0, -0.5);
+    /// assert!(container.is_triclinic());
+    /// ```
+    pub fn set_tilt(&mut self, xy: f64, xz: f64, yz: f64) {
+        self.tilt = [xy, xz, yz];
+        self.check_tilt();
+    }
+    fn check_tilt(&self) {
+        let [lx, ly, _] = self.rect.lengths();
+        let [xy, xz, yz] = self.tilt;
+        assert!(
+            xy.abs() <= 0.5 * lx && xz.abs() <= 0.5 * lx && yz.abs() <= 0.5 * ly,
+            "Tilt factors ({}, {}, {}) should be at most half the box lengths along x (xy, xz) \
+             and y (yz)",
+            xy,
+            xz,
+            yz
+        );
+        assert!(
+            (xy == 0.0 || self.is_periodic(Axis::Y))
+                && (xz == 0.0 && yz == 0.0 || self.is_periodic(Axis::Z)),
+            "A triclinic box should be periodic along the tilted axes"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triclinic() -> Container {
+        let mut container = Container::new(0.0, 4.0, 0.0, 3.0, 0.0, 2.0, BC::PP, BC::PP, BC::PP);
+        container.set_tilt(1.0, -0.5, 0.5);
+        container
+    }
+
+    #[test]
+    fn test_lamda_round_trip() {
+        let container = triclinic();
+        let p = [2.5, 1.0, 1.5];
+        let back = container.from_lamda(&container.to_lamda(&p));
+        for k in 0..3 {
+            assert!((back[k] - p[k]).abs() < 1e-12);
+        }
+        assert_eq!(container.from_lamda(&[0.0, 1.0, 0.0]), [1.0, 3.0, 0.0]);
+    }
+
+    #[test]
+    fn test_wrap_and_minimum_image() {
+        let container = triclinic();
+        // One box vector b = (1, 3, 0) above a position in the box
+        let wrapped = container.wrap(&[3.0, 4.0, 1.0]);
+        assert!((wrapped[0] - 2.0).abs() < 1e-12 && (wrapped[1] - 1.0).abs() < 1e-12);
+        assert!(container.contains(&wrapped));
+
+        let d = container.minimum_image(&[3.5, 2.9, 0.0]);
+        assert!((d[0] + 1.5).abs() < 1e-12 && (d[1] + 0.1).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_face_spacings() {
+        let mut container = Container::new(0.0, 4.0, 0.0, 3.0, 0.0, 2.0, BC::PP, BC::PP, BC::PP);
+        assert_eq!(container.face_spacings(), [4.0, 3.0, 2.0]);
+        container.set_tilt(1.5, 0.0, 0.0);
+        // The x faces are spanned by b = (1.5, 3, 0) and c = (0, 0, 2)
+        let expected = 4.0 * 3.0 / (1.5f64 * 1.5 + 3.0 * 3.0).sqrt();
+        assert!((container.face_spacings()[0] - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_tilt_too_large() {
+        let mut container = Container::new(0.0, 4.0, 0.0, 3.0, 0.0, 2.0, BC::PP, BC::PP, BC::PP);
+        container.set_tilt(2.5, 0.0, 0.0);
     }
 }
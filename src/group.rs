@@ -0,0 +1,59 @@
+/// The bit of the group of all atoms, which every atom belongs to
+pub const ALL_GROUPBIT: u32 = 1;
+/// The maximum number of groups, including the group of all atoms
+pub const MAX_GROUPS: usize = 32;
+
+/// The names of the groups of a simulation, with the group of each name given by its bit
+/// in the per-atom group masks. The first group, "all", holds every atom.
+#[derive(Clone, Debug)]
+pub struct Groups {
+    names: Vec<String>,
+}
+impl Groups {
+    pub(crate) fn new() -> Self {
+        Self {
+            names: vec![String::from("all")],
+        }
+    }
+    /// The names of the groups, in order of their bits
+    pub fn names(&self) -> &Vec<String> {
+        &self.names
+    }
+    /// The bit of the group with the given name, if it exists
+    pub fn bit(&self, name: &str) -> Option<u32> {
+        self.names.iter().position(|n| n == name).map(|i| 1 << i)
+    }
+    /// The bit of the group with the given name, panicking if it does not exist
+    pub(crate) fn expect_bit(&self, name: &str) -> u32 {
+        self.bit(name)
+            .unwrap_or_else(|| panic!("Unknown group {}", name))
+    }
+    /// The bit of the group with the given name, adding it if it does not exist
+    pub(crate) fn bit_or_add(&mut self, name: &str) -> u32 {
+        if let Some(bit) = self.bit(name) {
+            return bit;
+        }
+        assert!(
+            self.names.len() < MAX_GROUPS,
+            "At most {} groups can be defined",
+            MAX_GROUPS
+        );
+        self.names.push(String::from(name));
+        1 << (self.names.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_group_bits() {
+        let mut groups = Groups::new();
+        assert_eq!(groups.bit("all"), Some(ALL_GROUPBIT));
+        assert_eq!(groups.bit_or_add("mobile"), 2);
+        assert_eq!(groups.bit_or_add("wall"), 4);
+        assert_eq!(groups.bit_or_add("mobile"), 2);
+        assert_eq!(groups.bit("missing"), None);
+    }
+}
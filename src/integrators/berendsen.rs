@@ -0,0 +1,153 @@
+use super::*;
+use crate::compute::global_pressure;
+
+/// Velocity-verlet integrator with a Berendsen barostat (NPH), and optionally a
+/// Berendsen thermostat (NPT)
+///
+/// After each step the box and atom positions are rescaled isotropically so that the
+/// pressure relaxes exponentially towards the target with the given damping time.
+/// The relaxation does not sample the isothermal-isobaric ensemble exactly, so this is
+/// best used for equilibration; see `Mtk` for production runs.
+#[derive(Clone, Debug)]
+pub struct Berendsen {
+    pressure: f64,
+    damping: f64,
+    modulus: f64,
+    thermostat: Option<(f64, f64)>,
+}
+impl Berendsen {
+    /// Relax towards the target pressure with the given damping time, where the bulk
+    /// modulus is an estimate of that of the system
+    pub fn new(pressure: f64, damping: f64, modulus: f64) -> Self {
+        assert!(
+            damping > 0.0,
+            "Damping time should be positive, found {}",
+            damping
+        );
+        assert!(
+            modulus > 0.0,
+            "Bulk modulus should be positive, found {}",
+            modulus
+        );
+        Self {
+            pressure,
+            damping,
+            modulus,
+            thermostat: None,
+        }
+    }
+    /// Also rescale the velocities so that the temperature relaxes towards the target
+    /// with the given damping time
+    pub fn with_thermostat(mut self, temperature: f64, damping: f64) -> Self {
+        assert!(
+            temperature > 0.0,
+            "Target temperature should be positive, found {}",
+            temperature
+        );
+        assert!(
+            damping > 0.0,
+            "Damping time should be positive, found {}",
+            damping
+        );
+        self.thermostat = Some((temperature, damping));
+        self
+    }
+
+    fn rescale_velocities<T, A>(&self, simulation: &mut Simulation<T, A>)
+    where
+        T: AtomType,
+        A: AtomicPotentialTrait<T>,
+    {
+        let (temperature, damping) = match self.thermostat {
+            Some(thermostat) => thermostat,
+            None => return,
+        };
+        let natoms = simulation.group_count(simulation.integrator_groupbit());
+        if natoms == 0 {
+            return;
+        }
+        let current = global_mvsq(simulation) / (3.0 * natoms as f64);
+        if current == 0.0 {
+            return;
+        }
+        let ratio = simulation.timestep() / damping;
+        let factor = (1.0 + ratio * (temperature / current - 1.0))
+            .max(0.0)
+            .sqrt();
+        scale_velocities(simulation, factor);
+    }
+
+    fn rescale_box<T, A>(&self, simulation: &mut Simulation<T, A>)
+    where
+        T: AtomType,
+        A: AtomicPotentialTrait<T>,
+    {
+        let current = global_pressure(simulation);
+        let ratio = simulation.timestep() / (self.damping * self.modulus);
+        let factor = (1.0 - ratio * (self.pressure - current)).cbrt();
+        simulation.scale_box([factor; 3]);
+    }
+}
+
+impl<T, A> Integrator<T, A> for Berendsen
+where
+    T: AtomType,
+    A: AtomicPotentialTrait<T>,
+{
+    fn pre_forward_comm(&mut self, simulation: &mut Simulation<T, A>) {
+        let ts = simulation.timestep();
+        Verlet::increment_velocity_halfstep(simulation);
+        Verlet::increment_positions(simulation, ts);
+    }
+    fn post_reverse_comm(&mut self, simulation: &mut Simulation<T, A>) {
+        Verlet::increment_velocity_halfstep(simulation);
+        self.rescale_velocities(simulation);
+        self.rescale_box(simulation);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{
+        atom_type::Basic,
+        atomic::LJCut,
+        compute::Compute,
+        simulation::tests::{global_float, lj_lattice, run_threads},
+    };
+
+    /// The pressure before the run and after each stretch of steps
+    static PRESSURES: Mutex<Vec<f64>> = Mutex::new(Vec::new());
+
+    #[test]
+    fn test_pressure_relaxes() {
+        fn run(mut sim: Simulation<Basic, LJCut>) {
+            lj_lattice(&mut sim, 5);
+            sim.set_seed(3);
+            sim.set_temperature(1.0);
+            sim.set_timestep(0.005);
+            sim.set_integrator(Berendsen::new(3.0, 0.5, 10.0).with_thermostat(1.0, 0.5));
+            sim.run(0);
+            for _ in 0..40 {
+                let pressure = global_float(&sim, Compute::Pressure);
+                PRESSURES.lock().unwrap().push(pressure);
+                sim.run(50);
+            }
+        }
+        let pressures = &run_threads(&[1], run, &PRESSURES)[0];
+        assert!(
+            pressures[0] < 1.5,
+            "Initial pressure {} should be far from the target",
+            pressures[0]
+        );
+        // Averaged over the fluctuations once relaxed
+        let average = pressures[20..].iter().sum::<f64>() / 20.0;
+        assert!(
+            (average - 3.0).abs() < 0.15,
+            "Pressure {} should have relaxed to 3",
+            average
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use rand::Rng;
+use rand_distr::StandardNormal;
+
+use super::*;
+use crate::atoms::Atoms;
+
+/// Langevin dynamics integrator, using the BAOAB splitting of Leimkuhler and Matthews
+///
+/// Each step kicks the velocities by the forces (B), drifts the positions for half a
+/// timestep (A), partially randomizes the velocities with an exact Ornstein–Uhlenbeck
+/// step (O), drifts the positions for the rest of the timestep (A), then kicks the
+/// velocities by the new forces (B). Random numbers are drawn from the stream of each
+/// process, seeded with `Simulation::set_seed`. Reduced units are used, so the
+/// Boltzmann constant is 1.
+#[derive(Clone, Debug)]
+pub struct Langevin {
+    temperature: f64,
+    damping: f64,
+    energy: f64,
+}
+impl Langevin {
+    pub fn new(temperature: f64, damping: f64) -> Self {
+        assert!(
+            temperature >= 0.0,
+            "Target temperature should be non-negative, found {}",
+            temperature
+        );
+        assert!(
+            damping > 0.0,
+            "Damping time should be positive, found {}",
+            damping
+        );
+        Self {
+            temperature,
+            damping,
+            energy: 0.0,
+        }
+    }
+    /// The kinetic energy removed from the atoms of this process by the heat bath,
+    /// which added to the total energy gives a conserved quantity
+    pub fn energy(&self) -> f64 {
+        self.energy
+    }
+
+    /// Apply the Ornstein–Uhlenbeck step to the owned atoms in the group, given three
+    /// standard normal samples per owned atom
+    fn ornstein_uhlenbeck<T: AtomType>(
+        &mut self,
+        atoms: &mut Atoms<T>,
+        groupbit: u32,
+        noise: &[f64],
+        ts: f64,
+    ) {
+        let c1 = (-ts / self.damping).exp();
+        let c2 = (1.0 - c1 * c1).sqrt();
+        for i in 0..atoms.num_local_atoms() {
+            if !atoms.in_group(i, groupbit) {
+                continue;
+            }
+            let mass = atoms.mass(i);
+            let sigma = c2 * (self.temperature / mass).sqrt();
+            let v = atoms.velocities[i];
+            let new_v = [
+                c1 * v[0] + sigma * noise[3 * i],
+                c1 * v[1] + sigma * noise[3 * i + 1],
+                c1 * v[2] + sigma * noise[3 * i + 2],
+            ];
+            let vsq = v[0] * v[0] + v[1] * v[1] + v[2] * v[2];
+            let new_vsq = new_v[0] * new_v[0] + new_v[1] * new_v[1] + new_v[2] * new_v[2];
+            self.energy -= 0.5 * mass * (new_vsq - vsq);
+            atoms.velocities[i] = new_v;
+        }
+    }
+
+    /// Draws the random numbers for and applies the Ornstein–Uhlenbeck step
+    fn thermalize<T, A>(&mut self, simulation: &mut Simulation<T, A>)
+    where
+        T: AtomType,
+        A: AtomicPotentialTrait<T>,
+    {
+        let ts = simulation.timestep();
+        let nlocal = simulation.atoms.num_local_atoms();
+        let noise: Vec<f64> = simulation
+            .mut_rng()
+            .sample_iter(StandardNormal)
+            .take(3 * nlocal)
+            .collect();
+        let groupbit = simulation.integrator_groupbit();
+        self.ornstein_uhlenbeck(&mut simulation.atoms, groupbit, &noise, ts);
+    }
+}
+
+impl<T, A> Integrator<T, A> for Langevin
+where
+    T: AtomType,
+    A: AtomicPotentialTrait<T>,
+{
+    fn pre_forward_comm(&mut self, simulation: &mut Simulation<T, A>) {
+        let half_ts = 0.5 * simulation.timestep();
+        Verlet::increment_velocity_halfstep(simulation);
+        Verlet::increment_positions(simulation, half_ts);
+        self.thermalize(simulation);
+        Verlet::increment_positions(simulation, half_ts);
+    }
+    fn post_reverse_comm(&mut self, simulation: &mut Simulation<T, A>) {
+        Verlet::increment_velocity_halfstep(simulation);
+    }
+    /// The energy exchanged with the heat bath is tallied on each process
+    fn extended_energy(&self, _simulation: &Simulation<T, A>) -> f64 {
+        self.energy
+    }
+    /// The tallies of all processes are summed, and attributed to the first process on reading
+    fn write_restart(
+        &self,
+        simulation: &Simulation<T, A>,
+        writer: &mut RestartWriter,
+    ) -> io::Result<()> {
+        writer.write_f64(simulation.domain().sum_float(self.energy))
+    }
+    fn read_restart(
+        &mut self,
+        simulation: &Simulation<T, A>,
+        reader: &mut RestartReader,
+    ) -> io::Result<()> {
+        let energy = reader.read_f64()?;
+        self.energy = if simulation.domain().proc_index() == 0 {
+            energy
+        } else {
+            0.0
+        };
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use rand_distr::StandardNormal;
+
+    use super::*;
+    use crate::{
+        atom_type::Basic,
+        atomic::LJCut,
+        atoms::Atoms,
+        group::ALL_GROUPBIT,
+        simulation::tests::{lj_lattice, run_threads},
+    };
+
+    fn thermalized_velocities(seed: u64, num_steps: usize) -> (Vec<[f64; 3]>, f64) {
+        let natoms = 1000;
+        let mut atoms: Atoms<Basic> = Atoms::new();
+        atoms.atom_types = vec![Basic::new(2.0)];
+        atoms.types = vec![0; natoms];
+        atoms.velocities = vec![[0.0; 3]; natoms];
+        atoms.nlocal = natoms;
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut langevin = Langevin::new(1.5, 0.1);
+        for _ in 0..num_steps {
+            let noise: Vec<f64> = (&mut rng)
+                .sample_iter(StandardNormal)
+                .take(3 * natoms)
+                .collect();
+            langevin.ornstein_uhlenbeck(&mut atoms, ALL_GROUPBIT, &noise, 0.01);
+        }
+        (atoms.velocities, langevin.energy())
+    }
+
+    #[test]
+    fn test_equipartition() {
+        let (velocities, energy) = thermalized_velocities(1, 200);
+        let ke: f64 = velocities
+            .iter()
+            .map(|v| 0.5 * 2.0 * (v[0] * v[0] + v[1] * v[1] + v[2] * v[2]))
+            .sum();
+        let temperature = 2.0 / 3.0 * ke / velocities.len() as f64;
+        assert!(
+            (temperature - 1.5).abs() < 0.1,
+            "Temperature {} should be near 1.5",
+            temperature
+        );
+        // All of the kinetic energy came from the heat bath
+        assert!((ke + energy).abs() < 1e-8 * ke);
+    }
+
+    /// The id and velocity of each atom after a Langevin run
+    static VELOCITIES: Mutex<Vec<(usize, [f64; 3])>> = Mutex::new(Vec::new());
+
+    fn run_seeded(mut sim: Simulation<Basic, LJCut>, seed: u64) {
+        lj_lattice(&mut sim, 8);
+        sim.set_seed(seed);
+        sim.set_timestep(0.005);
+        sim.set_integrator(Langevin::new(1.5, 0.1));
+        sim.run(50);
+        let mut results = VELOCITIES.lock().unwrap();
+        for i in 0..sim.atoms.num_local_atoms() {
+            results.push((sim.atoms.ids[i], sim.atoms.velocities[i]));
+        }
+    }
+
+    #[test]
+    fn test_reproducible() {
+        fn run(sim: Simulation<Basic, LJCut>) {
+            run_seeded(sim, 7);
+        }
+        fn run_other_seed(sim: Simulation<Basic, LJCut>) {
+            run_seeded(sim, 8);
+        }
+        let mut runs = run_threads(&[4, 4], run, &VELOCITIES);
+        runs.extend(run_threads(&[4], run_other_seed, &VELOCITIES));
+        for results in &mut runs {
+            results.sort_by_key(|&(id, _)| id);
+            assert_eq!(results.len(), 512);
+        }
+        // Bit-identical for the same seed and number of threads
+        assert_eq!(runs[0], runs[1]);
+        assert_ne!(runs[0], runs[2]);
+    }
+}
@@ -1,17 +1,92 @@
-use crate::{atom_type::AtomType, atomic::AtomicPotentialTrait, simulation::Simulation};
+use std::io;
 
+use crate::{
+    atom_type::AtomType,
+    atomic::AtomicPotentialTrait,
+    output::{RestartReader, RestartWriter},
+    simulation::Simulation,
+};
+
+mod berendsen;
+mod langevin;
+mod mtk;
+mod nose_hoover;
 mod verlet;
 
+pub use berendsen::Berendsen;
+pub use langevin::Langevin;
+pub use mtk::Mtk;
+pub use nose_hoover::NoseHoover;
 pub use verlet::Verlet;
 
-/// Simulation integrator
+/// Simulation integrator, held by the simulation and set with `Simulation::set_integrator`.
+///
+/// Each hook is called once per step at the corresponding point of `Simulation::run`.
 pub trait Integrator<T, A>
 where
     T: AtomType,
     A: AtomicPotentialTrait<T>,
 {
-    fn pre_forward_comm(_simulation: &mut Simulation<T, A>) {}
-    fn post_forward_comm(_simulation: &mut Simulation<T, A>) {}
-    fn pre_reverse_comm(_simulation: &mut Simulation<T, A>) {}
-    fn post_reverse_comm(_simulation: &mut Simulation<T, A>) {}
+    fn pre_forward_comm(&mut self, _simulation: &mut Simulation<T, A>) {}
+    fn post_forward_comm(&mut self, _simulation: &mut Simulation<T, A>) {}
+    fn pre_reverse_comm(&mut self, _simulation: &mut Simulation<T, A>) {}
+    fn post_reverse_comm(&mut self, _simulation: &mut Simulation<T, A>) {}
+    /// The energy of any extra degrees of freedom of the integrator (e.g., thermostat
+    /// variables) attributed to the current process. Added to the total energy and
+    /// summed over all processes, this gives the conserved quantity of the integrator.
+    fn extended_energy(&self, _simulation: &Simulation<T, A>) -> f64 {
+        0.0
+    }
+    /// Write the state of the integrator to a restart file. Called by every process, but
+    /// only the output of the first process is kept.
+    fn write_restart(
+        &self,
+        _simulation: &Simulation<T, A>,
+        _writer: &mut RestartWriter,
+    ) -> io::Result<()> {
+        Ok(())
+    }
+    /// Read the state written by `write_restart`. Called by every process.
+    fn read_restart(
+        &mut self,
+        _simulation: &Simulation<T, A>,
+        _reader: &mut RestartReader,
+    ) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The sum of `m v^2` over the owned atoms of the integrated group on all processes.
+/// Must be called by every process.
+fn global_mvsq<T, A>(simulation: &Simulation<T, A>) -> f64
+where
+    T: AtomType,
+    A: AtomicPotentialTrait<T>,
+{
+    let atoms = &simulation.atoms;
+    let groupbit = simulation.integrator_groupbit();
+    let local_mvsq: f64 = (0..atoms.num_local_atoms())
+        .filter(|&i| atoms.in_group(i, groupbit))
+        .map(|i| {
+            let v = atoms.velocities[i];
+            atoms.mass(i) * (v[0] * v[0] + v[1] * v[1] + v[2] * v[2])
+        })
+        .sum();
+    simulation.domain().sum_float(local_mvsq)
+}
+
+/// Scale the velocities of the owned atoms of the integrated group by the given factor
+fn scale_velocities<T, A>(simulation: &mut Simulation<T, A>, factor: f64)
+where
+    T: AtomType,
+    A: AtomicPotentialTrait<T>,
+{
+    let groupbit = simulation.integrator_groupbit();
+    let atoms = &mut simulation.atoms;
+    for i in 0..atoms.num_local_atoms() {
+        if atoms.in_group(i, groupbit) {
+            let v = atoms.velocities[i];
+            atoms.velocities[i] = [v[0] * factor, v[1] * factor, v[2] * factor];
+        }
+    }
 }
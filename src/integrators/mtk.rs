@@ -0,0 +1,260 @@
+use super::*;
+use crate::{compute::global_virial_trace, group::ALL_GROUPBIT, region::Region};
+
+/// Isotropic Martyna–Tobias–Klein barostat with Nosé–Hoover chain thermostats (NPT)
+///
+/// The box is coupled to a single strain rate with the equations of motion of Martyna,
+/// Tobias and Klein, and both the atoms and the strain rate are coupled to their own
+/// Nosé–Hoover chain at the target temperature. The splitting follows that of Shinoda,
+/// Shiga and Mikami, as used by LAMMPS for `fix npt ... iso`. Reduced units are used, so
+/// the Boltzmann constant is 1.
+#[derive(Clone, Debug)]
+pub struct Mtk {
+    pressure: f64,
+    pressure_damping: f64,
+    thermostat: NoseHoover,
+    barostat_thermostat: NoseHoover,
+    omega_dot: f64,
+    omega_mass: f64,
+    initial_volume: Option<f64>,
+}
+impl Mtk {
+    /// Hold the temperature and pressure at the given targets, with the given damping
+    /// times and a chain length shared by both thermostats
+    pub fn new(
+        temperature: f64,
+        temperature_damping: f64,
+        pressure: f64,
+        pressure_damping: f64,
+        chain_length: usize,
+    ) -> Self {
+        assert!(
+            pressure_damping > 0.0,
+            "Damping time should be positive, found {}",
+            pressure_damping
+        );
+        Self {
+            pressure,
+            pressure_damping,
+            thermostat: NoseHoover::new(temperature, temperature_damping, chain_length),
+            barostat_thermostat: NoseHoover::new(temperature, pressure_damping, chain_length),
+            omega_dot: 0.0,
+            omega_mass: 0.0,
+            initial_volume: None,
+        }
+    }
+    /// The energy of the thermostat chains and barostat, which added to the total energy
+    /// of the atoms gives the conserved quantity of the NPT ensemble, given the current volume
+    pub fn energy(&self, volume: f64) -> f64 {
+        self.thermostat.energy()
+            + self.barostat_thermostat.energy()
+            + 1.5 * self.omega_mass * self.omega_dot * self.omega_dot
+            + self.pressure * (volume - self.initial_volume.unwrap_or(volume))
+    }
+
+    /// Steps the thermostat of the strain rate by half a timestep
+    fn barostat_thermostat_halfstep(&mut self, timestep: f64) {
+        let mvsq = 3.0 * self.omega_mass * self.omega_dot * self.omega_dot;
+        self.omega_dot *= self
+            .barostat_thermostat
+            .integrate_chain(mvsq, 1.0, timestep);
+    }
+
+    /// Steps the thermostat of the atoms by half a timestep and rescales the velocities
+    /// accordingly. Returns the new global sum of `m v^2`.
+    fn thermostat_halfstep<T, A>(&mut self, simulation: &mut Simulation<T, A>) -> f64
+    where
+        T: AtomType,
+        A: AtomicPotentialTrait<T>,
+    {
+        let mvsq = global_mvsq(simulation);
+        let dof = 3.0 * simulation.atoms.num_atoms_global() as f64;
+        let factor = self
+            .thermostat
+            .integrate_chain(mvsq, dof, simulation.timestep());
+        scale_velocities(simulation, factor);
+        mvsq * factor * factor
+    }
+
+    /// Steps the strain rate by half a timestep, given the global sum of `m v^2`
+    fn omega_halfstep<T, A>(&mut self, simulation: &Simulation<T, A>, mvsq: f64)
+    where
+        T: AtomType,
+        A: AtomicPotentialTrait<T>,
+    {
+        let natoms = simulation.atoms.num_atoms_global() as f64;
+        let volume = simulation.container().rect().volume();
+        let current = (mvsq + global_virial_trace(simulation)) / (3.0 * volume);
+        self.omega_mass = (natoms + 1.0)
+            * self.thermostat.temperature()
+            * self.pressure_damping
+            * self.pressure_damping;
+        let force = ((current - self.pressure) * volume + mvsq / (3.0 * natoms)) / self.omega_mass;
+        self.omega_dot += 0.5 * simulation.timestep() * force;
+    }
+
+    /// Scales the velocities by the coupling to the strain rate over half a timestep
+    fn velocity_halfstep<T, A>(&self, simulation: &mut Simulation<T, A>)
+    where
+        T: AtomType,
+        A: AtomicPotentialTrait<T>,
+    {
+        let natoms = simulation.atoms.num_atoms_global() as f64;
+        let rate = self.omega_dot * (1.0 + 1.0 / natoms);
+        let factor = (-0.5 * simulation.timestep() * rate).exp();
+        scale_velocities(simulation, factor);
+    }
+
+    /// Dilates the box and atom positions by the strain rate over half a timestep
+    fn remap<T, A>(&self, simulation: &mut Simulation<T, A>)
+    where
+        T: AtomType,
+        A: AtomicPotentialTrait<T>,
+    {
+        let factor = (0.5 * simulation.timestep() * self.omega_dot).exp();
+        simulation.scale_box([factor; 3]);
+    }
+}
+
+impl<T, A> Integrator<T, A> for Mtk
+where
+    T: AtomType,
+    A: AtomicPotentialTrait<T>,
+{
+    fn pre_forward_comm(&mut self, simulation: &mut Simulation<T, A>) {
+        assert_eq!(
+            simulation.integrator_groupbit(),
+            ALL_GROUPBIT,
+            "The barostat dilates all atoms, so it cannot be restricted to a group"
+        );
+        if simulation.atoms.num_atoms_global() == 0 {
+            return;
+        }
+        let ts = simulation.timestep();
+        self.initial_volume
+            .get_or_insert(simulation.container().rect().volume());
+
+        self.barostat_thermostat_halfstep(ts);
+        let mvsq = self.thermostat_halfstep(simulation);
+        self.omega_halfstep(simulation, mvsq);
+        self.velocity_halfstep(simulation);
+
+        Verlet::increment_velocity_halfstep(simulation);
+        self.remap(simulation);
+        Verlet::increment_positions(simulation, ts);
+        self.remap(simulation);
+    }
+    fn post_reverse_comm(&mut self, simulation: &mut Simulation<T, A>) {
+        if simulation.atoms.num_atoms_global() == 0 {
+            return;
+        }
+        Verlet::increment_velocity_halfstep(simulation);
+        self.velocity_halfstep(simulation);
+
+        let mvsq = global_mvsq(simulation);
+        self.omega_halfstep(simulation, mvsq);
+        self.thermostat_halfstep(simulation);
+        self.barostat_thermostat_halfstep(simulation.timestep());
+    }
+    /// The extended energy is global, so it is only attributed to the first process
+    fn extended_energy(&self, simulation: &Simulation<T, A>) -> f64 {
+        if simulation.domain().proc_index() == 0 {
+            self.energy(simulation.container().rect().volume())
+        } else {
+            0.0
+        }
+    }
+    fn write_restart(
+        &self,
+        _simulation: &Simulation<T, A>,
+        writer: &mut RestartWriter,
+    ) -> io::Result<()> {
+        self.thermostat.write_chain(writer)?;
+        self.barostat_thermostat.write_chain(writer)?;
+        writer.write_f64(self.omega_dot)?;
+        writer.write_f64(self.omega_mass)?;
+        writer.write_bool(self.initial_volume.is_some())?;
+        writer.write_f64(self.initial_volume.unwrap_or(0.0))
+    }
+    fn read_restart(
+        &mut self,
+        _simulation: &Simulation<T, A>,
+        reader: &mut RestartReader,
+    ) -> io::Result<()> {
+        self.thermostat.read_chain(reader)?;
+        self.barostat_thermostat.read_chain(reader)?;
+        self.omega_dot = reader.read_f64()?;
+        self.omega_mass = reader.read_f64()?;
+        let has_initial_volume = reader.read_bool()?;
+        let initial_volume = reader.read_f64()?;
+        self.initial_volume = has_initial_volume.then_some(initial_volume);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{
+        atom_type::Basic,
+        atomic::LJCut,
+        compute::Compute,
+        simulation::tests::{global_float, lj_lattice, run_threads},
+    };
+
+    /// The conserved energy, total energy and volume before the run and after each stretch of steps
+    static ENERGIES: Mutex<Vec<[f64; 3]>> = Mutex::new(Vec::new());
+
+    #[test]
+    fn test_conserves_extended_energy() {
+        fn run(mut sim: Simulation<Basic, LJCut>) {
+            lj_lattice(&mut sim, 5);
+            sim.set_seed(5);
+            sim.set_temperature(1.0);
+            sim.set_timestep(0.002);
+            sim.set_integrator(Mtk::new(1.0, 0.2, 3.0, 1.0, 3));
+            sim.run(0);
+            for _ in 0..40 {
+                let conserved = global_float(&sim, Compute::ConservedE);
+                let total = global_float(&sim, Compute::TotalE);
+                ENERGIES
+                    .lock()
+                    .unwrap()
+                    .push([conserved, total, sim.container().rect().volume()]);
+                sim.run(50);
+            }
+        }
+        let energies = &run_threads(&[1], run, &ENERGIES)[0];
+        let [initial, _, volume] = energies[0];
+        // The thermostat and barostat exchange energy with the system...
+        let total_range = energies
+            .iter()
+            .map(|e| (e[1] - initial).abs())
+            .fold(0.0, f64::max);
+        let volume_range = energies
+            .iter()
+            .map(|e| (e[2] - volume).abs())
+            .fold(0.0, f64::max);
+        assert!(
+            total_range > 10.0,
+            "Total energy should fluctuate, found {}",
+            total_range
+        );
+        assert!(
+            volume_range > 5.0,
+            "Volume should fluctuate, found {}",
+            volume_range
+        );
+        // ...but the extended energy is conserved
+        for e in energies {
+            assert!(
+                (e[0] - initial).abs() < 0.05,
+                "Conserved energy {} should be {}",
+                e[0],
+                initial
+            );
+        }
+    }
+}
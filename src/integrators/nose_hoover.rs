@@ -0,0 +1,220 @@
+use super::*;
+use crate::output::invalid_data;
+
+/// Velocity-verlet integrator coupled to a Nosé–Hoover chain thermostat (NVT)
+///
+/// The chain is integrated with the scheme of Martyna, Tuckerman, Tobias and Klein,
+/// taking a half step before the first velocity half step and after the second.
+/// Reduced units are used, so the Boltzmann constant is 1.
+#[derive(Clone, Debug)]
+pub struct NoseHoover {
+    temperature: f64,
+    damping: f64,
+    dof: f64,
+    eta: Vec<f64>,
+    /// One longer than the chain, with the last element always zero
+    eta_dot: Vec<f64>,
+    eta_dotdot: Vec<f64>,
+    eta_mass: Vec<f64>,
+}
+impl NoseHoover {
+    pub fn new(temperature: f64, damping: f64, chain_length: usize) -> Self {
+        assert!(
+            temperature > 0.0,
+            "Target temperature should be positive, found {}",
+            temperature
+        );
+        assert!(
+            damping > 0.0,
+            "Damping time should be positive, found {}",
+            damping
+        );
+        assert!(chain_length > 0, "Chain length should be at least 1");
+        Self {
+            temperature,
+            damping,
+            dof: 0.0,
+            eta: vec![0.0; chain_length],
+            eta_dot: vec![0.0; chain_length + 1],
+            eta_dotdot: vec![0.0; chain_length],
+            eta_mass: vec![0.0; chain_length],
+        }
+    }
+    pub fn temperature(&self) -> f64 {
+        self.temperature
+    }
+    pub fn chain_length(&self) -> usize {
+        self.eta.len()
+    }
+    /// The energy of the thermostat chain, which added to the total energy of the
+    /// atoms gives the conserved quantity of the NVT ensemble
+    pub fn energy(&self) -> f64 {
+        let kt = self.temperature;
+        let mut energy = self.dof * kt * self.eta[0]
+            + 0.5 * self.eta_mass[0] * self.eta_dot[0] * self.eta_dot[0];
+        for i in 1..self.chain_length() {
+            energy += kt * self.eta[i] + 0.5 * self.eta_mass[i] * self.eta_dot[i] * self.eta_dot[i];
+        }
+        energy
+    }
+
+    /// Advance the thermostat chain by half a timestep, given the global sum of
+    /// `m v^2` and number of degrees of freedom. Returns the factor by which
+    /// all velocities should be scaled.
+    pub(super) fn integrate_chain(&mut self, mvsq: f64, dof: f64, timestep: f64) -> f64 {
+        if dof == 0.0 {
+            return 1.0;
+        }
+        let n = self.chain_length();
+        let kt = self.temperature;
+        let ke_target = dof * kt;
+        let freq_sq = 1.0 / (self.damping * self.damping);
+        let half_ts = 0.5 * timestep;
+        let quarter_ts = 0.25 * timestep;
+        let eighth_ts = 0.125 * timestep;
+
+        self.dof = dof;
+        self.eta_mass[0] = ke_target / freq_sq;
+        for i in 1..n {
+            self.eta_mass[i] = kt / freq_sq;
+        }
+
+        let mut ke_current = mvsq;
+        self.eta_dotdot[0] = (ke_current - ke_target) / self.eta_mass[0];
+
+        // Propagate the chain velocities from the end of the chain inwards
+        for i in (1..n).rev() {
+            let expfac = (-eighth_ts * self.eta_dot[i + 1]).exp();
+            self.eta_dot[i] = (self.eta_dot[i] * expfac + self.eta_dotdot[i] * quarter_ts) * expfac;
+        }
+        let expfac = (-eighth_ts * self.eta_dot[1]).exp();
+        self.eta_dot[0] = (self.eta_dot[0] * expfac + self.eta_dotdot[0] * quarter_ts) * expfac;
+
+        let factor = (-half_ts * self.eta_dot[0]).exp();
+        ke_current *= factor * factor;
+        self.eta_dotdot[0] = (ke_current - ke_target) / self.eta_mass[0];
+
+        for i in 0..n {
+            self.eta[i] += half_ts * self.eta_dot[i];
+        }
+
+        // Propagate the chain velocities from the start of the chain outwards
+        self.eta_dot[0] = (self.eta_dot[0] * expfac + self.eta_dotdot[0] * quarter_ts) * expfac;
+        for i in 1..n {
+            let expfac = (-eighth_ts * self.eta_dot[i + 1]).exp();
+            self.eta_dotdot[i] = (self.eta_mass[i - 1] * self.eta_dot[i - 1] * self.eta_dot[i - 1]
+                - kt)
+                / self.eta_mass[i];
+            self.eta_dot[i] = (self.eta_dot[i] * expfac + self.eta_dotdot[i] * quarter_ts) * expfac;
+        }
+
+        factor
+    }
+
+    /// Write the state of the chain to a restart file
+    pub(super) fn write_chain(&self, writer: &mut RestartWriter) -> io::Result<()> {
+        writer.write_f64(self.dof)?;
+        writer.write_f64s(&self.eta)?;
+        writer.write_f64s(&self.eta_dot)?;
+        writer.write_f64s(&self.eta_dotdot)?;
+        writer.write_f64s(&self.eta_mass)
+    }
+    /// Read the state written by `write_chain`, which should have the same chain length
+    pub(super) fn read_chain(&mut self, reader: &mut RestartReader) -> io::Result<()> {
+        let dof = reader.read_f64()?;
+        let eta = reader.read_f64s()?;
+        if eta.len() != self.chain_length() {
+            return Err(invalid_data("Chain length differs from the restart file"));
+        }
+        self.dof = dof;
+        self.eta = eta;
+        self.eta_dot = reader.read_f64s()?;
+        self.eta_dotdot = reader.read_f64s()?;
+        self.eta_mass = reader.read_f64s()?;
+        Ok(())
+    }
+
+    /// Steps the thermostat by half a timestep and rescales the velocities accordingly
+    fn thermostat_halfstep<T, A>(&mut self, simulation: &mut Simulation<T, A>)
+    where
+        T: AtomType,
+        A: AtomicPotentialTrait<T>,
+    {
+        let mvsq = global_mvsq(simulation);
+        let dof = 3.0 * simulation.group_count(simulation.integrator_groupbit()) as f64;
+        let factor = self.integrate_chain(mvsq, dof, simulation.timestep());
+        scale_velocities(simulation, factor);
+    }
+}
+
+impl<T, A> Integrator<T, A> for NoseHoover
+where
+    T: AtomType,
+    A: AtomicPotentialTrait<T>,
+{
+    fn pre_forward_comm(&mut self, simulation: &mut Simulation<T, A>) {
+        let ts = simulation.timestep();
+        self.thermostat_halfstep(simulation);
+        Verlet::increment_velocity_halfstep(simulation);
+        Verlet::increment_positions(simulation, ts);
+    }
+    fn post_reverse_comm(&mut self, simulation: &mut Simulation<T, A>) {
+        Verlet::increment_velocity_halfstep(simulation);
+        self.thermostat_halfstep(simulation);
+    }
+    /// The chain energy is global, so it is only attributed to the first process
+    fn extended_energy(&self, simulation: &Simulation<T, A>) -> f64 {
+        if simulation.domain().proc_index() == 0 {
+            self.energy()
+        } else {
+            0.0
+        }
+    }
+    fn write_restart(
+        &self,
+        _simulation: &Simulation<T, A>,
+        writer: &mut RestartWriter,
+    ) -> io::Result<()> {
+        self.write_chain(writer)
+    }
+    fn read_restart(
+        &mut self,
+        _simulation: &Simulation<T, A>,
+        reader: &mut RestartReader,
+    ) -> io::Result<()> {
+        self.read_chain(reader)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NoseHoover;
+
+    #[test]
+    fn test_free_particles_conserve_extended_energy() {
+        // For non-interacting particles only the thermostat changes the kinetic energy,
+        // so the kinetic energy plus the chain energy should be conserved
+        let mut nh = NoseHoover::new(1.0, 0.5, 3);
+        let dof = 300.0;
+        let timestep = 0.005;
+        let mut mvsq = 2.0 * dof;
+
+        nh.integrate_chain(mvsq, dof, 0.0);
+        let initial = 0.5 * mvsq + nh.energy();
+        for _ in 0..4000 {
+            let factor = nh.integrate_chain(mvsq, dof, timestep);
+            mvsq *= factor * factor;
+            let factor = nh.integrate_chain(mvsq, dof, timestep);
+            mvsq *= factor * factor;
+        }
+        let fin = 0.5 * mvsq + nh.energy();
+        assert!(
+            ((fin - initial) / initial).abs() < 1e-4,
+            "Extended energy drifted from {} to {}",
+            initial,
+            fin
+        );
+        // Temperature should have moved towards the target
+        assert!(mvsq / dof < 1.5);
+    }
+}
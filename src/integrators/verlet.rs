@@ -4,14 +4,18 @@ use super::*;
 pub struct Verlet {}
 
 impl Verlet {
-    /// Steps the velocities of the simulation by half a timestep
-    fn increment_velocity_halfstep<T, A>(simulation: &mut Simulation<T, A>)
+    /// Steps the velocities of the integrated group by half a timestep
+    pub(super) fn increment_velocity_halfstep<T, A>(simulation: &mut Simulation<T, A>)
     where
         T: AtomType,
         A: AtomicPotentialTrait<T>,
     {
         let half_ts = 0.5 * simulation.timestep();
+        let groupbit = simulation.integrator_groupbit();
         for i in 0..simulation.atoms.num_local_atoms() {
+            if !simulation.atoms.in_group(i, groupbit) {
+                continue;
+            }
             let mass = simulation.atoms.mass(i);
             simulation.atoms.increment_velocity(
                 i,
@@ -23,14 +27,17 @@ impl Verlet {
             );
         }
     }
-    /// Steps the positions of the simulation forward
-    fn increment_positions<T, A>(simulation: &mut Simulation<T, A>)
+    /// Steps the positions of the integrated group forward by the given time increment
+    pub(super) fn increment_positions<T, A>(simulation: &mut Simulation<T, A>, ts: f64)
     where
         T: AtomType,
         A: AtomicPotentialTrait<T>,
     {
-        let ts = simulation.timestep();
+        let groupbit = simulation.integrator_groupbit();
         for i in 0..simulation.atoms.num_local_atoms() {
+            if !simulation.atoms.in_group(i, groupbit) {
+                continue;
+            }
             let vel = simulation.atoms.velocities()[i];
 
             simulation
@@ -45,11 +52,12 @@ where
     T: AtomType,
     A: AtomicPotentialTrait<T>,
 {
-    fn pre_forward_comm(simulation: &mut Simulation<T, A>) {
+    fn pre_forward_comm(&mut self, simulation: &mut Simulation<T, A>) {
+        let ts = simulation.timestep();
         Verlet::increment_velocity_halfstep(simulation);
-        Verlet::increment_positions(simulation);
+        Verlet::increment_positions(simulation, ts);
     }
-    fn post_reverse_comm(simulation: &mut Simulation<T, A>) {
+    fn post_reverse_comm(&mut self, simulation: &mut Simulation<T, A>) {
         Verlet::increment_velocity_halfstep(simulation);
     }
 }
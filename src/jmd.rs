@@ -1,9 +1,11 @@
-use std::{sync::mpsc, thread, time::Duration};
+use std::{collections::VecDeque, sync::mpsc, thread, time::Duration};
 
 use crate::{
     atom_type::AtomType,
     atomic::AtomicPotentialTrait,
-    output::{Operatable, Operation, OutputSpec, Value},
+    atoms::Atoms,
+    container::Container,
+    output::{write_restart_file, Dump, Operatable, Operation, OutputSpec, Value},
     parallel::{Worker, M2W, W2M},
     simulation::Simulation,
 };
@@ -19,6 +21,8 @@ pub struct Jmd<T: AtomType, A: AtomicPotentialTrait<T>> {
     rx: mpsc::Receiver<W2M<T>>,
     tx: mpsc::Sender<W2M<T>>,
     threads: Vec<ThreadContainer<T, A>>,
+    deferred: VecDeque<W2M<T>>,
+    dumps: Vec<Dump>,
 }
 impl<T, A> Jmd<T, A>
 where
@@ -31,6 +35,8 @@ where
             rx,
             tx,
             threads: Vec::new(),
+            deferred: VecDeque::new(),
+            dumps: Vec::new(),
         }
     }
     pub fn run(&mut self, num_threads: usize, f: fn(Simulation<T, A>) -> ()) {
@@ -39,6 +45,7 @@ where
             self.send(t, M2W::Run(f));
         }
         self.manage_comm();
+        self.dumps.clear();
     }
 
     fn setup(&mut self, num_threads: usize) {
@@ -67,19 +74,39 @@ where
     fn recv(&self) -> W2M<T> {
         self.rx.recv().expect("Disconnect error")
     }
+    /// Receive the next message matching the predicate. Any other messages received
+    /// in the meantime are deferred, to be handled later by `manage_comm`, except for
+    /// senders exchanged between neighboring processes, which are forwarded right away
+    /// since processes that are still connecting wait on them before taking part in any
+    /// reduction.
+    fn recv_matching(&mut self, predicate: impl Fn(&W2M<T>) -> bool) -> W2M<T> {
+        if let Some(i) = self.deferred.iter().position(&predicate) {
+            return self.deferred.remove(i).expect("Index should be valid");
+        }
+        loop {
+            let message = self.recv();
+            if predicate(&message) {
+                return message;
+            }
+            match message {
+                W2M::Sender(tx, idx, direction) => self.send(idx, M2W::Sender(tx, direction)),
+                message => self.deferred.push_back(message),
+            }
+        }
+    }
     fn send(&self, thread_idx: usize, msg: M2W<T, A>) {
         self.threads[thread_idx]
             .tx
             .send(msg)
             .expect("Disconnect error");
     }
-    fn initial_output(&self, output_spec: &Vec<OutputSpec>) {
+    fn initial_output(&self, output_spec: &[OutputSpec]) {
         for spec in output_spec {
             print!("{}\t", spec)
         }
         println!();
     }
-    fn output(&self, id: thread::ThreadId, value: Value, output_spec: &Vec<OutputSpec>) {
+    fn output(&mut self, id: thread::ThreadId, value: Value, output_spec: &[OutputSpec]) {
         let num_messages_expected = self.threads.len() * output_spec.len();
         let mut num_messages_per_thread: Vec<usize> = Vec::new();
         num_messages_per_thread.resize(self.threads.len(), 0);
@@ -93,18 +120,15 @@ where
             .expect("Invalid thread id");
         values_per_thread[idx].push(value);
         for _i in 1..num_messages_expected {
-            let message = self.recv();
-            match message {
-                W2M::Output(id, value) => {
-                    let idx = self
-                        .threads
-                        .iter()
-                        .position(|t| t.id == id)
-                        .expect("Invalid thread id");
-                    values_per_thread[idx].push(value);
-                }
-                _ => {}
-            };
+            let message = self.recv_matching(|m| matches!(m, W2M::Output(_, _)));
+            if let W2M::Output(id, value) = message {
+                let idx = self
+                    .threads
+                    .iter()
+                    .position(|t| t.id == id)
+                    .expect("Invalid thread id");
+                values_per_thread[idx].push(value);
+            }
         }
 
         let values: Vec<Value> = output_spec
@@ -130,44 +154,135 @@ where
         }
         println!();
     }
-    fn sum(&self, mut value: usize) {
+    /// Gather the atoms of a dump frame from every thread and write them
+    fn dump(&mut self, idx: usize, step: usize, atoms: Atoms<T>, container: Container) {
+        let mut atoms_per_proc = vec![atoms];
+        for _ in 1..self.threads.len() {
+            let message = self
+                .recv_matching(|m| matches!(m, W2M::Dump(i, s, _, _) if *i == idx && *s == step));
+            if let W2M::Dump(_, _, atoms, _) = message {
+                atoms_per_proc.push(atoms);
+            }
+        }
+        // The dump is set up by the first process, whose setup message may have been
+        // deferred while waiting on the frames
+        while self.dumps.len() <= idx {
+            let message = self.recv_matching(|m| matches!(m, W2M::SetupDump(_)));
+            if let W2M::SetupDump(spec) = message {
+                self.dumps.push(Dump::new(spec));
+            }
+        }
+        self.dumps[idx].write_frame(step, &atoms_per_proc, &container);
+    }
+    /// Gather the atoms of a restart file from every thread and write it
+    fn write_restart(&mut self, path: String, state: Option<Vec<u8>>, atoms: Atoms<T>) {
+        let mut states = vec![state];
+        let mut atoms_per_proc = vec![atoms];
+        for _ in 1..self.threads.len() {
+            let message =
+                self.recv_matching(|m| matches!(m, W2M::WriteRestart(p, _, _) if *p == path));
+            if let W2M::WriteRestart(_, state, atoms) = message {
+                states.push(state);
+                atoms_per_proc.push(atoms);
+            }
+        }
+        let state = states
+            .into_iter()
+            .flatten()
+            .next()
+            .expect("No state sent by the first process");
+        write_restart_file(&path, &state, &atoms_per_proc).expect("Could not write restart file");
+    }
+    fn sum(&mut self, mut value: usize) {
         for _ in 0..self.threads.len() - 1 {
-            let message = self.recv();
-            match message {
-                W2M::Sum(v) => value += v,
-                _ => panic!("Invalid message"),
+            let message = self.recv_matching(|m| matches!(m, W2M::Sum(_)));
+            if let W2M::Sum(v) = message {
+                value += v;
             }
         }
         for t in 0..self.threads.len() {
             self.send(t, M2W::SumResult(value));
         }
     }
+    fn sums(&mut self, mut values: Vec<usize>) {
+        for _ in 0..self.threads.len() - 1 {
+            let message = self.recv_matching(|m| matches!(m, W2M::Sums(_)));
+            if let W2M::Sums(v) = message {
+                values.iter_mut().zip(v).for_each(|(x, y)| *x += y);
+            }
+        }
+        for t in 0..self.threads.len() {
+            self.send(t, M2W::SumsResult(values.clone()));
+        }
+    }
+    fn sum_float(&mut self, mut value: f64) {
+        for _ in 0..self.threads.len() - 1 {
+            let message = self.recv_matching(|m| matches!(m, W2M::SumFloat(_)));
+            if let W2M::SumFloat(v) = message {
+                value += v;
+            }
+        }
+        for t in 0..self.threads.len() {
+            self.send(t, M2W::SumFloatResult(value));
+        }
+    }
+    fn sum_floats(&mut self, mut values: Vec<f64>) {
+        for _ in 0..self.threads.len() - 1 {
+            let message = self.recv_matching(|m| matches!(m, W2M::SumFloats(_)));
+            if let W2M::SumFloats(v) = message {
+                values.iter_mut().zip(v).for_each(|(x, y)| *x += y);
+            }
+        }
+        for t in 0..self.threads.len() {
+            self.send(t, M2W::SumFloatsResult(values.clone()));
+        }
+    }
+    fn max_floats(&mut self, mut values: Vec<f64>) {
+        for _ in 0..self.threads.len() - 1 {
+            let message = self.recv_matching(|m| matches!(m, W2M::MaxFloats(_)));
+            if let W2M::MaxFloats(v) = message {
+                values.iter_mut().zip(v).for_each(|(x, y)| *x = x.max(y));
+            }
+        }
+        for t in 0..self.threads.len() {
+            self.send(t, M2W::MaxFloatsResult(values.clone()));
+        }
+    }
     fn handle_message(
-        &self,
+        &mut self,
         message: W2M<T>,
         threads_complete: &mut usize,
         output_spec: &mut Vec<OutputSpec>,
     ) {
         match message {
             W2M::Complete => *threads_complete += 1,
-            W2M::ProcDims(pd) => {
-                for t in &self.threads {
-                    t.tx.send(M2W::ProcDims(pd.clone())).unwrap();
-                }
-            }
-            W2M::Sender(tx, idx) => self.threads[idx].tx.send(M2W::Sender(tx)).unwrap(),
+            W2M::Sender(tx, idx, direction) => self.threads[idx]
+                .tx
+                .send(M2W::Sender(tx, direction))
+                .unwrap(),
             W2M::SetupOutput(specs) => *output_spec = specs,
+            W2M::SetupDump(spec) => self.dumps.push(Dump::new(spec)),
+            W2M::Dump(idx, step, atoms, container) => self.dump(idx, step, atoms, container),
+            W2M::WriteRestart(path, state, atoms) => self.write_restart(path, state, atoms),
             W2M::Output(id, value) => self.output(id, value, &output_spec),
             W2M::InitialOutput => self.initial_output(output_spec),
+            W2M::Warning(message) => eprintln!("Warning: {}", message),
             W2M::Sum(value) => self.sum(value),
+            W2M::Sums(values) => self.sums(values),
+            W2M::SumFloat(value) => self.sum_float(value),
+            W2M::SumFloats(values) => self.sum_floats(values),
+            W2M::MaxFloats(values) => self.max_floats(values),
             _ => {}
         };
     }
-    fn manage_comm(&self) {
+    fn manage_comm(&mut self) {
         let mut threads_complete = 0usize;
         let mut output_spec: Vec<OutputSpec> = Vec::new();
         loop {
-            let result = self.rx.recv_timeout(Duration::from_millis(200));
+            let result = match self.deferred.pop_front() {
+                Some(message) => Ok(message),
+                None => self.rx.recv_timeout(Duration::from_millis(200)),
+            };
             match result {
                 Ok(message) => {
                     self.handle_message(message, &mut threads_complete, &mut output_spec)
@@ -178,9 +293,17 @@ where
             if threads_complete == self.threads.len() {
                 return;
             }
-            for t in &self.threads {
-                if t.handle.is_finished() {
-                    panic!("Thread finished without message");
+            // A finished thread should have sent its completion message, which may still be
+            // waiting in the channel
+            let num_finished = self
+                .threads
+                .iter()
+                .filter(|t| t.handle.is_finished())
+                .count();
+            if num_finished > threads_complete && self.deferred.is_empty() {
+                match self.rx.try_recv() {
+                    Ok(message) => self.deferred.push_back(message),
+                    Err(_) => panic!("Thread finished without message"),
                 }
             }
         }
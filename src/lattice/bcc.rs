@@ -0,0 +1,42 @@
+use super::*;
+
+const BASIS: [[f64; 3]; 2] = [[0.0, 0.0, 0.0], [0.5, 0.5, 0.5]];
+
+/// Body-centered cubic lattice, with a conventional cubic cell of 2 atoms
+#[derive(Debug)]
+pub struct BCC {
+    a: f64,
+    types: Vec<usize>,
+}
+impl BCC {
+    pub fn new(a: f64) -> Self {
+        assert!(a > 0.0, "Lattice constant should be positive, found {}", a);
+        Self {
+            a,
+            types: vec![0; BASIS.len()],
+        }
+    }
+    /// The lattice with the given number density of atoms
+    pub fn from_density(rho: f64) -> Self {
+        assert!(rho > 0.0, "Density should be positive, found {}", rho);
+        Self::new((2.0 / rho).cbrt())
+    }
+    /// Set the atom type of each basis atom: the corner, then the body center. For
+    /// example, `vec![0, 1]` gives a B2 structure like CsCl.
+    pub fn with_types(mut self, types: Vec<usize>) -> Self {
+        check_basis_types(&types, BASIS.len());
+        self.types = types;
+        self
+    }
+}
+impl Lattice for BCC {
+    fn cell_lengths(&self) -> [f64; 3] {
+        [self.a, self.a, self.a]
+    }
+    fn basis(&self) -> &[[f64; 3]] {
+        &BASIS
+    }
+    fn basis_types(&self) -> &[usize] {
+        &self.types
+    }
+}
@@ -0,0 +1,52 @@
+use super::*;
+
+const BASIS: [[f64; 3]; 8] = [
+    [0.0, 0.0, 0.0],
+    [0.5, 0.5, 0.0],
+    [0.5, 0.0, 0.5],
+    [0.0, 0.5, 0.5],
+    [0.25, 0.25, 0.25],
+    [0.75, 0.75, 0.25],
+    [0.75, 0.25, 0.75],
+    [0.25, 0.75, 0.75],
+];
+
+/// Diamond cubic lattice, with a conventional cubic cell of 8 atoms
+#[derive(Debug)]
+pub struct Diamond {
+    a: f64,
+    types: Vec<usize>,
+}
+impl Diamond {
+    pub fn new(a: f64) -> Self {
+        assert!(a > 0.0, "Lattice constant should be positive, found {}", a);
+        Self {
+            a,
+            types: vec![0; BASIS.len()],
+        }
+    }
+    /// The lattice with the given number density of atoms
+    pub fn from_density(rho: f64) -> Self {
+        assert!(rho > 0.0, "Density should be positive, found {}", rho);
+        Self::new((8.0 / rho).cbrt())
+    }
+    /// Set the atom type of each basis atom: the four face-centered cubic sites, then the
+    /// four sites a quarter of the body diagonal from them. For example,
+    /// `vec![0, 0, 0, 0, 1, 1, 1, 1]` gives a zincblende structure like GaAs.
+    pub fn with_types(mut self, types: Vec<usize>) -> Self {
+        check_basis_types(&types, BASIS.len());
+        self.types = types;
+        self
+    }
+}
+impl Lattice for Diamond {
+    fn cell_lengths(&self) -> [f64; 3] {
+        [self.a, self.a, self.a]
+    }
+    fn basis(&self) -> &[[f64; 3]] {
+        &BASIS
+    }
+    fn basis_types(&self) -> &[usize] {
+        &self.types
+    }
+}
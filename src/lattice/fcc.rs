@@ -0,0 +1,47 @@
+use super::*;
+
+const BASIS: [[f64; 3]; 4] = [
+    [0.0, 0.0, 0.0],
+    [0.5, 0.5, 0.0],
+    [0.5, 0.0, 0.5],
+    [0.0, 0.5, 0.5],
+];
+
+/// Face-centered cubic lattice, with a conventional cubic cell of 4 atoms
+#[derive(Debug)]
+pub struct FCC {
+    a: f64,
+    types: Vec<usize>,
+}
+impl FCC {
+    pub fn new(a: f64) -> Self {
+        assert!(a > 0.0, "Lattice constant should be positive, found {}", a);
+        Self {
+            a,
+            types: vec![0; BASIS.len()],
+        }
+    }
+    /// The lattice with the given number density of atoms
+    pub fn from_density(rho: f64) -> Self {
+        assert!(rho > 0.0, "Density should be positive, found {}", rho);
+        Self::new((4.0 / rho).cbrt())
+    }
+    /// Set the atom type of each basis atom: the corner, then the centers of the xy, xz
+    /// and yz faces. For example, `vec![1, 0, 0, 0]` gives an L1_2 structure like Cu3Au.
+    pub fn with_types(mut self, types: Vec<usize>) -> Self {
+        check_basis_types(&types, BASIS.len());
+        self.types = types;
+        self
+    }
+}
+impl Lattice for FCC {
+    fn cell_lengths(&self) -> [f64; 3] {
+        [self.a, self.a, self.a]
+    }
+    fn basis(&self) -> &[[f64; 3]] {
+        &BASIS
+    }
+    fn basis_types(&self) -> &[usize] {
+        &self.types
+    }
+}
@@ -0,0 +1,129 @@
+use super::*;
+
+/// Lattice with arbitrary cell vectors and basis atoms, optionally reoriented so that
+/// given lattice directions lie along the axes, e.g., to build surface slabs or grain
+/// boundaries.
+///
+/// ```rust
+/// use jmd::lattice::{General, Lattice, FCC};
+/// use jmd::region::Rect;
+///
+/// // Face-centered cubic lattice with the (111) planes normal to z
+/// let lattice = General::from_lattice(&FCC::new(1.0))
+///     .with_orientation([[1, -1, 0], [1, 1, -2], [1, 1, 1]]);
+/// let rect = Rect::from_lattice(&lattice, [1, 1, 1]);
+/// let (types, coords) = lattice.coords_within_region(&rect, &[0.0, 0.0, 0.0]);
+/// assert_eq!(coords.len(), 24);
+/// ```
+#[derive(Debug)]
+pub struct General {
+    /// The cell vectors before reorientation
+    vectors: [[f64; 3]; 3],
+    /// The lattice directions, in multiples of the cell vectors, along each axis
+    orientation: Option<[[i32; 3]; 3]>,
+    basis: Vec<[f64; 3]>,
+    types: Vec<usize>,
+}
+impl General {
+    /// The lattice with the given cell vectors and basis atoms, given by their fractional
+    /// coordinates along the cell vectors and their types. Until reoriented, the cell
+    /// vectors should lie along x, y and z, since the box would otherwise not be
+    /// periodic, and the cell lengths are their lengths.
+    pub fn new(vectors: [[f64; 3]; 3], basis: Vec<([f64; 3], usize)>) -> Self {
+        assert!(
+            dot(&vectors[0], &cross(&vectors[1], &vectors[2])) > 0.0,
+            "Cell vectors should be linearly independent and right-handed, found {:?}",
+            vectors
+        );
+        assert!(
+            !basis.is_empty(),
+            "Lattice should have at least one basis atom"
+        );
+        let (basis, types) = basis.into_iter().unzip();
+        Self {
+            vectors,
+            orientation: None,
+            basis,
+            types,
+        }
+    }
+    /// A copy of another lattice, which can then be reoriented
+    pub fn from_lattice(lattice: &impl Lattice) -> Self {
+        Self::new(
+            lattice.cell_vectors(),
+            lattice
+                .basis()
+                .iter()
+                .copied()
+                .zip(lattice.basis_types().iter().copied())
+                .collect(),
+        )
+    }
+    /// Rotate the lattice so that the given lattice directions, in multiples of the cell
+    /// vectors, lie along x, y and z. The directions should be orthogonal and
+    /// right-handed, and the lengths of the directions become the cell lengths.
+    pub fn with_orientation(mut self, orientation: [[i32; 3]; 3]) -> Self {
+        let directions = self.directions(&orientation);
+        let lengths = directions.map(|d| dot(&d, &d).sqrt());
+        for (i, j) in [(0, 1), (0, 2), (1, 2)] {
+            assert!(
+                dot(&directions[i], &directions[j]).abs() <= 1e-10 * lengths[i] * lengths[j],
+                "Orientation directions {:?} and {:?} should be orthogonal",
+                orientation[i],
+                orientation[j]
+            );
+        }
+        assert!(
+            dot(&directions[0], &cross(&directions[1], &directions[2])) > 0.0,
+            "Orientation directions {:?} should be right-handed",
+            orientation
+        );
+        self.orientation = Some(orientation);
+        self
+    }
+    /// Set the atom type of each basis atom
+    pub fn with_types(mut self, types: Vec<usize>) -> Self {
+        check_basis_types(&types, self.basis.len());
+        self.types = types;
+        self
+    }
+    /// The given lattice directions as vectors, before reorientation
+    fn directions(&self, orientation: &[[i32; 3]; 3]) -> [[f64; 3]; 3] {
+        orientation
+            .map(|n| [0, 1, 2].map(|d| (0..3).map(|i| n[i] as f64 * self.vectors[i][d]).sum()))
+    }
+}
+impl Lattice for General {
+    fn cell_lengths(&self) -> [f64; 3] {
+        let Some(orientation) = &self.orientation else {
+            let lengths = self.vectors.map(|v| dot(&v, &v).sqrt());
+            for (i, j) in [(0, 1), (0, 2), (1, 0), (1, 2), (2, 0), (2, 1)] {
+                assert!(
+                    self.vectors[i][j].abs() <= 1e-10 * lengths[i],
+                    "Cell vectors {:?} should lie along x, y and z unless the lattice is \
+                     reoriented with orthogonal directions",
+                    self.vectors
+                );
+            }
+            return lengths;
+        };
+        self.directions(orientation).map(|d| dot(&d, &d).sqrt())
+    }
+    fn cell_vectors(&self) -> [[f64; 3]; 3] {
+        let Some(orientation) = &self.orientation else {
+            return self.vectors;
+        };
+        // Rows of the rotation are the unit directions along each axis
+        let rotation = self.directions(orientation).map(|d| {
+            let length = dot(&d, &d).sqrt();
+            d.map(|x| x / length)
+        });
+        self.vectors.map(|v| rotation.map(|r| dot(&r, &v)))
+    }
+    fn basis(&self) -> &[[f64; 3]] {
+        &self.basis
+    }
+    fn basis_types(&self) -> &[usize] {
+        &self.types
+    }
+}
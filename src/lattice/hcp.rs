@@ -0,0 +1,57 @@
+use super::*;
+
+/// Basis of the orthogonal cell with lengths a, sqrt(3) a and c
+const BASIS: [[f64; 3]; 4] = [
+    [0.0, 0.0, 0.0],
+    [0.5, 0.5, 0.0],
+    [0.5, 5.0 / 6.0, 0.5],
+    [0.0, 1.0 / 3.0, 0.5],
+];
+
+/// Hexagonal close-packed lattice, with an orthogonal cell of 4 atoms with lengths a,
+/// sqrt(3) a and c along x, y and z, and the close-packed planes normal to z
+#[derive(Debug)]
+pub struct HCP {
+    a: f64,
+    c: f64,
+    types: Vec<usize>,
+}
+impl HCP {
+    /// The lattice with the ideal ratio c/a = sqrt(8/3)
+    pub fn new(a: f64) -> Self {
+        Self::with_c(a, (8.0f64 / 3.0).sqrt() * a)
+    }
+    pub fn with_c(a: f64, c: f64) -> Self {
+        assert!(a > 0.0, "Lattice constant should be positive, found {}", a);
+        assert!(c > 0.0, "Lattice constant should be positive, found {}", c);
+        Self {
+            a,
+            c,
+            types: vec![0; BASIS.len()],
+        }
+    }
+    /// The lattice with the ideal ratio c/a and the given number density of atoms
+    pub fn from_density(rho: f64) -> Self {
+        assert!(rho > 0.0, "Density should be positive, found {}", rho);
+        // Four atoms in a cell of volume sqrt(8) a^3
+        Self::new((4.0 / (8.0f64.sqrt() * rho)).cbrt())
+    }
+    /// Set the atom type of each basis atom: the two atoms of the lower close-packed
+    /// layer, then the two of the upper one
+    pub fn with_types(mut self, types: Vec<usize>) -> Self {
+        check_basis_types(&types, BASIS.len());
+        self.types = types;
+        self
+    }
+}
+impl Lattice for HCP {
+    fn cell_lengths(&self) -> [f64; 3] {
+        [self.a, 3.0f64.sqrt() * self.a, self.c]
+    }
+    fn basis(&self) -> &[[f64; 3]] {
+        &BASIS
+    }
+    fn basis_types(&self) -> &[usize] {
+        &self.types
+    }
+}
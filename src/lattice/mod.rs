@@ -2,12 +2,258 @@ use crate::region::Region;
 
 mod bcc;
 mod cubic;
+mod diamond;
 mod fcc;
+mod general;
 mod hcp;
 
+pub use bcc::BCC;
 pub use cubic::Cubic;
+pub use diamond::Diamond;
+pub use fcc::FCC;
+pub use general::General;
+pub use hcp::HCP;
 
+/// A crystal lattice, given by the vectors spanning its unit cell and the basis atoms
+/// within it
 pub trait Lattice {
-    fn coords_within_region<R: Region>(&self, region: &R, origin: &[f64; 3]) -> Vec<[f64; 3]>;
+    /// The periodic lengths of the lattice along each axis, which give a box commensurate
+    /// with the lattice when multiplied by whole numbers
     fn cell_lengths(&self) -> [f64; 3];
+    /// The vectors spanning the unit cell, which by default form an orthogonal cell with
+    /// the cell lengths
+    fn cell_vectors(&self) -> [[f64; 3]; 3] {
+        let [lx, ly, lz] = self.cell_lengths();
+        [[lx, 0.0, 0.0], [0.0, ly, 0.0], [0.0, 0.0, lz]]
+    }
+    /// The positions of the basis atoms within the unit cell, as fractions of the cell
+    /// vectors
+    fn basis(&self) -> &[[f64; 3]];
+    /// The atom type of each basis atom
+    fn basis_types(&self) -> &[usize];
+    /// The type and position of all lattice sites within the region, with a lattice point
+    /// at `origin`, as taken by `Simulation::add_atoms`
+    fn coords_within_region<R: Region>(
+        &self,
+        region: &R,
+        origin: &[f64; 3],
+    ) -> (Vec<usize>, Vec<[f64; 3]>) {
+        sites_within_region(self, region, origin)
+            .into_iter()
+            .unzip()
+    }
+    /// The lattice sites within the region of basis atoms of the given type, with a
+    /// lattice point at `origin`
+    fn coords_of_type_within_region<R: Region>(
+        &self,
+        region: &R,
+        origin: &[f64; 3],
+        atom_type: usize,
+    ) -> Vec<[f64; 3]> {
+        sites_within_region(self, region, origin)
+            .into_iter()
+            .filter(|&(t, _)| t == atom_type)
+            .map(|(_, coord)| coord)
+            .collect()
+    }
+}
+
+/// The type and position of each lattice site within the region, found by filling the
+/// bounding box of the region with unit cells aligned to `origin`
+fn sites_within_region<L, R>(lattice: &L, region: &R, origin: &[f64; 3]) -> Vec<(usize, [f64; 3])>
+where
+    L: Lattice + ?Sized,
+    R: Region,
+{
+    let vectors = lattice.cell_vectors();
+    let bounding_box = region.bounding_box();
+    let bblo = bounding_box.lo();
+    let bbhi = bounding_box.hi();
+
+    // Range of cells covering the corners of the bounding box, in fractional coordinates
+    let mut lo = [f64::INFINITY; 3];
+    let mut hi = [f64::NEG_INFINITY; 3];
+    for corner in 0..8 {
+        let p = [0, 1, 2].map(|k| {
+            let bound = if corner >> k & 1 == 0 {
+                bblo[k]
+            } else {
+                bbhi[k]
+            };
+            bound - origin[k]
+        });
+        let f = to_fractional(&vectors, &p);
+        for k in 0..3 {
+            lo[k] = lo[k].min(f[k]);
+            hi[k] = hi[k].max(f[k]);
+        }
+    }
+    let cell_range = |k: usize| lo[k].floor() as i64..hi[k].ceil() as i64;
+    let tolerance = 1e-10 * vectors.iter().map(|v| dot(v, v).sqrt()).fold(0.0, f64::max);
+
+    let mut sites: Vec<(usize, [f64; 3])> = Vec::new();
+    for i in cell_range(0) {
+        for j in cell_range(1) {
+            for k in cell_range(2) {
+                let cell = [i as f64, j as f64, k as f64];
+                for (fraction, &atom_type) in lattice.basis().iter().zip(lattice.basis_types()) {
+                    let f = [0, 1, 2].map(|d| cell[d] + fraction[d]);
+                    let coord = [0, 1, 2].map(|d| {
+                        let x = origin[d]
+                            + f[0] * vectors[0][d]
+                            + f[1] * vectors[1][d]
+                            + f[2] * vectors[2][d];
+                        // Snap sites within rounding error of a side of the bounding box
+                        // onto it, so that each periodic image is kept exactly once
+                        if (x - bblo[d]).abs() < tolerance {
+                            bblo[d]
+                        } else if (x - bbhi[d]).abs() < tolerance {
+                            bbhi[d]
+                        } else {
+                            x
+                        }
+                    });
+                    if region.contains(&coord) {
+                        sites.push((atom_type, coord));
+                    }
+                }
+            }
+        }
+    }
+    sites
+}
+
+fn dot(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn cross(a: &[f64; 3], b: &[f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+/// The coordinates of a vector as fractions of the given cell vectors
+fn to_fractional(vectors: &[[f64; 3]; 3], p: &[f64; 3]) -> [f64; 3] {
+    let [a, b, c] = vectors;
+    let volume = dot(a, &cross(b, c));
+    [
+        dot(p, &cross(b, c)) / volume,
+        dot(p, &cross(c, a)) / volume,
+        dot(p, &cross(a, b)) / volume,
+    ]
+}
+
+/// Check that one type is given per basis atom
+fn check_basis_types(types: &[usize], num_basis: usize) {
+    assert_eq!(
+        types.len(),
+        num_basis,
+        "One atom type should be given per basis atom ({}), found {}",
+        num_basis,
+        types.len()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::region::Rect;
+
+    fn min_distance(coords: &[[f64; 3]]) -> f64 {
+        let mut min = f64::INFINITY;
+        for (i, p) in coords.iter().enumerate() {
+            for q in &coords[i + 1..] {
+                let d = [p[0] - q[0], p[1] - q[1], p[2] - q[2]];
+                min = min.min((d[0] * d[0] + d[1] * d[1] + d[2] * d[2]).sqrt());
+            }
+        }
+        min
+    }
+
+    #[test]
+    fn test_fcc() {
+        let lattice = FCC::from_density(4.0 / 8.0).with_types(vec![1, 0, 0, 0]);
+        let rect = Rect::from_lattice(&lattice, [3, 3, 3]);
+        let (types, coords) = lattice.coords_within_region(&rect, &[0.0, 0.0, 0.0]);
+        assert_eq!(coords.len(), 108);
+        assert_eq!(types.iter().filter(|&&t| t == 1).count(), 27);
+        assert!((min_distance(&coords) - 2.0f64.sqrt()).abs() < 1e-12);
+        let corners = lattice.coords_of_type_within_region(&rect, &[0.0, 0.0, 0.0], 1);
+        assert_eq!(corners.len(), 27);
+    }
+
+    #[test]
+    fn test_origin() {
+        let lattice = BCC::new(2.0);
+        let rect = Rect::new(0.0, 4.0, 0.0, 4.0, 0.0, 4.0);
+        let (_, coords) = lattice.coords_within_region(&rect, &[0.5, 0.0, 0.0]);
+        assert_eq!(coords.len(), 16);
+        assert!(coords.contains(&[0.5, 0.0, 0.0]));
+        assert!(coords.contains(&[1.5, 1.0, 1.0]));
+        assert!(!coords.contains(&[0.0, 0.0, 0.0]));
+    }
+
+    #[test]
+    fn test_hcp_and_diamond_nearest_neighbors() {
+        let hcp = HCP::new(1.5);
+        let rect = Rect::from_lattice(&hcp, [3, 2, 2]);
+        let (_, coords) = hcp.coords_within_region(&rect, &[0.0, 0.0, 0.0]);
+        assert_eq!(coords.len(), 48);
+        assert!((min_distance(&coords) - 1.5).abs() < 1e-12);
+
+        let diamond = Diamond::new(4.0);
+        let rect = Rect::from_lattice(&diamond, [2, 2, 2]);
+        let (_, coords) = diamond.coords_within_region(&rect, &[0.0, 0.0, 0.0]);
+        assert_eq!(coords.len(), 64);
+        assert!((min_distance(&coords) - 3.0f64.sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_general_orientation() {
+        let lattice = General::from_lattice(&FCC::new(1.0)).with_orientation([
+            [1, -1, 0],
+            [1, 1, -2],
+            [1, 1, 1],
+        ]);
+        let lengths = lattice.cell_lengths();
+        assert!((lengths[2] - 3.0f64.sqrt()).abs() < 1e-12);
+        let rect = Rect::from_lattice(&lattice, [2, 1, 2]);
+        let (_, coords) = lattice.coords_within_region(&rect, &[0.0, 0.0, 0.0]);
+        assert_eq!(coords.len(), 96);
+        assert!((min_distance(&coords) - 0.5f64.sqrt()).abs() < 1e-12);
+        // Close-packed planes normal to z, a third of the cell length apart
+        let mut heights: Vec<f64> = coords.iter().map(|p| p[2]).collect();
+        heights.sort_by(f64::total_cmp);
+        heights.dedup_by(|a, b| (*a - *b).abs() < 1e-9);
+        assert_eq!(heights.len(), 6);
+    }
+
+    #[test]
+    fn test_general_hexagonal() {
+        let c = (8.0f64 / 3.0).sqrt();
+        let vectors = [[1.0, 0.0, 0.0], [-0.5, 0.75f64.sqrt(), 0.0], [0.0, 0.0, c]];
+        let basis = vec![([0.0, 0.0, 0.0], 0), ([1.0 / 3.0, 2.0 / 3.0, 0.5], 1)];
+        let lattice =
+            General::new(vectors, basis).with_orientation([[1, 0, 0], [1, 2, 0], [0, 0, 1]]);
+        let rect = Rect::from_lattice(&lattice, [3, 2, 2]);
+        let (types, coords) = lattice.coords_within_region(&rect, &[0.0, 0.0, 0.0]);
+        assert_eq!(coords.len(), 48);
+        assert_eq!(types.iter().filter(|&&t| t == 1).count(), 24);
+        assert!((min_distance(&coords) - 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    #[should_panic(expected = "should lie along x, y and z")]
+    fn test_general_hexagonal_unoriented() {
+        let vectors = [
+            [1.0, 0.0, 0.0],
+            [-0.5, 0.75f64.sqrt(), 0.0],
+            [0.0, 0.0, 1.6],
+        ];
+        let lattice = General::new(vectors, vec![([0.0, 0.0, 0.0], 0)]);
+        Rect::from_lattice(&lattice, [3, 2, 2]);
+    }
 }
@@ -1,4 +1,3 @@
-mod integrators;
 mod jmd;
 mod neighbor;
 mod parallel;
@@ -9,6 +8,8 @@ pub mod atomic;
 pub mod atoms;
 pub mod compute;
 pub mod container;
+pub mod group;
+pub mod integrators;
 pub mod lattice;
 pub mod output;
 pub mod prelude;
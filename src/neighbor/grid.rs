@@ -1,9 +1,12 @@
-use std::rc::Rc;
-
-use crate::{container::Container, region::Rect, utils::Index};
+use crate::{container::Container, utils::Index};
 
 /// Neighbor list grid of bins
 ///
+/// In a triclinic box, atoms are binned by their fractional (lamda) coordinates scaled by
+/// the spacing between faces of the box, so that the bins are sheared with the box and
+/// atoms within a given distance are at most that distance apart along each axis of the
+/// grid.
+///
 /// Should only be accessed by `super::NeighborList`
 #[derive(Debug)]
 pub(super) struct Grid {
@@ -11,10 +14,10 @@ pub(super) struct Grid {
     bin_size: f64,
     neighbor_distance: f64,
     num_bins: [usize; 3],
-    container: Rc<Container>,
+    container: Container,
 }
 impl Grid {
-    pub(super) fn new(container: Rc<Container>, bin_size: f64, neighbor_distance: f64) -> Self {
+    pub(super) fn new(container: &Container, bin_size: f64, neighbor_distance: f64) -> Self {
         assert!(
             bin_size > 0.0,
             "Bin size should be positive, found {}",
@@ -25,50 +28,41 @@ impl Grid {
             "Neighbor distance should be positive, found {}",
             neighbor_distance
         );
-        let rect = container.rect();
-        let min_box_length = rect.lx().min(rect.ly()).min(rect.lz());
-        assert!(
-            bin_size < 0.5 * min_box_length,
-            "Bin size must be less than half the smallest box length, \
-             found bin_size {} and smallest box length {}",
-            bin_size,
-            min_box_length
-        );
-        let buffer = 2.0 * neighbor_distance;
-        let lo_corner = [
-            rect.xlo() - buffer,
-            rect.ylo() - buffer,
-            rect.zlo() - buffer,
-        ];
-        let num_bins: [usize; 3] = [
-            ((rect.lx() + 2.0 * buffer) / bin_size).ceil() as usize,
-            ((rect.ly() + 2.0 * buffer) / bin_size).ceil() as usize,
-            ((rect.lz() + 2.0 * buffer) / bin_size).ceil() as usize,
-        ];
-        Self {
-            lo_corner,
+        let mut grid = Self {
+            lo_corner: [0.0; 3],
             bin_size,
             neighbor_distance,
-            num_bins,
-            container,
-        }
+            num_bins: [0; 3],
+            container: container.clone(),
+        };
+        grid.recompute();
+        grid
     }
-    fn rect(&self) -> &Rect {
-        self.container.rect()
+    /// Update the grid to cover a resized container
+    pub(super) fn set_container(&mut self, container: &Container) {
+        self.container = container.clone();
+        self.recompute();
     }
     /// Recompute the grid based on the updated container or other new values
     fn recompute(&mut self) {
+        let spacings = self.container.face_spacings();
+        let min_box_length = spacings[0].min(spacings[1]).min(spacings[2]);
+        assert!(
+            self.bin_size < 0.5 * min_box_length,
+            "Bin size must be less than half the smallest box length, \
+             found bin_size {} and smallest box length {}",
+            self.bin_size,
+            min_box_length
+        );
         let buffer = 2.0 * self.neighbor_distance;
-        self.lo_corner = [
-            self.rect().xlo() - buffer,
-            self.rect().ylo() - buffer,
-            self.rect().zlo() - buffer,
-        ];
-        self.num_bins = [
-            ((self.rect().lx() + 2.0 * buffer) / self.bin_size).ceil() as usize,
-            ((self.rect().ly() + 2.0 * buffer) / self.bin_size).ceil() as usize,
-            ((self.rect().lz() + 2.0 * buffer) / self.bin_size).ceil() as usize,
-        ];
+        let lo = self.container.rect().lo();
+        self.lo_corner = [0, 1, 2].map(|k| lo[k] - buffer);
+        self.num_bins =
+            [0, 1, 2].map(|k| ((spacings[k] + 2.0 * buffer) / self.bin_size).ceil() as usize);
+    }
+    /// Whether the grid is sheared with a triclinic box
+    pub(super) fn is_triclinic(&self) -> bool {
+        self.container.is_triclinic()
     }
     pub(super) fn bin_size(&self) -> f64 {
         self.bin_size
@@ -110,6 +104,7 @@ impl Grid {
     /// Given a coordinate within the grid (possibly outside the container),
     /// return the corresponding bin index
     pub(super) fn coord_to_index(&self, coord: &[f64; 3]) -> Index {
+        let coord = &self.binning_coord(coord);
         let inds = [
             ((coord[0] - self.lo_corner[0]) / self.bin_size).floor(),
             ((coord[1] - self.lo_corner[1]) / self.bin_size).floor(),
@@ -131,6 +126,16 @@ impl Grid {
             &self.num_bins(),
         )
     }
+    /// The coordinate used for binning, which is the position itself in an orthogonal box
+    fn binning_coord(&self, coord: &[f64; 3]) -> [f64; 3] {
+        if !self.container.is_triclinic() {
+            return *coord;
+        }
+        let lo = self.container.rect().lo();
+        let spacings = self.container.face_spacings();
+        let lamda = self.container.to_lamda(coord);
+        [0, 1, 2].map(|k| lo[k] + lamda[k] * spacings[k])
+    }
 }
 
 #[cfg(test)]
@@ -140,7 +145,7 @@ mod tests {
     use super::*;
     fn setup_grid() -> Grid {
         let container = Container::new(0.0, 10.0, 0.0, 10.0, 0.0, 10.0, BC::PP, BC::PP, BC::PP);
-        Grid::new(Rc::new(container), 2.0, 3.0)
+        Grid::new(&container, 2.0, 3.0)
     }
 
     #[test]
@@ -162,4 +167,21 @@ mod tests {
             [0usize, 0, 0]
         );
     }
+
+    #[test]
+    fn test_triclinic_bins() {
+        let mut container = Container::new(0.0, 10.0, 0.0, 10.0, 0.0, 10.0, BC::PP, BC::PP, BC::PP);
+        container.set_tilt(5.0, 0.0, 0.0);
+        let grid = Grid::new(&container, 2.0, 3.0);
+        // The bins are sheared with the box, so atoms at the same fractional x coordinate
+        // share a column of bins
+        assert_eq!(
+            grid.coord_to_index(&[1.0, 0.0, 1.0]).to_3d(),
+            [3usize, 3, 3]
+        );
+        assert_eq!(
+            grid.coord_to_index(&[6.0, 9.9, 1.0]).to_3d(),
+            [3usize, 7, 3]
+        );
+    }
 }
@@ -1,5 +1,3 @@
-use std::rc::Rc;
-
 use super::Grid;
 use crate::{
     container::Container,
@@ -14,9 +12,11 @@ pub struct NeighborList {
     neighbors: Vec<Vec<usize>>,
     force_distance: f64,
     skin_distance: f64,
+    /// Whether each pair is listed under both atoms, rather than once
+    full: bool,
 }
 impl NeighborList {
-    pub fn new(container: Rc<Container>, force_distance: f64, skin_distance: f64) -> Self {
+    pub fn new(container: &Container, force_distance: f64, skin_distance: f64) -> Self {
         assert!(
             force_distance > 0.0,
             "Force cutoff distance ({}) must be positive",
@@ -29,7 +29,8 @@ impl NeighborList {
         );
         let neighbor_distance = skin_distance + force_distance;
         let bin_size = neighbor_distance * 0.5;
-        let stencil = NeighborList::compute_stencil(bin_size, neighbor_distance);
+        let stencil =
+            NeighborList::compute_stencil(bin_size, neighbor_distance, container.is_triclinic());
         let grid = Grid::new(container, bin_size, neighbor_distance);
         Self {
             grid,
@@ -37,34 +38,23 @@ impl NeighborList {
             neighbors: Vec::new(),
             force_distance,
             skin_distance,
+            full: false,
         }
     }
-    /// Compute a set of integer offsets to a bin index that corresponds
-    /// to populating a half neighbor list
-    fn compute_stencil(bin_size: f64, neighbor_distance: f64) -> Vec<[i32; 3]> {
+    /// Compute the set of integer offsets to a bin index of all bins that may hold atoms
+    /// within the neighbor distance of an atom in the center bin. The bins of a triclinic
+    /// box are sheared, so the full cube of bins is used.
+    fn compute_stencil(bin_size: f64, neighbor_distance: f64, triclinic: bool) -> Vec<[i32; 3]> {
         let max_number_out = (neighbor_distance / bin_size).ceil() as i32;
         let mut stencil: Vec<[i32; 3]> = Vec::new();
-        for i in 0..max_number_out + 1 {
-            stencil.push([i, 0, 0]);
-        }
-        for i in -max_number_out..max_number_out + 1 {
-            for j in 1..max_number_out + 1 {
-                let i2 = (i.abs() - 1).max(0);
-                let j2 = (j.abs() - 1).max(0);
-                let min_dist = ((i2 * i2 + j2 * j2) as f64).sqrt();
-                if min_dist < neighbor_distance {
-                    stencil.push([i, j, 0]);
-                }
-            }
-        }
         for i in -max_number_out..max_number_out + 1 {
             for j in -max_number_out..max_number_out + 1 {
-                for k in 1..max_number_out + 1 {
+                for k in -max_number_out..max_number_out + 1 {
                     let i2 = (i.abs() - 1).max(0);
                     let j2 = (j.abs() - 1).max(0);
                     let k2 = (k.abs() - 1).max(0);
-                    let min_dist = ((i2 * i2 + j2 * j2 + k2 * k2) as f64).sqrt();
-                    if min_dist < neighbor_distance {
+                    let min_dist = ((i2 * i2 + j2 * j2 + k2 * k2) as f64).sqrt() * bin_size;
+                    if triclinic || min_dist < neighbor_distance {
                         stencil.push([i, j, k]);
                     }
                 }
@@ -89,11 +79,14 @@ impl NeighborList {
     pub fn is_built(&self) -> bool {
         !self.neighbors.is_empty()
     }
+    pub fn is_full(&self) -> bool {
+        self.full
+    }
 
     // Setters
     pub fn set_bin_size(&mut self, bin_size: f64) {
         self.grid.set_bin_size(bin_size);
-        self.stencil = NeighborList::compute_stencil(bin_size, self.max_neighbor_distance());
+        self.reset_stencil();
     }
     pub fn set_skin_distance(&mut self, skin_distance: f64) {
         if skin_distance <= 0.0 {
@@ -101,22 +94,50 @@ impl NeighborList {
         }
         self.skin_distance = skin_distance;
         self.neighbors.clear();
-        let bin_size = self.grid.bin_size();
         self.grid
             .set_neighbor_distance(self.max_neighbor_distance());
-        self.stencil = NeighborList::compute_stencil(bin_size, self.max_neighbor_distance());
+        self.reset_stencil();
+    }
+    /// Update the binning grid after the container has been resized. The neighbors
+    /// themselves are kept until the next update.
+    pub(crate) fn set_container(&mut self, container: &Container) {
+        self.grid.set_container(container);
+        self.reset_stencil();
+    }
+    fn reset_stencil(&mut self) {
+        self.stencil = NeighborList::compute_stencil(
+            self.grid.bin_size(),
+            self.max_neighbor_distance(),
+            self.grid.is_triclinic(),
+        );
+    }
+    /// Set whether to build full lists, as needed by many-body potentials
+    pub(crate) fn set_full(&mut self, full: bool) {
+        if self.full != full {
+            self.full = full;
+            self.neighbors.clear();
+        }
     }
     pub(crate) fn set_force_distance(&mut self, force_distance: f64) {
         self.force_distance = force_distance;
         self.neighbors.clear();
-        let bin_size = self.grid.bin_size();
         self.grid
             .set_neighbor_distance(self.max_neighbor_distance());
-        self.stencil = NeighborList::compute_stencil(bin_size, self.max_neighbor_distance());
+        self.reset_stencil();
     }
 
-    /// Update the neighbor list based on the positions of the owned and ghost atoms in the current process
-    pub fn update(&mut self, positions: &Vec<[f64; 3]>) {
+    /// Update the neighbor list based on the positions of the owned and ghost atoms in the
+    /// current process, of which the first `nlocal` are owned.
+    ///
+    /// This is a half list over the owned atoms: each pair of owned atoms is listed once,
+    /// under the lower index. A pair of an owned and a ghost atom is listed only if the
+    /// ghost atom is above the owned atom (by z, then y, then x), so that the process
+    /// owning the ghost atom, which sees the pair mirrored, does not list it as well.
+    /// Ghost atoms have no neighbors.
+    ///
+    /// A full list instead lists every owned or ghost atom within the neighbor distance of
+    /// an owned atom under it, so that pairs of owned atoms are listed under both atoms.
+    pub fn update(&mut self, positions: &Vec<[f64; 3]>, nlocal: usize) {
         let num_atoms = positions.len();
 
         self.neighbors.clear();
@@ -124,27 +145,37 @@ impl NeighborList {
         let neigh_dist_sq = self.max_neighbor_distance() * self.max_neighbor_distance();
 
         let atom_indices_per_bin = self.bin_atoms(&positions);
-        positions.iter().enumerate().for_each(|(i, pos)| {
-            let bin_idx = self.grid.coord_to_index(pos);
-            let bin_3d = bin_idx.to_3d();
-            for offset in &self.stencil {
-                let comp_bin = Index::from_3d(
-                    &[
-                        (offset[0] + bin_3d[0] as i32) as usize,
-                        (offset[1] + bin_3d[1] as i32) as usize,
-                        (offset[2] + bin_3d[2] as i32) as usize,
-                    ],
-                    &self.grid.num_bins(),
-                );
-                for &neigh_idx in &atom_indices_per_bin[comp_bin.idx()] {
-                    if distance_squared(&positions[neigh_idx], pos) < neigh_dist_sq
-                        && neigh_idx != i
-                    {
-                        self.neighbors[i].push(neigh_idx);
+        positions
+            .iter()
+            .take(nlocal)
+            .enumerate()
+            .for_each(|(i, pos)| {
+                let bin_idx = self.grid.coord_to_index(pos);
+                let bin_3d = bin_idx.to_3d();
+                for offset in &self.stencil {
+                    let comp_bin = Index::from_3d(
+                        &[
+                            (offset[0] + bin_3d[0] as i32) as usize,
+                            (offset[1] + bin_3d[1] as i32) as usize,
+                            (offset[2] + bin_3d[2] as i32) as usize,
+                        ],
+                        &self.grid.num_bins(),
+                    );
+                    for &neigh_idx in &atom_indices_per_bin[comp_bin.idx()] {
+                        let neigh_pos = &positions[neigh_idx];
+                        let include = if self.full {
+                            neigh_idx != i
+                        } else if neigh_idx < nlocal {
+                            neigh_idx > i
+                        } else {
+                            [neigh_pos[2], neigh_pos[1], neigh_pos[0]] > [pos[2], pos[1], pos[0]]
+                        };
+                        if include && distance_squared(neigh_pos, pos) < neigh_dist_sq {
+                            self.neighbors[i].push(neigh_idx);
+                        }
                     }
                 }
-            }
-        });
+            });
     }
     /// Assign each atom to a bin in the grid based on its position
     fn bin_atoms(&self, positions: &Vec<[f64; 3]>) -> Vec<Vec<usize>> {
@@ -166,29 +197,40 @@ mod tests {
 
     fn setup_nl() -> NeighborList {
         let container = Container::new(0.0, 10.0, 0.0, 10.0, 0.0, 10.0, BC::PP, BC::PP, BC::PP);
-        NeighborList::new(Rc::new(container), 2.0, 1.0)
+        NeighborList::new(&container, 2.0, 1.0)
     }
 
     #[test]
     fn test_single_atom() {
         let mut nl = setup_nl();
-        nl.update(&vec![[1.0, 1.0, 1.0]]);
+        nl.update(&vec![[1.0, 1.0, 1.0]], 1);
         assert_eq!(nl.neighbors()[0], vec![]);
     }
 
     #[test]
     fn test_two_atoms() {
         let mut nl = setup_nl();
-        nl.update(&vec![[1.0, 1.0, 1.0], [1.0, 1.0, 2.0]]);
+        nl.update(&vec![[1.0, 1.0, 1.0], [1.0, 1.0, 2.0]], 2);
         let neighbors = nl.neighbors();
         assert_eq!(neighbors[0], vec![1]);
         assert_eq!(neighbors[1], vec![]); // half neighbor list
     }
 
+    #[test]
+    fn test_two_atoms_full() {
+        let mut nl = setup_nl();
+        nl.set_full(true);
+        nl.update(&vec![[1.0, 1.0, 1.0], [1.0, 1.0, 2.0], [1.0, 1.0, 4.5]], 2);
+        let neighbors = nl.neighbors();
+        assert_eq!(neighbors[0], vec![1]);
+        assert_eq!(neighbors[1], vec![0, 2]);
+        assert_eq!(neighbors[2], vec![]); // ghost atoms have no neighbors
+    }
+
     #[test]
     fn test_two_atoms_far() {
         let mut nl = setup_nl();
-        nl.update(&vec![[1.0, 1.0, 1.0], [1.0, 1.0, 9.0]]);
+        nl.update(&vec![[1.0, 1.0, 1.0], [1.0, 1.0, 9.0]], 2);
         let neighbors = nl.neighbors();
         assert_eq!(neighbors[0], vec![]);
         assert_eq!(neighbors[1], vec![]);
@@ -197,6 +239,7 @@ mod tests {
     #[test]
     fn test_four_atoms() {
         let mut nl = setup_nl();
+        nl.set_bin_size(2.0);
         let pos = vec![
             [1.0, 1.0, 1.0],
             [1.0, 1.0, 9.0],
@@ -205,7 +248,7 @@ mod tests {
         ];
         dbg!(&nl.grid);
 
-        nl.update(&pos);
+        nl.update(&pos, 4);
         let neighbors = nl.neighbors();
         assert_eq!(neighbors[0], vec![2]);
         assert_eq!(neighbors[1], vec![3]);
@@ -222,8 +265,8 @@ mod tests {
         let occupied_bins = vec![
             (3usize * 121 + 3 * 11 + 3, 0usize),
             (3usize * 121 + 3 * 11 + 7, 1usize),
-            (3usize * 121 + 3 * 11 + 3, 2usize),
-            (3usize * 121 + 3 * 11 + 7, 3usize),
+            (3usize * 121 + 4 * 11 + 3, 2usize),
+            (3usize * 121 + 4 * 11 + 7, 3usize),
         ];
         bins.iter().enumerate().for_each(|(i, b)| {
             let res = occupied_bins.iter().find(|(j, _idx)| i == *j);
@@ -234,4 +277,15 @@ mod tests {
             assert_eq!(v, *b);
         });
     }
+
+    #[test]
+    fn test_ghost_pairs_listed_once() {
+        let mut nl = setup_nl();
+        // One owned atom with a ghost atom above it and one below it
+        nl.update(&vec![[5.0, 5.0, 5.0], [5.0, 5.0, 6.0], [5.0, 5.5, 4.0]], 1);
+        let neighbors = nl.neighbors();
+        assert_eq!(neighbors[0], vec![1]);
+        assert_eq!(neighbors[1], vec![]);
+        assert_eq!(neighbors[2], vec![]);
+    }
 }
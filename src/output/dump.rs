@@ -0,0 +1,246 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+use crate::{
+    atom_type::AtomType,
+    atoms::Atoms,
+    container::{Container, BC},
+    traits::Named,
+    utils::Axis,
+};
+
+use super::extxyz;
+
+/// Per-atom quantities that can be written to a dump file
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DumpColumn {
+    /// The atom id, counted from 1 as in LAMMPS
+    Id,
+    /// The atom type, counted from 1 as in LAMMPS
+    Type,
+    X,
+    Y,
+    Z,
+    Vx,
+    Vy,
+    Vz,
+    Q,
+}
+impl DumpColumn {
+    /// Find the column with the given name, as written in the header of the dump file
+    pub fn from_name(name: &str) -> Option<Self> {
+        [
+            DumpColumn::Id,
+            DumpColumn::Type,
+            DumpColumn::X,
+            DumpColumn::Y,
+            DumpColumn::Z,
+            DumpColumn::Vx,
+            DumpColumn::Vy,
+            DumpColumn::Vz,
+            DumpColumn::Q,
+        ]
+        .into_iter()
+        .find(|c| c.name() == name)
+    }
+}
+impl Named for DumpColumn {
+    fn name(&self) -> &str {
+        match self {
+            DumpColumn::Id => "id",
+            DumpColumn::Type => "type",
+            DumpColumn::X => "x",
+            DumpColumn::Y => "y",
+            DumpColumn::Z => "z",
+            DumpColumn::Vx => "vx",
+            DumpColumn::Vy => "vy",
+            DumpColumn::Vz => "vz",
+            DumpColumn::Q => "q",
+        }
+    }
+}
+
+/// File format of a dump
+#[derive(Clone, Debug)]
+pub enum DumpFormat {
+    /// LAMMPS text dump with the given columns
+    Lammps(Vec<DumpColumn>),
+    /// Extended XYZ with the species name of each atom type
+    ExtXyz(Vec<String>),
+}
+
+/// Settings of a dump, sent to the manager when the dump is added
+#[derive(Clone, Debug)]
+pub struct DumpSpec {
+    pub every: usize,
+    pub path: String,
+    pub format: DumpFormat,
+}
+
+/// Trajectory file written by the manager
+pub(crate) struct Dump {
+    format: DumpFormat,
+    writer: BufWriter<File>,
+}
+impl Dump {
+    pub(crate) fn new(spec: DumpSpec) -> Self {
+        let file = File::create(&spec.path).expect("Could not create dump file");
+        Self {
+            format: spec.format,
+            writer: BufWriter::new(file),
+        }
+    }
+
+    /// Write one frame from the owned atoms of every process, sorted by atom id
+    pub(crate) fn write_frame<T: AtomType>(
+        &mut self,
+        step: usize,
+        atoms_per_proc: &[Atoms<T>],
+        container: &Container,
+    ) {
+        let mut rows: Vec<(&Atoms<T>, usize)> = atoms_per_proc
+            .iter()
+            .flat_map(|atoms| (0..atoms.num_local_atoms()).map(move |i| (atoms, i)))
+            .collect();
+        rows.sort_by_key(|(atoms, i)| atoms.ids[*i]);
+
+        match &self.format {
+            DumpFormat::Lammps(columns) => {
+                write_lammps_frame(&mut self.writer, step, &rows, container, columns)
+            }
+            DumpFormat::ExtXyz(species) => {
+                extxyz::write_frame(&mut self.writer, step, &rows, container, species)
+            }
+        }
+        .and_then(|_| self.writer.flush())
+        .expect("Could not write to dump file");
+    }
+}
+
+/// Write a frame in the LAMMPS text dump format. Atom ids and types are written 1-based,
+/// as in LAMMPS and as read back by `read_lammps_data`.
+fn write_lammps_frame<W: Write, T: AtomType>(
+    writer: &mut W,
+    step: usize,
+    rows: &[(&Atoms<T>, usize)],
+    container: &Container,
+    columns: &[DumpColumn],
+) -> std::io::Result<()> {
+    writeln!(writer, "ITEM: TIMESTEP\n{}", step)?;
+    writeln!(writer, "ITEM: NUMBER OF ATOMS\n{}", rows.len())?;
+    let axes = [Axis::X, Axis::Y, Axis::Z];
+    let bcs: Vec<&str> = axes
+        .iter()
+        .map(|&axis| bc_name(container.boundary_condition(axis)))
+        .collect();
+    if container.is_triclinic() {
+        // Bounds of the bounding box of the triclinic box, followed by the tilt factors
+        let [xy, xz, yz] = container.tilt();
+        let x_tilts = [0.0, xy, xz, xy + xz];
+        let lo_offsets = [
+            x_tilts.into_iter().fold(f64::INFINITY, f64::min),
+            yz.min(0.0),
+            0.0,
+        ];
+        let hi_offsets = [
+            x_tilts.into_iter().fold(f64::NEG_INFINITY, f64::max),
+            yz.max(0.0),
+            0.0,
+        ];
+        writeln!(writer, "ITEM: BOX BOUNDS xy xz yz {}", bcs.join(" "))?;
+        for (k, axis) in axes.into_iter().enumerate() {
+            let [lo, hi] = container.rect().get_bounds(axis);
+            writeln!(
+                writer,
+                "{} {} {}",
+                lo + lo_offsets[k],
+                hi + hi_offsets[k],
+                container.tilt()[k]
+            )?;
+        }
+    } else {
+        writeln!(writer, "ITEM: BOX BOUNDS {}", bcs.join(" "))?;
+        for axis in axes {
+            let [lo, hi] = container.rect().get_bounds(axis);
+            writeln!(writer, "{} {}", lo, hi)?;
+        }
+    }
+    let names: Vec<&str> = columns.iter().map(|c| c.name()).collect();
+    writeln!(writer, "ITEM: ATOMS {}", names.join(" "))?;
+
+    for (atoms, i) in rows {
+        let values: Vec<String> = columns.iter().map(|c| column_value(c, atoms, *i)).collect();
+        writeln!(writer, "{}", values.join(" "))?;
+    }
+    Ok(())
+}
+
+fn column_value<T: AtomType>(column: &DumpColumn, atoms: &Atoms<T>, i: usize) -> String {
+    match column {
+        DumpColumn::Id => (atoms.ids[i] + 1).to_string(),
+        DumpColumn::Type => (atoms.types[i] + 1).to_string(),
+        DumpColumn::X => atoms.positions[i][0].to_string(),
+        DumpColumn::Y => atoms.positions[i][1].to_string(),
+        DumpColumn::Z => atoms.positions[i][2].to_string(),
+        DumpColumn::Vx => atoms.velocities[i][0].to_string(),
+        DumpColumn::Vy => atoms.velocities[i][1].to_string(),
+        DumpColumn::Vz => atoms.velocities[i][2].to_string(),
+        DumpColumn::Q => atoms.charges[i].to_string(),
+    }
+}
+
+/// The boundary condition as written in LAMMPS dump files
+fn bc_name(bc: &BC) -> &'static str {
+    match bc {
+        BC::PP => "pp",
+        BC::FF => "ff",
+        BC::FM => "fm",
+        BC::FS => "fs",
+        BC::MF => "mf",
+        BC::MM => "mm",
+        BC::MS => "ms",
+        BC::SF => "sf",
+        BC::SM => "sm",
+        BC::SS => "ss",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atom_type::Basic;
+
+    #[test]
+    fn test_frame_sorted_by_id() {
+        let mut atoms0: Atoms<Basic> = Atoms::new();
+        atoms0.ids = vec![2, 0];
+        atoms0.types = vec![1, 0];
+        atoms0.positions = vec![[2.0, 0.0, 0.0], [0.5, 0.0, 0.0]];
+        atoms0.velocities = vec![[0.0; 3]; 2];
+        atoms0.nlocal = 2;
+        let mut atoms1: Atoms<Basic> = Atoms::new();
+        atoms1.ids = vec![1];
+        atoms1.types = vec![0];
+        atoms1.positions = vec![[1.0, 0.0, 0.0]];
+        atoms1.velocities = vec![[0.0; 3]];
+        atoms1.nlocal = 1;
+
+        let path = std::env::temp_dir().join("jmd_test_frame_sorted_by_id.dump");
+        let container = Container::new(0.0, 3.0, 0.0, 1.0, 0.0, 1.0, BC::PP, BC::FF, BC::SS);
+        let mut dump = Dump::new(DumpSpec {
+            every: 1,
+            path: path.to_string_lossy().into_owned(),
+            format: DumpFormat::Lammps(vec![DumpColumn::Id, DumpColumn::Type, DumpColumn::X]),
+        });
+        dump.write_frame(5, &[atoms0, atoms1], &container);
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let expected = "ITEM: TIMESTEP\n5\nITEM: NUMBER OF ATOMS\n3\n\
+            ITEM: BOX BOUNDS pp ff ss\n0 3\n0 1\n0 1\nITEM: ATOMS id type x\n\
+            1 1 0.5\n2 1 1\n3 2 2\n";
+        assert_eq!(contents, expected);
+    }
+}
@@ -0,0 +1,286 @@
+use std::{fs, io::Write};
+
+use crate::{
+    atom_type::AtomType,
+    atoms::Atoms,
+    container::{Container, BC},
+    utils::Axis,
+};
+
+/// Atoms and simulation box read from a frame of an extended XYZ file
+#[derive(Debug)]
+pub struct ExtXyzFrame {
+    pub container: Container,
+    pub types: Vec<usize>,
+    pub positions: Vec<[f64; 3]>,
+    pub velocities: Option<Vec<[f64; 3]>>,
+}
+impl ExtXyzFrame {
+    /// The positions of the atoms of the given type
+    pub fn coords_of_type(&self, atom_type: usize) -> Vec<[f64; 3]> {
+        self.types
+            .iter()
+            .zip(self.positions.iter())
+            .filter_map(|(&t, &p)| if t == atom_type { Some(p) } else { None })
+            .collect()
+    }
+}
+
+/// Read the first frame of an extended XYZ file, such as those written by ASE, with the
+/// type of each atom given by the index of its species name in `species`.
+///
+/// The first `Lattice` vector should be along x and the second in the xy plane, as in a
+/// LAMMPS triclinic box. `Origin` defaults to zero and `pbc` to periodic along all axes,
+/// with non-periodic axes given fixed boundaries.
+/// Velocities are read from the `velo` property if present.
+///
+/// ```rust,no_run
+/// use jmd::{atom_type::Basic, atomic::LJCut, output::read_extxyz, prelude::*};
+///
+/// fn run(mut sim: Simulation<Basic, LJCut>) {
+///     let frame = read_extxyz("start.xyz", &["Ar"]);
+///     sim.set_container(frame.container.clone());
+///     sim.add_atoms(frame.types.clone(), frame.positions.clone());
+/// }
+/// ```
+pub fn read_extxyz(path: &str, species: &[&str]) -> ExtXyzFrame {
+    let contents = fs::read_to_string(path).expect("Could not read extended XYZ file");
+    parse_frame(&contents, species)
+}
+
+fn parse_frame(contents: &str, species: &[&str]) -> ExtXyzFrame {
+    let mut lines = contents.lines();
+    let num_atoms: usize = lines
+        .next()
+        .and_then(|line| line.trim().parse().ok())
+        .expect("First line of extended XYZ file should be the number of atoms");
+    let info = parse_comment(lines.next().expect("Missing comment line"));
+    let get = |key: &str| {
+        info.iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    };
+
+    let lattice = parse_floats(get("Lattice").expect("Missing Lattice in comment line"));
+    assert_eq!(lattice.len(), 9, "Lattice should have 9 values");
+    assert!(
+        [1, 2, 5].iter().all(|&i| lattice[i] == 0.0),
+        "Only lattice vectors in the LAMMPS orientation (a along x, b in the xy plane) are \
+         supported, found {:?}",
+        lattice
+    );
+    let origin = get("Origin").map_or(vec![0.0; 3], parse_floats);
+    assert_eq!(origin.len(), 3, "Origin should have 3 values");
+    let pbc: Vec<bool> = get("pbc").map_or(vec![true; 3], |v| {
+        v.split_whitespace()
+            .map(|b| matches!(b, "T" | "True" | "true" | "1"))
+            .collect()
+    });
+    assert_eq!(pbc.len(), 3, "pbc should have 3 values");
+    let bc = |periodic: bool| if periodic { BC::PP } else { BC::FF };
+    let mut container = Container::new(
+        origin[0],
+        origin[0] + lattice[0],
+        origin[1],
+        origin[1] + lattice[4],
+        origin[2],
+        origin[2] + lattice[8],
+        bc(pbc[0]),
+        bc(pbc[1]),
+        bc(pbc[2]),
+    );
+    container.set_tilt(lattice[3], lattice[6], lattice[7]);
+
+    let properties = get("Properties").unwrap_or("species:S:1:pos:R:3");
+    let columns = parse_properties(properties);
+    let column = |name: &str| columns.iter().find(|(n, _)| n == name).map(|(_, c)| *c);
+    let species_col = column("species").expect("Missing species property");
+    let pos_col = column("pos").expect("Missing pos property");
+    let velo_col = column("velo");
+
+    let mut types = Vec::with_capacity(num_atoms);
+    let mut positions = Vec::with_capacity(num_atoms);
+    let mut velocities = Vec::with_capacity(num_atoms);
+    for _ in 0..num_atoms {
+        let line = lines
+            .next()
+            .expect("Fewer atom lines than the number of atoms");
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let name = fields[species_col];
+        let atom_type = species
+            .iter()
+            .position(|s| *s == name)
+            .unwrap_or_else(|| panic!("Unknown species {}", name));
+        types.push(atom_type);
+        positions.push(parse_vector(&fields[pos_col..pos_col + 3]));
+        if let Some(c) = velo_col {
+            velocities.push(parse_vector(&fields[c..c + 3]));
+        }
+    }
+
+    ExtXyzFrame {
+        container,
+        types,
+        positions,
+        velocities: velo_col.map(|_| velocities),
+    }
+}
+
+/// Split the comment line into key-value pairs, where values may be quoted
+fn parse_comment(line: &str) -> Vec<(String, String)> {
+    let mut pairs = Vec::new();
+    let mut chars = line.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let key: String =
+            std::iter::from_fn(|| chars.next_if(|c| *c != '=' && !c.is_whitespace())).collect();
+        if key.is_empty() {
+            return pairs;
+        }
+        let value: String = if chars.next_if_eq(&'=').is_none() {
+            String::from("T")
+        } else if chars.next_if_eq(&'"').is_some() {
+            let value = std::iter::from_fn(|| chars.next_if(|c| *c != '"')).collect();
+            chars.next();
+            value
+        } else {
+            std::iter::from_fn(|| chars.next_if(|c| !c.is_whitespace())).collect()
+        };
+        pairs.push((key, value));
+    }
+}
+
+/// The name and first column of each property, from a `Properties` value such as
+/// `species:S:1:pos:R:3`
+fn parse_properties(properties: &str) -> Vec<(String, usize)> {
+    let fields: Vec<&str> = properties.split(':').collect();
+    assert!(
+        fields.len().is_multiple_of(3),
+        "Invalid Properties {}",
+        properties
+    );
+    let mut column = 0;
+    fields
+        .chunks(3)
+        .map(|chunk| {
+            let count: usize = chunk[2].parse().expect("Invalid property count");
+            column += count;
+            (String::from(chunk[0]), column - count)
+        })
+        .collect()
+}
+
+fn parse_floats(value: &str) -> Vec<f64> {
+    value
+        .split_whitespace()
+        .map(|v| v.parse().expect("Invalid number"))
+        .collect()
+}
+
+fn parse_vector(fields: &[&str]) -> [f64; 3] {
+    let values = parse_floats(&fields.join(" "));
+    [values[0], values[1], values[2]]
+}
+
+/// Write a frame in the extended XYZ format, with the box as the lattice and origin
+pub(super) fn write_frame<W: Write, T: AtomType>(
+    writer: &mut W,
+    step: usize,
+    rows: &[(&Atoms<T>, usize)],
+    container: &Container,
+    species: &[String],
+) -> std::io::Result<()> {
+    let rect = container.rect();
+    let lengths = rect.lengths();
+    let lo = rect.lo();
+    let [xy, xz, yz] = container.tilt();
+    let pbc: Vec<&str> = [Axis::X, Axis::Y, Axis::Z]
+        .iter()
+        .map(|&axis| {
+            if container.is_periodic(axis) {
+                "T"
+            } else {
+                "F"
+            }
+        })
+        .collect();
+    writeln!(writer, "{}", rows.len())?;
+    writeln!(
+        writer,
+        "Lattice=\"{} 0 0 {} {} 0 {} {} {}\" Origin=\"{} {} {}\" pbc=\"{}\" \
+         Properties=species:S:1:pos:R:3:velo:R:3:id:I:1 step={}",
+        lengths[0],
+        xy,
+        lengths[1],
+        xz,
+        yz,
+        lengths[2],
+        lo[0],
+        lo[1],
+        lo[2],
+        pbc.join(" "),
+        step
+    )?;
+    for (atoms, i) in rows {
+        let name = species
+            .get(atoms.types[*i])
+            .expect("No species given for atom type");
+        let p = atoms.positions[*i];
+        let v = atoms.velocities[*i];
+        writeln!(
+            writer,
+            "{} {} {} {} {} {} {} {}",
+            name, p[0], p[1], p[2], v[0], v[1], v[2], atoms.ids[*i]
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atom_type::Basic;
+
+    #[test]
+    fn test_read_ase_frame() {
+        let contents = "2\n\
+            Lattice=\"4.0 0.0 0.0 0.0 5.0 0.0 0.0 0.0 6.0\" Properties=species:S:1:pos:R:3:Z:I:1 \
+            energy=-1.5 pbc=\"T T F\"\n\
+            Ar 0.5 1.0 1.5 18\n\
+            Ne 3.0 4.0 5.0 10\n";
+        let frame = parse_frame(contents, &["Ne", "Ar"]);
+        assert_eq!(frame.types, vec![1, 0]);
+        assert_eq!(frame.positions[1], [3.0, 4.0, 5.0]);
+        assert!(frame.velocities.is_none());
+        assert_eq!(frame.container.rect().hi(), [4.0, 5.0, 6.0]);
+        assert!(frame.container.is_periodic(Axis::Y));
+        assert!(!frame.container.is_periodic(Axis::Z));
+        assert_eq!(frame.coords_of_type(0), vec![[3.0, 4.0, 5.0]]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut atoms: Atoms<Basic> = Atoms::new();
+        atoms.ids = vec![0, 1];
+        atoms.types = vec![0, 1];
+        atoms.positions = vec![[-1.0, 0.5, 0.25], [1.5, -0.5, 0.75]];
+        atoms.velocities = vec![[0.1, 0.2, 0.3], [-0.1, -0.2, -0.3]];
+        atoms.nlocal = 2;
+        let rows = vec![(&atoms, 0), (&atoms, 1)];
+        let mut container = Container::new(-2.0, 2.0, -1.0, 1.0, 0.0, 1.0, BC::PP, BC::PP, BC::FF);
+        container.set_tilt(0.5, 0.0, 0.0);
+        let species = vec![String::from("A"), String::from("B")];
+
+        let mut buffer: Vec<u8> = Vec::new();
+        write_frame(&mut buffer, 0, &rows, &container, &species).unwrap();
+        let frame = parse_frame(&String::from_utf8(buffer).unwrap(), &["A", "B"]);
+
+        assert_eq!(frame.types, atoms.types);
+        assert_eq!(frame.positions, atoms.positions);
+        assert_eq!(frame.velocities, Some(atoms.velocities.clone()));
+        assert_eq!(frame.container.rect().lo(), container.rect().lo());
+        assert_eq!(frame.container.rect().hi(), container.rect().hi());
+        assert_eq!(frame.container.tilt(), [0.5, 0.0, 0.0]);
+        assert!(!frame.container.is_periodic(Axis::Z));
+    }
+}
@@ -0,0 +1,215 @@
+use std::fs;
+
+use crate::container::{Container, BC};
+
+/// Box, masses and atoms read from a LAMMPS data file, with ids and types starting from 0,
+/// and charges of zero for atom styles without them
+#[derive(Debug)]
+pub struct LammpsData {
+    pub container: Container,
+    pub masses: Vec<f64>,
+    pub ids: Vec<usize>,
+    pub types: Vec<usize>,
+    pub positions: Vec<[f64; 3]>,
+    pub velocities: Vec<[f64; 3]>,
+    pub charges: Vec<f64>,
+}
+
+/// Read a LAMMPS data file with an orthogonal or triclinic box, a `Masses` section, an `Atoms`
+/// section and an optional `Velocities` section. Other sections are skipped.
+///
+/// The `Atoms` section is read in the style given by its comment (`atomic`, `charge`,
+/// `molecular` or `full`), or `atomic` if none is given, and image flags are ignored.
+/// Charges are read for the `charge` and `full` styles.
+/// Atom ids and types are shifted to start from 0, and the box is periodic along all axes.
+pub fn read_lammps_data(path: &str) -> LammpsData {
+    let contents = fs::read_to_string(path).expect("Could not read LAMMPS data file");
+    parse_data(&contents)
+}
+
+fn parse_data(contents: &str) -> LammpsData {
+    // The first line is always a comment
+    let mut lines = contents.lines().skip(1).peekable();
+
+    let mut num_atoms = 0;
+    let mut num_types = 0;
+    let mut bounds = [[0.0, 0.0]; 3];
+    let mut tilt = [0.0; 3];
+    while let Some(line) = lines.next_if(|l| !is_section_header(l)) {
+        let fields: Vec<&str> = strip_comment(line).split_whitespace().collect();
+        match fields[..] {
+            [n, "atoms"] => num_atoms = parse(n),
+            [n, "atom", "types"] => num_types = parse(n),
+            [lo, hi, "xlo", "xhi"] => bounds[0] = [parse(lo), parse(hi)],
+            [lo, hi, "ylo", "yhi"] => bounds[1] = [parse(lo), parse(hi)],
+            [lo, hi, "zlo", "zhi"] => bounds[2] = [parse(lo), parse(hi)],
+            [xy, xz, yz, "xy", "xz", "yz"] => tilt = [parse(xy), parse(xz), parse(yz)],
+            _ => {}
+        }
+    }
+
+    let mut masses = vec![0.0; num_types];
+    let mut atoms: Vec<(usize, usize, [f64; 3], f64)> = Vec::with_capacity(num_atoms);
+    let mut velocities: Vec<(usize, [f64; 3])> = Vec::new();
+    while let Some(header) = lines.next() {
+        let name = strip_comment(header).trim();
+        let style = header.split_once('#').map(|(_, s)| s.trim());
+        let body = std::iter::from_fn(|| lines.next_if(|l| !is_section_header(l)))
+            .map(|l| strip_comment(l).split_whitespace().collect::<Vec<&str>>())
+            .filter(|fields| !fields.is_empty());
+        match name {
+            "Masses" => body.for_each(|fields| {
+                let t: usize = parse(fields[0]);
+                assert!(
+                    (1..=num_types).contains(&t),
+                    "Invalid atom type {} in Masses",
+                    t
+                );
+                masses[t - 1] = parse(fields[1]);
+            }),
+            "Atoms" => {
+                // Columns of the type, of x and of the charge for each atom style
+                let (type_col, x_col, q_col) = match style.unwrap_or("atomic") {
+                    "atomic" => (1, 2, None),
+                    "charge" => (1, 3, Some(2)),
+                    "molecular" => (2, 3, None),
+                    "full" => (2, 4, Some(3)),
+                    s => panic!("Unsupported atom style {}", s),
+                };
+                atoms.extend(body.map(|fields| {
+                    let t: usize = parse(fields[type_col]);
+                    assert!(
+                        (1..=num_types).contains(&t),
+                        "Invalid atom type {} in Atoms",
+                        t
+                    );
+                    (
+                        parse::<usize>(fields[0]) - 1,
+                        t - 1,
+                        parse_vector(&fields[x_col..x_col + 3]),
+                        q_col.map_or(0.0, |c| parse(fields[c])),
+                    )
+                }))
+            }
+            "Velocities" => velocities.extend(
+                body.map(|fields| (parse::<usize>(fields[0]) - 1, parse_vector(&fields[1..4]))),
+            ),
+            _ => body.for_each(drop),
+        }
+    }
+
+    assert_eq!(
+        atoms.len(),
+        num_atoms,
+        "Number of atoms in the Atoms section differs from the header"
+    );
+    assert!(
+        masses.iter().all(|&m| m > 0.0),
+        "A positive mass should be given for each atom type"
+    );
+    atoms.sort_by_key(|(id, _, _, _)| *id);
+    velocities.sort_by_key(|(id, _)| *id);
+
+    let mut atom_velocities = vec![[0.0; 3]; num_atoms];
+    for (id, velocity) in velocities {
+        let idx = atoms
+            .binary_search_by_key(&id, |(i, _, _, _)| *i)
+            .expect("Velocity given for an unknown atom id");
+        atom_velocities[idx] = velocity;
+    }
+
+    let mut container = Container::new(
+        bounds[0][0],
+        bounds[0][1],
+        bounds[1][0],
+        bounds[1][1],
+        bounds[2][0],
+        bounds[2][1],
+        BC::PP,
+        BC::PP,
+        BC::PP,
+    );
+    container.set_tilt(tilt[0], tilt[1], tilt[2]);
+    LammpsData {
+        container,
+        masses,
+        ids: atoms.iter().map(|(id, _, _, _)| *id).collect(),
+        types: atoms.iter().map(|(_, t, _, _)| *t).collect(),
+        positions: atoms.iter().map(|(_, _, p, _)| *p).collect(),
+        velocities: atom_velocities,
+        charges: atoms.iter().map(|(_, _, _, q)| *q).collect(),
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    line.split('#').next().unwrap_or("")
+}
+
+/// Section headers are the only lines that start with a letter, such as `Atoms` or
+/// `Pair Coeffs`
+fn is_section_header(line: &str) -> bool {
+    strip_comment(line)
+        .trim_start()
+        .starts_with(|c: char| c.is_ascii_alphabetic())
+}
+
+fn parse<F: std::str::FromStr>(field: &str) -> F {
+    field
+        .parse()
+        .unwrap_or_else(|_| panic!("Invalid value {} in LAMMPS data file", field))
+}
+
+fn parse_vector(fields: &[&str]) -> [f64; 3] {
+    [parse(fields[0]), parse(fields[1]), parse(fields[2])]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_data() {
+        let contents = "LAMMPS data file via write_data\n\
+            \n\
+            3 atoms\n\
+            2 atom types\n\
+            \n\
+            0.0 10.0 xlo xhi\n\
+            -5.0 5.0 ylo yhi\n\
+            0.0 20.0 zlo zhi\n\
+            1.0 0.0 -2.5 xy xz yz\n\
+            \n\
+            Masses\n\
+            \n\
+            1 1.0\n\
+            2 39.948 # Ar\n\
+            \n\
+            Pair Coeffs # lj/cut\n\
+            \n\
+            1 1.0 1.0\n\
+            2 1.0 1.0\n\
+            \n\
+            Atoms # charge\n\
+            \n\
+            3 2 0.0 1.0 2.0 3.0 0 0 0\n\
+            1 1 0.5 4.0 -1.0 6.0 0 1 0\n\
+            2 1 -0.5 7.0 0.0 9.0 0 0 0\n\
+            \n\
+            Velocities\n\
+            \n\
+            1 0.1 0.2 0.3\n\
+            3 -0.1 -0.2 -0.3\n";
+        let data = parse_data(contents);
+        assert_eq!(data.masses, vec![1.0, 39.948]);
+        assert_eq!(data.ids, vec![0, 1, 2]);
+        assert_eq!(data.types, vec![0, 0, 1]);
+        assert_eq!(data.positions[0], [4.0, -1.0, 6.0]);
+        assert_eq!(data.positions[2], [1.0, 2.0, 3.0]);
+        assert_eq!(data.velocities[1], [0.0; 3]);
+        assert_eq!(data.velocities[2], [-0.1, -0.2, -0.3]);
+        assert_eq!(data.charges, vec![0.5, -0.5, 0.0]);
+        assert_eq!(data.container.rect().lo(), [0.0, -5.0, 0.0]);
+        assert_eq!(data.container.rect().hi(), [10.0, 5.0, 20.0]);
+        assert_eq!(data.container.tilt(), [1.0, 0.0, -2.5]);
+    }
+}
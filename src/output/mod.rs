@@ -5,6 +5,21 @@ use std::{
 
 use crate::{compute::Compute, traits::Named};
 
+mod dump;
+mod extxyz;
+mod lammps_data;
+mod restart;
+
+pub(crate) use dump::Dump;
+pub use dump::{DumpColumn, DumpFormat, DumpSpec};
+pub use extxyz::{read_extxyz, ExtXyzFrame};
+pub use lammps_data::{read_lammps_data, LammpsData};
+pub(crate) use restart::{
+    invalid_data, read_container, read_restart_atoms, read_restart_header, write_container,
+    write_restart_file,
+};
+pub use restart::{RestartReader, RestartWriter};
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum OutputSpec {
     Step,
@@ -42,22 +57,27 @@ pub enum Value {
     Int(i32),
     Usize(usize),
     Float(f64),
+    /// A symmetric tensor, ordered [xx, yy, zz, xy, xz, yz]
+    Tensor([f64; 6]),
 }
 impl Value {
     pub fn default(&self, op: Operation) -> Self {
         match op {
             Operation::Sum | Operation::First => match self {
                 Value::Float(_) => Value::Float(0.0),
+                Value::Tensor(_) => Value::Tensor([0.0; 6]),
                 Value::Int(_) => Value::Int(0),
                 Value::Usize(_) => Value::Usize(0),
             },
             Operation::Max => match self {
                 Value::Float(_) => Value::Float(f64::MIN),
+                Value::Tensor(_) => Value::Tensor([f64::MIN; 6]),
                 Value::Int(_) => Value::Int(i32::MIN),
                 Value::Usize(_) => Value::Usize(usize::MIN),
             },
             Operation::Min => match self {
                 Value::Float(_) => Value::Float(f64::MAX),
+                Value::Tensor(_) => Value::Tensor([f64::MAX; 6]),
                 Value::Int(_) => Value::Int(i32::MAX),
                 Value::Usize(_) => Value::Usize(usize::MAX),
             },
@@ -66,6 +86,9 @@ impl Value {
     pub fn max(self, other: Self) -> Self {
         match (self, other) {
             (Value::Float(f1), Value::Float(f2)) => Value::Float(f64::max(f1, f2)),
+            (Value::Tensor(t1), Value::Tensor(t2)) => {
+                Value::Tensor(std::array::from_fn(|k| f64::max(t1[k], t2[k])))
+            }
             (Value::Int(i1), Value::Int(i2)) => Value::Int(i1.max(i2)),
             (Value::Usize(u1), Value::Usize(u2)) => Value::Usize(u1.max(u2)),
             _ => panic!("Mismatched types"),
@@ -74,6 +97,9 @@ impl Value {
     pub fn min(self, other: Self) -> Self {
         match (self, other) {
             (Value::Float(f1), Value::Float(f2)) => Value::Float(f64::min(f1, f2)),
+            (Value::Tensor(t1), Value::Tensor(t2)) => {
+                Value::Tensor(std::array::from_fn(|k| f64::min(t1[k], t2[k])))
+            }
             (Value::Int(i1), Value::Int(i2)) => Value::Int(i1.min(i2)),
             (Value::Usize(u1), Value::Usize(u2)) => Value::Usize(u1.min(u2)),
             _ => panic!("Mismatched types"),
@@ -85,6 +111,9 @@ impl Add for Value {
     fn add(self, rhs: Self) -> Self::Output {
         match (self, rhs) {
             (Value::Float(i), Value::Float(j)) => Value::Float(i + j),
+            (Value::Tensor(i), Value::Tensor(j)) => {
+                Value::Tensor(std::array::from_fn(|k| i[k] + j[k]))
+            }
             (Value::Int(i), Value::Int(j)) => Value::Int(i + j),
             (Value::Usize(i), Value::Usize(j)) => Value::Usize(i + j),
             _ => panic!("Mismatched types"),
@@ -95,6 +124,7 @@ impl AddAssign for Value {
     fn add_assign(&mut self, rhs: Self) {
         match (self, rhs) {
             (Value::Float(i), Value::Float(j)) => *i += j,
+            (Value::Tensor(i), Value::Tensor(j)) => i.iter_mut().zip(j).for_each(|(a, b)| *a += b),
             (Value::Int(i), Value::Int(j)) => *i += j,
             (Value::Usize(i), Value::Usize(j)) => *i += j,
             _ => panic!("Mismatched types"),
@@ -107,6 +137,10 @@ impl Display for Value {
             Value::Float(v) => v.fmt(f),
             Value::Int(v) => v.fmt(f),
             Value::Usize(v) => v.fmt(f),
+            Value::Tensor(v) => {
+                let components: Vec<String> = v.iter().map(|c| c.to_string()).collect();
+                components.join(" ").fmt(f)
+            }
         }
     }
 }
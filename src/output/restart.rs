@@ -0,0 +1,328 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Read, Write},
+};
+
+use crate::{
+    atom_type::AtomType,
+    atoms::Atoms,
+    container::{Container, BC},
+    utils::Axis,
+};
+
+const MAGIC: &[u8; 8] = b"JMDRST\0\0";
+/// Incremented whenever the layout of restart files changes
+const VERSION: u32 = 6;
+
+const BCS: [BC; 10] = [
+    BC::PP,
+    BC::FF,
+    BC::FM,
+    BC::FS,
+    BC::MF,
+    BC::MM,
+    BC::MS,
+    BC::SF,
+    BC::SM,
+    BC::SS,
+];
+
+/// Writes values to a restart file in a fixed little-endian binary layout
+pub struct RestartWriter<'w> {
+    inner: &'w mut dyn Write,
+}
+impl<'w> RestartWriter<'w> {
+    pub fn new(inner: &'w mut dyn Write) -> Self {
+        Self { inner }
+    }
+    pub fn write_f64(&mut self, value: f64) -> io::Result<()> {
+        self.inner.write_all(&value.to_le_bytes())
+    }
+    pub fn write_usize(&mut self, value: usize) -> io::Result<()> {
+        self.inner.write_all(&(value as u64).to_le_bytes())
+    }
+    pub fn write_bool(&mut self, value: bool) -> io::Result<()> {
+        self.inner.write_all(&[value as u8])
+    }
+    /// Write a slice of floats, preceded by its length
+    pub fn write_f64s(&mut self, values: &[f64]) -> io::Result<()> {
+        self.write_usize(values.len())?;
+        values.iter().try_for_each(|&v| self.write_f64(v))
+    }
+    /// Write a slice of booleans, preceded by its length
+    pub fn write_bools(&mut self, values: &[bool]) -> io::Result<()> {
+        self.write_usize(values.len())?;
+        values.iter().try_for_each(|&v| self.write_bool(v))
+    }
+    /// Write a string, preceded by its length in bytes
+    pub fn write_string(&mut self, value: &str) -> io::Result<()> {
+        self.write_usize(value.len())?;
+        self.inner.write_all(value.as_bytes())
+    }
+    /// Write a section of values with the given function, preceded by its length in bytes
+    pub(crate) fn write_section(
+        &mut self,
+        f: impl FnOnce(&mut RestartWriter) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let mut bytes: Vec<u8> = Vec::new();
+        f(&mut RestartWriter::new(&mut bytes))?;
+        self.write_usize(bytes.len())?;
+        self.inner.write_all(&bytes)
+    }
+}
+
+/// Reads values written by `RestartWriter`, in the same order
+pub struct RestartReader<'r> {
+    inner: &'r mut dyn Read,
+}
+impl<'r> RestartReader<'r> {
+    pub fn new(inner: &'r mut dyn Read) -> Self {
+        Self { inner }
+    }
+    pub fn read_f64(&mut self) -> io::Result<f64> {
+        let mut bytes = [0u8; 8];
+        self.inner.read_exact(&mut bytes)?;
+        Ok(f64::from_le_bytes(bytes))
+    }
+    pub fn read_usize(&mut self) -> io::Result<usize> {
+        let mut bytes = [0u8; 8];
+        self.inner.read_exact(&mut bytes)?;
+        Ok(u64::from_le_bytes(bytes) as usize)
+    }
+    pub fn read_bool(&mut self) -> io::Result<bool> {
+        let mut bytes = [0u8; 1];
+        self.inner.read_exact(&mut bytes)?;
+        match bytes[0] {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(invalid_data("Invalid boolean")),
+        }
+    }
+    pub fn read_f64s(&mut self) -> io::Result<Vec<f64>> {
+        let len = self.read_usize()?;
+        (0..len).map(|_| self.read_f64()).collect()
+    }
+    pub fn read_bools(&mut self) -> io::Result<Vec<bool>> {
+        let len = self.read_usize()?;
+        (0..len).map(|_| self.read_bool()).collect()
+    }
+    pub fn read_string(&mut self) -> io::Result<String> {
+        let len = self.read_usize()?;
+        let mut bytes = vec![0u8; len];
+        self.inner.read_exact(&mut bytes)?;
+        String::from_utf8(bytes).map_err(|_| invalid_data("Invalid string"))
+    }
+    /// Read a section written by `write_section` with the given function, which should
+    /// read the whole section
+    pub(crate) fn read_section(
+        &mut self,
+        name: &str,
+        f: impl FnOnce(&mut RestartReader) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let len = self.read_usize()?;
+        let mut bytes: Vec<u8> = Vec::new();
+        (&mut self.inner).take(len as u64).read_to_end(&mut bytes)?;
+        if bytes.len() != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let mut remaining = bytes.as_slice();
+        f(&mut RestartReader::new(&mut remaining))
+            .map_err(|e| invalid_data(&format!("Could not read the {} section: {}", name, e)))?;
+        if !remaining.is_empty() {
+            return Err(invalid_data(&format!(
+                "The {} section is longer than expected",
+                name
+            )));
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub(crate) fn write_container(writer: &mut RestartWriter, container: &Container) -> io::Result<()> {
+    let rect = container.rect();
+    for value in rect.lo().iter().chain(rect.hi().iter()) {
+        writer.write_f64(*value)?;
+    }
+    for value in container.tilt() {
+        writer.write_f64(value)?;
+    }
+    for axis in [Axis::X, Axis::Y, Axis::Z] {
+        let bc = container.boundary_condition(axis);
+        let idx = BCS.iter().position(|b| b == bc).expect("All BCs listed");
+        writer.write_usize(idx)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn read_container(reader: &mut RestartReader) -> io::Result<Container> {
+    let mut bounds = [0.0; 6];
+    for bound in bounds.iter_mut() {
+        *bound = reader.read_f64()?;
+    }
+    let mut tilt = [0.0; 3];
+    for t in tilt.iter_mut() {
+        *t = reader.read_f64()?;
+    }
+    let mut bcs = [BC::PP; 3];
+    for bc in bcs.iter_mut() {
+        *bc = *BCS
+            .get(reader.read_usize()?)
+            .ok_or_else(|| invalid_data("Invalid boundary condition"))?;
+    }
+    let mut container = Container::new(
+        bounds[0], bounds[3], bounds[1], bounds[4], bounds[2], bounds[5], bcs[0], bcs[1], bcs[2],
+    );
+    container.set_tilt(tilt[0], tilt[1], tilt[2]);
+    Ok(container)
+}
+
+/// Write a restart file from the serialized state of the simulation and the owned atoms
+/// of every process, sorted by atom id
+pub(crate) fn write_restart_file<T: AtomType>(
+    path: &str,
+    state: &[u8],
+    atoms_per_proc: &[Atoms<T>],
+) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(MAGIC)?;
+    file.write_all(&VERSION.to_le_bytes())?;
+    file.write_all(state)?;
+
+    let mut rows: Vec<(&Atoms<T>, usize)> = atoms_per_proc
+        .iter()
+        .flat_map(|atoms| (0..atoms.num_local_atoms()).map(move |i| (atoms, i)))
+        .collect();
+    rows.sort_by_key(|(atoms, i)| atoms.ids[*i]);
+
+    let mut writer = RestartWriter::new(&mut file);
+    writer.write_usize(rows.len())?;
+    for (atoms, i) in rows {
+        writer.write_usize(atoms.ids[i])?;
+        writer.write_usize(atoms.types[i])?;
+        for k in 0..3 {
+            writer.write_f64(atoms.positions[i][k])?;
+        }
+        for k in 0..3 {
+            writer.write_f64(atoms.velocities[i][k])?;
+        }
+        writer.write_usize(atoms.masks[i] as usize)?;
+        writer.write_f64(atoms.charges[i])?;
+    }
+    file.flush()
+}
+
+/// Check the magic bytes and version at the start of a restart file
+pub(crate) fn read_restart_header(reader: &mut dyn Read) -> io::Result<()> {
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("Not a restart file"));
+    }
+    let mut version = [0u8; 4];
+    reader.read_exact(&mut version)?;
+    let version = u32::from_le_bytes(version);
+    if version != VERSION {
+        return Err(invalid_data(&format!(
+            "Unsupported restart version {}, expected {}",
+            version, VERSION
+        )));
+    }
+    Ok(())
+}
+
+/// Read the atoms at the end of a restart file, keeping only those for which `keep` is
+/// true, as owned atoms
+pub(crate) fn read_restart_atoms<T: AtomType>(
+    reader: &mut RestartReader,
+    keep: impl Fn(&[f64; 3]) -> bool,
+) -> io::Result<Atoms<T>> {
+    let mut atoms = Atoms::new();
+    let num_atoms = reader.read_usize()?;
+    for _ in 0..num_atoms {
+        let id = reader.read_usize()?;
+        let type_ = reader.read_usize()?;
+        let mut position = [0.0; 3];
+        for p in position.iter_mut() {
+            *p = reader.read_f64()?;
+        }
+        let mut velocity = [0.0; 3];
+        for v in velocity.iter_mut() {
+            *v = reader.read_f64()?;
+        }
+        let mask = reader.read_usize()? as u32;
+        let charge = reader.read_f64()?;
+        if keep(&position) {
+            atoms.ids.push(id);
+            atoms.types.push(type_);
+            atoms.positions.push(position);
+            atoms.velocities.push(velocity);
+            atoms.masks.push(mask);
+            atoms.charges.push(charge);
+        }
+    }
+    atoms.nlocal = atoms.ids.len();
+    atoms.num_atoms_global = num_atoms;
+    Ok(atoms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::atom_type::Basic;
+
+    #[test]
+    fn test_round_trip() {
+        let container = Container::new(-1.0, 1.0, 0.0, 2.0, 0.5, 3.0, BC::PP, BC::FS, BC::MM);
+        let mut state: Vec<u8> = Vec::new();
+        let mut writer = RestartWriter::new(&mut state);
+        write_container(&mut writer, &container).unwrap();
+        writer
+            .write_section(|w| {
+                w.write_f64s(&[1.0, -2.5])?;
+                w.write_string("mobile")
+            })
+            .unwrap();
+
+        let mut atoms: Atoms<Basic> = Atoms::new();
+        atoms.ids = vec![3, 1, 2];
+        atoms.types = vec![0, 1, 0];
+        atoms.positions = vec![[0.1, 0.2, 0.3], [-0.5, 1.5, 2.5], [0.9, 0.1, 0.6]];
+        atoms.velocities = vec![[1.0, 2.0, 3.0], [4.0, 5.0, 6.0], [7.0, 8.0, 9.0]];
+        atoms.masks = vec![3, 1, 1];
+        atoms.charges = vec![0.5, -1.0, 0.5];
+        atoms.nlocal = 3;
+
+        let path = std::env::temp_dir().join("jmd_test_restart_round_trip.restart");
+        let path = path.to_string_lossy().into_owned();
+        write_restart_file(&path, &state, &[atoms]).unwrap();
+
+        let mut file = std::fs::File::open(&path).unwrap();
+        read_restart_header(&mut file).unwrap();
+        let mut reader = RestartReader::new(&mut file);
+        let read = read_container(&mut reader).unwrap();
+        assert_eq!(read.rect().lo(), container.rect().lo());
+        assert_eq!(read.rect().hi(), container.rect().hi());
+        assert_eq!(read.boundary_condition(Axis::Y), &BC::FS);
+        reader
+            .read_section("test", |r| {
+                assert_eq!(r.read_f64s()?, vec![1.0, -2.5]);
+                assert_eq!(r.read_string()?, "mobile");
+                Ok(())
+            })
+            .unwrap();
+        let read: Atoms<Basic> = read_restart_atoms(&mut reader, |p| p[0] > 0.0).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(read.ids, vec![2, 3]);
+        assert_eq!(read.types, vec![0, 0]);
+        assert_eq!(read.velocities[1], [1.0, 2.0, 3.0]);
+        assert_eq!(read.masks, vec![1, 3]);
+        assert_eq!(read.charges, vec![0.5, 0.5]);
+        assert_eq!(read.num_local_atoms(), 2);
+        assert_eq!(read.num_atoms_global(), 3);
+    }
+}
@@ -1,16 +1,14 @@
-use std::sync::mpsc;
-
 use super::*;
 use crate::utils::Direction;
 
 /// Message transmitters to the six neighboring processes
 pub struct AdjacentProcs {
-    xlo: Option<mpsc::Sender<AtomMessage>>,
-    xhi: Option<mpsc::Sender<AtomMessage>>,
-    ylo: Option<mpsc::Sender<AtomMessage>>,
-    yhi: Option<mpsc::Sender<AtomMessage>>,
-    zlo: Option<mpsc::Sender<AtomMessage>>,
-    zhi: Option<mpsc::Sender<AtomMessage>>,
+    xlo: Option<AtomSender>,
+    xhi: Option<AtomSender>,
+    ylo: Option<AtomSender>,
+    yhi: Option<AtomSender>,
+    zlo: Option<AtomSender>,
+    zhi: Option<AtomSender>,
 }
 impl AdjacentProcs {
     pub fn new() -> Self {
@@ -23,12 +21,7 @@ impl AdjacentProcs {
             zhi: None,
         }
     }
-    pub fn as_vec(&self) -> Vec<&Option<mpsc::Sender<AtomMessage>>> {
-        vec![
-            &self.xlo, &self.xhi, &self.ylo, &self.yhi, &self.zlo, &self.zhi,
-        ]
-    }
-    pub fn set(&mut self, direction: Direction, sender: mpsc::Sender<AtomMessage>) {
+    pub fn set(&mut self, direction: Direction, sender: AtomSender) {
         match direction {
             Direction::Xlo => self.xlo = Some(sender),
             Direction::Xhi => self.xhi = Some(sender),
@@ -38,22 +31,14 @@ impl AdjacentProcs {
             Direction::Zhi => self.zhi = Some(sender),
         };
     }
-    pub fn xlo(&self) -> &Option<mpsc::Sender<AtomMessage>> {
-        &self.xlo
-    }
-    pub fn xhi(&self) -> &Option<mpsc::Sender<AtomMessage>> {
-        &self.xhi
-    }
-    pub fn ylo(&self) -> &Option<mpsc::Sender<AtomMessage>> {
-        &self.ylo
-    }
-    pub fn yhi(&self) -> &Option<mpsc::Sender<AtomMessage>> {
-        &self.yhi
-    }
-    pub fn zlo(&self) -> &Option<mpsc::Sender<AtomMessage>> {
-        &self.zlo
-    }
-    pub fn zhi(&self) -> &Option<mpsc::Sender<AtomMessage>> {
-        &self.zhi
+    pub fn get(&self, direction: Direction) -> &Option<AtomSender> {
+        match direction {
+            Direction::Xlo => &self.xlo,
+            Direction::Xhi => &self.xhi,
+            Direction::Ylo => &self.ylo,
+            Direction::Yhi => &self.yhi,
+            Direction::Zlo => &self.zlo,
+            Direction::Zhi => &self.zhi,
+        }
     }
 }
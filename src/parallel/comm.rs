@@ -1,311 +1,329 @@
 use super::*;
 use crate::{
     atom_type::AtomType,
-    atomic::AtomicPotentialTrait,
+    atomic::{AtomicPotentialTrait, Comm},
     atoms::Atom,
-    region::{Rect, Region},
     simulation::Simulation,
-    utils::Direction,
+    utils::{Axis, Direction},
 };
 
-pub(crate) fn reverse_comm<T, A>(sim: &mut Simulation<T, A>)
-where
-    T: AtomType,
-    A: AtomicPotentialTrait<T>,
-{
-    let mut sent_ids: Vec<usize> = Vec::new();
-
-    // z-direction
-    let mut ids = send_reverse_comm(sim, Direction::Zhi);
-    sent_ids.append(&mut ids);
-    recv_reverse_comm(sim);
-
-    let mut ids = send_reverse_comm(sim, Direction::Zlo);
-    sent_ids.append(&mut ids);
-    recv_reverse_comm(sim);
-
-    // y-direction
-    let mut ids = send_reverse_comm(sim, Direction::Yhi);
-    sent_ids.append(&mut ids);
-    recv_reverse_comm(sim);
-
-    let mut ids = send_reverse_comm(sim, Direction::Ylo);
-    sent_ids.append(&mut ids);
-    recv_reverse_comm(sim);
-
-    // x-direction
-    let mut ids = send_reverse_comm(sim, Direction::Xhi);
-    sent_ids.append(&mut ids);
-    recv_reverse_comm(sim);
-
-    let mut ids = send_reverse_comm(sim, Direction::Xlo);
-    sent_ids.append(&mut ids);
-    recv_reverse_comm(sim);
+/// One exchange of ghost atoms with the neighboring process in a given direction, set up
+/// when the neighbor list is built and repeated on each forward and reverse communication
+pub(crate) struct Swap {
+    direction: Direction,
+    /// Indices of the atoms sent to the neighbor in `direction`
+    send_idxs: Vec<usize>,
+    /// Number of box vectors along the axis of `direction` added to the sent positions,
+    /// nonzero when the swap crosses a periodic boundary
+    image: i32,
+    /// Index of the first ghost atom received from the neighbor opposite `direction`
+    recv_first: usize,
+    recv_count: usize,
 }
 
-pub(crate) fn forward_comm<T, A>(sim: &mut Simulation<T, A>)
-where
-    T: AtomType,
-    A: AtomicPotentialTrait<T>,
-{
-    // delete all ghost atoms
-    sim.atoms.ids.resize(sim.atoms.nlocal, 0);
-    sim.atoms.types.resize(sim.atoms.nlocal, 0);
-    sim.atoms.positions.resize(sim.atoms.nlocal, [0.0; 3]);
-    sim.atoms.velocities.resize(sim.atoms.nlocal, [0.0; 3]);
-
-    // x-direction
-    send_forward_comm(sim, Direction::Xlo);
-    recv_forward_comm(sim);
-    send_forward_comm(sim, Direction::Xhi);
-    recv_forward_comm(sim);
-
-    // y-direction
-    send_forward_comm(sim, Direction::Ylo);
-    recv_forward_comm(sim);
-    send_forward_comm(sim, Direction::Yhi);
-    recv_forward_comm(sim);
-
-    // z-direction
-    send_forward_comm(sim, Direction::Zlo);
-    recv_forward_comm(sim);
-    send_forward_comm(sim, Direction::Zhi);
-    recv_forward_comm(sim);
-}
-
-fn collect_comm_atoms<T, A>(sim: &Simulation<T, A>, direction: &Direction) -> Vec<usize>
-where
-    T: AtomType,
-    A: AtomicPotentialTrait<T>,
-{
-    let idx = direction.axis().index();
-    sim.atoms
-        .positions
-        .iter()
-        .enumerate()
-        .filter_map(|(i, p)| {
-            if direction.is_lo() && p[idx] < sim.domain().subdomain().lo()[idx] {
-                Some(i)
-            } else if !direction.is_lo() && p[idx] > sim.domain().subdomain().hi()[idx] {
-                Some(i)
-            } else {
-                None
-            }
-        })
-        .collect()
-}
-
-fn send_atoms<T, A>(sim: &mut Simulation<T, A>, direction: Direction)
+/// Communicate the forces of ghost atoms back to the owning processes
+pub(crate) fn reverse_comm<T, A>(sim: &mut Simulation<T, A>)
 where
     T: AtomType,
     A: AtomicPotentialTrait<T>,
 {
-    let atom_idxs = collect_comm_atoms(sim, &direction);
-    let ids: Vec<usize> = atom_idxs.iter().map(|i| sim.atoms.ids[*i]).collect();
-    sim.domain().send(AtomMessage::Idxs(ids), direction);
-
-    sim.remove_idxs(atom_idxs);
+    let mut forces = std::mem::take(sim.mut_forces());
+    reverse_comm_values(sim.domain(), &mut forces);
+    *sim.mut_forces() = forces;
 }
 
-fn recv_atoms<T, A>(sim: &mut Simulation<T, A>)
-where
+/// Communicate per-atom values of ghost atoms back to the owning processes, which add
+/// them to the values of their owned atoms. The values are indexed like the atoms,
+/// including ghost atoms.
+///
+/// The swaps are undone in reverse order, so that values of ghost atoms that were
+/// forwarded on (e.g., across a corner) reach their owner.
+pub(crate) fn reverse_comm_values<T, A, const N: usize>(
+    domain: &Domain<T, A>,
+    values: &mut [[f64; N]],
+) where
     T: AtomType,
     A: AtomicPotentialTrait<T>,
 {
-    let msg = sim.domain().receive();
-    match msg {
-        AtomMessage::Idxs(new_ids) => {
-            let num_new_owned_atoms = sim
-                .atoms
-                .ids
+    for swap in domain.swaps().iter().rev() {
+        let back = swap.direction.opposite();
+        if domain.has_neighbor(back) {
+            let send_values: Vec<f64> = values[swap.recv_first..swap.recv_first + swap.recv_count]
                 .iter()
-                .enumerate()
-                .filter(|(i, curr_id)| {
-                    new_ids.contains(curr_id)
-                        && sim.domain().subdomain().contains(&sim.atoms.positions[*i])
-                })
-                .count();
-            sim.atoms.nlocal += num_new_owned_atoms;
+                .flatten()
+                .copied()
+                .collect();
+            domain.send(AtomMessage::Float(send_values), back);
         }
-        _ => panic!("Invalid message"),
-    };
-}
-
-pub(crate) fn comm_atom_ownership<T, A>(sim: &mut Simulation<T, A>)
-where
-    T: AtomType,
-    A: AtomicPotentialTrait<T>,
-{
-    // dbg!(&sim.atoms);
-    send_atoms(sim, Direction::Xlo);
-    // dbg!(&sim.atoms);
-    recv_atoms(sim);
-
-    send_atoms(sim, Direction::Xhi);
-    recv_atoms(sim);
-
-    send_atoms(sim, Direction::Ylo);
-    recv_atoms(sim);
-
-    send_atoms(sim, Direction::Yhi);
-    recv_atoms(sim);
-
-    send_atoms(sim, Direction::Zlo);
-    recv_atoms(sim);
-
-    send_atoms(sim, Direction::Zhi);
-    recv_atoms(sim);
+        if domain.has_neighbor(swap.direction) {
+            match domain.receive(back) {
+                AtomMessage::Float(new_values) => {
+                    for (&i, new_value) in swap.send_idxs.iter().zip(new_values.chunks_exact(N)) {
+                        for (v, nv) in values[i].iter_mut().zip(new_value) {
+                            *v += nv;
+                        }
+                    }
+                }
+                _ => panic!("Invalid message"),
+            }
+        }
+    }
 }
 
-fn recv_reverse_comm<T, A>(sim: &mut Simulation<T, A>)
-where
+/// Communicate per-atom values of owned atoms to the processes holding them as ghost
+/// atoms, which overwrite the values of the ghost atoms. The values are indexed like the
+/// atoms, including ghost atoms.
+pub(crate) fn forward_comm_values<T, A, const N: usize>(
+    domain: &Domain<T, A>,
+    values: &mut [[f64; N]],
+) where
     T: AtomType,
     A: AtomicPotentialTrait<T>,
 {
-    let id_msg = sim.domain().receive();
-    let force_msg = sim.domain().receive();
-    match (id_msg, force_msg) {
-        (AtomMessage::Idxs(ids), AtomMessage::Float3(new_forces)) => {
-            accumulate_forces(sim, &ids, &new_forces)
+    for swap in domain.swaps() {
+        if domain.has_neighbor(swap.direction) {
+            let send_values: Vec<f64> = swap.send_idxs.iter().flat_map(|&i| values[i]).collect();
+            domain.send(AtomMessage::Float(send_values), swap.direction);
         }
-        (AtomMessage::Float3(new_forces), AtomMessage::Idxs(ids)) => {
-            accumulate_forces(sim, &ids, &new_forces)
+        if domain.has_neighbor(swap.direction.opposite()) {
+            match domain.receive(swap.direction) {
+                AtomMessage::Float(new_values) => {
+                    let range = swap.recv_first..swap.recv_first + swap.recv_count;
+                    for (v, nv) in values[range].iter_mut().zip(new_values.chunks_exact(N)) {
+                        v.copy_from_slice(nv);
+                    }
+                }
+                _ => panic!("Invalid message"),
+            }
         }
-        _ => panic!("Invalid communication"),
     }
 }
 
-fn recv_forward_comm<T, A>(sim: &mut Simulation<T, A>)
-where
-    T: AtomType,
-    A: AtomicPotentialTrait<T>,
-{
-    let msg = sim.domain().receive();
-    match msg {
-        AtomMessage::Atom(atoms) => {
-            for atom in atoms {
-                sim.atoms.update_or_push(atom);
-            }
+impl<T: AtomType, A: AtomicPotentialTrait<T>> Comm for Domain<'_, T, A> {
+    fn reverse_comm(&self, values: &mut [f64]) {
+        reverse_comm_values(self, values.as_chunks_mut::<1>().0);
+    }
+    fn forward_comm(&self, values: &mut [f64]) {
+        forward_comm_values(self, values.as_chunks_mut::<1>().0);
+    }
+    fn sum(&self, values: &mut [f64]) {
+        let sums = self.sum_floats(values.to_vec());
+        values.copy_from_slice(&sums);
+    }
+    fn procs(&self) -> [usize; 3] {
+        [Axis::X, Axis::Y, Axis::Z].map(|axis| self.procs_along(axis))
+    }
+    fn position(&self) -> [usize; 3] {
+        self.proc_position()
+    }
+    fn send(&self, values: Vec<f64>, direction: Direction) {
+        Domain::send(self, AtomMessage::Float(values), direction);
+    }
+    fn receive(&self, direction: Direction) -> Vec<f64> {
+        match Domain::receive(self, direction) {
+            AtomMessage::Float(values) => values,
+            _ => panic!("Invalid message"),
         }
-        _ => panic!("Invalid message"),
-    };
+    }
 }
 
-fn gather_ghost_ids<T, A>(sim: &Simulation<T, A>, rect: &Rect) -> Vec<usize>
+/// Forward communication: update the positions and velocities of the ghost atoms from
+/// their owners, following the swaps set up by `borders`
+pub(crate) fn forward_comm<T, A>(sim: &mut Simulation<T, A>)
 where
     T: AtomType,
     A: AtomicPotentialTrait<T>,
 {
-    sim.atoms
-        .ids
-        .iter()
-        .skip(sim.nlocal())
-        .zip(sim.atoms.positions.iter())
-        .filter_map(
-            |(&id, pos)| {
-                if rect.contains(pos) {
-                    Some(id)
-                } else {
-                    None
+    let swaps = std::mem::take(sim.mut_domain().mut_swaps());
+    for swap in &swaps {
+        let vector = sim.container().box_vectors()[swap.direction.axis().index()];
+        let shift = vector.map(|v| swap.image as f64 * v);
+        if sim.domain().has_neighbor(swap.direction) {
+            let positions: Vec<[f64; 3]> = swap
+                .send_idxs
+                .iter()
+                .map(|&i| {
+                    let p = sim.atoms.positions[i];
+                    [p[0] + shift[0], p[1] + shift[1], p[2] + shift[2]]
+                })
+                .collect();
+            let velocities: Vec<[f64; 3]> = swap
+                .send_idxs
+                .iter()
+                .map(|&i| sim.atoms.velocities[i])
+                .collect();
+            sim.domain()
+                .send(AtomMessage::Float3(positions), swap.direction);
+            sim.domain()
+                .send(AtomMessage::Float3(velocities), swap.direction);
+        }
+        if sim.domain().has_neighbor(swap.direction.opposite()) {
+            let range = swap.recv_first..swap.recv_first + swap.recv_count;
+            match sim.domain().receive(swap.direction) {
+                AtomMessage::Float3(positions) => {
+                    sim.atoms.positions[range.clone()].copy_from_slice(&positions)
                 }
-            },
-        )
-        .collect()
-}
-
-fn gather_owned_idxs<T, A>(sim: &Simulation<T, A>, rect: &Rect) -> Vec<usize>
-where
-    T: AtomType,
-    A: AtomicPotentialTrait<T>,
-{
-    sim.atoms
-        .positions
-        .iter()
-        .take(sim.nlocal())
-        .enumerate()
-        .filter_map(
-            |(idx, pos)| {
-                if rect.contains(pos) {
-                    Some(idx)
-                } else {
-                    None
+                _ => panic!("Invalid message"),
+            };
+            match sim.domain().receive(swap.direction) {
+                AtomMessage::Float3(velocities) => {
+                    sim.atoms.velocities[range].copy_from_slice(&velocities)
                 }
-            },
-        )
-        .collect()
-}
-
-fn accumulate_forces<T, A>(
-    sim: &mut Simulation<T, A>,
-    communicated_ids: &Vec<usize>,
-    communicated_forces: &Vec<[f64; 3]>,
-) where
-    T: AtomType,
-    A: AtomicPotentialTrait<T>,
-{
-    let natoms = sim.atoms.num_total_atoms();
-    for i in 0..natoms {
-        let opt = communicated_ids
-            .iter()
-            .position(|id| *id == sim.atoms.ids[i]);
-        if let Some(j) = opt {
-            sim.mut_forces()[i][0] += communicated_forces[j][0];
-            sim.mut_forces()[i][1] += communicated_forces[j][1];
-            sim.mut_forces()[i][2] += communicated_forces[j][2];
+                _ => panic!("Invalid message"),
+            };
         }
     }
+    *sim.mut_domain().mut_swaps() = swaps;
 }
 
-fn send_reverse_comm<T, A>(sim: &Simulation<T, A>, direction: Direction) -> Vec<usize>
+/// Replace the ghost atoms with copies of the atoms within the neighbor distance of each
+/// face of the neighboring subdomains, shifted across periodic boundaries, and record
+/// the swaps for later forward and reverse communication.
+///
+/// Along each axis, both the owned atoms and the ghost atoms received along previous
+/// axes are sent, so that atoms near edges and corners reach every neighbor. No ghost
+/// atoms are sent across non-periodic boundaries. Atoms are selected in fractional
+/// coordinates, with the neighbor distance converted using the spacing between faces of
+/// the box, so that triclinic boxes are handled as well.
+pub(crate) fn borders<T, A>(sim: &mut Simulation<T, A>)
 where
     T: AtomType,
     A: AtomicPotentialTrait<T>,
 {
-    let ids = gather_ghost_ids(sim, &sim.domain().get_outer_rect(&direction, sim.nl()));
-    let mut send_forces: Vec<[f64; 3]> = Vec::new();
-    send_forces.reserve(ids.len());
-
-    for id in &ids {
-        let j = sim
-            .atoms
-            .ids
-            .iter()
-            .position(|i| *i == *id)
-            .expect("Should exist");
+    sim.atoms.truncate_ghosts();
+    let dist = sim.nl().max_neighbor_distance();
+    let mut swaps: Vec<Swap> = Vec::new();
+    let spacings = sim.container().face_spacings();
+    let vectors = sim.container().box_vectors();
+
+    for axis in [Axis::X, Axis::Y, Axis::Z] {
+        let k = axis.index();
+        let [lo, hi] = sim.domain().subdomain().get_bounds(axis);
+        let width = (hi - lo) * spacings[k];
+        let dist_lamda = dist / spacings[k];
+        let num_candidates = sim.atoms.num_total_atoms();
+        for direction in [axis.direction(false), axis.direction(true)] {
+            let domain = sim.domain();
+            assert!(
+                !domain.has_neighbor(direction) || width >= dist,
+                "Subdomain width along {:?} ({}) should be at least the neighbor distance ({}), \
+                 try using fewer threads",
+                axis,
+                width,
+                dist
+            );
+            let image = domain.periodic_image(direction);
+            let shift = vectors[k].map(|v| image as f64 * v);
+
+            let send_idxs: Vec<usize> = if domain.has_neighbor(direction) {
+                (0..num_candidates)
+                    .filter(|&i| {
+                        let x = sim.container().to_lamda(&sim.atoms.positions[i])[k];
+                        if direction.is_lo() {
+                            x < lo + dist_lamda
+                        } else {
+                            x >= hi - dist_lamda
+                        }
+                    })
+                    .collect()
+            } else {
+                Vec::new()
+            };
+            if domain.has_neighbor(direction) {
+                let atoms: Vec<Atom> = send_idxs
+                    .iter()
+                    .map(|&i| {
+                        let mut atom = sim.atoms.atom(i);
+                        (0..3).for_each(|j| atom.position[j] += shift[j]);
+                        atom
+                    })
+                    .collect();
+                domain.send(AtomMessage::Atom(atoms), direction);
+            }
 
-        send_forces.push(sim.forces()[j].clone());
+            let recv_first = sim.atoms.num_total_atoms();
+            if domain.has_neighbor(direction.opposite()) {
+                match domain.receive(direction) {
+                    AtomMessage::Atom(atoms) => {
+                        atoms.into_iter().for_each(|atom| sim.atoms.push(atom))
+                    }
+                    _ => panic!("Invalid message"),
+                };
+            }
+            swaps.push(Swap {
+                direction,
+                send_idxs,
+                image,
+                recv_first,
+                recv_count: sim.atoms.num_total_atoms() - recv_first,
+            });
+        }
     }
-
-    sim.domain().send(AtomMessage::Idxs(ids.clone()), direction);
-    sim.domain()
-        .send(AtomMessage::Float3(send_forces), direction);
-    ids
+    *sim.mut_domain().mut_swaps() = swaps;
 }
 
-fn send_forward_comm<T, A>(sim: &Simulation<T, A>, direction: Direction)
+/// Send the owned atoms that have left the subdomain to the neighboring processes, one
+/// axis at a time in fractional coordinates, and take ownership of the received atoms
+/// within the subdomain. All
+/// ghost atoms are removed first. Atoms should be wrapped into the box along periodic
+/// axes beforehand, and should not move further than a neighboring subdomain.
+///
+/// With more than two processes along an axis, leaving atoms are sent to both neighbors
+/// and each keeps those in its own subdomain, since atoms wrapped across a periodic
+/// boundary can belong to either.
+pub(crate) fn comm_atom_ownership<T, A>(sim: &mut Simulation<T, A>)
 where
     T: AtomType,
     A: AtomicPotentialTrait<T>,
 {
-    let idxs = gather_owned_idxs(sim, &sim.domain().get_inner_rect(&direction, sim.nl()));
-    let atoms: Vec<Atom> = sim
-        .atoms
-        .ids
-        .iter()
-        .enumerate()
-        .filter_map(|(i, id)| {
-            if idxs.contains(&i) {
-                Some(Atom {
-                    id: *id,
-                    type_: sim.atoms.types[i],
-                    position: sim.atoms.positions[i],
-                    velocity: sim.atoms.velocities[i],
-                })
-            } else {
-                None
+    sim.atoms.truncate_ghosts();
+    for axis in [Axis::X, Axis::Y, Axis::Z] {
+        let num_procs = sim.domain().procs_along(axis);
+        if num_procs == 1 {
+            continue;
+        }
+        let k = axis.index();
+        let [lo, hi] = sim.domain().subdomain().get_bounds(axis);
+
+        let leaving: Vec<usize> = (0..sim.nlocal())
+            .filter(|&i| {
+                let x = sim.container().to_lamda(&sim.atoms.positions[i])[k];
+                x < lo || x >= hi
+            })
+            .collect();
+        let atoms: Vec<Atom> = leaving.iter().map(|&i| sim.atoms.atom(i)).collect();
+        sim.remove_idxs(leaving);
+
+        // With two processes along a periodic axis, both neighbors are the same process
+        let directions = [axis.direction(false), axis.direction(true)];
+        let domain = sim.domain();
+        let send_directions: Vec<Direction> = directions
+            .into_iter()
+            .filter(|&d| domain.has_neighbor(d))
+            .take(if num_procs == 2 { 1 } else { 2 })
+            .collect();
+        let recv_directions: Vec<Direction> = directions
+            .into_iter()
+            .filter(|&d| domain.has_neighbor(d.opposite()))
+            .take(if num_procs == 2 { 1 } else { 2 })
+            .collect();
+
+        for &direction in &send_directions {
+            domain.send(AtomMessage::Atom(atoms.clone()), direction);
+        }
+        let mut received: Vec<Atom> = Vec::new();
+        for direction in recv_directions {
+            match domain.receive(direction) {
+                AtomMessage::Atom(atoms) => received.extend(atoms),
+                _ => panic!("Invalid message"),
+            };
+        }
+        for atom in received {
+            let x = sim.container().to_lamda(&atom.position)[k];
+            if lo <= x && x < hi {
+                sim.atoms.push(atom);
+                sim.atoms.nlocal += 1;
             }
-        })
-        .collect();
-
-    sim.domain().send(AtomMessage::Atom(atoms), direction);
+        }
+    }
 }
@@ -1,14 +1,15 @@
 // TODO: integrate utils::indices
-use std::{sync::mpsc, thread};
+use std::{cell::RefCell, collections::VecDeque, sync::mpsc, thread};
+
+use rand::{rngs::StdRng, SeedableRng};
 
 use super::*;
 use crate::{
     atom_type::AtomType,
     atomic,
     container::Container,
-    neighbor::NeighborList,
     region::Rect,
-    utils::{Direction, Index},
+    utils::{Axis, Direction, Index},
 };
 
 /// Determine and return the best configuration of processes to
@@ -47,12 +48,19 @@ fn procs_in_box(nprocs: usize, lx: f64, ly: f64, lz: f64) -> [usize; 3] {
 
 /// Represents a process in relation to the other neighboring processes
 pub struct Domain<'a, T: AtomType, A: atomic::AtomicPotentialTrait<T>> {
-    receiver: mpsc::Receiver<AtomMessage>,
-    my_sender: mpsc::Sender<AtomMessage>,
+    receiver: mpsc::Receiver<(Direction, AtomMessage)>,
+    my_sender: AtomSender,
+    /// Messages received while waiting for a message sent in another direction
+    deferred: RefCell<VecDeque<(Direction, AtomMessage)>>,
     worker: Option<Box<&'a Worker<T, A>>>,
     procs: AdjacentProcs,
+    /// Subdomain of this process in fractional (lamda) coordinates of the box
     subdomain: Rect,
     proc_index: Index,
+    /// Whether each axis is periodic, so that neighbors across the box are used
+    periodic: [bool; 3],
+    rng: StdRng,
+    swaps: Vec<comm::Swap>,
 }
 impl<'a, T: AtomType, A: atomic::AtomicPotentialTrait<T>> Domain<'a, T, A> {
     pub(crate) fn new() -> Self {
@@ -62,10 +70,14 @@ impl<'a, T: AtomType, A: atomic::AtomicPotentialTrait<T>> Domain<'a, T, A> {
         Self {
             receiver,
             my_sender,
+            deferred: RefCell::new(VecDeque::new()),
             worker: None,
             procs: neighbor_procs,
-            subdomain: Rect::new(0.0, 10.0, 0.0, 10.0, 0.0, 10.0),
+            subdomain: Rect::new(0.0, 1.0, 0.0, 1.0, 0.0, 1.0),
             proc_index: Index::new(),
+            periodic: [true; 3],
+            rng: StdRng::from_entropy(),
+            swaps: Vec::new(),
         }
     }
     pub(crate) fn init(&mut self, container: &Container, worker: Box<&'a Worker<T, A>>) {
@@ -86,221 +98,134 @@ impl<'a, T: AtomType, A: atomic::AtomicPotentialTrait<T>> Domain<'a, T, A> {
             .unwrap();
         self.proc_index = Index::from_1d(idx, proc_dimensions);
 
-        self.reset_subdomain(container.rect());
-
-        vec![
-            Direction::Xlo,
-            Direction::Xhi,
-            Direction::Ylo,
-            Direction::Yhi,
-            Direction::Zlo,
-            Direction::Zhi,
-        ]
-        .iter()
-        .for_each(|direction| self.setup_neighbor(*direction, container));
+        self.reset_subdomain(container);
+        self.setup_neighbors();
     }
     pub(crate) fn proc_index(&self) -> usize {
         self.proc_index.idx()
     }
+    /// The position of this process along each axis
+    pub(crate) fn proc_position(&self) -> [usize; 3] {
+        self.proc_index.to_3d()
+    }
     pub(crate) fn subdomain(&self) -> &Rect {
         &self.subdomain
     }
+    /// The random number generator of this process
+    pub(crate) fn mut_rng(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+    /// Seed the random number generator, giving each process an independent stream
+    /// so that runs are reproducible for a fixed number of processes
+    pub(crate) fn seed_rng(&mut self, seed: u64) {
+        let stream = (self.proc_index() as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        self.rng = StdRng::seed_from_u64(seed ^ stream);
+    }
     pub(crate) fn worker(&self) -> &Box<&'a Worker<T, A>> {
         self.worker.as_ref().expect("Must init")
     }
-    fn setup_neighbor(&mut self, direction: Direction, container: &Container) {
-        // Get index of neighbor (3d then 1d), if neighbor is present, send Option<mpsc::Sender> to main with proc idx, otherwise None and 0
-        // Receive from main Option<mpsc::Sender> for opposite neighbor
-        let idx = self.get_neighbor(direction.clone(), container);
-        let message = match idx {
-            Some(i) => W2M::Sender(Some(self.my_sender.clone()), i.idx()),
-            None => W2M::Sender(None, 0),
-        };
-        self.worker().send(message);
-        let message = self.worker().recv();
-        match message {
-            M2W::Sender(Some(sender)) => {
-                self.procs.set(direction.opposite(), sender);
-            }
-            M2W::Sender(None) => {}
-            _ => panic!("Invalid message"),
-        };
+    /// Exchange senders with the neighboring processes through the manager. Each
+    /// process sends its own sender to its neighbor in each direction, wrapping around
+    /// the box, then receives the senders of its neighbors in the opposite directions.
+    /// Whether a neighbor across the box is used depends on the boundary conditions set
+    /// by `reset_subdomain`.
+    fn setup_neighbors(&mut self) {
+        let directions = [
+            Direction::Xlo,
+            Direction::Xhi,
+            Direction::Ylo,
+            Direction::Yhi,
+            Direction::Zlo,
+            Direction::Zhi,
+        ];
+        for direction in directions {
+            let idx = self.neighbor_index(direction).idx();
+            self.worker()
+                .send(W2M::Sender(self.my_sender.clone(), idx, direction));
+        }
+        for _ in directions {
+            match self.worker().recv() {
+                M2W::Sender(sender, direction) => {
+                    self.procs.set(direction.opposite(), sender);
+                }
+                _ => panic!("Invalid message"),
+            };
+        }
     }
     pub fn initialized(&self) -> bool {
         self.worker.is_some()
     }
-    pub fn reset_subdomain(&mut self, rect: &Rect) {
+    /// Split the box of the given container evenly between the processes in fractional
+    /// (lamda) coordinates, and record along which axes neighbors across the box are used
+    pub fn reset_subdomain(&mut self, container: &Container) {
+        self.periodic = [Axis::X, Axis::Y, Axis::Z].map(|axis| container.is_periodic(axis));
         let bounds = self.proc_index.bounds();
-        let l = [
-            rect.lx() / (bounds[0] as f64),
-            rect.ly() / (bounds[1] as f64),
-            rect.lz() / (bounds[2] as f64),
-        ];
-        let lo = rect.lo();
         let idx3d = self.proc_index.to_3d();
-        let sdlo = [
-            lo[0] + l[0] * idx3d[0] as f64,
-            lo[1] + l[1] * idx3d[1] as f64,
-            lo[2] + l[2] * idx3d[2] as f64,
-        ];
-        self.subdomain = Rect::new(
-            sdlo[0],
-            sdlo[0] + l[0],
-            sdlo[1],
-            sdlo[1] + l[1],
-            sdlo[2],
-            sdlo[2] + l[2],
-        );
-    }
-
-    pub fn get_inner_rect(&self, direction: &Direction, neighbor_list: &NeighborList) -> Rect {
-        let dist = neighbor_list.max_neighbor_distance();
-        let half_skin = neighbor_list.skin_distance() * 0.5;
-
-        match direction {
-            Direction::Xlo => Rect::new(
-                self.subdomain.xlo() - half_skin,
-                self.subdomain.xlo() + dist,
-                self.subdomain.ylo() - half_skin,
-                self.subdomain.yhi() + half_skin,
-                self.subdomain.zlo() - half_skin,
-                self.subdomain.zhi() + half_skin,
-            ),
-            Direction::Xhi => Rect::new(
-                self.subdomain.xhi() - dist,
-                self.subdomain.xhi() + half_skin,
-                self.subdomain.ylo() - half_skin,
-                self.subdomain.yhi() + half_skin,
-                self.subdomain.zlo() - half_skin,
-                self.subdomain.zhi() + half_skin,
-            ),
-            Direction::Ylo => Rect::new(
-                self.subdomain.xlo() - dist,
-                self.subdomain.xhi() + dist,
-                self.subdomain.ylo() - half_skin,
-                self.subdomain.ylo() + dist,
-                self.subdomain.zlo() - half_skin,
-                self.subdomain.zhi() + half_skin,
-            ),
-            Direction::Yhi => Rect::new(
-                self.subdomain.xlo() - dist,
-                self.subdomain.xhi() + dist,
-                self.subdomain.yhi() - dist,
-                self.subdomain.yhi() + half_skin,
-                self.subdomain.zlo() - half_skin,
-                self.subdomain.zhi() + half_skin,
-            ),
-            Direction::Zlo => Rect::new(
-                self.subdomain.xlo() - dist,
-                self.subdomain.xhi() + dist,
-                self.subdomain.ylo() - dist,
-                self.subdomain.yhi() + dist,
-                self.subdomain.zlo() - half_skin,
-                self.subdomain.zlo() + dist,
-            ),
-            Direction::Zhi => Rect::new(
-                self.subdomain.xlo() - dist,
-                self.subdomain.xhi() + dist,
-                self.subdomain.ylo() - dist,
-                self.subdomain.yhi() + dist,
-                self.subdomain.zhi() - dist,
-                self.subdomain.zhi() + half_skin,
-            ),
-        }
-    }
-
-    pub fn get_outer_rect(&self, direction: &Direction, neighbor_list: &NeighborList) -> Rect {
-        let dist = neighbor_list.max_neighbor_distance();
-        let half_skin = neighbor_list.skin_distance() * 0.5;
-
-        match direction {
-            Direction::Xlo => Rect::new(
-                self.subdomain.xlo() - dist,
-                self.subdomain.xlo() + half_skin,
-                self.subdomain.ylo() - half_skin,
-                self.subdomain.yhi() + half_skin,
-                self.subdomain.zlo() - half_skin,
-                self.subdomain.zhi() + half_skin,
-            ),
-            Direction::Xhi => Rect::new(
-                self.subdomain.xhi() - half_skin,
-                self.subdomain.xhi() + dist,
-                self.subdomain.ylo() - half_skin,
-                self.subdomain.yhi() + half_skin,
-                self.subdomain.zlo() - half_skin,
-                self.subdomain.zhi() + half_skin,
-            ),
-            Direction::Ylo => Rect::new(
-                self.subdomain.xlo() - dist,
-                self.subdomain.xhi() + dist,
-                self.subdomain.ylo() - dist,
-                self.subdomain.ylo() + half_skin,
-                self.subdomain.zlo() - half_skin,
-                self.subdomain.zhi() + half_skin,
-            ),
-            Direction::Yhi => Rect::new(
-                self.subdomain.xlo() - dist,
-                self.subdomain.xhi() + dist,
-                self.subdomain.yhi() - half_skin,
-                self.subdomain.yhi() + dist,
-                self.subdomain.zlo() - half_skin,
-                self.subdomain.zhi() + half_skin,
-            ),
-            Direction::Zlo => Rect::new(
-                self.subdomain.xlo() - dist,
-                self.subdomain.xhi() + dist,
-                self.subdomain.ylo() - dist,
-                self.subdomain.yhi() + dist,
-                self.subdomain.zlo() - dist,
-                self.subdomain.zlo() + half_skin,
-            ),
-            Direction::Zhi => Rect::new(
-                self.subdomain.xlo() - dist,
-                self.subdomain.xhi() + dist,
-                self.subdomain.ylo() - dist,
-                self.subdomain.yhi() + dist,
-                self.subdomain.zhi() - half_skin,
-                self.subdomain.zhi() + dist,
-            ),
-        }
+        let [xlo, ylo, zlo] = [0, 1, 2].map(|k| idx3d[k] as f64 / bounds[k] as f64);
+        let [xhi, yhi, zhi] = [0, 1, 2].map(|k| (idx3d[k] + 1) as f64 / bounds[k] as f64);
+        self.subdomain = Rect::new(xlo, xhi, ylo, yhi, zlo, zhi);
     }
-
-    pub fn clone_sender(&self) -> mpsc::Sender<AtomMessage> {
+    pub fn clone_sender(&self) -> AtomSender {
         self.my_sender.clone()
     }
-    pub fn receiver(&self) -> &mpsc::Receiver<AtomMessage> {
-        &self.receiver
-    }
     pub fn neighbor_procs(&self) -> &AdjacentProcs {
         &self.procs
     }
-    pub fn set_neighbor_proc(&mut self, direction: Direction, sender: mpsc::Sender<AtomMessage>) {
+    pub fn set_neighbor_proc(&mut self, direction: Direction, sender: AtomSender) {
         self.procs.set(direction, sender);
     }
     pub fn thread_ids(&self) -> &Vec<thread::ThreadId> {
         self.worker().thread_ids()
     }
-    pub fn num_neighbors(&self) -> usize {
-        self.procs
-            .as_vec()
-            .iter()
-            .filter(|&&p| (*p).is_some())
-            .count()
-    }
-    pub fn receive(&self) -> AtomMessage {
-        self.receiver.recv().expect("Disconnect error")
-    }
-    pub fn send(&self, value: AtomMessage, neighbor: Direction) {
-        let n = match neighbor {
-            Direction::Xlo => self.procs.xlo(),
-            Direction::Xhi => self.procs.xhi(),
-            Direction::Ylo => self.procs.ylo(),
-            Direction::Yhi => self.procs.yhi(),
-            Direction::Zlo => self.procs.zlo(),
-            Direction::Zhi => self.procs.zhi(),
-        };
-        if let Some(s) = n {
-            s.send(value).expect("Disconnect error");
+    /// Whether there is a neighboring process in the given direction, which is not the
+    /// case across a non-periodic boundary
+    pub(crate) fn has_neighbor(&self, direction: Direction) -> bool {
+        self.procs.get(direction).is_some()
+            && (self.periodic[direction.axis().index()] || !self.crosses_box(direction))
+    }
+    /// The number of processes along the given axis
+    pub(crate) fn procs_along(&self, axis: Axis) -> usize {
+        self.proc_index.bounds()[axis.index()]
+    }
+    /// The number of box lengths to shift positions sent to the neighbor in the given
+    /// direction: 1 when sending across the lower box boundary, -1 across the upper one,
+    /// and 0 otherwise
+    pub(crate) fn periodic_image(&self, direction: Direction) -> i32 {
+        match (self.crosses_box(direction), direction.is_lo()) {
+            (false, _) => 0,
+            (true, true) => 1,
+            (true, false) => -1,
+        }
+    }
+    /// The ghost atom swaps set up when the neighbor list was last built
+    pub(crate) fn swaps(&self) -> &Vec<comm::Swap> {
+        &self.swaps
+    }
+    pub(crate) fn mut_swaps(&mut self) -> &mut Vec<comm::Swap> {
+        &mut self.swaps
+    }
+    /// Receive the next message sent in the given direction, i.e., from the neighbor in
+    /// the opposite direction. Messages sent in other directions are kept for later.
+    pub fn receive(&self, direction: Direction) -> AtomMessage {
+        let mut deferred = self.deferred.borrow_mut();
+        if let Some(i) = deferred.iter().position(|(d, _)| *d == direction) {
+            return deferred.remove(i).expect("Should exist").1;
+        }
+        loop {
+            let (d, message) = self.receiver.recv().expect("Disconnect error");
+            if d == direction {
+                return message;
+            }
+            deferred.push_back((d, message));
+        }
+    }
+    /// Send a message to the neighbor in the given direction, if there is one
+    pub fn send(&self, value: AtomMessage, direction: Direction) {
+        if !self.has_neighbor(direction) {
+            return;
+        }
+        if let Some(s) = self.procs.get(direction) {
+            s.send((direction, value)).expect("Disconnect error");
         }
     }
     pub(crate) fn send_to_main(&self, message: W2M<T>) {
@@ -309,19 +234,71 @@ impl<'a, T: AtomType, A: atomic::AtomicPotentialTrait<T>> Domain<'a, T, A> {
     pub(crate) fn recv_from_main(&self) -> M2W<T, A> {
         self.worker().recv()
     }
+    /// Sum a value over all processes through the manager. Must be called by every process.
+    pub(crate) fn sum(&self, value: usize) -> usize {
+        self.send_to_main(W2M::Sum(value));
+        match self.recv_from_main() {
+            M2W::SumResult(sum) => sum,
+            _ => panic!("Invalid message"),
+        }
+    }
+    /// Sum each value over all processes through the manager. Must be called by every
+    /// process with the same number of values.
+    pub(crate) fn sums(&self, values: Vec<usize>) -> Vec<usize> {
+        self.send_to_main(W2M::Sums(values));
+        match self.recv_from_main() {
+            M2W::SumsResult(values) => values,
+            _ => panic!("Invalid message"),
+        }
+    }
+    /// Sum a float over all processes through the manager. Must be called by every process.
+    pub(crate) fn sum_float(&self, value: f64) -> f64 {
+        self.send_to_main(W2M::SumFloat(value));
+        match self.recv_from_main() {
+            M2W::SumFloatResult(sum) => sum,
+            _ => panic!("Invalid message"),
+        }
+    }
+
+    /// Sum each value over all processes through the manager. Must be called by every
+    /// process with the same number of values.
+    pub(crate) fn sum_floats(&self, values: Vec<f64>) -> Vec<f64> {
+        self.send_to_main(W2M::SumFloats(values));
+        match self.recv_from_main() {
+            M2W::SumFloatsResult(values) => values,
+            _ => panic!("Invalid message"),
+        }
+    }
+    /// Maximum of each value over all processes through the manager. Must be called by
+    /// every process with the same number of values.
+    pub(crate) fn max_floats(&self, values: Vec<f64>) -> Vec<f64> {
+        self.send_to_main(W2M::MaxFloats(values));
+        match self.recv_from_main() {
+            M2W::MaxFloatsResult(values) => values,
+            _ => panic!("Invalid message"),
+        }
+    }
 
-    fn get_neighbor(&self, direction: Direction, container: &Container) -> Option<Index> {
+    /// Whether this process is at the box boundary in the given direction
+    fn crosses_box(&self, direction: Direction) -> bool {
+        let axis_index = direction.axis().index();
+        let i = self.proc_index.to_3d()[axis_index];
+        let n = self.proc_index.bounds()[axis_index];
+        if direction.is_lo() {
+            i == 0
+        } else {
+            i == n - 1
+        }
+    }
+    /// The index of the neighboring process in the given direction, wrapping around the
+    /// box regardless of the boundary conditions
+    fn neighbor_index(&self, direction: Direction) -> Index {
         let axis_index = direction.axis().index();
         let my_idx = self.proc_index.to_3d();
         let bounds = self.proc_index.bounds();
 
-        let i = my_idx[axis_index];
         let n = bounds[axis_index];
-        let across_box = if direction.is_lo() {
-            i == 0
-        } else {
-            i == n - 1
-        };
+        let across_box = self.crosses_box(direction);
         let mut idx = my_idx.clone();
         match (across_box, direction.is_lo()) {
             (false, false) => {
@@ -338,11 +315,7 @@ impl<'a, T: AtomType, A: atomic::AtomicPotentialTrait<T>> Domain<'a, T, A> {
             }
         };
 
-        if across_box && !container.is_periodic(direction.axis()) {
-            None
-        } else {
-            Some(Index::from_3d(&idx, &bounds))
-        }
+        Index::from_3d(&idx, &bounds)
     }
 
     pub(crate) fn send_to_main_once(&self, message: W2M<T>) {
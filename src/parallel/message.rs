@@ -5,8 +5,9 @@ use crate::{
     atomic::AtomicPotentialTrait,
     atoms::{Atom, Atoms},
     container::Container,
-    output::{OutputSpec, Value},
+    output::{DumpSpec, OutputSpec, Value},
     simulation::Simulation,
+    utils::Direction,
 };
 
 /// Message between procs communicating atom info
@@ -19,25 +20,46 @@ pub(crate) enum AtomMessage {
     Atom(Vec<Atom>),
 }
 
+/// Sending half of the channel between neighboring procs. Each message is tagged with
+/// the direction it was sent in, so that the receiver can tell apart messages from
+/// different neighbors.
+pub(crate) type AtomSender = mpsc::Sender<(Direction, AtomMessage)>;
+
 /// Worker-to-Manager messages
-pub enum W2M<T: AtomType> {
+pub(crate) enum W2M<T: AtomType> {
     Complete,
     Output(thread::ThreadId, Value),
-    Dump(Atoms<T>, Container),
+    /// Dump index, step, and the owned atoms and container of the sending process
+    Dump(usize, usize, Atoms<T>, Container),
     Id(thread::ThreadId),
-    Sender(Option<mpsc::Sender<AtomMessage>>, usize),
-    ProcDims([usize; 3]),
+    /// Sender of the sending process, the index of its neighbor in the given direction,
+    /// and the direction
+    Sender(AtomSender, usize, Direction),
     SetupOutput(Vec<OutputSpec>),
+    SetupDump(DumpSpec),
+    /// Path, serialized state (from the first process only), and the owned atoms of
+    /// the sending process
+    WriteRestart(String, Option<Vec<u8>>, Atoms<T>),
     InitialOutput,
+    /// A warning to print once, from the first process
+    Warning(String),
     Sum(usize),
+    Sums(Vec<usize>),
+    SumFloat(f64),
+    SumFloats(Vec<f64>),
+    MaxFloats(Vec<f64>),
 }
 
 /// Manager-to-Worker messages
 #[derive(Clone, Debug)]
-pub enum M2W<T: AtomType, A: AtomicPotentialTrait<T>> {
+pub(crate) enum M2W<T: AtomType, A: AtomicPotentialTrait<T>> {
     Setup(Vec<thread::ThreadId>),
     Run(fn(Simulation<T, A>) -> ()),
-    Sender(Option<mpsc::Sender<AtomMessage>>),
-    ProcDims([usize; 3]),
+    /// Sender of the neighbor in the opposite of the given direction
+    Sender(AtomSender, Direction),
     SumResult(usize),
+    SumsResult(Vec<usize>),
+    SumFloatResult(f64),
+    SumFloatsResult(Vec<f64>),
+    MaxFloatsResult(Vec<f64>),
 }
@@ -7,5 +7,5 @@ pub(crate) mod comm;
 
 pub(crate) use adjacent_procs::AdjacentProcs;
 pub(crate) use domain::Domain;
-pub(crate) use message::{AtomMessage, M2W, W2M};
+pub(crate) use message::{AtomMessage, AtomSender, M2W, W2M};
 pub(crate) use worker::Worker;
@@ -4,7 +4,7 @@ use super::*;
 use crate::{atom_type::AtomType, atomic::AtomicPotentialTrait, simulation::Simulation};
 
 /// Channels for communication between each process and the manager
-pub struct Worker<T: AtomType, A: AtomicPotentialTrait<T>> {
+pub(crate) struct Worker<T: AtomType, A: AtomicPotentialTrait<T>> {
     rx: mpsc::Receiver<M2W<T, A>>,
     tx: mpsc::Sender<W2M<T>>,
     thread_ids: Vec<thread::ThreadId>,
@@ -37,17 +37,15 @@ impl<T: AtomType, A: AtomicPotentialTrait<T>> Worker<T, A> {
     pub fn recv(&self) -> M2W<T, A> {
         self.rx.recv().expect("Disconnect error")
     }
-    pub fn try_recv(&self) -> Result<M2W<T, A>, mpsc::TryRecvError> {
-        self.rx.try_recv()
-    }
 
     fn run(&self) {
         let message = self.recv();
         match message {
             M2W::Run(f) => {
                 let mut sim = Simulation::new();
-                sim.connect(Box::new(self));
+                sim.connect(self);
                 f(sim);
+                self.send(W2M::Complete);
             }
             _ => panic!("Invalid communication"),
         };
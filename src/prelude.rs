@@ -2,6 +2,7 @@ pub use super::atom_type::AtomType;
 pub use super::atomic::AtomicPotentialTrait;
 pub use super::compute::{Compute, ComputeTrait};
 pub use super::container::{Container, BC};
+pub use super::integrators::Integrator;
 pub use super::jmd::Jmd;
 pub use super::lattice::Lattice;
 pub use super::region::{Rect, Region};
@@ -0,0 +1,101 @@
+use super::*;
+
+/// Region of the coordinates within any of the given regions. Its volume and surface area
+/// are estimated by random sampling.
+pub struct Union {
+    regions: Vec<Box<dyn Region>>,
+}
+impl Union {
+    pub fn new(regions: Vec<Box<dyn Region>>) -> Self {
+        assert!(!regions.is_empty(), "Union should have at least one region");
+        Self { regions }
+    }
+}
+impl Region for Union {
+    fn contains(&self, coord: &[f64; 3]) -> bool {
+        self.regions.iter().any(|r| r.contains(coord))
+    }
+    fn get_random_coord(&self) -> [f64; 3] {
+        random_coord_by_rejection(self)
+    }
+    fn bounding_box(&self) -> Rect {
+        self.regions
+            .iter()
+            .map(|r| r.bounding_box())
+            .reduce(|a, b| a.enclose(&b))
+            .expect("At least one region")
+    }
+    fn volume(&self) -> f64 {
+        estimate_volume(self)
+    }
+    fn surface_area(&self) -> f64 {
+        estimate_surface_area(self)
+    }
+}
+
+/// Region of the coordinates within all of the given regions. Its volume and surface area
+/// are estimated by random sampling.
+pub struct Intersection {
+    regions: Vec<Box<dyn Region>>,
+}
+impl Intersection {
+    pub fn new(regions: Vec<Box<dyn Region>>) -> Self {
+        assert!(
+            !regions.is_empty(),
+            "Intersection should have at least one region"
+        );
+        Self { regions }
+    }
+}
+impl Region for Intersection {
+    fn contains(&self, coord: &[f64; 3]) -> bool {
+        self.regions.iter().all(|r| r.contains(coord))
+    }
+    fn get_random_coord(&self) -> [f64; 3] {
+        random_coord_by_rejection(self)
+    }
+    fn bounding_box(&self) -> Rect {
+        self.regions
+            .iter()
+            .map(|r| r.bounding_box())
+            .reduce(|a, b| a.intersect(&b))
+            .expect("At least one region")
+    }
+    fn volume(&self) -> f64 {
+        estimate_volume(self)
+    }
+    fn surface_area(&self) -> f64 {
+        estimate_surface_area(self)
+    }
+}
+
+/// Region of the coordinates outside the given region, which is unbounded and best
+/// intersected with a bounded region
+pub struct Not {
+    region: Box<dyn Region>,
+}
+impl Not {
+    pub fn new(region: Box<dyn Region>) -> Self {
+        Self { region }
+    }
+}
+impl Region for Not {
+    fn contains(&self, coord: &[f64; 3]) -> bool {
+        !self.region.contains(coord)
+    }
+    /// # Panics
+    ///
+    /// Always, since the region is unbounded
+    fn get_random_coord(&self) -> [f64; 3] {
+        random_coord_by_rejection(self)
+    }
+    fn bounding_box(&self) -> Rect {
+        Rect::infinite()
+    }
+    fn volume(&self) -> f64 {
+        f64::INFINITY
+    }
+    fn surface_area(&self) -> f64 {
+        self.region.surface_area()
+    }
+}
@@ -0,0 +1,73 @@
+use std::f64::consts::PI;
+
+use super::*;
+use crate::utils::Axis;
+
+/// Cylindrical region with its axis parallel to one of the coordinate axes
+#[derive(Clone, Copy, Debug)]
+pub struct Cylinder {
+    axis: Axis,
+    /// Coordinates of the axis along the other two coordinate axes, in order
+    center: [f64; 2],
+    radius: f64,
+    /// Bounds of the cylinder along its axis
+    lo: f64,
+    hi: f64,
+}
+impl Cylinder {
+    /// A cylinder along `axis` between `lo` and `hi`, centered at `center` along the other
+    /// two axes in order (e.g., y and z for a cylinder along x)
+    pub fn new(axis: Axis, center: [f64; 2], radius: f64, lo: f64, hi: f64) -> Self {
+        assert!(radius > 0.0, "Radius should be positive, found {}", radius);
+        assert!(
+            lo < hi,
+            "Lower bound {} should be less than upper bound {}",
+            lo,
+            hi
+        );
+        Self {
+            axis,
+            center,
+            radius,
+            lo,
+            hi,
+        }
+    }
+    /// Indices of the axes normal to the cylinder axis
+    fn radial_axes(&self) -> [usize; 2] {
+        match self.axis {
+            Axis::X => [1, 2],
+            Axis::Y => [0, 2],
+            Axis::Z => [0, 1],
+        }
+    }
+}
+impl Region for Cylinder {
+    fn contains(&self, coord: &[f64; 3]) -> bool {
+        let [i, j] = self.radial_axes();
+        let [di, dj] = [coord[i] - self.center[0], coord[j] - self.center[1]];
+        let h = coord[self.axis.index()];
+        di * di + dj * dj < self.radius * self.radius && self.lo <= h && h < self.hi
+    }
+    fn get_random_coord(&self) -> [f64; 3] {
+        random_coord_by_rejection(self)
+    }
+    fn bounding_box(&self) -> Rect {
+        let [i, j] = self.radial_axes();
+        let mut lo = [0.0; 3];
+        let mut hi = [0.0; 3];
+        for (k, c) in [i, j].into_iter().zip(self.center) {
+            lo[k] = c - self.radius;
+            hi[k] = c + self.radius;
+        }
+        lo[self.axis.index()] = self.lo;
+        hi[self.axis.index()] = self.hi;
+        Rect::new(lo[0], hi[0], lo[1], hi[1], lo[2], hi[2])
+    }
+    fn volume(&self) -> f64 {
+        PI * self.radius * self.radius * (self.hi - self.lo)
+    }
+    fn surface_area(&self) -> f64 {
+        2.0 * PI * self.radius * (self.radius + self.hi - self.lo)
+    }
+}
@@ -1,6 +1,16 @@
+mod boolean;
+mod cylinder;
+mod plane;
 mod rect;
+mod sphere;
 
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+pub use boolean::{Intersection, Not, Union};
+pub use cylinder::Cylinder;
+pub use plane::Plane;
 pub use rect::Rect;
+pub use sphere::Sphere;
 
 /// A region of the simulation space
 pub trait Region {
@@ -14,3 +24,182 @@ pub trait Region {
     fn volume(&self) -> f64;
     fn surface_area(&self) -> f64;
 }
+
+/// Seed for the random numbers used to estimate volumes and surface areas, so that the
+/// estimates are reproducible
+const ESTIMATE_SEED: u64 = 12345;
+const NUM_VOLUME_SAMPLES: usize = 100_000;
+const NUM_SURFACE_CHORDS: usize = 10_000;
+/// Number of steps along the diameter of the sphere bounding the region taken when
+/// counting crossings of the surface
+const NUM_CHORD_STEPS: usize = 200;
+/// Number of random coordinates drawn within the bounding box of a region before giving
+/// up on finding one within it
+const MAX_REJECTIONS: usize = 1_000_000;
+
+fn dot(a: &[f64; 3], b: &[f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+/// Draw random coordinates within the bounding box until one is within the region
+///
+/// # Panics
+///
+/// If the region is unbounded, or if none of `MAX_REJECTIONS` coordinates are within it
+fn random_coord_by_rejection<R: Region + ?Sized>(region: &R) -> [f64; 3] {
+    let bounding_box = region.bounding_box();
+    assert!(
+        bounding_box.is_finite(),
+        "Cannot draw a random coordinate from an unbounded region"
+    );
+    for _ in 0..MAX_REJECTIONS {
+        let coord = bounding_box.get_random_coord();
+        if region.contains(&coord) {
+            return coord;
+        }
+    }
+    panic!(
+        "No random coordinate within the region after {} attempts, it may be empty",
+        MAX_REJECTIONS
+    );
+}
+
+/// Estimate the volume of a bounded region from the fraction of random coordinates in
+/// its bounding box that are within it
+fn estimate_volume<R: Region + ?Sized>(region: &R) -> f64 {
+    let bounding_box = region.bounding_box();
+    if !bounding_box.is_finite() {
+        return f64::INFINITY;
+    }
+    if bounding_box.volume() == 0.0 {
+        return 0.0;
+    }
+    let lo = bounding_box.lo();
+    let lengths = bounding_box.lengths();
+    let mut rng = StdRng::seed_from_u64(ESTIMATE_SEED);
+    let num_inside = (0..NUM_VOLUME_SAMPLES)
+        .filter(|_| region.contains(&[0, 1, 2].map(|k| lo[k] + rng.gen::<f64>() * lengths[k])))
+        .count();
+    bounding_box.volume() * num_inside as f64 / NUM_VOLUME_SAMPLES as f64
+}
+
+/// Estimate the surface area of a bounded region with the Cauchy-Crofton formula: the
+/// mean number of times that isotropic random lines through a ball cross the surface is
+/// twice its area divided by that of the ball.
+fn estimate_surface_area<R: Region + ?Sized>(region: &R) -> f64 {
+    let bounding_box = region.bounding_box();
+    if !bounding_box.is_finite() {
+        return f64::INFINITY;
+    }
+    let center = bounding_box.center();
+    let lengths = bounding_box.lengths();
+    let radius = 0.5 * dot(&lengths, &lengths).sqrt();
+    let step = 2.0 * radius / NUM_CHORD_STEPS as f64;
+    let mut rng = StdRng::seed_from_u64(ESTIMATE_SEED);
+
+    let mut num_crossings = 0;
+    for _ in 0..NUM_SURFACE_CHORDS {
+        // Uniform random direction, and an orthonormal basis of the plane normal to it
+        let cos_theta: f64 = rng.gen_range(-1.0..1.0);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let phi = rng.gen_range(0.0..std::f64::consts::TAU);
+        let (sin_phi, cos_phi) = phi.sin_cos();
+        let u = [sin_theta * cos_phi, sin_theta * sin_phi, cos_theta];
+        let e1 = [cos_theta * cos_phi, cos_theta * sin_phi, -sin_theta];
+        let e2 = [-sin_phi, cos_phi, 0.0];
+
+        // Uniform random point in the disk through the center, normal to the direction
+        let r = radius * rng.gen::<f64>().sqrt();
+        let (sin_alpha, cos_alpha) = rng.gen_range(0.0..std::f64::consts::TAU).sin_cos();
+        let half_chord = (radius * radius - r * r).sqrt();
+        let start = [0, 1, 2]
+            .map(|k| center[k] + r * (cos_alpha * e1[k] + sin_alpha * e2[k]) - half_chord * u[k]);
+
+        let num_steps = (2.0 * half_chord / step).ceil() as usize;
+        let mut was_inside = region.contains(&start);
+        for i in 1..=num_steps {
+            let t = (i as f64 * step).min(2.0 * half_chord);
+            let inside = region.contains(&[0, 1, 2].map(|k| start[k] + t * u[k]));
+            if inside != was_inside {
+                num_crossings += 1;
+            }
+            was_inside = inside;
+        }
+    }
+    let ball_area = 4.0 * std::f64::consts::PI * radius * radius;
+    0.5 * ball_area * num_crossings as f64 / NUM_SURFACE_CHORDS as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Axis;
+
+    fn assert_close(estimate: f64, exact: f64, tolerance: f64) {
+        assert!(
+            (estimate - exact).abs() < tolerance * exact,
+            "Estimate {} differs from {} by more than {}",
+            estimate,
+            exact,
+            tolerance
+        );
+    }
+
+    #[test]
+    fn test_estimates() {
+        let sphere = Sphere::new([1.0, 2.0, 3.0], 1.5);
+        assert_close(estimate_volume(&sphere), sphere.volume(), 0.02);
+        assert_close(estimate_surface_area(&sphere), sphere.surface_area(), 0.05);
+        let rect = Rect::new(0.0, 1.0, 0.0, 2.0, 0.0, 0.5);
+        assert_close(estimate_surface_area(&rect), rect.surface_area(), 0.05);
+    }
+
+    #[test]
+    fn test_boolean_regions() {
+        let sphere = Sphere::new([0.0; 3], 1.0);
+        let plane = Plane::new([0.0; 3], [0.0, 0.0, 1.0]);
+        let hemisphere = Intersection::new(vec![Box::new(sphere), Box::new(plane)]);
+        assert!(hemisphere.contains(&[0.0, 0.0, 0.5]));
+        assert!(!hemisphere.contains(&[0.0, 0.0, -0.5]));
+        assert_eq!(hemisphere.bounding_box().lo(), [-1.0, -1.0, 0.0]);
+        assert_close(hemisphere.volume(), 0.5 * sphere.volume(), 0.02);
+        // Half of the sphere and the disk it is cut by
+        assert_close(hemisphere.surface_area(), 3.0 * std::f64::consts::PI, 0.05);
+        assert!(hemisphere.contains(&hemisphere.get_random_coord()));
+
+        let cylinder = Cylinder::new(Axis::Z, [0.0, 0.0], 0.5, -2.0, 2.0);
+        let union = Union::new(vec![Box::new(sphere), Box::new(cylinder)]);
+        assert!(union.contains(&[0.0, 0.0, 1.5]) && union.contains(&[0.9, 0.0, 0.0]));
+        let void = Intersection::new(vec![
+            Box::new(Rect::new(-3.0, 3.0, -3.0, 3.0, -3.0, 3.0)),
+            Box::new(Not::new(Box::new(union))),
+        ]);
+        assert!(!void.contains(&[0.0, 0.0, 1.5]) && void.contains(&[2.0, 0.0, 0.0]));
+    }
+
+    fn disjoint_spheres() -> Intersection {
+        Intersection::new(vec![
+            Box::new(Sphere::new([0.0; 3], 1.0)),
+            Box::new(Sphere::new([3.0, 0.0, 0.0], 1.0)),
+        ])
+    }
+
+    #[test]
+    fn test_empty_intersection() {
+        let empty = disjoint_spheres();
+        assert_eq!(empty.bounding_box().lengths()[0], 0.0);
+        assert_eq!(empty.volume(), 0.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "it may be empty")]
+    fn test_empty_random_coord() {
+        disjoint_spheres().get_random_coord();
+    }
+
+    #[test]
+    #[should_panic(expected = "unbounded region")]
+    fn test_unbounded_random_coord() {
+        Plane::new([0.0; 3], [0.0, 0.0, 1.0]).get_random_coord();
+    }
+}
@@ -0,0 +1,50 @@
+use super::*;
+use crate::utils::Axis;
+
+/// Half-space on the side of a plane that its normal points toward
+#[derive(Clone, Copy, Debug)]
+pub struct Plane {
+    point: [f64; 3],
+    /// Unit normal
+    normal: [f64; 3],
+}
+impl Plane {
+    /// The half-space bounded by the plane through `point` with the given normal, on the
+    /// side the normal points toward
+    pub fn new(point: [f64; 3], normal: [f64; 3]) -> Self {
+        let length = dot(&normal, &normal).sqrt();
+        assert!(length > 0.0, "Plane normal should be nonzero");
+        Self {
+            point,
+            normal: normal.map(|n| n / length),
+        }
+    }
+}
+impl Region for Plane {
+    fn contains(&self, coord: &[f64; 3]) -> bool {
+        let d = [0, 1, 2].map(|k| coord[k] - self.point[k]);
+        dot(&d, &self.normal) >= 0.0
+    }
+    /// # Panics
+    ///
+    /// Always, since the half-space is unbounded
+    fn get_random_coord(&self) -> [f64; 3] {
+        random_coord_by_rejection(self)
+    }
+    /// Unbounded, except on one side along an axis the normal is parallel to
+    fn bounding_box(&self) -> Rect {
+        let mut rect = Rect::infinite();
+        let nonzero: Vec<usize> = (0..3).filter(|&k| self.normal[k] != 0.0).collect();
+        if let [k] = nonzero[..] {
+            let axis = [Axis::X, Axis::Y, Axis::Z][k];
+            rect.set_bound(axis.direction(self.normal[k] < 0.0), self.point[k]);
+        }
+        rect
+    }
+    fn volume(&self) -> f64 {
+        f64::INFINITY
+    }
+    fn surface_area(&self) -> f64 {
+        f64::INFINITY
+    }
+}
@@ -83,6 +83,13 @@ impl Rect {
     pub fn lengths(&self) -> [f64; 3] {
         [self.lx(), self.ly(), self.lz()]
     }
+    pub fn center(&self) -> [f64; 3] {
+        [
+            0.5 * (self.xlo + self.xhi),
+            0.5 * (self.ylo + self.yhi),
+            0.5 * (self.zlo + self.zhi),
+        ]
+    }
 
     // Generic getters
     pub fn get_length(&self, axis: Axis) -> f64 {
@@ -116,16 +123,50 @@ impl Rect {
             Direction::Zhi => self.zhi = bound,
         };
     }
+    /// The overlap of this and the other, which has zero length along any axis where they
+    /// do not overlap
     pub fn intersect(&self, other: &Self) -> Self {
+        let xlo = self.xlo.max(other.xlo);
+        let ylo = self.ylo.max(other.ylo);
+        let zlo = self.zlo.max(other.zlo);
+        Self {
+            xlo,
+            xhi: self.xhi.min(other.xhi).max(xlo),
+            ylo,
+            yhi: self.yhi.min(other.yhi).max(ylo),
+            zlo,
+            zhi: self.zhi.min(other.zhi).max(zlo),
+        }
+    }
+    /// The smallest rectangular prism containing both this and the other
+    pub fn enclose(&self, other: &Self) -> Self {
         Self {
             xlo: self.xlo.min(other.xlo),
-            xhi: self.xhi.min(other.xhi),
+            xhi: self.xhi.max(other.xhi),
             ylo: self.ylo.min(other.ylo),
-            yhi: self.yhi.min(other.yhi),
+            yhi: self.yhi.max(other.yhi),
             zlo: self.zlo.min(other.zlo),
-            zhi: self.zhi.min(other.zhi),
+            zhi: self.zhi.max(other.zhi),
         }
     }
+    /// Unbounded along every axis
+    pub fn infinite() -> Self {
+        Self::new(
+            f64::NEG_INFINITY,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::INFINITY,
+            f64::NEG_INFINITY,
+            f64::INFINITY,
+        )
+    }
+    /// Whether all bounds are finite
+    pub fn is_finite(&self) -> bool {
+        self.lo()
+            .iter()
+            .chain(self.hi().iter())
+            .all(|x| x.is_finite())
+    }
 }
 impl Region for Rect {
     fn contains(&self, coord: &[f64; 3]) -> bool {
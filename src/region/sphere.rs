@@ -0,0 +1,42 @@
+use std::f64::consts::PI;
+
+use super::*;
+
+/// Spherical region
+#[derive(Clone, Copy, Debug)]
+pub struct Sphere {
+    center: [f64; 3],
+    radius: f64,
+}
+impl Sphere {
+    pub fn new(center: [f64; 3], radius: f64) -> Self {
+        assert!(radius > 0.0, "Radius should be positive, found {}", radius);
+        Self { center, radius }
+    }
+    pub fn center(&self) -> [f64; 3] {
+        self.center
+    }
+    pub fn radius(&self) -> f64 {
+        self.radius
+    }
+}
+impl Region for Sphere {
+    fn contains(&self, coord: &[f64; 3]) -> bool {
+        let d = [0, 1, 2].map(|k| coord[k] - self.center[k]);
+        dot(&d, &d) < self.radius * self.radius
+    }
+    fn get_random_coord(&self) -> [f64; 3] {
+        random_coord_by_rejection(self)
+    }
+    fn bounding_box(&self) -> Rect {
+        let [x, y, z] = self.center;
+        let r = self.radius;
+        Rect::new(x - r, x + r, y - r, y + r, z - r, z + r)
+    }
+    fn volume(&self) -> f64 {
+        4.0 / 3.0 * PI * self.radius.powi(3)
+    }
+    fn surface_area(&self) -> f64 {
+        4.0 * PI * self.radius * self.radius
+    }
+}
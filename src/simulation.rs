@@ -1,19 +1,31 @@
-use std::{rc::Rc, thread};
+use std::{
+    collections::HashSet,
+    fs::File,
+    io::{self, BufReader},
+    mem,
+    ops::Range,
+    thread,
+};
 
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use rand_distr::Distribution;
 
 use crate::{
-    atom_type::AtomType,
+    atom_type::{AtomType, Basic},
     atomic::AtomicPotentialTrait,
     atoms::Atoms,
     compute::{Compute, ComputeTrait},
     container::{Container, BC},
+    group::{Groups, ALL_GROUPBIT},
     integrators::{Integrator, Verlet},
     neighbor::NeighborList,
-    output::{Output, OutputSpec, Value},
-    parallel::{comm, Domain, Worker, M2W, W2M},
+    output::{
+        read_container, read_lammps_data, read_restart_atoms, read_restart_header, write_container,
+        DumpColumn, DumpFormat, DumpSpec, Output, OutputSpec, RestartReader, RestartWriter, Value,
+    },
+    parallel::{comm, Domain, Worker, W2M},
     region::{Rect, Region},
-    utils::{Axis, KeyedVec},
+    utils::{computations::distance_squared, Axis, Delete, KeyedVec, Types},
 };
 type ComputeVec = KeyedVec<String, Compute>;
 
@@ -24,6 +36,12 @@ struct NLUpdateSettings {
     pub check: bool,
 }
 
+struct DumpSettings {
+    pub path: String,
+    pub every: usize,
+    pub groupbit: u32,
+}
+
 /// The main simulation class in JMD, with one copy held by each process.
 pub struct Simulation<'a, T, A>
 where
@@ -31,7 +49,7 @@ where
     A: AtomicPotentialTrait<T>,
 {
     pub(crate) atoms: Atoms<T>,
-    container: Rc<Container>,
+    container: Container,
     atomic_potential: A,
     neighbor_list: NeighborList,
     domain: Domain<'a, T, A>,
@@ -39,8 +57,16 @@ where
     pos_at_prev_nl_build: Vec<[f64; 3]>,
     computes: ComputeVec,
     timestep: f64,
+    step: usize,
     forces: Vec<[f64; 3]>,
     nl_update_settings: NLUpdateSettings,
+    integrator: Box<dyn Integrator<T, A>>,
+    integrator_groupbit: u32,
+    dumps: Vec<DumpSettings>,
+    groups: Groups,
+    /// The number of atoms of each type over all processes, as of the last neighbor
+    /// list build
+    type_counts: Vec<usize>,
 }
 impl<'a, T, A> Simulation<'a, T, A>
 where
@@ -52,19 +78,10 @@ where
         let timestep = 1.0;
         let atomic_potential = A::new();
         let dist = atomic_potential.cutoff_distance() * 3.0;
-        let container = Rc::new(Container::new(
-            0.0,
-            dist,
-            0.0,
-            dist,
-            0.0,
-            dist,
-            BC::PP,
-            BC::PP,
-            BC::PP,
-        ));
-        let neighbor_list =
-            NeighborList::new(container.clone(), atomic_potential.cutoff_distance(), 1.0);
+        let container = Container::new(0.0, dist, 0.0, dist, 0.0, dist, BC::PP, BC::PP, BC::PP);
+        let mut neighbor_list =
+            NeighborList::new(&container, atomic_potential.cutoff_distance(), 1.0);
+        neighbor_list.set_full(atomic_potential.full_neighbor_list());
         Self {
             atoms: Atoms::new(),
             container,
@@ -75,6 +92,7 @@ where
             pos_at_prev_nl_build: Vec::new(),
             computes: KeyedVec::new(),
             timestep,
+            step: 0,
             forces: Vec::new(),
             nl_update_settings: NLUpdateSettings {
                 last_update_step: 0,
@@ -82,15 +100,26 @@ where
                 delay: 0,
                 check: true,
             },
+            integrator: Box::new(Verlet {}),
+            integrator_groupbit: ALL_GROUPBIT,
+            dumps: Vec::new(),
+            groups: Groups::new(),
+            type_counts: Vec::new(),
         }
     }
 
     /// Initializes the simulation from a worker thread
-    pub fn connect(&mut self, worker: Box<&'a Worker<T, A>>) {
-        self.domain.init(&self.container, worker)
+    pub(crate) fn connect(&mut self, worker: &'a Worker<T, A>) {
+        self.domain.init(&self.container, Box::new(worker))
     }
 
     // Getters
+    pub fn atoms(&self) -> &Atoms<T> {
+        &self.atoms
+    }
+    pub fn mut_atoms(&mut self) -> &mut Atoms<T> {
+        &mut self.atoms
+    }
     pub fn container(&self) -> &Container {
         &self.container
     }
@@ -103,6 +132,9 @@ where
     pub(crate) fn domain(&self) -> &Domain<T, A> {
         &self.domain
     }
+    pub(crate) fn mut_domain(&mut self) -> &mut Domain<'a, T, A> {
+        &mut self.domain
+    }
     pub(crate) fn nlocal(&self) -> usize {
         self.atoms.nlocal
     }
@@ -112,7 +144,11 @@ where
     pub fn timestep(&self) -> f64 {
         self.timestep
     }
-    pub(crate) fn forces(&self) -> &Vec<[f64; 3]> {
+    /// The current step, counted over all runs
+    pub fn step(&self) -> usize {
+        self.step
+    }
+    pub fn forces(&self) -> &Vec<[f64; 3]> {
         &self.forces
     }
     pub(crate) fn mut_forces(&mut self) -> &mut Vec<[f64; 3]> {
@@ -125,16 +161,37 @@ where
     pub fn nl(&self) -> &NeighborList {
         &self.neighbor_list
     }
+    pub fn integrator(&self) -> &dyn Integrator<T, A> {
+        self.integrator.as_ref()
+    }
+    pub(crate) fn mut_rng(&mut self) -> &mut StdRng {
+        self.domain.mut_rng()
+    }
 
     // Setters
     pub fn set_container(&mut self, container: Container) {
-        self.container = Rc::new(container);
-        self.domain.reset_subdomain(&self.container.rect());
+        self.container = container;
+        self.domain.reset_subdomain(&self.container);
         self.neighbor_list = NeighborList::new(
-            self.container.clone(),
+            &self.container,
             self.atomic_potential.cutoff_distance(),
             self.neighbor_list.skin_distance(),
         );
+        self.neighbor_list
+            .set_full(self.atomic_potential.full_neighbor_list());
+    }
+    /// Scale the simulation box about its center by the given factor along each axis,
+    /// moving the atoms with it. Must be called with the same factors by every process.
+    pub fn scale_box(&mut self, factors: [f64; 3]) {
+        let center = self.container.rect().center();
+        self.container.scale(factors);
+        self.atoms.positions.iter_mut().for_each(|p| {
+            for k in 0..3 {
+                p[k] = center[k] + (p[k] - center[k]) * factors[k];
+            }
+        });
+        self.domain.reset_subdomain(&self.container);
+        self.neighbor_list.set_container(&self.container);
     }
     pub fn set_output(&mut self, every: usize, output_keys: Vec<&str>) {
         let output_specs: Vec<OutputSpec> = output_keys
@@ -159,6 +216,65 @@ where
         self.domain
             .send_to_main(W2M::SetupOutput(self.output.values.clone()));
     }
+    /// Write the given per-atom columns of all atoms to a LAMMPS-style text dump file
+    /// every given number of steps, sorted by atom id. Columns are named as in LAMMPS:
+    /// "id", "type", "x", "y", "z", "vx", "vy", "vz", "q". Ids and types are counted
+    /// from 1, as LAMMPS and its readers expect.
+    ///
+    /// ```rust
+    /// use jmd::{atom_type::Basic, atomic::LJCut, prelude::*};
+    ///
+    /// fn run(mut sim: Simulation<Basic, LJCut>) {
+    ///     sim.add_dump(100, "traj.dump", vec!["id", "type", "x", "y", "z"]);
+    /// }
+    /// ```
+    pub fn add_dump(&mut self, every: usize, path: &str, columns: Vec<&str>) {
+        assert!(every > 0, "Dump frequency should be positive");
+        let columns: Vec<DumpColumn> = columns
+            .iter()
+            .map(|&name| DumpColumn::from_name(name).expect("Invalid dump column"))
+            .collect();
+        self.dumps.push(DumpSettings {
+            path: String::from(path),
+            every,
+            groupbit: ALL_GROUPBIT,
+        });
+        self.domain.send_to_main_once(W2M::SetupDump(DumpSpec {
+            every,
+            path: String::from(path),
+            format: DumpFormat::Lammps(columns),
+        }));
+    }
+    /// Write all atoms to an extended XYZ file every given number of steps, sorted by atom
+    /// id, with the species name of each atom type and the box as the lattice. Positions,
+    /// velocities and atom ids are written.
+    pub fn add_extxyz_dump(&mut self, every: usize, path: &str, species: Vec<&str>) {
+        assert!(every > 0, "Dump frequency should be positive");
+        assert!(
+            species.len() >= self.atoms.num_types(),
+            "A species name should be given for each atom type"
+        );
+        self.dumps.push(DumpSettings {
+            path: String::from(path),
+            every,
+            groupbit: ALL_GROUPBIT,
+        });
+        self.domain.send_to_main_once(W2M::SetupDump(DumpSpec {
+            every,
+            path: String::from(path),
+            format: DumpFormat::ExtXyz(species.iter().map(|&s| String::from(s)).collect()),
+        }));
+    }
+    /// Restrict the dump written to the given path to the atoms of a group
+    pub fn set_dump_group(&mut self, path: &str, group: &str) {
+        let groupbit = self.groups.expect_bit(group);
+        let dump = self
+            .dumps
+            .iter_mut()
+            .find(|d| d.path == path)
+            .unwrap_or_else(|| panic!("No dump written to {}", path));
+        dump.groupbit = groupbit;
+    }
     pub fn add_compute(&mut self, id: &str, compute: Compute) {
         self.computes.add(String::from(id), compute)
     }
@@ -174,6 +290,8 @@ where
             self.neighbor_list
                 .set_force_distance(atomic_potential.cutoff_distance());
         }
+        self.neighbor_list
+            .set_full(atomic_potential.full_neighbor_list());
         self.atomic_potential = atomic_potential;
         self.atomic_potential.set_num_types(self.atoms.num_types());
     }
@@ -186,6 +304,37 @@ where
         self.timestep = timestep;
     }
 
+    /// Set the integrator used to step the simulation forward (velocity-verlet by default).
+    ///
+    /// ```rust
+    /// use jmd::{atom_type::Basic, atomic::LJCut, integrators::NoseHoover, prelude::*};
+    ///
+    /// fn run(mut sim: Simulation<Basic, LJCut>) {
+    ///     // Hold the temperature at 1.0 with a damping time of 0.5 and a chain of 3
+    ///     sim.set_integrator(NoseHoover::new(1.0, 0.5, 3));
+    /// }
+    /// ```
+    pub fn set_integrator<I: Integrator<T, A> + 'static>(&mut self, integrator: I) {
+        self.integrator = Box::new(integrator);
+    }
+
+    /// Restrict the integrator to the atoms of a group, so that other atoms keep their
+    /// positions and velocities
+    pub fn set_integrator_group(&mut self, group: &str) {
+        self.integrator_groupbit = self.groups.expect_bit(group);
+    }
+    /// The bit of the group of atoms moved by the integrator
+    pub(crate) fn integrator_groupbit(&self) -> u32 {
+        self.integrator_groupbit
+    }
+    /// Seed the random number generators used by `set_temperature` and the Langevin
+    /// thermostat. The velocities set by `set_temperature` do not depend on the number of
+    /// threads, but each process draws the Langevin noise from its own stream, so a run is
+    /// reproducible given the same seed and number of threads.
+    pub fn set_seed(&mut self, seed: u64) {
+        self.domain.seed_rng(seed);
+    }
+
     pub fn set_atomic_coeff(&mut self, typei: usize, typej: usize, coeff: &A::Coeff) {
         self.atomic_potential.set_coeff(typei, typej, coeff);
     }
@@ -204,140 +353,424 @@ where
         self.neighbor_list.set_skin_distance(skin_distance);
     }
 
-    // Atoms methods
+    // Restart methods
 
-    /// Add a given number of atoms of the given type with the given region
-    pub fn add_random_atoms(&mut self, rect: &Rect, num_atoms: usize, atom_type: usize) {
-        let sub_region = rect.intersect(self.domain.subdomain());
-        let mut my_natoms =
-            (sub_region.volume() / rect.volume() * num_atoms as f64).floor() as usize;
-        self.domain.send_to_main(W2M::Sum(my_natoms));
-        let message = self.domain.recv_from_main();
-        let added_natoms = match message {
-            M2W::SumResult(sum) => sum,
-            _ => panic!("Invalid message"),
-        };
-        if self.domain.proc_index() < num_atoms - added_natoms {
-            my_natoms += 1;
+    /// Write the atoms, container, timestep, current step, groups, atomic potential
+    /// coefficients and integrator state to a binary restart file. Must be called by every process.
+    ///
+    /// The atom types, computes, outputs, neighbor list settings and random number
+    /// generators are not stored.
+    pub fn write_restart(&self, path: &str) {
+        let mut state: Vec<u8> = Vec::new();
+        self.write_state(&mut RestartWriter::new(&mut state))
+            .expect("Could not serialize simulation state");
+        let state = (self.domain.proc_index() == 0).then_some(state);
+        self.domain.send_to_main(W2M::WriteRestart(
+            String::from(path),
+            state,
+            self.atoms.owned_atoms(),
+        ));
+    }
+    /// Read a restart file written by `write_restart`, with any number of processes. Must
+    /// be called by every process, after setting the atom types and the same kind of
+    /// atomic potential and integrator as when it was written. Panics if the stored
+    /// potential or integrator state does not match those set.
+    ///
+    /// ```rust,no_run
+    /// use jmd::{atom_type::Basic, atomic::LJCut, integrators::NoseHoover, prelude::*};
+    ///
+    /// fn run(mut sim: Simulation<Basic, LJCut>) {
+    ///     sim.set_atom_types(vec![Basic::new(1.0)]);
+    ///     sim.set_integrator(NoseHoover::new(1.0, 0.5, 3));
+    ///     sim.read_restart("run1.restart");
+    ///     sim.run(1000);
+    ///     sim.write_restart("run2.restart");
+    /// }
+    /// ```
+    pub fn read_restart(&mut self, path: &str) {
+        let file = File::open(path).expect("Could not open restart file");
+        let mut file = BufReader::new(file);
+        read_restart_header(&mut file).expect("Invalid restart file");
+        self.read_state(&mut RestartReader::new(&mut file))
+            .expect("Invalid restart file");
+    }
+    fn write_state(&self, writer: &mut RestartWriter) -> io::Result<()> {
+        writer.write_usize(self.step)?;
+        writer.write_f64(self.timestep)?;
+        write_container(writer, &self.container)?;
+        writer.write_usize(self.groups.names().len())?;
+        for name in self.groups.names() {
+            writer.write_string(name)?;
         }
+        writer.write_section(|w| self.atomic_potential.write_restart(w))?;
+        writer.write_section(|w| self.integrator.write_restart(self, w))
+    }
+    fn read_state(&mut self, reader: &mut RestartReader) -> io::Result<()> {
+        self.step = reader.read_usize()?;
+        self.set_timestep(reader.read_f64()?);
+        self.set_container(read_container(reader)?);
+        self.groups = Groups::new();
+        let num_groups = reader.read_usize()?;
+        for _ in 0..num_groups {
+            self.groups.bit_or_add(&reader.read_string()?);
+        }
+        reader.read_section("atomic potential", |r| {
+            self.atomic_potential.read_restart(r)
+        })?;
+        self.neighbor_list
+            .set_force_distance(self.atomic_potential.cutoff_distance());
 
-        let atoms = &mut self.atoms;
-        let atom_id = match atoms.ids().iter().max() {
-            Some(j) => j + 1,
-            None => 0,
-        };
-        atoms.ids.extend(atom_id..atom_id + my_natoms);
-        atoms.types.reserve(my_natoms);
-        atoms.positions.reserve(my_natoms);
-        atoms.velocities.reserve(my_natoms);
-        atoms.nlocal += my_natoms;
+        let mut integrator: Box<dyn Integrator<T, A>> =
+            mem::replace(&mut self.integrator, Box::new(Verlet {}));
+        let result = reader.read_section("integrator", |r| integrator.read_restart(self, r));
+        self.integrator = integrator;
+        result?;
+
+        // Each process keeps the atoms within its subdomain, after wrapping them into the
+        // box, so that any number of processes can read the same file. Atoms are then
+        // exchanged as usual when the neighbor list is first built.
+        let mut atoms = read_restart_atoms(reader, |position| self.owns_position(position))?;
+        assert!(
+            atoms.types.iter().all(|&t| t < self.atoms.num_types()),
+            "Atom types should be set before reading a restart file"
+        );
+        atoms.atom_types = mem::take(&mut self.atoms.atom_types);
+        self.atoms = atoms;
+        self.forces.clear();
+        Ok(())
+    }
 
-        for _i in 0..my_natoms {
-            atoms.types.push(atom_type);
-            atoms.velocities.push([0.0, 0.0, 0.0]);
-            atoms.positions.push(sub_region.get_random_coord())
+    /// Whether the given position, once wrapped into the box along periodic axes and
+    /// clamped to it along other axes, is within the subdomain of this process
+    fn owns_position(&self, position: &[f64; 3]) -> bool {
+        let mut lamda = self.container.to_lamda(&self.container.wrap(position));
+        for (k, axis) in [Axis::X, Axis::Y, Axis::Z].into_iter().enumerate() {
+            if !self.container.is_periodic(axis) {
+                lamda[k] = lamda[k].clamp(0.0, 1.0 - f64::EPSILON);
+            }
         }
+        self.domain.subdomain().contains(&lamda)
     }
-    /// Add atoms of the given type at the given coordinates
-    pub fn add_atoms(&mut self, atom_type: usize, coords: Vec<[f64; 3]>) {
-        let atoms = &mut self.atoms;
-        let num_atoms = coords.len();
-        let atom_id = match atoms.ids().iter().max() {
+    /// Whether the given position is within the subdomain of this process
+    fn in_subdomain(&self, position: &[f64; 3]) -> bool {
+        self.domain
+            .subdomain()
+            .contains(&self.container.to_lamda(position))
+    }
+
+    // Per-atom methods
+
+    /// The potential energy of each owned atom, with the energy of each pair split evenly
+    /// between its atoms and contributions to ghost atoms communicated back to their
+    /// owners. Must be called by every process.
+    pub fn per_atom_energy(&self) -> Vec<f64> {
+        let energies = self
+            .atomic_potential
+            .compute_per_atom_energy(&self.atoms, &self.neighbor_list)
+            .expect("Atomic potential does not support per-atom energies");
+        let mut energies: Vec<[f64; 1]> = energies.into_iter().map(|e| [e]).collect();
+        comm::reverse_comm_values(&self.domain, &mut energies);
+        energies
+            .into_iter()
+            .take(self.nlocal())
+            .map(|[e]| e)
+            .collect()
+    }
+    /// The virial tensor of each owned atom, ordered [xx, yy, zz, xy, xz, yz], with the
+    /// virial of each pair split evenly between its atoms and contributions to ghost atoms
+    /// communicated back to their owners. Dividing by a volume per atom gives the negative
+    /// of the per-atom stress. Must be called by every process.
+    pub fn per_atom_virial(&self) -> Vec<[f64; 6]> {
+        let mut virials = self
+            .atomic_potential
+            .compute_per_atom_virial(&self.atoms, &self.neighbor_list)
+            .expect("Atomic potential does not support per-atom virials");
+        comm::reverse_comm_values(&self.domain, &mut virials);
+        virials.truncate(self.nlocal());
+        virials
+    }
+
+    // Group methods
+
+    /// The names of the groups and their bits in the per-atom group masks
+    pub fn groups(&self) -> &Groups {
+        &self.groups
+    }
+    /// Add the owned atoms of the given types to a group, creating it if needed
+    ///
+    /// Groups are static: atoms added to the simulation afterwards are not included.
+    ///
+    /// ```rust
+    /// use jmd::{atom_type::Basic, atomic::LJCut, prelude::*, region::Sphere, utils::Types};
+    ///
+    /// fn run(mut sim: Simulation<Basic, LJCut>) {
+    ///     sim.group_types("solvent", Types::Range(1..3));
+    ///     sim.group_region("particle", &Sphere::new([0.0; 3], 5.0));
+    ///     sim.add_compute("TempSolvent", Compute::Temperature.in_group("solvent"));
+    /// }
+    /// ```
+    pub fn group_types(&mut self, group: &str, types: Types) {
+        let types = types.to_range();
+        self.add_to_group(group, |atoms, i| types.contains(&atoms.types[i]));
+    }
+    /// Add the owned atoms with ids in the given range to a group, creating it if needed
+    pub fn group_ids(&mut self, group: &str, ids: Range<usize>) {
+        self.add_to_group(group, |atoms, i| ids.contains(&atoms.ids[i]));
+    }
+    /// Add the owned atoms within the given region to a group, creating it if needed
+    pub fn group_region(&mut self, group: &str, region: &impl Region) {
+        self.add_to_group(group, |atoms, i| region.contains(&atoms.positions[i]));
+    }
+    fn add_to_group(&mut self, group: &str, include: impl Fn(&Atoms<T>, usize) -> bool) {
+        let groupbit = self.groups.bit_or_add(group);
+        for i in 0..self.nlocal() {
+            if include(&self.atoms, i) {
+                self.atoms.masks[i] |= groupbit;
+            }
+        }
+    }
+    /// Set the charge of the owned atoms in the given group. Charges are zero unless set,
+    /// or read from a restart or LAMMPS data file.
+    ///
+    /// ```rust
+    /// use jmd::{atom_type::Basic, atomic::Ewald, prelude::*, utils::Types};
+    ///
+    /// fn run(mut sim: Simulation<Basic, Ewald>) {
+    ///     sim.group_types("Na", Types::One(0));
+    ///     sim.group_types("Cl", Types::One(1));
+    ///     sim.set_charge("Na", 1.0);
+    ///     sim.set_charge("Cl", -1.0);
+    /// }
+    /// ```
+    pub fn set_charge(&mut self, group: &str, charge: f64) {
+        let groupbit = self.groups.expect_bit(group);
+        for i in 0..self.nlocal() {
+            if self.atoms.in_group(i, groupbit) {
+                self.atoms.charges[i] = charge;
+            }
+        }
+    }
+    /// The long-range corrections to the energy and the trace of the virial of the
+    /// atomic potential, which are global, so they are only attributed to the first
+    /// process
+    pub(crate) fn tail_correction(&self) -> [f64; 2] {
+        if self.domain.proc_index() != 0 {
+            return [0.0, 0.0];
+        }
+        self.atomic_potential
+            .tail_correction(&self.type_counts, self.container.rect().volume())
+    }
+    /// The number of atoms in the group with the given bit, over all processes. Must be
+    /// called by every process.
+    pub(crate) fn group_count(&self, groupbit: u32) -> usize {
+        if groupbit == ALL_GROUPBIT {
+            return self.atoms.num_atoms_global();
+        }
+        let count = (0..self.nlocal())
+            .filter(|&i| self.atoms.in_group(i, groupbit))
+            .count();
+        self.domain.sum(count)
+    }
+
+    // Atoms methods
+
+    /// Add a given number of atoms of the given type with the given region. Must be
+    /// called by every process.
+    ///
+    /// The positions are drawn identically on every process from a shared seed, and each
+    /// process keeps those within its subdomain.
+    pub fn add_random_atoms(&mut self, rect: &Rect, num_atoms: usize, atom_type: usize) {
+        let my_seed = if self.domain.proc_index() == 0 {
+            self.domain.mut_rng().gen::<u32>() as usize
+        } else {
+            0
+        };
+        let mut rng = StdRng::seed_from_u64(self.domain.sum(my_seed) as u64);
+        let lo = rect.lo();
+        let lengths = rect.lengths();
+        let coords: Vec<[f64; 3]> = (0..num_atoms)
+            .map(|_| [0, 1, 2].map(|k| lo[k] + rng.gen::<f64>() * lengths[k]))
+            .collect();
+        self.add_atoms(vec![atom_type; num_atoms], coords);
+    }
+    /// Add atoms of the given types at the given coordinates, such as those of a lattice
+    /// from `Lattice::coords_within_region`. Must be called with the same atoms by every
+    /// process, each keeping those within its subdomain.
+    ///
+    /// The new atoms are numbered from one past the highest id over all processes.
+    pub fn add_atoms(&mut self, types: Vec<usize>, coords: Vec<[f64; 3]>) {
+        assert_eq!(
+            types.len(),
+            coords.len(),
+            "One type should be given per coordinate"
+        );
+        let owned: Vec<bool> = coords.iter().map(|c| self.in_subdomain(c)).collect();
+        let next_id = match self.atoms.ids().iter().max() {
             Some(j) => j + 1,
             None => 0,
         };
+        let atom_id = self.domain.max_floats(vec![next_id as f64])[0] as usize;
+        let atoms = &mut self.atoms;
+        let num_atoms = coords.len();
 
         atoms.ids.reserve(num_atoms);
         atoms.types.reserve(num_atoms);
         atoms.positions.reserve(num_atoms);
         atoms.velocities.reserve(num_atoms);
+        atoms.masks.reserve(num_atoms);
+        atoms.charges.reserve(num_atoms);
 
         let mut atoms_added = 0;
         coords
             .iter()
             .enumerate()
-            .filter(|(_i, coord)| self.domain.subdomain().contains(coord))
+            .filter(|&(i, _coord)| owned[i])
             .for_each(|(i, coord)| {
                 atoms_added += 1;
                 atoms.ids.push(atom_id + i);
-                atoms.types.push(atom_type);
+                atoms.types.push(types[i]);
                 atoms.positions.push(*coord);
                 atoms.velocities.push([0.0, 0.0, 0.0]);
+                atoms.masks.push(ALL_GROUPBIT);
+                atoms.charges.push(0.0);
             });
         atoms.nlocal += atoms_added;
         atoms.num_atoms_global += coords.len();
     }
-    /// Set the temperature
-    /// TODO: move to simulation, make work across multiple processes
+    /// Set the velocities of the owned atoms from a Maxwell-Boltzmann distribution at the
+    /// given temperature. Must be called by every process.
+    ///
+    /// Each atom draws its velocity from its own stream, seeded by its id and a seed
+    /// shared by the processes, so that the velocities given by `set_seed` do not depend
+    /// on the number of threads. The velocities are then shifted so that the center of mass
+    /// is at rest, and scaled to the exact temperature, with sums over all processes.
     pub fn set_temperature(&mut self, temperature: f64) {
+        let my_seed = if self.domain.proc_index() == 0 {
+            self.domain.mut_rng().gen::<u32>() as usize
+        } else {
+            0
+        };
+        let seed = self.domain.sum(my_seed) as u64;
+        let dist = rand_distr::Normal::new(0.0, 1.0).expect("Valid distribution");
+        let nlocal = self.nlocal();
         let atoms = &mut self.atoms;
-        let nlocal = atoms.num_local_atoms();
-        let mut rng = rand::thread_rng();
-        let dist = rand_distr::Normal::new(0.0, temperature.sqrt()).expect("Invalid temperature");
-        let sqrt_ke: Vec<f64> = dist.sample_iter(&mut rng).take(nlocal * 3).collect();
+        // Total momentum and mass
+        let mut sums = vec![0.0; 4];
         for i in 0..nlocal {
-            let sqrt_mass = atoms.atom_types[atoms.types[i]].mass().sqrt();
-            atoms.velocities[i] = [
-                sqrt_ke[3 * i + 0] / sqrt_mass,
-                sqrt_ke[3 * i + 1] / sqrt_mass,
-                sqrt_ke[3 * i + 2] / sqrt_mass,
-            ];
-        }
-        let res = atoms
-            .velocities
-            .iter()
-            .take(nlocal)
-            .map(|v| {
-                let vsq = v[0] * v[0] + v[1] * v[1] + v[2] * v[2];
-                (*v, vsq)
-            })
-            .reduce(|acc, el| {
-                (
-                    [acc.0[0] + el.0[0], acc.0[1] + el.0[1], acc.0[2] + el.0[2]],
-                    acc.1 + el.1,
-                )
-            });
-
-        if let Some((cum_vel, cum_vsq)) = res {
-            let avg_vsq = cum_vsq / nlocal as f64;
-            let factor = (3.0 * temperature / avg_vsq).sqrt();
-            let avg_vel = [
-                cum_vel[0] / nlocal as f64,
-                cum_vel[1] / nlocal as f64,
-                cum_vel[2] / nlocal as f64,
-            ];
-            atoms.velocities.iter_mut().for_each(|v| {
-                *v = [
-                    (v[0] - avg_vel[0]) * factor,
-                    (v[1] - avg_vel[1]) * factor,
-                    (v[2] - avg_vel[2]) * factor,
-                ]
-            });
+            let mut rng = StdRng::seed_from_u64(seed.wrapping_add(atoms.ids[i] as u64));
+            let mass = atoms.atom_types[atoms.types[i]].mass();
+            let velocity = [0, 1, 2].map(|_| dist.sample(&mut rng) / mass.sqrt());
+            for k in 0..3 {
+                sums[k] += mass * velocity[k];
+            }
+            sums[3] += mass;
+            atoms.velocities[i] = velocity;
+        }
+        let sums = self.domain.sum_floats(sums);
+        let center = [0, 1, 2].map(|k| sums[k] / sums[3]);
+        let mut mvsq = 0.0;
+        for i in 0..nlocal {
+            let mass = self.atoms.atom_types[self.atoms.types[i]].mass();
+            let v = &mut self.atoms.velocities[i];
+            for k in 0..3 {
+                v[k] -= center[k];
+                mvsq += mass * v[k] * v[k];
+            }
+        }
+        let mvsq = self.domain.sum_float(mvsq);
+        if mvsq > 0.0 {
+            let factor = (3.0 * temperature * self.atoms.num_atoms_global as f64 / mvsq).sqrt();
+            for v in &mut self.atoms.velocities[..nlocal] {
+                *v = v.map(|x| x * factor);
+            }
         }
     }
+    /// Delete owned atoms by id, by region, or to remove overlaps, and update the global
+    /// number of atoms. Must be called by every process.
+    ///
+    /// Overlaps are found with the neighbor list, so the distance should be at most the
+    /// neighbor distance, i.e., the force cutoff plus the skin distance. An atom is
+    /// deleted if it is closer than the distance to any atom with a lower id, so that
+    /// every process makes the same choice, though in a chain of overlapping atoms this
+    /// can delete more atoms than strictly needed. Ghost atoms are rebuilt on the next run.
+    ///
+    /// ```rust
+    /// use jmd::{atom_type::Basic, atomic::LJCut, prelude::*, region::Sphere, utils::Delete};
+    ///
+    /// fn run(mut sim: Simulation<Basic, LJCut>) {
+    ///     sim.delete_atoms(Delete::Ids(vec![0, 5]));
+    ///     sim.delete_atoms(Delete::Region(&Sphere::new([0.0; 3], 2.0)));
+    ///     sim.delete_atoms(Delete::Overlap(0.8));
+    /// }
+    /// ```
+    pub fn delete_atoms(&mut self, delete: Delete) {
+        let idxs: Vec<usize> = match delete {
+            Delete::Ids(ids) => {
+                let ids: HashSet<usize> = ids.into_iter().collect();
+                (0..self.nlocal())
+                    .filter(|&i| ids.contains(&self.atoms.ids[i]))
+                    .collect()
+            }
+            Delete::Region(region) => (0..self.nlocal())
+                .filter(|&i| region.contains(&self.atoms.positions[i]))
+                .collect(),
+            Delete::Overlap(distance) => self.overlapping_idxs(distance),
+        };
+        self.remove_idxs(idxs);
+        self.atoms.truncate_ghosts();
+        self.atoms.num_atoms_global = self.domain.sum(self.nlocal());
+    }
+    /// The indices of the owned atoms closer than the given distance to an atom with a
+    /// lower id, on any process. Must be called by every process.
+    fn overlapping_idxs(&mut self, distance: f64) -> Vec<usize> {
+        assert!(
+            distance <= self.neighbor_list.max_neighbor_distance(),
+            "Overlap distance ({}) should be at most the neighbor distance ({}), try \
+             increasing the skin distance",
+            distance,
+            self.neighbor_list.max_neighbor_distance()
+        );
+        self.build_neighbor_list();
+        let atoms = &self.atoms;
+        // Pairs of an owned and a ghost atom are only listed on one process, so the
+        // flags of ghost atoms are sent back to their owners
+        let mut flags = vec![[0.0]; atoms.num_total_atoms()];
+        for i in 0..self.nlocal() {
+            for &j in &self.neighbor_list.neighbors()[i] {
+                if atoms.ids[i] == atoms.ids[j]
+                    || distance_squared(&atoms.positions[i], &atoms.positions[j])
+                        >= distance * distance
+                {
+                    continue;
+                }
+                let k = if atoms.ids[j] > atoms.ids[i] { j } else { i };
+                flags[k][0] = 1.0;
+            }
+        }
+        comm::reverse_comm_values(&self.domain, &mut flags);
+        (0..self.nlocal()).filter(|&i| flags[i][0] > 0.0).collect()
+    }
     /// Remove atoms at the given indices
-    /// TODO: change to IDs instead, add convenience functions for regions
     pub(crate) fn remove_idxs(&mut self, atom_idxs: Vec<usize>) {
         let atoms = &mut self.atoms;
-        let num_local = atom_idxs.iter().filter(|&i| *i < atoms.nlocal).count();
-        atoms.nlocal -= num_local;
-        fn filter_by_idx<T: Copy>(atom_idxs: &Vec<usize>, vec: &Vec<T>) -> Vec<T> {
+        let mut removed = vec![false; atoms.num_total_atoms()];
+        for &i in &atom_idxs {
+            removed[i] = true;
+        }
+        atoms.nlocal -= removed[..atoms.nlocal].iter().filter(|&&r| r).count();
+        fn filter_by_idx<T: Copy>(removed: &[bool], vec: &[T]) -> Vec<T> {
             vec.iter()
-                .enumerate()
-                .filter_map(|(i, x)| {
-                    if atom_idxs.contains(&i) {
-                        None
-                    } else {
-                        Some(*x)
-                    }
-                })
+                .zip(removed)
+                .filter_map(|(x, &r)| if r { None } else { Some(*x) })
                 .collect()
         }
 
-        atoms.ids = filter_by_idx(&atom_idxs, &atoms.ids);
-        atoms.types = filter_by_idx(&atom_idxs, &atoms.types);
-        atoms.positions = filter_by_idx(&atom_idxs, &atoms.positions);
-        atoms.velocities = filter_by_idx(&atom_idxs, &atoms.velocities);
+        atoms.ids = filter_by_idx(&removed, &atoms.ids);
+        atoms.types = filter_by_idx(&removed, &atoms.types);
+        atoms.positions = filter_by_idx(&removed, &atoms.positions);
+        atoms.velocities = filter_by_idx(&removed, &atoms.velocities);
+        atoms.masks = filter_by_idx(&removed, &atoms.masks);
+        atoms.charges = filter_by_idx(&removed, &atoms.charges);
     }
 
     // Other public functions
@@ -346,21 +779,27 @@ where
 
         self.initial_output();
 
-        self.forward_comm();
         self.build_neighbor_list();
         self.compute_forces();
+        self.reverse_comm();
+
+        self.output(self.step);
+        self.check_do_dumps(self.step);
 
-        self.output(0);
+        let first_step = self.step;
+        for step in first_step + 1..=first_step + num_steps {
+            self.step = step;
 
-        for step in 1..=num_steps {
-            // Forward communication
+            // Forward communication, or rebuilding the ghost atoms along with the
+            // neighbor list if applicable
             self.pre_forward_comm();
-            self.forward_comm();
+            if self.nl_should_update(step) {
+                self.build_neighbor_list();
+            } else {
+                self.forward_comm();
+            }
             self.post_forward_comm();
 
-            // Build neighbor list if applicable
-            self.check_build_neighbor_list(step);
-
             // Compute forces
             self.pre_force();
             self.compute_forces();
@@ -372,6 +811,7 @@ where
 
             // Output
             self.check_do_output(step);
+            self.check_do_dumps(step);
         }
     }
 
@@ -384,65 +824,111 @@ where
             "All atomic potential coefficients should be set before running"
         );
     }
+    /// Temporarily take the integrator out of the simulation, so that it can be called
+    /// with mutable access to both
+    fn with_integrator(&mut self, f: impl FnOnce(&mut dyn Integrator<T, A>, &mut Self)) {
+        let mut integrator: Box<dyn Integrator<T, A>> =
+            mem::replace(&mut self.integrator, Box::new(Verlet {}));
+        f(integrator.as_mut(), self);
+        self.integrator = integrator;
+    }
     fn pre_forward_comm(&mut self) {
-        Verlet::pre_forward_comm(self);
+        self.with_integrator(|integrator, sim| integrator.pre_forward_comm(sim));
     }
-    /// Forward communication: communicating the details of owned atoms to neighboring
-    /// processes to use as ghost atoms.
+    /// Forward communication: communicating the positions and velocities of owned atoms
+    /// to neighboring processes holding them as ghost atoms.
     fn forward_comm(&mut self) {
         comm::forward_comm(self);
     }
-    fn post_forward_comm(&mut self) {}
+    fn post_forward_comm(&mut self) {
+        self.with_integrator(|integrator, sim| integrator.post_forward_comm(sim));
+    }
     fn pre_force(&mut self) {}
     /// Compute the atomic potential, etc. forces acting on the atoms
     fn compute_forces(&mut self) {
+        self.atomic_potential.compute_per_atom_values(
+            &self.atoms,
+            &self.neighbor_list,
+            &self.container,
+            &self.domain,
+        );
         self.forces = self
             .atomic_potential
             .compute_forces(&self.atoms, &self.neighbor_list);
     }
-    fn pre_reverse_comm(&mut self) {}
+    fn pre_reverse_comm(&mut self) {
+        self.with_integrator(|integrator, sim| integrator.pre_reverse_comm(sim));
+    }
     /// Reverse communication: communicating the forces of ghost atoms back to the owning
     /// processes
     fn reverse_comm(&mut self) {
         comm::reverse_comm(self);
     }
     fn post_reverse_comm(&mut self) {
-        Verlet::post_reverse_comm(self);
+        self.with_integrator(|integrator, sim| integrator.post_reverse_comm(sim));
     }
 
     // Neighbor list methods
-    /// Whether the neighbor list should update on a given step.
+    /// Whether the neighbor list should update on a given step. Must be called by every
+    /// process, as they should all agree.
     ///
     /// If number of steps since last update is not a multiple of nevery, then false.
     /// Else if number of steps since last update < delay, then false.
     /// Else if check is false, then true.
-    /// Else if atoms have moved too far, then true.
+    /// Else if atoms in any process have moved too far, then true.
     /// Else, false.
     fn nl_should_update(&self, step: usize) -> bool {
         let steps_since_last = step - self.nl_update_settings.last_update_step;
         (steps_since_last % self.nl_update_settings.every == 0)  // Step is a multiple of every
             && (steps_since_last >= self.nl_update_settings.delay)  // It has been longer than delay since last update
-            && (!self.nl_update_settings.check || self.atoms_moved_too_far()) // if check and atoms moved too far, or if check is false
+            && (!self.nl_update_settings.check || self.any_moved_too_far()) // if check and atoms moved too far, or if check is false
     }
-    /// If the neighbor list has not been built or should be rebuilt, then build it
-    fn check_build_neighbor_list(&mut self, step: usize) {
-        if !self.neighbor_list.is_built() || self.nl_should_update(step) {
-            self.build_neighbor_list();
-        }
+    /// Whether any atom in any process has moved further than half the skin distance
+    fn any_moved_too_far(&self) -> bool {
+        self.domain.sum(self.atoms_moved_too_far() as usize) > 0
     }
-    /// If this is the first build, then build first.
-    /// Wrap the atoms across periodic boundaries, communicate the new atom ownerships,
-    /// update the neighbor list, and save the positions to compare against in the future.
+    /// Apply the boundary conditions, communicate the new atom ownerships, rebuild the
+    /// ghost atoms, update the neighbor list, and save the positions to compare against
+    /// in the future. Must be called by every process.
+    ///
+    /// Atoms are wrapped across periodic boundaries, shrink-wrapped sides are moved to
+    /// the extent of the atoms, and atoms outside the box after that (i.e., past a fixed
+    /// side) are lost, with a warning.
     fn build_neighbor_list(&mut self) {
-        if !self.neighbor_list.is_built() {
-            self.neighbor_list.update(self.atoms.positions());
-        }
         self.wrap_pbs();
+        self.shrink_wrap();
+        let outside: Vec<usize> = (0..self.nlocal())
+            .filter(|&i| !self.container.contains(&self.atoms.positions[i]))
+            .collect();
+        self.remove_idxs(outside);
+
         comm::comm_atom_ownership(self);
-        self.neighbor_list.update(self.atoms.positions());
-        self.pos_at_prev_nl_build = self.atoms.positions.clone();
+        self.check_lost_atoms();
+        comm::borders(self);
+
+        self.neighbor_list
+            .update(self.atoms.positions(), self.atoms.nlocal);
+        self.pos_at_prev_nl_build = self.atoms.positions[..self.nlocal()].to_vec();
+        self.nl_update_settings.last_update_step = self.step;
     }
-    /// Whether any atom has moved further than half the skin distance
+    /// Update the global number of atoms of each type, warning if any have been lost
+    fn check_lost_atoms(&mut self) {
+        let mut type_counts = vec![0; self.atoms.num_types()];
+        for &t in self.atoms.types.iter().take(self.nlocal()) {
+            type_counts[t] += 1;
+        }
+        self.type_counts = self.domain.sums(type_counts);
+        let num_atoms = self.type_counts.iter().sum();
+        let num_lost = self.atoms.num_atoms_global.saturating_sub(num_atoms);
+        if num_lost > 0 && self.domain.proc_index() == 0 {
+            self.domain.send_to_main(W2M::Warning(format!(
+                "lost {} atoms on step {}",
+                num_lost, self.step
+            )));
+        }
+        self.atoms.num_atoms_global = num_atoms;
+    }
+    /// Whether any owned atom has moved further than half the skin distance
     fn atoms_moved_too_far(&self) -> bool {
         let half_skin_dist = self.neighbor_list.skin_distance() * 0.5;
         let opt = self
@@ -463,23 +949,34 @@ where
     /// Wrap atoms across periodic boundary conditions
     /// TODO: increment periodic image flags
     fn wrap_pbs(&mut self) {
-        let rect = self.container().rect().clone();
+        let container = &self.container;
+        self.atoms
+            .positions
+            .iter_mut()
+            .for_each(|p| *p = container.wrap(p));
+    }
+    /// Move the shrink-wrapped sides of the box to the extent of the owned atoms of all
+    /// processes, resizing the subdomains and neighbor grid to match
+    fn shrink_wrap(&mut self) {
+        if !self.container.is_shrink_wrapped() {
+            return;
+        }
+        // Maximum of the negative lower extents and the upper extents, in fractional
+        // coordinates
+        let mut local = vec![f64::NEG_INFINITY; 6];
+        for p in &self.atoms.positions[..self.nlocal()] {
+            let p = self.container.to_lamda(p);
+            for k in 0..3 {
+                local[2 * k] = local[2 * k].max(-p[k]);
+                local[2 * k + 1] = local[2 * k + 1].max(p[k]);
+            }
+        }
+        let global = self.domain.max_floats(local);
+        let extents = [0, 1, 2].map(|k| [-global[2 * k], global[2 * k + 1]]);
 
-        vec![Axis::X, Axis::Y, Axis::Z]
-            .iter()
-            .enumerate()
-            .for_each(|(i, &axis)| {
-                if self.container().is_periodic(axis) {
-                    let [lo, hi] = rect.get_bounds(axis);
-                    self.atoms.positions.iter_mut().for_each(|p| {
-                        if p[i] < lo {
-                            p[i] += hi - lo;
-                        } else if p[i] > hi {
-                            p[i] -= hi - lo;
-                        }
-                    });
-                }
-            });
+        self.container.shrink_wrap(extents);
+        self.domain.reset_subdomain(&self.container);
+        self.neighbor_list.set_container(&self.container);
     }
 
     // Output methods
@@ -491,16 +988,543 @@ where
         self.output(step);
     }
     fn output(&self, step: usize) {
-        for v in &self.output.values {
-            let value = match v {
+        // All values are computed before any is sent, since the main thread waits for
+        // every output value once it receives the first, and computes may need to
+        // reduce over processes
+        let values: Vec<Value> = self
+            .output
+            .values
+            .iter()
+            .map(|v| match v {
                 OutputSpec::Step => Value::Usize(step),
-                OutputSpec::Compute(c) => c.compute(&self),
-            };
+                OutputSpec::Compute(c) => c.compute(self),
+            })
+            .collect();
+        for value in values {
             self.domain
                 .send_to_main(W2M::Output(thread::current().id(), value));
         }
     }
+    fn check_do_dumps(&self, step: usize) {
+        for (idx, dump) in self.dumps.iter().enumerate() {
+            if step.is_multiple_of(dump.every) {
+                self.domain.send_to_main(W2M::Dump(
+                    idx,
+                    step,
+                    self.atoms.owned_atoms_in_group(dump.groupbit),
+                    self.container.clone(),
+                ));
+            }
+        }
+    }
     fn initial_output(&self) {
         self.domain().send_to_main_once(W2M::InitialOutput);
     }
 }
+
+impl<'a, A> Simulation<'a, Basic, A>
+where
+    A: AtomicPotentialTrait<Basic>,
+{
+    /// Read a LAMMPS data file, setting the container, one atom type per mass, and the
+    /// atoms with their velocities and charges, replacing any existing atoms. Each process keeps the
+    /// atoms within its subdomain. Must be called by every process.
+    ///
+    /// Atom ids and types start from 0, so LAMMPS atom type 1 becomes type 0. The box is
+    /// periodic along all axes, which can be changed with `Simulation::set_container`.
+    ///
+    /// ```rust,no_run
+    /// use jmd::{atom_type::Basic, atomic::LJCut, prelude::*};
+    ///
+    /// fn run(mut sim: Simulation<Basic, LJCut>) {
+    ///     sim.read_lammps_data("argon.data");
+    /// }
+    /// ```
+    pub fn read_lammps_data(&mut self, path: &str) {
+        let data = read_lammps_data(path);
+        self.set_container(data.container);
+        self.set_atom_types(data.masses.iter().map(|&m| Basic::new(m)).collect());
+
+        let mut atoms = Atoms::new();
+        for i in 0..data.ids.len() {
+            if self.owns_position(&data.positions[i]) {
+                atoms.ids.push(data.ids[i]);
+                atoms.types.push(data.types[i]);
+                atoms.positions.push(data.positions[i]);
+                atoms.velocities.push(data.velocities[i]);
+                atoms.masks.push(ALL_GROUPBIT);
+                atoms.charges.push(data.charges[i]);
+            }
+        }
+        atoms.nlocal = atoms.ids.len();
+        atoms.num_atoms_global = data.ids.len();
+        atoms.atom_types = mem::take(&mut self.atoms.atom_types);
+        self.atoms = atoms;
+        self.forces.clear();
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::{
+        atomic::{LJCut, LJCutCoeff},
+        jmd::Jmd,
+        lattice::{Cubic, Lattice},
+        output::Value,
+        region::Sphere,
+    };
+
+    /// Run the function with each of the given numbers of threads in turn, returning what
+    /// the processes pushed onto `results` in each run. Checks are left to the caller,
+    /// since a panicking process would leave the others waiting.
+    pub(crate) fn run_threads<A, R>(
+        thread_counts: &[usize],
+        f: fn(Simulation<Basic, A>),
+        results: &Mutex<Vec<R>>,
+    ) -> Vec<Vec<R>>
+    where
+        A: AtomicPotentialTrait<Basic> + Send + 'static,
+    {
+        thread_counts
+            .iter()
+            .map(|&num_threads| {
+                let mut app: Jmd<Basic, A> = Jmd::new();
+                app.run(num_threads, f);
+                mem::take(&mut *results.lock().unwrap())
+            })
+            .collect()
+    }
+
+    /// Fill a periodic box with a simple cubic lattice of LJ atoms of `n` cells along each
+    /// axis, with masses 1 and 3 alternating by id
+    pub(crate) fn lj_lattice(sim: &mut Simulation<Basic, LJCut>, n: usize) {
+        let lattice = Cubic::from_density(0.8);
+        let rect = Rect::from_lattice(&lattice, [n; 3]);
+        let (_, coords) = lattice.coords_within_region(&rect, &[0.0; 3]);
+        sim.set_atom_types(vec![Basic::new(1.0), Basic::new(3.0)]);
+        sim.set_atomic_potential(LJCut::new(2.5));
+        for (i, j) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+            sim.set_atomic_coeff(i, j, &LJCutCoeff::new(1.0, 1.0, 2.5));
+        }
+        sim.set_container(Container::from_rect_periodic(rect));
+        let types = (0..coords.len()).map(|i| i % 2).collect();
+        sim.add_atoms(types, coords);
+        sim.set_nl_skin_distance(0.3);
+    }
+
+    /// A compute summed over all processes
+    pub(crate) fn global_float<A: AtomicPotentialTrait<Basic>>(
+        sim: &Simulation<Basic, A>,
+        compute: Compute,
+    ) -> f64 {
+        match compute.compute(sim) {
+            Value::Float(value) => sim.domain().sum_float(value),
+            value => panic!(
+                "Compute {:?} should give a float, found {:?}",
+                compute, value
+            ),
+        }
+    }
+
+    /// The potential energy and virial of the whole system, and the id and force of each
+    /// atom owned by a process
+    pub(crate) type Forces = (f64, Vec<f64>, Vec<(usize, [f64; 3])>);
+
+    /// Push the potential energy and virial of the whole system, and the forces on the
+    /// owned atoms. Must be called by every process.
+    pub(crate) fn push_forces<A: AtomicPotentialTrait<Basic>>(
+        sim: &Simulation<Basic, A>,
+        results: &Mutex<Vec<Forces>>,
+    ) {
+        let energy = global_float(sim, Compute::PotentialE);
+        let virial = sim.atomic_potential().compute_virial(&sim.atoms, sim.nl());
+        let virial = sim.domain().sum_floats(virial.to_vec());
+        let forces = (0..sim.nlocal())
+            .map(|i| (sim.atoms.ids[i], sim.forces()[i]))
+            .collect();
+        results.lock().unwrap().push((energy, virial, forces));
+    }
+
+    /// The energy, the virial and the forces ordered by id of the whole system
+    pub(crate) type GatheredForces = (f64, Vec<f64>, Vec<[f64; 3]>);
+
+    /// The energy, the virial and the forces ordered by id pushed by the processes of a
+    /// run with `push_forces`
+    pub(crate) fn gather_forces(results: Vec<Forces>) -> GatheredForces {
+        let (energy, virial) = (results[0].0, results[0].1.clone());
+        let mut forces: Vec<_> = results.into_iter().flat_map(|r| r.2).collect();
+        forces.sort_by_key(|&(id, _)| id);
+        (energy, virial, forces.into_iter().map(|(_, f)| f).collect())
+    }
+
+    /// Assert that the energies, virials and forces agree to within the tolerance relative
+    /// to the largest of each
+    pub(crate) fn assert_forces_agree(
+        actual: &GatheredForces,
+        expected: &GatheredForces,
+        tolerance: f64,
+    ) {
+        let largest =
+            |values: &mut dyn Iterator<Item = &f64>| values.fold(1e-300_f64, |m, x| m.max(x.abs()));
+        assert!(
+            (actual.0 - expected.0).abs() < tolerance * expected.0.abs(),
+            "Energy {} should be {}",
+            actual.0,
+            expected.0
+        );
+        let scale = largest(&mut expected.1.iter());
+        for (v, e) in actual.1.iter().zip(&expected.1) {
+            assert!(
+                (v - e).abs() < tolerance * scale,
+                "Virial {:?} should be {:?}",
+                actual.1,
+                expected.1
+            );
+        }
+        assert_eq!(actual.2.len(), expected.2.len());
+        let scale = largest(&mut expected.2.iter().flatten());
+        for (id, (f, e)) in actual.2.iter().zip(&expected.2).enumerate() {
+            assert!(
+                (0..3).all(|k| (f[k] - e[k]).abs() < tolerance * scale),
+                "Force {:?} on atom {} should be {:?}",
+                f,
+                id,
+                e
+            );
+        }
+    }
+
+    /// The id and velocity of each atom, with the temperature given to them
+    static VELOCITIES: Mutex<Vec<(usize, [f64; 3], f64)>> = Mutex::new(Vec::new());
+
+    #[test]
+    fn test_set_temperature() {
+        fn run(mut sim: Simulation<Basic, LJCut>) {
+            lj_lattice(&mut sim, 6);
+            sim.set_seed(7);
+            sim.set_temperature(1.5);
+            let temperature = global_float(&sim, Compute::Temperature);
+            let mut results = VELOCITIES.lock().unwrap();
+            for i in 0..sim.nlocal() {
+                results.push((sim.atoms.ids[i], sim.atoms.velocities[i], temperature));
+            }
+        }
+        let mut runs = run_threads(&[1, 4], run, &VELOCITIES);
+        for results in &mut runs {
+            results.sort_by_key(|&(id, _, _)| id);
+            assert_eq!(results.len(), 216);
+            let mut momentum = [0.0; 3];
+            for &(id, v, temperature) in results.iter() {
+                assert!((temperature - 1.5).abs() < 1e-12);
+                let mass = [1.0, 3.0][id % 2];
+                for k in 0..3 {
+                    momentum[k] += mass * v[k];
+                }
+            }
+            assert!(momentum.iter().all(|p| p.abs() < 1e-10));
+        }
+        // The same velocities with any number of threads
+        for (a, b) in runs[0].iter().zip(&runs[1]) {
+            assert_eq!(a.0, b.0);
+            assert!((0..3).all(|k| (a.1[k] - b.1[k]).abs() < 1e-12));
+        }
+    }
+
+    /// An owned position and the number of neighbors listed for it, with the box lo
+    /// corner and lengths
+    type Scaled = ([f64; 3], usize, [f64; 3], [f64; 3]);
+    static SCALED: Mutex<Vec<Scaled>> = Mutex::new(Vec::new());
+
+    #[test]
+    fn test_scale_box() {
+        fn run(mut sim: Simulation<Basic, LJCut>) {
+            lj_lattice(&mut sim, 6);
+            sim.scale_box([3.0, 1.0, 0.9]);
+            sim.run(0);
+            let rect = sim.container().rect();
+            let mut results = SCALED.lock().unwrap();
+            for i in 0..sim.nlocal() {
+                results.push((
+                    sim.atoms.positions[i],
+                    sim.nl().neighbors()[i].len(),
+                    rect.lo(),
+                    rect.lengths(),
+                ));
+            }
+        }
+        let lattice = Cubic::from_density(0.8);
+        let rect = Rect::from_lattice(&lattice, [6; 3]);
+        let center = rect.center();
+        let expected_lengths = [0, 1, 2].map(|k| rect.lengths()[k] * [3.0, 1.0, 0.9][k]);
+        let (_, coords) = lattice.coords_within_region(&rect, &[0.0; 3]);
+        let mut expected: Vec<[f64; 3]> = coords
+            .iter()
+            .map(|p| [0, 1, 2].map(|k| center[k] + (p[k] - center[k]) * [3.0, 1.0, 0.9][k]))
+            .collect();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+        for results in run_threads(&[1, 8], run, &SCALED) {
+            let mut positions: Vec<[f64; 3]> = results.iter().map(|r| r.0).collect();
+            positions.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            assert_eq!(positions.len(), expected.len());
+            for (p, e) in positions.iter().zip(&expected) {
+                assert!((0..3).all(|k| (p[k] - e[k]).abs() < 1e-12));
+            }
+            for &(_, _, lo, lengths) in &results {
+                for k in 0..3 {
+                    assert!((lengths[k] - expected_lengths[k]).abs() < 1e-12);
+                    assert!((lo[k] + 0.5 * lengths[k] - center[k]).abs() < 1e-12);
+                }
+            }
+            // The neighbor grid covers the scaled box, so every pair within the neighbor
+            // distance, by minimum image, is listed once
+            let distance: f64 = 2.5 + 0.3;
+            let mut num_pairs = 0;
+            for (i, a) in positions.iter().enumerate() {
+                for b in &positions[i + 1..] {
+                    let r2: f64 = (0..3)
+                        .map(|k| {
+                            let d = a[k] - b[k];
+                            let d = d - (d / expected_lengths[k]).round() * expected_lengths[k];
+                            d * d
+                        })
+                        .sum();
+                    if r2 < distance * distance {
+                        num_pairs += 1;
+                    }
+                }
+            }
+            assert_eq!(results.iter().map(|r| r.1).sum::<usize>(), num_pairs);
+        }
+    }
+
+    /// The step, number of atoms and potential and kinetic energies, after writing or
+    /// reading a restart file
+    static RESTARTED: Mutex<Vec<(usize, usize, f64, f64)>> = Mutex::new(Vec::new());
+
+    const RESTART_PATH: &str = "jmd_test_restart_threads.restart";
+
+    fn push_restarted(sim: &Simulation<Basic, LJCut>) {
+        let potential = global_float(sim, Compute::PotentialE);
+        let kinetic = global_float(sim, Compute::KineticE);
+        if sim.domain().proc_index() == 0 {
+            let natoms = sim.atoms.num_atoms_global();
+            RESTARTED
+                .lock()
+                .unwrap()
+                .push((sim.step(), natoms, potential, kinetic));
+        }
+    }
+
+    #[test]
+    fn test_restart_threads() {
+        let path = std::env::temp_dir().join(RESTART_PATH);
+        fn write(mut sim: Simulation<Basic, LJCut>) {
+            lj_lattice(&mut sim, 8);
+            sim.set_seed(11);
+            sim.set_temperature(1.0);
+            sim.run(20);
+            let path = std::env::temp_dir().join(RESTART_PATH);
+            sim.write_restart(&path.to_string_lossy());
+            push_restarted(&sim);
+        }
+        fn read(mut sim: Simulation<Basic, LJCut>) {
+            sim.set_atom_types(vec![Basic::new(1.0), Basic::new(3.0)]);
+            let path = std::env::temp_dir().join(RESTART_PATH);
+            sim.read_restart(&path.to_string_lossy());
+            sim.set_nl_skin_distance(0.3);
+            sim.run(0);
+            push_restarted(&sim);
+        }
+        let written = run_threads(&[4], write, &RESTARTED)[0][0];
+        let read = run_threads(&[1, 3], read, &RESTARTED);
+        std::fs::remove_file(&path).unwrap();
+        // The same atoms, shared between any number of processes
+        for results in read {
+            let (step, natoms, potential, kinetic) = results[0];
+            assert_eq!((step, natoms), (written.0, written.1));
+            assert!((potential - written.2).abs() < 1e-9 * written.2.abs());
+            assert!((kinetic - written.3).abs() < 1e-9 * written.3.abs());
+        }
+    }
+
+    /// Whether the state is from after the run, and the id, mass, position and velocity of
+    /// an atom, whether it is a ghost and whether it is in the group
+    type GroupedAtom = (bool, usize, f64, [f64; 3], [f64; 3], bool, bool);
+    static GROUPED: Mutex<Vec<GroupedAtom>> = Mutex::new(Vec::new());
+    /// The temperature and kinetic energy of the group, and the temperature of an empty group
+    static GROUP_COMPUTES: Mutex<Vec<(f64, f64, f64)>> = Mutex::new(Vec::new());
+
+    fn push_grouped(sim: &Simulation<Basic, LJCut>, after: bool) {
+        let temperature = global_float(sim, Compute::Temperature.in_group("left"));
+        let kinetic = global_float(sim, Compute::KineticE.in_group("left"));
+        let empty = global_float(sim, Compute::Temperature.in_group("empty"));
+        if after && sim.domain().proc_index() == 0 {
+            GROUP_COMPUTES
+                .lock()
+                .unwrap()
+                .push((temperature, kinetic, empty));
+        }
+        let groupbit = sim.groups().expect_bit("left");
+        let atoms = &sim.atoms;
+        let mut results = GROUPED.lock().unwrap();
+        for i in 0..atoms.num_total_atoms() {
+            results.push((
+                after,
+                atoms.ids[i],
+                atoms.mass(i),
+                atoms.positions[i],
+                atoms.velocities[i],
+                i >= sim.nlocal(),
+                atoms.in_group(i, groupbit),
+            ));
+        }
+    }
+
+    #[test]
+    fn test_groups_threads() {
+        fn run(mut sim: Simulation<Basic, LJCut>) {
+            lj_lattice(&mut sim, 8);
+            sim.set_seed(13);
+            sim.set_timestep(0.005);
+            sim.set_temperature(1.0);
+            // The first four of the eight lattice planes along x
+            let (lo, hi) = (sim.container().rect().lo(), sim.container().rect().hi());
+            let xhi = lo[0] + 0.45 * (hi[0] - lo[0]);
+            let left = Rect::new(
+                lo[0] - 1.0,
+                xhi,
+                lo[1] - 1.0,
+                hi[1] + 1.0,
+                lo[2] - 1.0,
+                hi[2] + 1.0,
+            );
+            sim.group_region("left", &left);
+            sim.group_ids("empty", 0..0);
+            sim.set_integrator_group("left");
+            push_grouped(&sim, false);
+            sim.run(200);
+            push_grouped(&sim, true);
+        }
+        for results in run_threads(&[1, 8], run, &GROUPED) {
+            let owned = |after: bool| {
+                let mut atoms: Vec<_> = results.iter().filter(|a| a.0 == after && !a.5).collect();
+                atoms.sort_by_key(|a| a.1);
+                atoms
+            };
+            let (before, after) = (owned(false), owned(true));
+            assert_eq!(before.len(), 512);
+            assert_eq!(after.len(), 512);
+            assert_eq!(before.iter().filter(|a| a.6).count(), 256);
+            let mut max_displacement: f64 = 0.0;
+            for (a, b) in before.iter().zip(&after) {
+                assert_eq!(a.1, b.1);
+                assert_eq!(a.6, b.6, "Atom {} should keep its group", a.1);
+                let displacement = (0..3).map(|k| (b.3[k] - a.3[k]).abs()).fold(0.0, f64::max);
+                if a.6 {
+                    max_displacement = max_displacement.max(displacement);
+                } else {
+                    assert!(displacement < 1e-12, "Atom {} should not move", a.1);
+                    assert_eq!(a.4, b.4, "Atom {} should keep its velocity", a.1);
+                }
+            }
+            assert!(max_displacement > 0.1, "The group should move");
+            // Ghosts carry the group of the atoms they copy
+            for ghost in results.iter().filter(|a| a.0 && a.5) {
+                assert_eq!(ghost.6, after[ghost.1].6, "Ghost of atom {}", ghost.1);
+            }
+        }
+        for (temperature, kinetic, empty) in mem::take(&mut *GROUP_COMPUTES.lock().unwrap()) {
+            assert_eq!(empty, 0.0);
+            assert!(temperature > 0.0);
+            assert!((temperature - 2.0 / 3.0 * kinetic / 256.0).abs() < 1e-12);
+        }
+    }
+
+    /// The stage of the deletions, the number of atoms in the simulation and the ids of
+    /// the atoms owned by a process
+    static DELETED: Mutex<Vec<(usize, usize, Vec<usize>)>> = Mutex::new(Vec::new());
+    /// The lattice sites next to which atoms are added, to be deleted as overlapping
+    const OVERLAPPING_SITES: [usize; 3] = [1, 100, 511];
+
+    /// The coordinates of the atoms of `lj_lattice(sim, 8)`, and a sphere around the site
+    /// at the centre of the box that holds it and its 18 nearest neighbors
+    fn lattice_sites() -> (Vec<[f64; 3]>, Sphere) {
+        let lattice = Cubic::from_density(0.8);
+        let rect = Rect::from_lattice(&lattice, [8; 3]);
+        let (_, coords) = lattice.coords_within_region(&rect, &[0.0; 3]);
+        let a = lattice.cell_lengths()[0];
+        (coords, Sphere::new([0.0; 3], 1.5 * a))
+    }
+
+    fn push_deleted(sim: &Simulation<Basic, LJCut>, stage: usize) {
+        let ids = sim.atoms.ids[..sim.nlocal()].to_vec();
+        DELETED
+            .lock()
+            .unwrap()
+            .push((stage, sim.atoms.num_atoms_global(), ids));
+    }
+
+    #[test]
+    fn test_delete_atoms_threads() {
+        fn run(mut sim: Simulation<Basic, LJCut>) {
+            lj_lattice(&mut sim, 8);
+            let (coords, sphere) = lattice_sites();
+            let overlapping: Vec<_> = OVERLAPPING_SITES
+                .iter()
+                .map(|&site| [coords[site][0] + 0.3, coords[site][1], coords[site][2]])
+                .collect();
+            sim.add_atoms(vec![0; overlapping.len()], overlapping);
+            sim.set_timestep(0.005);
+            sim.run(0);
+            push_deleted(&sim, 0);
+            // Including an id that is not in the simulation
+            sim.delete_atoms(Delete::Ids(vec![0, 5, 100, 9999]));
+            push_deleted(&sim, 1);
+            sim.delete_atoms(Delete::Region(&sphere));
+            push_deleted(&sim, 2);
+            sim.delete_atoms(Delete::Overlap(0.5));
+            push_deleted(&sim, 3);
+            sim.run(10);
+            push_deleted(&sim, 4);
+        }
+        let (coords, sphere) = lattice_sites();
+        let in_sphere: Vec<_> = (0..512).filter(|&i| sphere.contains(&coords[i])).collect();
+        assert_eq!(in_sphere.len(), 19);
+        let mut expected: Vec<Vec<usize>> = vec![(0..515).collect()];
+        let remove = |ids: &Vec<usize>, removed: &[usize]| -> Vec<usize> {
+            ids.iter()
+                .copied()
+                .filter(|i| !removed.contains(i))
+                .collect()
+        };
+        expected.push(remove(&expected[0], &[0, 5, 100]));
+        expected.push(remove(&expected[1], &in_sphere));
+        // Each added atom has the higher id of its pair, so is deleted unless the atom on
+        // its site already was
+        let overlapping: Vec<_> = (0..OVERLAPPING_SITES.len())
+            .filter(|&k| expected[2].contains(&OVERLAPPING_SITES[k]))
+            .map(|k| 512 + k)
+            .collect();
+        assert_eq!(overlapping.len(), 2);
+        expected.push(remove(&expected[2], &overlapping));
+        expected.push(expected[3].clone());
+        for results in run_threads(&[1, 4], run, &DELETED) {
+            for (stage, expected) in expected.iter().enumerate() {
+                let stage_results: Vec<_> = results.iter().filter(|r| r.0 == stage).collect();
+                for r in &stage_results {
+                    assert_eq!(r.1, expected.len(), "Number of atoms after stage {}", stage);
+                }
+                let mut ids: Vec<usize> = stage_results
+                    .iter()
+                    .flat_map(|r| r.2.iter().copied())
+                    .collect();
+                ids.sort();
+                assert_eq!(&ids, expected, "Atoms after stage {}", stage);
+            }
+        }
+    }
+}
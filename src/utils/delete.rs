@@ -0,0 +1,11 @@
+use crate::region::Region;
+
+/// The atoms to delete with `Simulation::delete_atoms`
+pub enum Delete<'a> {
+    /// The atoms with the given ids
+    Ids(Vec<usize>),
+    /// The atoms within the given region
+    Region(&'a dyn Region),
+    /// One atom of each pair closer than the given distance, the one with the higher id
+    Overlap(f64),
+}
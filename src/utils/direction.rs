@@ -23,7 +23,7 @@ impl Axis {
         }
     }
 }
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Direction {
     Xlo,
     Xhi,
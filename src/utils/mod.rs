@@ -1,12 +1,16 @@
 pub(crate) mod computations;
+mod delete;
 mod direction;
 mod indices;
 mod keyed_vec;
 mod sort;
+mod spline;
 mod types;
 
+pub use delete::Delete;
 pub use direction::*;
 pub use indices::Index;
 pub use keyed_vec::{KeyError, KeyedVec};
 pub use sort::*;
+pub(crate) use spline::{Spline, UniformSpline};
 pub use types::Types;
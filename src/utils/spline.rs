@@ -0,0 +1,167 @@
+/// Cubic spline through points with increasing x
+#[derive(Clone, Debug)]
+pub(crate) struct Spline {
+    x: Vec<f64>,
+    y: Vec<f64>,
+    /// Second derivatives at the points
+    y2: Vec<f64>,
+}
+impl Spline {
+    /// The spline through the given points, with the given first derivatives at the
+    /// ends, or with zero second derivatives (natural ends) where not given
+    pub(crate) fn new(x: Vec<f64>, y: Vec<f64>, end_derivatives: [Option<f64>; 2]) -> Self {
+        let n = x.len();
+        assert_eq!(n, y.len(), "One y value should be given per x value");
+        assert!(n >= 2, "A spline needs at least 2 points, found {}", n);
+        assert!(
+            x.windows(2).all(|w| w[0] < w[1]),
+            "Spline x values should be increasing"
+        );
+
+        // Solve the tridiagonal system for the second derivatives
+        let mut y2 = vec![0.0; n];
+        let mut u = vec![0.0; n];
+        if let Some(d) = end_derivatives[0] {
+            y2[0] = -0.5;
+            u[0] = 3.0 / (x[1] - x[0]) * ((y[1] - y[0]) / (x[1] - x[0]) - d);
+        }
+        for i in 1..n - 1 {
+            let sig = (x[i] - x[i - 1]) / (x[i + 1] - x[i - 1]);
+            let p = sig * y2[i - 1] + 2.0;
+            y2[i] = (sig - 1.0) / p;
+            let slopes =
+                (y[i + 1] - y[i]) / (x[i + 1] - x[i]) - (y[i] - y[i - 1]) / (x[i] - x[i - 1]);
+            u[i] = (6.0 * slopes / (x[i + 1] - x[i - 1]) - sig * u[i - 1]) / p;
+        }
+        let (qn, un) = match end_derivatives[1] {
+            Some(d) => {
+                let h = x[n - 1] - x[n - 2];
+                (0.5, 3.0 / h * (d - (y[n - 1] - y[n - 2]) / h))
+            }
+            None => (0.0, 0.0),
+        };
+        y2[n - 1] = (un - qn * u[n - 2]) / (qn * y2[n - 2] + 1.0);
+        for k in (0..n - 1).rev() {
+            y2[k] = y2[k] * y2[k + 1] + u[k];
+        }
+        Self { x, y, y2 }
+    }
+    /// The index of the interval containing x, clamped to the first and last intervals
+    fn interval(&self, x: f64) -> usize {
+        let k = self.x.partition_point(|&xk| xk <= x);
+        k.clamp(1, self.x.len() - 1) - 1
+    }
+    pub(crate) fn value(&self, x: f64) -> f64 {
+        let k = self.interval(x);
+        let h = self.x[k + 1] - self.x[k];
+        let a = (self.x[k + 1] - x) / h;
+        let b = 1.0 - a;
+        a * self.y[k]
+            + b * self.y[k + 1]
+            + ((a * a * a - a) * self.y2[k] + (b * b * b - b) * self.y2[k + 1]) * h * h / 6.0
+    }
+    pub(crate) fn derivative(&self, x: f64) -> f64 {
+        let k = self.interval(x);
+        let h = self.x[k + 1] - self.x[k];
+        let a = (self.x[k + 1] - x) / h;
+        let b = 1.0 - a;
+        (self.y[k + 1] - self.y[k]) / h - (3.0 * a * a - 1.0) / 6.0 * h * self.y2[k]
+            + (3.0 * b * b - 1.0) / 6.0 * h * self.y2[k + 1]
+    }
+}
+
+/// Cubic spline through equally spaced points, evaluated in constant time from the
+/// polynomial coefficients of each interval
+#[derive(Clone, Debug)]
+pub(crate) struct UniformSpline {
+    lo: f64,
+    dx: f64,
+    /// Coefficients `[a, b, c, d]` of `a + b t + c t^2 + d t^3` in each interval, with `t`
+    /// the distance from the start of the interval
+    coeffs: Vec<[f64; 4]>,
+}
+impl UniformSpline {
+    /// The spline through `num_points` equally spaced samples of the function from `lo` to
+    /// `hi`, with the given first derivatives at the ends as in `Spline::new`
+    pub(crate) fn from_fn(
+        lo: f64,
+        hi: f64,
+        num_points: usize,
+        f: impl Fn(f64) -> f64,
+        end_derivatives: [Option<f64>; 2],
+    ) -> Self {
+        assert!(
+            num_points >= 2,
+            "A spline needs at least 2 points, found {}",
+            num_points
+        );
+        let dx = (hi - lo) / (num_points - 1) as f64;
+        let y = (0..num_points).map(|i| f(lo + i as f64 * dx)).collect();
+        Self::from_values(lo, dx, y, end_derivatives)
+    }
+    /// The spline through the given values at equally spaced points from `lo`, with the
+    /// given first derivatives at the ends as in `Spline::new`
+    pub(crate) fn from_values(
+        lo: f64,
+        dx: f64,
+        y: Vec<f64>,
+        end_derivatives: [Option<f64>; 2],
+    ) -> Self {
+        let num_points = y.len();
+        let x = (0..num_points).map(|i| lo + i as f64 * dx).collect();
+        let spline = Spline::new(x, y, end_derivatives);
+        let coeffs = (0..num_points - 1)
+            .map(|k| {
+                let [y0, y1] = [spline.y[k], spline.y[k + 1]];
+                let [s0, s1] = [spline.y2[k], spline.y2[k + 1]];
+                [
+                    y0,
+                    (y1 - y0) / dx - dx * (2.0 * s0 + s1) / 6.0,
+                    0.5 * s0,
+                    (s1 - s0) / (6.0 * dx),
+                ]
+            })
+            .collect();
+        Self { lo, dx, coeffs }
+    }
+    /// The index of the interval containing x, clamped to the first and last intervals,
+    /// and the distance of x from its start
+    fn interval(&self, x: f64) -> (usize, f64) {
+        let u = (x - self.lo) / self.dx;
+        let k = (u.max(0.0) as usize).min(self.coeffs.len() - 1);
+        (k, x - self.lo - k as f64 * self.dx)
+    }
+    pub(crate) fn value(&self, x: f64) -> f64 {
+        let (k, t) = self.interval(x);
+        let [a, b, c, d] = self.coeffs[k];
+        ((d * t + c) * t + b) * t + a
+    }
+    pub(crate) fn derivative(&self, x: f64) -> f64 {
+        let (k, t) = self.interval(x);
+        let [_, b, c, d] = self.coeffs[k];
+        (3.0 * d * t + 2.0 * c) * t + b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_splines_reproduce_cubics() {
+        let f = |x: f64| 2.0 - x + 0.5 * x * x - 0.3 * x * x * x;
+        let df = |x: f64| -1.0 + x - 0.9 * x * x;
+        let ends = [Some(df(-1.0)), Some(df(2.0))];
+
+        let x = vec![-1.0, -0.7, 0.1, 0.2, 1.1, 2.0];
+        let y = x.iter().map(|&x| f(x)).collect();
+        let spline = Spline::new(x, y, ends);
+        let uniform = UniformSpline::from_fn(-1.0, 2.0, 7, f, ends);
+        for x in [-1.0, -0.9, 0.0, 0.15, 0.5, 1.7, 2.0] {
+            assert!((spline.value(x) - f(x)).abs() < 1e-12);
+            assert!((spline.derivative(x) - df(x)).abs() < 1e-12);
+            assert!((uniform.value(x) - f(x)).abs() < 1e-12);
+            assert!((uniform.derivative(x) - df(x)).abs() < 1e-12);
+        }
+    }
+}